@@ -0,0 +1,131 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rust_varint::formats::{g8iu, group as group_varint, leb128, streamvbyte, Format};
+use rust_varint::{pack_int_into_array, pack_uint_into_array, MAX_ENCODED_LEN};
+
+const FORMATS: [(&str, Format); 8] = [
+	("ordered", Format::Ordered),
+	("cassandra", Format::Cassandra),
+	("prefix_varint", Format::PrefixVarint),
+	("leb128", Format::Leb128),
+	("fast_le", Format::FastLE),
+	("sqlite", Format::Sqlite),
+	("git_offset", Format::GitOffset),
+	("compact_size", Format::CompactSize),
+];
+
+// Values of mixed magnitudes, from a fixed xorshift sequence.
+fn values(n: usize) -> Vec<u64> {
+	let mut x = 0x9e37_79b9_7f4a_7c15u64;
+	(0..n)
+		.map(|_| {
+			x ^= x << 13;
+			x ^= x >> 7;
+			x ^= x << 17;
+			x >> (x % 64)
+		})
+		.collect()
+}
+
+fn bench_formats(c: &mut Criterion) {
+	let values = values(10_000);
+	let mut group = c.benchmark_group("u64");
+	group.throughput(Throughput::Elements(values.len() as u64));
+	for &(name, format) in &FORMATS {
+		let mut encoded = Vec::new();
+		for &v in &values {
+			format.encode_u64(&mut encoded, v);
+		}
+
+		group.bench_function(format!("encode/{}", name), |b| {
+			let mut out = Vec::with_capacity(encoded.len());
+			b.iter(|| {
+				out.clear();
+				for &v in &values {
+					format.encode_u64(&mut out, black_box(v));
+				}
+				out.len()
+			})
+		});
+		group.bench_function(format!("decode/{}", name), |b| {
+			b.iter(|| {
+				let (mut pos, mut sum) = (0, 0u64);
+				while pos < encoded.len() {
+					let (v, used) = format.decode_u64(black_box(&encoded[pos..])).unwrap();
+					sum = sum.wrapping_add(v);
+					pos += used;
+				}
+				sum
+			})
+		});
+	}
+	group.finish();
+}
+
+fn bench_u32_bulk(c: &mut Criterion) {
+	let values: Vec<u32> = values(10_000).into_iter().map(|v| v as u32).collect();
+	let mut encoded = Vec::new();
+	g8iu::encode(&mut encoded, &values);
+	let mut grouped = Vec::new();
+	group_varint::encode(&mut grouped, &values);
+	let mut streamed = Vec::new();
+	streamvbyte::encode(&mut streamed, &values);
+	let mut packed = Vec::new();
+	for &v in &values {
+		leb128::encode_u64(&mut packed, v as u64);
+	}
+	let mut group = c.benchmark_group("u32");
+	group.throughput(Throughput::Elements(values.len() as u64));
+	group.bench_function("decode/leb128", |b| {
+		let mut out = Vec::with_capacity(values.len());
+		b.iter(|| {
+			out.clear();
+			leb128::decode_u32s(black_box(&packed), &mut out).unwrap();
+			out.len()
+		})
+	});
+	group.bench_function("decode/g8iu", |b| {
+		let mut out = Vec::with_capacity(values.len());
+		b.iter(|| {
+			out.clear();
+			g8iu::decode(black_box(&encoded), &mut out).unwrap();
+			out.len()
+		})
+	});
+	group.bench_function("decode/group", |b| {
+		let mut out = Vec::with_capacity(values.len());
+		b.iter(|| {
+			out.clear();
+			group_varint::decode(black_box(&grouped), values.len(), &mut out).unwrap();
+			out.len()
+		})
+	});
+	group.bench_function("decode/streamvbyte", |b| {
+		let mut out = Vec::with_capacity(values.len());
+		b.iter(|| {
+			out.clear();
+			streamvbyte::decode(black_box(&streamed), values.len(), &mut out).unwrap();
+			out.len()
+		})
+	});
+	group.finish();
+}
+
+fn bench_pack_into_array(c: &mut Criterion) {
+	let values = values(10_000);
+	let mut group = c.benchmark_group("pack_into_array");
+	group.throughput(Throughput::Elements(values.len() as u64));
+	group.bench_function("uint", |b| {
+		let mut res = [0; MAX_ENCODED_LEN];
+		b.iter(|| values.iter().map(|&v| pack_uint_into_array(black_box(v), &mut res)).sum::<usize>())
+	});
+	group.bench_function("int", |b| {
+		let mut res = [0; MAX_ENCODED_LEN];
+		b.iter(|| {
+			values.iter().map(|&v| pack_int_into_array(black_box(v.rotate_right(1) as i64), &mut res)).sum::<usize>()
+		})
+	});
+	group.finish();
+}
+
+criterion_group!(benches, bench_formats, bench_u32_bulk, bench_pack_into_array);
+criterion_main!(benches);
@@ -0,0 +1,113 @@
+//! `#[derive(OrderedKey)]`, re-exported by `rust_varint::key` with the
+//! `derive` feature.
+//!
+//! The derive appends the fields of a struct to a key in declaration
+//! order, so keys sort like the struct's fields compared left to right.
+//! Each field may carry `#[key(desc)]` to sort it in reverse, and an
+//! `Option` field `#[key(nulls_first)]` (the default) or
+//! `#[key(nulls_last)]`.
+
+extern crate proc_macro;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Index, Type};
+
+#[proc_macro_derive(OrderedKey, attributes(key))]
+pub fn derive_ordered_key(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+struct Options {
+	desc: bool,
+	nulls_last: Option<bool>,
+}
+
+fn options(field: &syn::Field) -> Result<Options, Error> {
+	let mut opts = Options { desc: false, nulls_last: None };
+	for attr in field.attrs.iter().filter(|a| a.path().is_ident("key")) {
+		attr.parse_nested_meta(|meta| {
+			let last = if meta.path.is_ident("desc") {
+				opts.desc = true;
+				return Ok(());
+			} else if meta.path.is_ident("nulls_first") {
+				false
+			} else if meta.path.is_ident("nulls_last") {
+				true
+			} else {
+				return Err(meta.error("expected `desc`, `nulls_first` or `nulls_last`"));
+			};
+			if opts.nulls_last.replace(last).is_some() {
+				return Err(meta.error("null placement given twice"));
+			}
+			Ok(())
+		})?;
+	}
+	Ok(opts)
+}
+
+fn is_option(ty: &Type) -> bool {
+	match ty {
+		Type::Path(p) if p.qself.is_none() => p.path.segments.last().is_some_and(|s| s.ident == "Option"),
+		_ => false,
+	}
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream, Error> {
+	let fields = match &input.data {
+		Data::Struct(s) => &s.fields,
+		_ => return Err(Error::new_spanned(&input.ident, "OrderedKey can only be derived for structs")),
+	};
+
+	let mut appends = Vec::new();
+	let mut reads = Vec::new();
+	for (i, field) in fields.iter().enumerate() {
+		let opts = options(field)?;
+		let member = match &field.ident {
+			Some(ident) => quote!(#ident),
+			None => {
+				let index = Index::from(i);
+				quote!(#index)
+			}
+		};
+		let dir = if opts.desc { quote!(Desc) } else { quote!(Asc) };
+		let dir = quote!(::rust_varint::key::Direction::#dir);
+		if is_option(&field.ty) {
+			let nulls = if opts.nulls_last == Some(true) { quote!(Last) } else { quote!(First) };
+			let nulls = quote!(::rust_varint::NullPlacement::#nulls);
+			appends.push(quote!(key.append_opt(&self.#member, #dir, #nulls);));
+			reads.push(quote!(#member: r.read_opt(#dir, #nulls)?));
+		} else if opts.nulls_last.is_some() {
+			return Err(Error::new_spanned(field, "null placement only applies to `Option` fields"));
+		} else {
+			appends.push(quote!(key.append(&self.#member, #dir);));
+			reads.push(quote!(#member: r.read(#dir)?));
+		}
+	}
+
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	let value = match fields {
+		Fields::Unit => quote!(#name),
+		_ => quote!(#name { #(#reads,)* }),
+	};
+	Ok(quote! {
+		impl #impl_generics ::rust_varint::key::OrderedKey for #name #ty_generics #where_clause {
+			fn encode_key(&self) -> ::std::vec::Vec<u8> {
+				#[allow(unused_mut)]
+				let mut key = ::rust_varint::key::KeyBuilder::new();
+				#(#appends)*
+				key.finish()
+			}
+
+			fn decode_key(key: &[u8]) -> ::std::result::Result<Self, ::rust_varint::DecodeError> {
+				#[allow(unused_mut)]
+				let mut r = ::rust_varint::key::KeyReader::new(key);
+				let value = #value;
+				r.finish()?;
+				::std::result::Result::Ok(value)
+			}
+		}
+	})
+}
@@ -0,0 +1,209 @@
+//! Self-describing container for n-dimensional integer arrays.
+//!
+//! ```text
+//! [rank: uint][dim: uint ...][dtype: uint][element count: uint][payload kind: uint][payload ...]
+//! ```
+//!
+//! Elements are in row-major order. A raw payload holds each element
+//! little-endian at the width of its dtype; a block payload is a sealed
+//! block as written by `block::encode_block`.
+
+use std::convert::TryFrom;
+
+use crate::block::{decode_block, encode_block};
+use crate::{decode_uint, pack_uint, DecodeError};
+
+/// Element type, stored as its tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DType {
+	I8,
+	I16,
+	I32,
+	I64,
+	U8,
+	U16,
+	U32,
+}
+
+const DTYPES: [DType; 7] = [DType::I8, DType::I16, DType::I32, DType::I64, DType::U8, DType::U16, DType::U32];
+
+impl DType {
+	fn tag(self) -> u64 {
+		DTYPES.iter().position(|&d| d == self).unwrap() as u64
+	}
+
+	/// Width of one element in a raw payload.
+	pub fn width(self) -> usize {
+		match self {
+			DType::I8 | DType::U8 => 1,
+			DType::I16 | DType::U16 => 2,
+			DType::I32 | DType::U32 => 4,
+			DType::I64 => 8,
+		}
+	}
+
+	fn signed(self) -> bool {
+		matches!(self, DType::I8 | DType::I16 | DType::I32 | DType::I64)
+	}
+
+	fn contains(self, v: i64) -> bool {
+		let bits = self.width() as u32 * 8;
+		match (self.signed(), bits) {
+			(true, 64) => true,
+			(true, _) => v >= -(1 << (bits - 1)) && v < 1 << (bits - 1),
+			(false, _) => v >= 0 && v < 1 << bits,
+		}
+	}
+}
+
+/// How the elements follow the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Payload {
+	Raw,
+	Block,
+}
+
+/// Everything before the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayHeader {
+	pub shape: Vec<u64>,
+	pub dtype: DType,
+	pub payload: Payload,
+}
+
+impl ArrayHeader {
+	/// Product of the dimensions, `None` if it overflows.
+	pub fn element_count(&self) -> Option<u64> {
+		self.shape.iter().try_fold(1u64, |n, &d| n.checked_mul(d))
+	}
+
+	pub fn encode(&self, out: &mut Vec<u8>) {
+		let count = self.element_count().expect("array element count overflows u64");
+		out.extend_from_slice(&pack_uint(self.shape.len() as u64));
+		for &d in &self.shape {
+			out.extend_from_slice(&pack_uint(d));
+		}
+		out.extend_from_slice(&pack_uint(self.dtype.tag()));
+		out.extend_from_slice(&pack_uint(count));
+		out.extend_from_slice(&pack_uint(self.payload as u64));
+	}
+
+	/// Parses the header at the front of `buf`, returning it and its size.
+	pub fn decode(buf: &[u8]) -> Result<(ArrayHeader, usize), DecodeError> {
+		let mut pos = 0;
+		let mut next = || -> Result<u64, DecodeError> {
+			let (v, used) = decode_uint(&buf[pos..]).map_err(|e| match e {
+				DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+				e => e,
+			})?;
+			pos += used;
+			Ok(v)
+		};
+
+		let rank = next()?;
+		let mut shape = Vec::new();
+		for _ in 0..rank {
+			shape.push(next()?);
+		}
+		let dtype = usize::try_from(next()?).ok().and_then(|t| DTYPES.get(t).copied());
+		let dtype = dtype.ok_or(DecodeError::Malformed("unknown array dtype"))?;
+		let count = next()?;
+		let payload = match next()? {
+			0 => Payload::Raw,
+			1 => Payload::Block,
+			_ => return Err(DecodeError::Malformed("unknown array payload kind")),
+		};
+		let header = ArrayHeader { shape, dtype, payload };
+		if header.element_count() != Some(count) {
+			return Err(DecodeError::Malformed("array element count does not match its shape"));
+		}
+		Ok((header, pos))
+	}
+}
+
+/// Writes `values` in row-major order under `header`.
+///
+/// Panics if the number of values does not match the shape or a value
+/// does not fit the dtype.
+pub fn encode_array(header: &ArrayHeader, values: &[i64]) -> Vec<u8> {
+	assert_eq!(header.element_count(), Some(values.len() as u64), "values do not match the array shape");
+	assert!(values.iter().all(|&v| header.dtype.contains(v)), "value does not fit the array dtype");
+	let mut out = Vec::new();
+	header.encode(&mut out);
+	match header.payload {
+		Payload::Raw => {
+			for &v in values {
+				out.extend_from_slice(&v.to_le_bytes()[..header.dtype.width()]);
+			}
+		}
+		Payload::Block => out.extend_from_slice(&encode_block(values)),
+	}
+	out
+}
+
+/// Decodes the array at the front of `buf`, returning its header, its
+/// values and the number of bytes it occupied.
+pub fn decode_array(buf: &[u8]) -> Result<(ArrayHeader, Vec<i64>, usize), DecodeError> {
+	let (header, start) = ArrayHeader::decode(buf)?;
+	let count = header.element_count().unwrap();
+	let width = header.dtype.width();
+	let (values, end) = match header.payload {
+		Payload::Raw => {
+			let len = usize::try_from(count).ok().and_then(|n| n.checked_mul(width)).ok_or(DecodeError::Overflow)?;
+			let end = start.checked_add(len).ok_or(DecodeError::Overflow)?;
+			let raw = buf.get(start..end).ok_or(DecodeError::Truncated { needed: end })?;
+			let values = raw.chunks(width).map(|c| {
+				let fill = if header.dtype.signed() && c[width - 1] & 0x80 != 0 { 0xff } else { 0 };
+				let mut bytes = [fill; 8];
+				bytes[..width].copy_from_slice(c);
+				i64::from_le_bytes(bytes)
+			});
+			(values.collect(), end)
+		}
+		Payload::Block => {
+			let (values, used) = decode_block(&buf[start..]).map_err(|e| match e {
+				DecodeError::Truncated { needed } => DecodeError::Truncated { needed: start + needed },
+				e => e,
+			})?;
+			if values.len() as u64 != count || values.iter().any(|&v| !header.dtype.contains(v)) {
+				return Err(DecodeError::Malformed("array block does not match its header"));
+			}
+			(values, start + used)
+		}
+	};
+	Ok((header, values, end))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(values: Vec<i64>, dtype: u8, block: bool) -> bool {
+		let dtype = DTYPES[dtype as usize % DTYPES.len()];
+		let bits = dtype.width() as u32 * 8;
+		let values: Vec<i64> = match (dtype.signed(), bits) {
+			(_, 64) => values,
+			(true, _) => values.into_iter().map(|v| v.wrapping_shl(64 - bits) >> (64 - bits)).collect(),
+			(false, _) => values.into_iter().map(|v| v & ((1 << bits) - 1)).collect(),
+		};
+		let payload = if block { Payload::Block } else { Payload::Raw };
+		let header = ArrayHeader { shape: vec![1, values.len() as u64], dtype, payload };
+		let buf = encode_array(&header, &values);
+		decode_array(&buf) == Ok((header, values, buf.len()))
+	}
+
+	#[test]
+	fn layout_and_errors() {
+		let header = ArrayHeader { shape: vec![2, 2], dtype: DType::I16, payload: Payload::Raw };
+		let buf = encode_array(&header, &[1, -1, 256, 0]);
+		assert_eq!(buf, vec![0x82, 0x82, 0x82, 0x81, 0x84, 0x80, 1, 0, 0xff, 0xff, 0, 1, 0, 0]);
+		assert_eq!(decode_array(&buf[..buf.len() - 1]), Err(DecodeError::Truncated { needed: buf.len() }));
+
+		let mut bad = buf.clone();
+		bad[4] = 0x85;
+		assert_eq!(decode_array(&bad), Err(DecodeError::Malformed("array element count does not match its shape")));
+		bad[3] = 0x90;
+		assert_eq!(decode_array(&bad), Err(DecodeError::Malformed("unknown array dtype")));
+	}
+}
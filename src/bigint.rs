@@ -0,0 +1,140 @@
+//! `num_bigint::BigInt` values of any size, in order.
+//!
+//! The encoding extends `wide` the way `wide` extends the 64-bit one, and
+//! agrees with both wherever they can hold a value. Values past the
+//! 128-bit classes carry their length, so there is no ceiling:
+//!
+//! ```text
+//! above u128:  [0xf1][len: uint][x - 8256, big-endian, len bytes]
+//! below i128:  [0x07] then [len: uint][-x - 1, big-endian], complemented
+//! ```
+//!
+//! Complementing the negative form sorts larger magnitudes first.
+//!
+//! ```
+//! use num_bigint::BigInt;
+//! use rust_varint::bigint::{decode_bigint, pack_bigint};
+//!
+//! let big = BigInt::from(1) << 200u32;
+//! assert!(pack_bigint(&BigInt::from(u128::MAX)) < pack_bigint(&big));
+//! assert_eq!(pack_bigint(&BigInt::from(-300)), rust_varint::pack_int(-300));
+//! assert_eq!(decode_bigint(&pack_bigint(&-&big)).unwrap().0, -big);
+//! ```
+
+use std::convert::TryFrom;
+
+use num_bigint::{BigInt, BigUint, Sign};
+
+use crate::wide::{decode_i128, decode_u128, pack_i128, pack_u128};
+use crate::{decode_uint, pack_uint_append, DecodeError, MAX_ENCODED_LEN, POS_2BYTE_MAX};
+
+const POS_BIG_MARKER: u8 = 0xf1;
+const NEG_BIG_MARKER: u8 = 0x07;
+// `wide`'s marker of a positive value with a 16-byte payload.
+const POS_16BYTE_MARKER: u8 = 0xf0;
+
+fn offset() -> BigInt {
+	BigInt::from(POS_2BYTE_MAX + 1)
+}
+
+/// Encodes `x` in the shortest class that holds it.
+pub fn pack_bigint(x: &BigInt) -> Vec<u8> {
+	if let Ok(v) = i128::try_from(x) {
+		return pack_i128(v);
+	}
+	if let Ok(v) = u128::try_from(x) {
+		return pack_u128(v);
+	}
+	if x.sign() == Sign::Minus {
+		let m = (-x - 1u32).magnitude().to_bytes_be();
+		let mut out = vec![NEG_BIG_MARKER];
+		pack_uint_append(m.len() as u64, &mut out);
+		out.extend_from_slice(&m);
+		out[1..].iter_mut().for_each(|b| *b = !*b);
+		return out;
+	}
+	let y = (x - offset()).magnitude().to_bytes_be();
+	let mut out = Vec::with_capacity(y.len() + MAX_ENCODED_LEN);
+	if y.len() == 16 {
+		out.push(POS_16BYTE_MARKER);
+	} else {
+		out.push(POS_BIG_MARKER);
+		pack_uint_append(y.len() as u64, &mut out);
+	}
+	out.extend_from_slice(&y);
+	out
+}
+
+/// Decodes a value written by `pack_bigint`, `wide::pack_i128` or
+/// `pack_int`, returning it with the number of bytes it occupied.
+pub fn decode_bigint(buf: &[u8]) -> Result<(BigInt, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	match first {
+		POS_16BYTE_MARKER => {
+			let y = buf.get(1..17).ok_or(DecodeError::Truncated { needed: 17 })?;
+			Ok((BigInt::from(BigUint::from_bytes_be(y)) + offset(), 17))
+		}
+		POS_BIG_MARKER => {
+			let (y, used) = read_magnitude(buf, 0x00)?;
+			Ok((BigInt::from(y) + offset(), used))
+		}
+		NEG_BIG_MARKER => {
+			let (m, used) = read_magnitude(buf, 0xff)?;
+			Ok((-BigInt::from(m) - 1u32, used))
+		}
+		0x80..=0xff => decode_u128(buf).map(|(x, used)| (BigInt::from(x), used)),
+		_ => decode_i128(buf).map(|(x, used)| (BigInt::from(x), used)),
+	}
+}
+
+// Reads the length and bytes after a big marker, each byte XORed with
+// `flip`.
+fn read_magnitude(buf: &[u8], flip: u8) -> Result<(BigUint, usize), DecodeError> {
+	let head: Vec<u8> = buf[1..].iter().take(MAX_ENCODED_LEN).map(|&b| b ^ flip).collect();
+	let (len, used) = decode_uint(&head).map_err(|e| match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: needed + 1 },
+		e => e,
+	})?;
+	let end = usize::try_from(len).ok().and_then(|len| (1 + used).checked_add(len)).ok_or(DecodeError::Overflow)?;
+	let bytes = buf.get(1 + used..end).ok_or(DecodeError::Truncated { needed: end })?;
+	let bytes: Vec<u8> = bytes.iter().map(|&b| b ^ flip).collect();
+	Ok((BigUint::from_bytes_be(&bytes), end))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn big((digits, negative): (Vec<u32>, bool)) -> BigInt {
+		let x = BigInt::from_slice(Sign::Plus, &digits);
+		if negative { -x } else { x }
+	}
+
+	#[quickcheck]
+	fn sorts_and_round_trips(a: (Vec<u32>, bool), b: (Vec<u32>, bool)) -> bool {
+		let (x, y) = (big(a), big(b));
+		let (px, py) = (pack_bigint(&x), pack_bigint(&y));
+		px.cmp(&py) == x.cmp(&y) && decode_bigint(&px) == Ok((x.clone(), px.len())) &&
+			i128::try_from(&x).map_or(true, |v| px == pack_i128(v))
+	}
+
+	#[test]
+	fn class_edges_are_ordered() {
+		let max = BigInt::from(u128::MAX);
+		let min = BigInt::from(i128::MIN);
+		let edges = [-(BigInt::from(1) << 300u32), &min - 256, &min - 1, min.clone(), BigInt::from(0),
+			max.clone(), &max + 8256, &max + 8257, BigInt::from(1) << 300u32];
+		for pair in edges.windows(2) {
+			let (a, b) = (pack_bigint(&pair[0]), pack_bigint(&pair[1]));
+			assert!(a < b, "{} {}", pair[0], pair[1]);
+			assert_eq!(decode_bigint(&a), Ok((pair[0].clone(), a.len())));
+		}
+		assert_eq!(pack_bigint(&max), pack_u128(u128::MAX));
+		assert_eq!(pack_bigint(&(&max + 8256))[0], 0xf0);
+		assert_eq!(pack_bigint(&(&max + 8257))[..2], [0xf1, 0x91]);
+		assert_eq!(pack_bigint(&(&min - 1))[..2], [0x07, !0x90]);
+		assert_eq!(decode_bigint(&[0xf1, 0x91, 0x01]), Err(DecodeError::Truncated { needed: 19 }));
+		assert_eq!(decode_bigint(&[0xf1, 0xe8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]), Err(DecodeError::Overflow));
+		assert_eq!(decode_bigint(&[0x07]), Err(DecodeError::Truncated { needed: 2 }));
+	}
+}
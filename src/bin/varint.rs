@@ -0,0 +1,47 @@
+//! Command-line tools for inspecting encoded data.
+//!
+//! ```text
+//! varint analyze [FILE]    size distribution of a run of blocks
+//! ```
+//!
+//! Input is read from `FILE`, or from stdin when it is omitted.
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+use rust_varint::block;
+
+const USAGE: &str = "usage: varint analyze [FILE]";
+
+fn read_input(path: Option<&str>) -> io::Result<Vec<u8>> {
+	match path {
+		Some(path) => fs::read(path),
+		None => {
+			let mut buf = Vec::new();
+			io::stdin().read_to_end(&mut buf)?;
+			Ok(buf)
+		}
+	}
+}
+
+fn fail(msg: &str, code: i32) -> ! {
+	eprintln!("varint: {}", msg);
+	process::exit(code)
+}
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+	match args.as_slice() {
+		["analyze"] | ["analyze", _] => {
+			let input = read_input(args.get(1).copied()).unwrap_or_else(|e| fail(&e.to_string(), 1));
+			match block::analyze(&input) {
+				Ok(report) => println!("{}", report),
+				Err(e) => fail(&e.to_string(), 1),
+			}
+		}
+		_ => fail(USAGE, 2),
+	}
+}
@@ -0,0 +1,155 @@
+//! MSB-first bit-level writer and reader, shared by the bit-oriented
+//! codecs such as `formats::golomb`.
+
+/// Appends bits to a byte buffer, most significant bit first.
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+	buf: Vec<u8>,
+	// bits already used in the last byte of `buf`, 0 when byte-aligned
+	used: u32,
+}
+
+impl BitWriter {
+	pub fn write_bit(&mut self, bit: bool) {
+		self.write_bits(bit as u64, 1);
+	}
+
+	/// Writes the low `n` bits of `value`. Panics if `n > 64`.
+	pub fn write_bits(&mut self, value: u64, mut n: u32) {
+		assert!(n <= 64, "cannot write {} bits at once", n);
+		while n > 0 {
+			if self.used == 0 {
+				self.buf.push(0);
+			}
+			let free = 8 - self.used;
+			let take = free.min(n);
+			let chunk = (value >> (n - take)) & ((1u64 << take) - 1);
+			*self.buf.last_mut().unwrap() |= (chunk as u8) << (free - take);
+			self.used = (self.used + take) % 8;
+			n -= take;
+		}
+	}
+
+	/// Writes `n` one bits and then a zero.
+	pub fn write_unary(&mut self, mut n: u64) {
+		while n >= 64 {
+			self.write_bits(u64::MAX, 64);
+			n -= 64;
+		}
+		self.write_bits(((1 << n) - 1) << 1, n as u32 + 1);
+	}
+
+	/// Number of bits written so far.
+	pub fn bit_len(&self) -> u64 {
+		let full = self.buf.len() as u64 * 8;
+		if self.used == 0 { full } else { full - 8 + self.used as u64 }
+	}
+
+	/// Returns the written bytes, zero-padding the final partial byte.
+	pub fn finish(self) -> Vec<u8> {
+		self.buf
+	}
+}
+
+/// Reads bits written by `BitWriter`.
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+	buf: &'a [u8],
+	pos: u64,
+}
+
+impl<'a> BitReader<'a> {
+	pub fn new(buf: &'a [u8]) -> BitReader<'a> {
+		BitReader { buf, pos: 0 }
+	}
+
+	pub fn read_bit(&mut self) -> Option<bool> {
+		self.read_bits(1).map(|b| b == 1)
+	}
+
+	/// Reads `n` bits as the low bits of the result. Panics if `n > 64`.
+	pub fn read_bits(&mut self, n: u32) -> Option<u64> {
+		assert!(n <= 64, "cannot read {} bits at once", n);
+		if self.remaining() < n as u64 {
+			return None;
+		}
+		let mut value = 0u64;
+		let mut left = n;
+		while left > 0 {
+			let byte = self.buf[(self.pos / 8) as usize];
+			let offset = (self.pos % 8) as u32;
+			let take = (8 - offset).min(left);
+			let chunk = (byte >> (8 - offset - take)) & ((1u16 << take) - 1) as u8;
+			value = (value << take) | chunk as u64;
+			self.pos += take as u64;
+			left -= take;
+		}
+		Some(value)
+	}
+
+	/// Reads one bits up to and including a zero, returning how many ones
+	/// there were.
+	pub fn read_unary(&mut self) -> Option<u64> {
+		let start = self.pos;
+		while self.read_bit()? {}
+		Some(self.pos - start - 1)
+	}
+
+	/// Number of bits read so far.
+	pub fn bit_pos(&self) -> u64 {
+		self.pos
+	}
+
+	pub fn remaining(&self) -> u64 {
+		self.buf.len() as u64 * 8 - self.pos
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(fields: Vec<(u64, u8)>) -> bool {
+		let fields: Vec<(u64, u32)> = fields
+			.into_iter()
+			.map(|(v, n)| {
+				let n = n as u32 % 65;
+				(if n == 64 { v } else { v & ((1u64 << n) - 1) }, n)
+			})
+			.collect();
+
+		let mut w = BitWriter::default();
+		for &(v, n) in &fields {
+			w.write_bits(v, n);
+		}
+		let total: u64 = fields.iter().map(|&(_, n)| n as u64).sum();
+		if w.bit_len() != total {
+			return false;
+		}
+
+		let bytes = w.finish();
+		let mut r = BitReader::new(&bytes);
+		fields.iter().all(|&(v, n)| r.read_bits(n) == Some(v)) && r.remaining() < 8
+	}
+
+	#[test]
+	fn msb_first_layout() {
+		let mut w = BitWriter::default();
+		w.write_bit(true);
+		w.write_bits(0b01, 2);
+		w.write_bits(0xff, 8);
+		assert_eq!(w.finish(), vec![0b1011_1111, 0b1110_0000]);
+	}
+
+	#[quickcheck]
+	fn unary_round_trip(runs: Vec<u8>) -> bool {
+		let mut w = BitWriter::default();
+		for &n in &runs {
+			w.write_unary(n as u64);
+		}
+		let bytes = w.finish();
+		let mut r = BitReader::new(&bytes);
+		runs.iter().all(|&n| r.read_unary() == Some(n as u64)) && r.remaining() < 8
+	}
+}
@@ -0,0 +1,302 @@
+//! Succinct bitvector with rank and select support.
+//!
+//! Bits are grouped into 512-bit superblocks. The number of set bits in
+//! each superblock is kept alongside the raw words, so `rank` touches at
+//! most eight words and `select` binary-searches the superblocks first.
+//! When serialized the per-superblock counts are written as varints, which
+//! keeps the index overhead to one or two bytes per 64 bytes of bits.
+
+use crate::{decode_uint, pack_uint, DecodeError};
+
+const WORD_BITS: usize = 64;
+const SUPERBLOCK_WORDS: usize = 8;
+const SUPERBLOCK_BITS: usize = WORD_BITS * SUPERBLOCK_WORDS;
+
+/// Accumulates bits before the rank/select index is built.
+#[derive(Debug, Clone, Default)]
+pub struct BitVecBuilder {
+	words: Vec<u64>,
+	len: usize,
+}
+
+impl BitVecBuilder {
+	pub fn new() -> BitVecBuilder {
+		BitVecBuilder::default()
+	}
+
+	/// Creates a builder holding `len` zero bits.
+	pub fn with_len(len: usize) -> BitVecBuilder {
+		BitVecBuilder {
+			words: vec![0; words_for(len)],
+			len,
+		}
+	}
+
+	pub fn push(&mut self, bit: bool) {
+		if self.len.is_multiple_of(WORD_BITS) {
+			self.words.push(0);
+		}
+		if bit {
+			self.words[self.len / WORD_BITS] |= 1 << (self.len % WORD_BITS);
+		}
+		self.len += 1;
+	}
+
+	/// Sets bit `i` to one. Panics if `i` is out of bounds.
+	pub fn set(&mut self, i: usize) {
+		assert!(i < self.len, "bit index {} out of range for length {}", i, self.len);
+		self.words[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	pub fn build(self) -> BitVec {
+		BitVec::from_words(self.words, self.len)
+	}
+}
+
+/// Immutable bitvector answering `rank` and `select` queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitVec {
+	words: Vec<u64>,
+	len: usize,
+	// ones[j] is the number of set bits before superblock j; the last
+	// entry holds the total.
+	ones: Vec<u64>,
+}
+
+impl BitVec {
+	fn from_words(words: Vec<u64>, len: usize) -> BitVec {
+		let mut ones = Vec::with_capacity(words.len() / SUPERBLOCK_WORDS + 2);
+		let mut total = 0u64;
+		ones.push(0);
+		for sb in words.chunks(SUPERBLOCK_WORDS) {
+			total += sb.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+			ones.push(total);
+		}
+		BitVec { words, len, ones }
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Returns bit `i`. Panics if `i` is out of bounds.
+	pub fn get(&self, i: usize) -> bool {
+		assert!(i < self.len, "bit index {} out of range for length {}", i, self.len);
+		self.words[i / WORD_BITS] >> (i % WORD_BITS) & 1 == 1
+	}
+
+	pub fn count_ones(&self) -> usize {
+		*self.ones.last().unwrap() as usize
+	}
+
+	pub fn count_zeros(&self) -> usize {
+		self.len - self.count_ones()
+	}
+
+	/// Number of set bits in positions `[0, i)`. Panics if `i > len`.
+	pub fn rank1(&self, i: usize) -> usize {
+		assert!(i <= self.len, "rank position {} out of range for length {}", i, self.len);
+		let word = i / WORD_BITS;
+		let sb = i / SUPERBLOCK_BITS;
+		let mut count = self.ones[sb] as usize;
+		for w in &self.words[sb * SUPERBLOCK_WORDS..word] {
+			count += w.count_ones() as usize;
+		}
+		let rem = i % WORD_BITS;
+		if rem != 0 {
+			count += (self.words[word] & ((1 << rem) - 1)).count_ones() as usize;
+		}
+		count
+	}
+
+	/// Number of zero bits in positions `[0, i)`. Panics if `i > len`.
+	pub fn rank0(&self, i: usize) -> usize {
+		i - self.rank1(i)
+	}
+
+	/// Position of the `k`-th set bit (counting from zero), if there is one.
+	pub fn select1(&self, k: usize) -> Option<usize> {
+		if k >= self.count_ones() {
+			return None;
+		}
+		// last superblock whose preceding count is <= k
+		let sb = self.ones.partition_point(|&c| c as usize <= k) - 1;
+		let mut remaining = k - self.ones[sb] as usize;
+		for (w, &bits) in self.words.iter().enumerate().skip(sb * SUPERBLOCK_WORDS) {
+			let n = bits.count_ones() as usize;
+			if remaining < n {
+				return Some(w * WORD_BITS + select_in_word(bits, remaining));
+			}
+			remaining -= n;
+		}
+		None
+	}
+
+	/// Position of the `k`-th zero bit (counting from zero), if there is one.
+	pub fn select0(&self, k: usize) -> Option<usize> {
+		if k >= self.count_zeros() {
+			return None;
+		}
+		let zeros_before = |j: usize| j * SUPERBLOCK_BITS - self.ones[j] as usize;
+		let mut lo = 0;
+		let mut hi = self.ones.len() - 1;
+		while hi - lo > 1 {
+			let mid = (lo + hi) / 2;
+			if zeros_before(mid) <= k {
+				lo = mid;
+			} else {
+				hi = mid;
+			}
+		}
+		let mut remaining = k - zeros_before(lo);
+		for (w, &bits) in self.words.iter().enumerate().skip(lo * SUPERBLOCK_WORDS) {
+			let n = bits.count_zeros() as usize;
+			if remaining < n {
+				let pos = w * WORD_BITS + select_in_word(!bits, remaining);
+				return if pos < self.len { Some(pos) } else { None };
+			}
+			remaining -= n;
+		}
+		None
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+		(0..self.len).map(move |i| self.get(i))
+	}
+
+	/// Serializes as `[len][per-superblock counts][words]`, with the length
+	/// and counts packed as varints and the words little-endian.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = pack_uint(self.len as u64);
+		for pair in self.ones.windows(2) {
+			out.extend_from_slice(&pack_uint(pair[1] - pair[0]));
+		}
+		for w in &self.words {
+			out.extend_from_slice(&w.to_le_bytes());
+		}
+		out
+	}
+
+	/// Parses the output of `to_bytes`, returning the bitvector and the
+	/// number of bytes consumed. The stored counts are checked against the
+	/// words, so a corrupted page is reported rather than answering
+	/// queries incorrectly.
+	pub fn from_bytes(buf: &[u8]) -> Result<(BitVec, usize), DecodeError> {
+		let (len, mut pos) = decode_uint(buf)?;
+		let len = len as usize;
+		let nwords = words_for(len);
+		let nsuper = nwords.div_ceil(SUPERBLOCK_WORDS);
+
+		let mut counts = Vec::with_capacity(nsuper.min(buf.len()));
+		for _ in 0..nsuper {
+			let (count, used) = decode_uint(&buf[pos..])?;
+			counts.push(count);
+			pos += used;
+		}
+
+		let end = pos + nwords * 8;
+		if buf.len() < end {
+			return Err(DecodeError::Truncated { needed: end });
+		}
+		let words: Vec<u64> = buf[pos..end]
+			.chunks(8)
+			.map(|c| {
+				let mut b = [0u8; 8];
+				b.copy_from_slice(c);
+				u64::from_le_bytes(b)
+			})
+			.collect();
+
+		if !len.is_multiple_of(WORD_BITS) && words[nwords - 1] >> (len % WORD_BITS) != 0 {
+			return Err(DecodeError::Malformed("bits set past the end of the bitvector"));
+		}
+		let bv = BitVec::from_words(words, len);
+		let consistent = bv.ones.windows(2).zip(&counts).all(|(pair, &c)| pair[1] - pair[0] == c);
+		if !consistent {
+			return Err(DecodeError::Malformed("superblock count does not match its bits"));
+		}
+		Ok((bv, end))
+	}
+}
+
+impl std::iter::FromIterator<bool> for BitVec {
+	fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> BitVec {
+		let mut builder = BitVecBuilder::new();
+		for bit in iter {
+			builder.push(bit);
+		}
+		builder.build()
+	}
+}
+
+fn words_for(bits: usize) -> usize {
+	bits.div_ceil(WORD_BITS)
+}
+
+// Position of the k-th set bit within a single word.
+fn select_in_word(mut bits: u64, k: usize) -> usize {
+	for _ in 0..k {
+		bits &= bits - 1;
+	}
+	bits.trailing_zeros() as usize
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn rank_matches_naive(bits: Vec<bool>) -> bool {
+		let bv: BitVec = bits.iter().cloned().collect();
+		(0..=bits.len()).all(|i| bv.rank1(i) == bits[..i].iter().filter(|&&b| b).count())
+	}
+
+	#[quickcheck]
+	fn select_matches_naive(bits: Vec<bool>) -> bool {
+		let bv: BitVec = bits.iter().cloned().collect();
+		let ones: Vec<usize> = (0..bits.len()).filter(|&i| bits[i]).collect();
+		let zeros: Vec<usize> = (0..bits.len()).filter(|&i| !bits[i]).collect();
+		(0..=ones.len()).all(|k| bv.select1(k) == ones.get(k).cloned()) &&
+			(0..=zeros.len()).all(|k| bv.select0(k) == zeros.get(k).cloned())
+	}
+
+	#[test]
+	fn spans_many_superblocks() {
+		let mut builder = BitVecBuilder::with_len(5000);
+		for i in (0..5000).step_by(3) {
+			builder.set(i);
+		}
+		let bv = builder.build();
+		assert_eq!(bv.count_ones(), 1667);
+		assert_eq!(bv.rank1(4096), 1366);
+		assert_eq!(bv.select1(1000), Some(3000));
+		assert_eq!(bv.select0(1000), Some(1501));
+		assert_eq!(bv.select1(1667), None);
+	}
+
+	#[test]
+	fn bytes_round_trip() {
+		let bv: BitVec = (0..2000).map(|i| i % 7 == 0 || i % 11 == 0).collect();
+		let mut bytes = bv.to_bytes();
+		bytes.extend_from_slice(b"tail");
+		let (back, used) = BitVec::from_bytes(&bytes).unwrap();
+		assert_eq!(back, bv);
+		assert_eq!(&bytes[used..], b"tail");
+
+		assert!(BitVec::from_bytes(&bytes[..used - 1]).is_err());
+		bytes[3] ^= 1;
+		assert!(BitVec::from_bytes(&bytes).is_err());
+	}
+}
@@ -0,0 +1,412 @@
+//! Sealed blocks of signed integers.
+//!
+//! A block is self-delimiting, so blocks can be concatenated and walked
+//! without an external index:
+//!
+//! ```text
+//! [count: uint][payload length: uint][count packed ints ...]
+//! ```
+//!
+//! The `_with` variants store the values in another `VarintFormat`; the
+//! header stays in the crate's own encoding.
+//!
+//! A reference block stores clustered values, such as sensor readings
+//! around some level, as their distance from the block's minimum:
+//!
+//! ```text
+//! [count: uint][payload length: uint][minimum: int][value - minimum: uint ...]
+//! ```
+
+use std::convert::TryInto;
+use std::fmt;
+
+use crate::formats::{Ordered, VarintFormat};
+use crate::{decode_int, decode_int_at, decode_uint, decode_uint_at, pack_int, pack_int_append, pack_uint,
+	pack_uint_append, DecodeError, MAX_ENCODED_LEN};
+
+/// Packs `values` into a single sealed block.
+pub fn encode_block(values: &[i64]) -> Vec<u8> {
+	encode_block_with(Ordered, values)
+}
+
+pub(crate) fn seal(count: usize, payload: Vec<u8>) -> Vec<u8> {
+	let mut out = pack_uint(count as u64);
+	out.extend_from_slice(&pack_uint(payload.len() as u64));
+	out.extend_from_slice(&payload);
+	out
+}
+
+// Splits the sealed block at the front of `buf` into its count and
+// payload, with the block's total length.
+pub(crate) fn unseal(buf: &[u8]) -> Result<(u64, &[u8], usize), DecodeError> {
+	let (count, n1) = decode_uint(buf)?;
+	let (len, n2) = decode_uint(&buf[n1..])?;
+	let start = n1 + n2;
+	let end = start.checked_add(len as usize).ok_or(DecodeError::Overflow)?;
+	if buf.len() < end {
+		return Err(DecodeError::Truncated { needed: end });
+	}
+	if count > len {
+		return Err(DecodeError::Malformed("block count exceeds its payload length"));
+	}
+	Ok((count, &buf[start..end], end))
+}
+
+/// Packs `values` into a sealed block, each value in `format`.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(values = values.len())))]
+pub fn encode_block_with<F: VarintFormat>(format: F, values: &[i64]) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(values.len());
+	for &v in values {
+		format.encode_i64_into(&mut payload, v);
+	}
+	let out = seal(values.len(), payload);
+	trace!(bytes = out.len(), "encoded block");
+	out
+}
+
+/// Decodes the block at the front of `buf`, returning its values and the
+/// number of bytes it occupied.
+pub fn decode_block(buf: &[u8]) -> Result<(Vec<i64>, usize), DecodeError> {
+	decode_block_with(Ordered, buf)
+}
+
+/// Decodes a block written by `encode_block_with` with the same format.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = buf.len()), err))]
+pub fn decode_block_with<F: VarintFormat>(format: F, buf: &[u8]) -> Result<(Vec<i64>, usize), DecodeError> {
+	let (iter, used) = BlockIter::with_format(format, buf)?;
+	let values = iter.collect::<Result<Vec<i64>, DecodeError>>()?;
+	trace!(values = values.len(), bytes = used, "decoded block");
+	Ok((values, used))
+}
+
+/// Packs `values` into a reference block, see the module docs. An empty
+/// block has no minimum and is the same as an empty `encode_block`.
+pub fn encode_reference_block(values: &[i64]) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(values.len() + MAX_ENCODED_LEN);
+	if let Some(&min) = values.iter().min() {
+		pack_int_append(min, &mut payload);
+		for &v in values {
+			pack_uint_append(v.wrapping_sub(min) as u64, &mut payload);
+		}
+	}
+	seal(values.len(), payload)
+}
+
+/// Decodes the reference block at the front of `buf`, returning its
+/// values and the number of bytes it occupied.
+pub fn decode_reference_block(buf: &[u8]) -> Result<(Vec<i64>, usize), DecodeError> {
+	let (count, payload, used) = unseal(buf)?;
+	let mut values = Vec::with_capacity(count as usize);
+	let mut pos = 0;
+	if count > 0 {
+		let min = decode_int_at(payload, &mut pos)?;
+		for _ in 0..count {
+			let offset = decode_uint_at(payload, &mut pos)?;
+			let v = (min as i128 + offset as i128).try_into().map_err(|_| DecodeError::Overflow)?;
+			values.push(v);
+		}
+	}
+	if pos != payload.len() {
+		return Err(DecodeError::Malformed("trailing bytes after the last block value"));
+	}
+	Ok((values, used))
+}
+
+/// Summary of the values in one block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+	pub count: u64,
+	/// `None` for an empty block, like `max`.
+	pub min: Option<i64>,
+	pub max: Option<i64>,
+	/// Wide enough that it cannot overflow for any block.
+	pub sum: i128,
+}
+
+/// Folds `f` over the values of the block at the front of `buf` without
+/// collecting them, returning the result and the size of the block.
+pub fn reduce<T, F>(buf: &[u8], init: T, mut f: F) -> Result<(T, usize), DecodeError>
+where
+	F: FnMut(T, i64) -> T,
+{
+	let (iter, used) = BlockIter::new(buf)?;
+	let mut acc = init;
+	for v in iter {
+		acc = f(acc, v?);
+	}
+	Ok((acc, used))
+}
+
+/// Count, min, max and sum of the block at the front of `buf`, in one
+/// decoding pass.
+pub fn block_stats(buf: &[u8]) -> Result<(Stats, usize), DecodeError> {
+	reduce(buf, Stats::default(), |s, v| Stats {
+		count: s.count + 1,
+		min: Some(s.min.map_or(v, |m| m.min(v))),
+		max: Some(s.max.map_or(v, |m| m.max(v))),
+		sum: s.sum + v as i128,
+	})
+}
+
+/// How many values took each encoded length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClassCounts {
+	pub one_byte: u64,
+	pub two_byte: u64,
+	pub multi_byte: u64,
+}
+
+impl ClassCounts {
+	pub fn values(&self) -> u64 {
+		self.one_byte + self.two_byte + self.multi_byte
+	}
+
+	fn add(&mut self, other: ClassCounts) {
+		self.one_byte += other.one_byte;
+		self.two_byte += other.two_byte;
+		self.multi_byte += other.multi_byte;
+	}
+}
+
+/// Size distribution of a run of concatenated blocks, see `analyze`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Report {
+	/// One entry per block, in order.
+	pub blocks: Vec<ClassCounts>,
+	pub total: ClassCounts,
+	/// Encoded size, headers included.
+	pub bytes: usize,
+}
+
+impl Report {
+	/// Size the values would take as fixed 8-byte integers, divided by
+	/// their encoded size.
+	pub fn compression_ratio(&self) -> f64 {
+		(self.total.values() * 8) as f64 / self.bytes.max(1) as f64
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let pct = |n: u64| 100.0 * n as f64 / self.total.values().max(1) as f64;
+		writeln!(f, "blocks:      {}", self.blocks.len())?;
+		writeln!(f, "values:      {}", self.total.values())?;
+		writeln!(f, "1-byte:      {} ({:.1}%)", self.total.one_byte, pct(self.total.one_byte))?;
+		writeln!(f, "2-byte:      {} ({:.1}%)", self.total.two_byte, pct(self.total.two_byte))?;
+		writeln!(f, "multi-byte:  {} ({:.1}%)", self.total.multi_byte, pct(self.total.multi_byte))?;
+		writeln!(f, "bytes:       {}", self.bytes)?;
+		write!(f, "vs 8-byte:   {:.2}x", self.compression_ratio())
+	}
+}
+
+/// Walks every block in `buf` and counts how many values fell into each
+/// length class, to help tune key and value designs.
+pub fn analyze(buf: &[u8]) -> Result<Report, DecodeError> {
+	let mut report = Report { bytes: buf.len(), ..Report::default() };
+	let mut pos = 0;
+	while pos < buf.len() {
+		let (iter, used) = BlockIter::new(&buf[pos..])?;
+		let mut counts = ClassCounts::default();
+		let mut payload = iter.payload;
+		for _ in 0..iter.remaining {
+			let (_, len) = decode_int(payload)?;
+			match len {
+				1 => counts.one_byte += 1,
+				2 => counts.two_byte += 1,
+				_ => counts.multi_byte += 1,
+			}
+			payload = &payload[len..];
+		}
+		if !payload.is_empty() {
+			return Err(DecodeError::Malformed("trailing bytes after the last block value"));
+		}
+		report.total.add(counts);
+		report.blocks.push(counts);
+		pos += used;
+	}
+	Ok(report)
+}
+
+/// Rewrites the block at the front of `buf` with every value shifted by
+/// `delta`, in one pass over the payload. Fails with `Overflow` if any
+/// shifted value leaves the `i64` range.
+pub fn add_offset_block(buf: &[u8], delta: i64) -> Result<Vec<u8>, DecodeError> {
+	let (iter, used) = BlockIter::new(buf)?;
+	let count = iter.remaining() as usize;
+	let mut payload = Vec::with_capacity(used);
+	for v in iter {
+		let v = v?.checked_add(delta).ok_or(DecodeError::Overflow)?;
+		payload.extend_from_slice(&pack_int(v));
+	}
+	Ok(seal(count, payload))
+}
+
+/// Lazily decodes the values of one block.
+#[derive(Debug, Clone)]
+pub struct BlockIter<'a, F = Ordered> {
+	format: F,
+	payload: &'a [u8],
+	remaining: u64,
+	failed: bool,
+}
+
+impl<'a> BlockIter<'a> {
+	/// Parses the block header at the front of `buf`. Returns the iterator
+	/// and the total size of the block, so the caller can step to the next.
+	pub fn new(buf: &'a [u8]) -> Result<(BlockIter<'a>, usize), DecodeError> {
+		BlockIter::with_format(Ordered, buf)
+	}
+}
+
+impl<'a, F: VarintFormat> BlockIter<'a, F> {
+	/// `new` for a block whose values are in `format`.
+	pub fn with_format(format: F, buf: &'a [u8]) -> Result<(BlockIter<'a, F>, usize), DecodeError> {
+		let (count, payload, end) = unseal(buf)?;
+		Ok((BlockIter { format, payload, remaining: count, failed: false }, end))
+	}
+
+	/// Number of values not yet yielded.
+	pub fn remaining(&self) -> u64 {
+		self.remaining
+	}
+}
+
+impl<'a, F: VarintFormat> Iterator for BlockIter<'a, F> {
+	type Item = Result<i64, DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.failed {
+			return None;
+		}
+		if self.remaining == 0 {
+			if !self.payload.is_empty() {
+				self.failed = true;
+				return Some(Err(DecodeError::Malformed("trailing bytes after the last block value")));
+			}
+			return None;
+		}
+		match self.format.decode_i64_from(self.payload) {
+			Ok((v, used)) => {
+				self.payload = &self.payload[used..];
+				self.remaining -= 1;
+				Some(Ok(v))
+			}
+			Err(e) => {
+				self.failed = true;
+				Some(Err(e))
+			}
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(0, Some(self.remaining as usize + 1))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(values: Vec<i64>) -> bool {
+		let block = encode_block(&values);
+		decode_block(&block) == Ok((values, block.len()))
+	}
+
+	#[quickcheck]
+	fn reference_blocks_round_trip(level: i64, offsets: Vec<i8>, wide: Vec<i64>) -> bool {
+		let near: Vec<i64> = offsets.iter().map(|&o| level.saturating_add(o as i64)).collect();
+		let (a, b) = (encode_reference_block(&near), encode_reference_block(&wide));
+		// two bytes an offset at most, whatever the level
+		decode_reference_block(&a) == Ok((near.clone(), a.len())) && decode_reference_block(&b) == Ok((wide, b.len())) &&
+			a.len() <= 2 * MAX_ENCODED_LEN + MAX_ENCODED_LEN + 2 * near.len()
+	}
+
+	#[test]
+	fn reference_blocks_shrink_clustered_values() {
+		let readings = [1_000_000_020, 1_000_000_003, 1_000_000_041, 1_000_000_000];
+		let block = encode_reference_block(&readings);
+		assert_eq!(block[2..], [pack_int(1_000_000_000), vec![0x94, 0x83, 0xa9, 0x80]].concat()[..]);
+		assert!(block.len() < encode_block(&readings).len());
+		assert_eq!(decode_reference_block(&encode_reference_block(&[i64::MIN, i64::MAX])).unwrap().0, [i64::MIN, i64::MAX]);
+		assert_eq!(encode_reference_block(&[]), encode_block(&[]));
+		// an offset that runs past i64::MAX
+		let bad = seal(1, [pack_int(i64::MAX), pack_uint(1)].concat());
+		assert_eq!(decode_reference_block(&bad), Err(DecodeError::Overflow));
+	}
+
+	#[quickcheck]
+	fn add_offset_shifts_values(values: Vec<i64>, delta: i64) -> bool {
+		let block = encode_block(&values);
+		match values.iter().map(|v| v.checked_add(delta)).collect::<Option<Vec<i64>>>() {
+			Some(shifted) => add_offset_block(&block, delta) == Ok(encode_block(&shifted)),
+			None => add_offset_block(&block, delta) == Err(DecodeError::Overflow),
+		}
+	}
+
+	#[quickcheck]
+	fn round_trip_with_formats(values: Vec<i64>) -> bool {
+		use crate::formats::Format;
+		let formats = [Format::Ordered, Format::Cassandra, Format::PrefixVarint, Format::Leb128, Format::FastLE,
+			Format::Sqlite, Format::GitOffset, Format::CompactSize];
+		formats.iter().all(|&f| {
+			let block = encode_block_with(f, &values);
+			decode_block_with(f, &block) == Ok((values.clone(), block.len()))
+		}) && encode_block_with(Ordered, &values) == encode_block(&values)
+	}
+
+	#[test]
+	fn analyze_counts_classes() {
+		let mut buf = encode_block(&[0, 1, -1, 100, 1 << 40]);
+		buf.extend(encode_block(&[-100, 5]));
+		let report = analyze(&buf).unwrap();
+		assert_eq!(report.blocks, vec![
+			ClassCounts { one_byte: 3, two_byte: 1, multi_byte: 1 },
+			ClassCounts { one_byte: 1, two_byte: 1, multi_byte: 0 },
+		]);
+		assert_eq!((report.total.values(), report.bytes), (7, buf.len()));
+		assert!((report.compression_ratio() - 56.0 / buf.len() as f64).abs() < 1e-9);
+		assert!(analyze(&buf[..buf.len() - 1]).is_err());
+	}
+
+	#[quickcheck]
+	fn stats_match_values(values: Vec<i64>) -> bool {
+		let block = encode_block(&values);
+		let want = Stats {
+			count: values.len() as u64,
+			min: values.iter().copied().min(),
+			max: values.iter().copied().max(),
+			sum: values.iter().map(|&v| v as i128).sum(),
+		};
+		block_stats(&block) == Ok((want, block.len())) &&
+			reduce(&block, 0usize, |n, v| n + (v < 0) as usize) ==
+				Ok((values.iter().filter(|&&v| v < 0).count(), block.len()))
+	}
+
+	#[test]
+	fn stats_do_not_overflow() {
+		let (stats, _) = block_stats(&encode_block(&[i64::MAX, i64::MAX, i64::MIN])).unwrap();
+		assert_eq!((stats.min, stats.max, stats.sum), (Some(i64::MIN), Some(i64::MAX), i64::MAX as i128 - 1));
+		assert_eq!(block_stats(&encode_block(&[])), Ok((Stats::default(), 2)));
+	}
+
+	#[test]
+	fn add_offset_detects_overflow() {
+		let block = encode_block(&[-5, 0, i64::MAX - 10]);
+		assert_eq!(add_offset_block(&block, 10), Ok(encode_block(&[5, 10, i64::MAX])));
+		assert_eq!(add_offset_block(&block, 11), Err(DecodeError::Overflow));
+		assert_eq!(add_offset_block(&block, i64::MIN), Err(DecodeError::Overflow));
+		assert_eq!(add_offset_block(&block, -i64::MAX + 4), Ok(encode_block(&[i64::MIN, -i64::MAX + 4, -6])));
+	}
+
+	#[test]
+	fn rejects_inconsistent_headers() {
+		let mut block = encode_block(&[1, -200, 70_000]);
+		assert!(decode_block(&block[..block.len() - 1]).is_err());
+
+		// claim one value fewer than the payload holds
+		block[0] = pack_uint(2)[0];
+		assert_eq!(decode_block(&block),
+			Err(DecodeError::Malformed("trailing bytes after the last block value")));
+	}
+}
@@ -0,0 +1,95 @@
+//! Reading and writing values on `bytes` buffers, so `Bytes` and
+//! `BytesMut` need no copy into a `Vec` to decode one integer.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use rust_varint::buf::{get_varint_i64, get_varint_u64, put_varint_i64, put_varint_u64};
+//!
+//! let mut b = BytesMut::new();
+//! put_varint_u64(&mut b, 300);
+//! put_varint_i64(&mut b, -2);
+//! let mut b = b.freeze();
+//! assert_eq!(get_varint_u64(&mut b), Ok(300));
+//! assert_eq!(get_varint_i64(&mut b), Ok(-2));
+//! ```
+
+use bytes::{Buf, BufMut};
+
+use crate::{
+	decode_int, decode_uint, int_len_from_marker, pack_int_into_array, pack_uint_into_array, uint_len_from_marker,
+	DecodeError, MAX_ENCODED_LEN,
+};
+
+pub fn put_varint_u64<B: BufMut + ?Sized>(buf: &mut B, x: u64) {
+	let mut out = [0; MAX_ENCODED_LEN];
+	let len = pack_uint_into_array(x, &mut out);
+	buf.put_slice(&out[..len]);
+}
+
+pub fn put_varint_i64<B: BufMut + ?Sized>(buf: &mut B, x: i64) {
+	let mut out = [0; MAX_ENCODED_LEN];
+	let len = pack_int_into_array(x, &mut out);
+	buf.put_slice(&out[..len]);
+}
+
+/// Takes the unsigned value at the front of `buf`. Nothing is consumed
+/// if the value is truncated or has an invalid marker.
+pub fn get_varint_u64<B: Buf + ?Sized>(buf: &mut B) -> Result<u64, DecodeError> {
+	get_value(buf, uint_len_from_marker, decode_uint)
+}
+
+/// Signed counterpart of `get_varint_u64`.
+pub fn get_varint_i64<B: Buf + ?Sized>(buf: &mut B) -> Result<i64, DecodeError> {
+	get_value(buf, int_len_from_marker, decode_int)
+}
+
+// Decodes in place when the front chunk holds the whole value, which is
+// always the case for contiguous buffers. Otherwise the value is copied
+// out, and consumed even if it then overflows.
+fn get_value<B, T, L, D>(buf: &mut B, len_from_marker: L, decode: D) -> Result<T, DecodeError>
+where
+	B: Buf + ?Sized,
+	L: Fn(u8) -> Result<usize, DecodeError>,
+	D: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+{
+	let first = *buf.chunk().first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = len_from_marker(first)?;
+	if buf.remaining() < len {
+		return Err(DecodeError::Truncated { needed: len });
+	}
+	if buf.chunk().len() >= len {
+		let (v, _) = decode(&buf.chunk()[..len])?;
+		buf.advance(len);
+		return Ok(v);
+	}
+	let mut out = [0; MAX_ENCODED_LEN];
+	buf.copy_to_slice(&mut out[..len]);
+	decode(&out[..len]).map(|(v, _)| v)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_int;
+
+	#[quickcheck]
+	fn reads_across_chunks(x: i64, y: u64, split: u8) -> bool {
+		let mut b = Vec::new();
+		put_varint_i64(&mut b, x);
+		put_varint_u64(&mut b, y);
+		let at = split as usize % (b.len() + 1);
+		let mut chained = (&b[..at]).chain(&b[at..]);
+		b[..pack_int(x).len()] == pack_int(x)[..] && get_varint_i64(&mut chained) == Ok(x) &&
+			get_varint_u64(&mut chained) == Ok(y) && !chained.has_remaining()
+	}
+
+	#[test]
+	fn consumes_nothing_on_bad_framing() {
+		let mut b = &[0xe2u8, 0x01][..];
+		assert_eq!(get_varint_u64(&mut b), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(b.len(), 2);
+		let mut b = &[0x00u8][..];
+		assert_eq!(get_varint_i64(&mut b), Err(DecodeError::InvalidMarker(0x00)));
+		assert_eq!(get_varint_u64(&mut &[][..]), Err(DecodeError::Truncated { needed: 1 }));
+	}
+}
@@ -0,0 +1,136 @@
+//! A `tokio_util` codec, so a `Framed` transport yields values directly.
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use tokio_util::codec::{Decoder, Encoder};
+//! use rust_varint::codec::VarintCodec;
+//!
+//! let mut codec = VarintCodec::<i64>::new();
+//! let mut buf = BytesMut::new();
+//! codec.encode(-300, &mut buf).unwrap();
+//! assert_eq!(codec.decode(&mut buf).unwrap(), Some(-300));
+//! ```
+//!
+//! Values split across reads are held in the read buffer until complete.
+//! A value that cannot be decoded fails the stream with an `InvalidData`
+//! error wrapping the `DecodeError`.
+
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{decode_int, decode_uint, pack_int_into_array, pack_uint_into_array, DecodeError, MAX_ENCODED_LEN};
+
+/// Frames `u64` or `i64` values in the crate's own encoding, chosen by `T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VarintCodec<T>(PhantomData<T>);
+
+impl<T> VarintCodec<T> {
+	pub fn new() -> VarintCodec<T> {
+		VarintCodec(PhantomData)
+	}
+}
+
+impl<T> Default for VarintCodec<T> {
+	fn default() -> VarintCodec<T> {
+		VarintCodec::new()
+	}
+}
+
+// Takes the value at the front of `src` if all of it has arrived.
+fn decode_front<T, D>(src: &mut BytesMut, decode: D) -> io::Result<Option<T>>
+where
+	D: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+{
+	match decode(src) {
+		Ok((v, used)) => {
+			src.advance(used);
+			Ok(Some(v))
+		}
+		Err(DecodeError::Truncated { needed }) => {
+			src.reserve(needed - src.len());
+			Ok(None)
+		}
+		Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+	}
+}
+
+impl Decoder for VarintCodec<u64> {
+	type Item = u64;
+	type Error = io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<u64>> {
+		decode_front(src, decode_uint)
+	}
+}
+
+impl Decoder for VarintCodec<i64> {
+	type Item = i64;
+	type Error = io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<i64>> {
+		decode_front(src, decode_int)
+	}
+}
+
+impl Encoder<u64> for VarintCodec<u64> {
+	type Error = io::Error;
+
+	fn encode(&mut self, v: u64, dst: &mut BytesMut) -> io::Result<()> {
+		let mut out = [0; MAX_ENCODED_LEN];
+		let len = pack_uint_into_array(v, &mut out);
+		dst.put_slice(&out[..len]);
+		Ok(())
+	}
+}
+
+impl Encoder<i64> for VarintCodec<i64> {
+	type Error = io::Error;
+
+	fn encode(&mut self, v: i64, dst: &mut BytesMut) -> io::Result<()> {
+		let mut out = [0; MAX_ENCODED_LEN];
+		let len = pack_int_into_array(v, &mut out);
+		dst.put_slice(&out[..len]);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn survives_any_segmentation(values: Vec<i64>, seg: u8) -> bool {
+		let mut codec = VarintCodec::<i64>::new();
+		let mut wire = BytesMut::new();
+		for &v in &values {
+			codec.encode(v, &mut wire).unwrap();
+		}
+		let mut src = BytesMut::new();
+		let mut out = Vec::new();
+		for segment in wire.chunks(seg as usize % 10 + 1) {
+			src.extend_from_slice(segment);
+			while let Some(v) = codec.decode(&mut src).unwrap() {
+				out.push(v);
+			}
+		}
+		out == values && src.is_empty()
+	}
+
+	#[test]
+	fn fails_on_invalid_markers() {
+		let mut codec = VarintCodec::<u64>::new();
+		let mut src = BytesMut::from(&[0x81, 0xe3, 0x01][..]);
+		assert_eq!(codec.decode(&mut src).unwrap(), Some(1));
+		assert_eq!(codec.decode(&mut src).unwrap(), None);
+		assert!(src.capacity() >= 4);
+
+		let mut src = BytesMut::from(&[0xf5][..]);
+		let err = codec.decode(&mut src).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert_eq!(err.get_ref().unwrap().downcast_ref(), Some(&DecodeError::InvalidMarker(0xf5)));
+		assert!(codec.decode_eof(&mut BytesMut::from(&[0xc1][..])).is_err());
+	}
+}
@@ -0,0 +1,207 @@
+//! Columns of signed integers stored as a run of sealed blocks.
+//!
+//! A column is just its blocks back to back (see the `block` module), so
+//! readers can skip whole blocks using the length in each block header.
+
+use crate::block::{self, BlockIter};
+use crate::metrics::CodecMetrics;
+use crate::DecodeError;
+
+/// Values per block unless configured otherwise.
+pub const DEFAULT_BLOCK_LEN: usize = 4096;
+
+/// Buffers values and seals them into blocks of a fixed length.
+#[derive(Debug, Clone)]
+pub struct ColumnWriter<M = ()> {
+	block_len: usize,
+	pending: Vec<i64>,
+	out: Vec<u8>,
+	count: u64,
+	metrics: M,
+}
+
+impl Default for ColumnWriter {
+	fn default() -> ColumnWriter {
+		ColumnWriter::with_block_len(DEFAULT_BLOCK_LEN)
+	}
+}
+
+impl ColumnWriter {
+	pub fn new() -> ColumnWriter {
+		ColumnWriter::default()
+	}
+
+	/// Panics if `block_len` is zero.
+	pub fn with_block_len(block_len: usize) -> ColumnWriter {
+		assert!(block_len > 0, "block length must be positive");
+		ColumnWriter { block_len, pending: Vec::with_capacity(block_len), out: Vec::new(), count: 0, metrics: () }
+	}
+}
+
+impl<M: CodecMetrics> ColumnWriter<M> {
+	/// Reports each sealed block to `metrics`.
+	pub fn with_metrics<N: CodecMetrics>(self, metrics: N) -> ColumnWriter<N> {
+		let ColumnWriter { block_len, pending, out, count, .. } = self;
+		ColumnWriter { block_len, pending, out, count, metrics }
+	}
+
+	pub fn push(&mut self, value: i64) {
+		self.pending.push(value);
+		self.count += 1;
+		if self.pending.len() == self.block_len {
+			self.flush_block();
+		}
+	}
+
+	/// Number of values pushed so far.
+	pub fn len(&self) -> u64 {
+		self.count
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count == 0
+	}
+
+	/// Seals any partial block and returns the column bytes.
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(values = self.count)))]
+	pub fn finish(mut self) -> Vec<u8> {
+		if !self.pending.is_empty() {
+			self.flush_block();
+		}
+		trace!(bytes = self.out.len(), "finished column");
+		self.out
+	}
+
+	fn flush_block(&mut self) {
+		let raw = block::encode_block(&self.pending);
+		self.metrics.values_encoded(self.pending.len() as u64);
+		self.metrics.bytes_written(raw.len() as u64);
+		self.out.extend_from_slice(&raw);
+		self.pending.clear();
+	}
+}
+
+/// Iterates the raw bytes of each block in a column.
+pub fn blocks(column: &[u8]) -> Blocks<'_> {
+	Blocks { rest: column }
+}
+
+#[derive(Debug, Clone)]
+pub struct Blocks<'a> {
+	rest: &'a [u8],
+}
+
+impl<'a> Iterator for Blocks<'a> {
+	type Item = Result<&'a [u8], DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.rest.is_empty() {
+			return None;
+		}
+		match BlockIter::new(self.rest) {
+			Ok((_, used)) => {
+				let (head, tail) = self.rest.split_at(used);
+				self.rest = tail;
+				Some(Ok(head))
+			}
+			Err(e) => {
+				self.rest = &[];
+				Some(Err(e))
+			}
+		}
+	}
+}
+
+/// Iterates every value of a column in order.
+#[derive(Debug, Clone)]
+pub struct ColumnReader<'a, M = ()> {
+	blocks: Blocks<'a>,
+	current: Option<BlockIter<'a>>,
+	metrics: M,
+}
+
+impl<'a> ColumnReader<'a> {
+	pub fn new(column: &'a [u8]) -> ColumnReader<'a> {
+		ColumnReader { blocks: blocks(column), current: None, metrics: () }
+	}
+}
+
+impl<'a, M: CodecMetrics> ColumnReader<'a, M> {
+	/// Reports decode errors to `metrics`.
+	pub fn with_metrics<N: CodecMetrics>(self, metrics: N) -> ColumnReader<'a, N> {
+		ColumnReader { blocks: self.blocks, current: self.current, metrics }
+	}
+
+	fn next_value(&mut self) -> Option<Result<i64, DecodeError>> {
+		loop {
+			if let Some(values) = self.current.as_mut() {
+				if let Some(v) = values.next() {
+					return Some(v);
+				}
+			}
+			match self.blocks.next()? {
+				Ok(raw) => self.current = Some(BlockIter::new(raw).expect("block header already validated").0),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+impl<'a, M: CodecMetrics> Iterator for ColumnReader<'a, M> {
+	type Item = Result<i64, DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let v = self.next_value();
+		if let Some(Err(e)) = &v {
+			self.metrics.decode_errors(e);
+		}
+		v
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(values: Vec<i64>, block_len: u8) -> bool {
+		let mut w = ColumnWriter::with_block_len(block_len as usize % 16 + 1);
+		for &v in &values {
+			w.push(v);
+		}
+		let column = w.finish();
+		ColumnReader::new(&column).collect::<Result<Vec<_>, _>>() == Ok(values)
+	}
+
+	#[test]
+	fn splits_into_blocks() {
+		let mut w = ColumnWriter::with_block_len(100);
+		for v in 0..250 {
+			w.push(v);
+		}
+		let column = w.finish();
+		let counts: Vec<u64> = blocks(&column)
+			.map(|b| BlockIter::new(b.unwrap()).unwrap().0.remaining())
+			.collect();
+		assert_eq!(counts, vec![100, 100, 50]);
+	}
+
+	#[test]
+	fn reports_metrics() {
+		use crate::metrics::Counters;
+		use std::sync::atomic::Ordering;
+
+		let counters = Counters::default();
+		let mut w = ColumnWriter::with_block_len(2).with_metrics(&counters);
+		for v in 0..5 {
+			w.push(v);
+		}
+		let column = w.finish();
+		assert_eq!(counters.values_encoded.load(Ordering::Relaxed), 5);
+		assert_eq!(counters.bytes_written.load(Ordering::Relaxed), column.len() as u64);
+
+		let torn = ColumnReader::new(&column[..column.len() - 1]).with_metrics(&counters);
+		assert_eq!(torn.filter(Result::is_err).count(), 1);
+		assert_eq!(counters.decode_errors.load(Ordering::Relaxed), 1);
+	}
+}
@@ -0,0 +1,126 @@
+//! Merges sorted tables into new ones, the compaction step of an LSM tree.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::sst::{Entry, SstReader, SstWriter};
+use crate::DecodeError;
+
+/// Streams a k-way merge of `inputs` into `output`, returning the finished
+/// tables.
+///
+/// `inputs` go from newest to oldest: when several hold the same key, the
+/// entry of the earliest one wins and the rest are discarded. Each winning
+/// tombstone is passed to `drop_tombstone`, and left out of the output if
+/// it returns true, as it may once no older table can hold the key. Once
+/// the current table reaches `max_table_len` bytes it is finished and the
+/// merge continues into a new one; pass `usize::MAX` for a single table.
+///
+/// Returns an empty list if every entry was dropped.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(inputs = inputs.len(), max_table_len), err))]
+pub fn compact<F>(
+	inputs: Vec<SstReader<'_>>,
+	mut output: SstWriter,
+	max_table_len: usize,
+	mut drop_tombstone: F,
+) -> Result<Vec<Vec<u8>>, DecodeError>
+where
+	F: FnMut(&[u8]) -> bool,
+{
+	let mut inputs = inputs;
+	let mut heads: Vec<Option<Entry>> = Vec::with_capacity(inputs.len());
+	let mut heap = BinaryHeap::new();
+	for (i, input) in inputs.iter_mut().enumerate() {
+		let head = input.next().transpose()?;
+		if let Some(entry) = head {
+			heap.push(Reverse((entry.key, i)));
+		}
+		heads.push(head);
+	}
+
+	let mut tables = Vec::new();
+	while let Some(Reverse((key, i))) = heap.pop() {
+		let entry = heads[i].unwrap();
+		let mut advance = vec![i];
+		while let Some(&Reverse((k, j))) = heap.peek() {
+			if k != key {
+				break;
+			}
+			heap.pop();
+			advance.push(j);
+		}
+		for j in advance {
+			heads[j] = inputs[j].next().transpose()?;
+			if let Some(next) = heads[j] {
+				heap.push(Reverse((next.key, j)));
+			}
+		}
+
+		if entry.value.is_none() && drop_tombstone(entry.key) {
+			continue;
+		}
+		output.write(entry);
+		if output.len() >= max_table_len {
+			tables.push(std::mem::take(&mut output).finish());
+		}
+	}
+	if !output.is_empty() {
+		tables.push(output.finish());
+	}
+	trace!(tables = tables.len(), bytes = tables.iter().map(Vec::len).sum::<usize>(), "compacted");
+	Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+
+	type Run = BTreeMap<Vec<u8>, Option<Vec<u8>>>;
+
+	fn table(run: &Run) -> Vec<u8> {
+		let mut w = SstWriter::new();
+		for (k, v) in run {
+			w.write(Entry { key: k, value: v.as_deref() });
+		}
+		w.finish()
+	}
+
+	fn read(tables: &[Vec<u8>]) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+		let entries = tables.iter().flat_map(|t| SstReader::new(t));
+		entries.map(|e| e.unwrap()).map(|e| (e.key.to_vec(), e.value.map(|v| v.to_vec()))).collect()
+	}
+
+	#[quickcheck]
+	fn newest_wins(runs: Vec<Run>, drop: bool, max_len: u8) -> bool {
+		// apply the runs oldest first, so newer ones overwrite
+		let mut want = Run::new();
+		for run in runs.iter().rev() {
+			want.extend(run.clone());
+		}
+		if drop {
+			want.retain(|_, v| v.is_some());
+		}
+
+		let tables: Vec<Vec<u8>> = runs.iter().map(table).collect();
+		let readers = tables.iter().map(|t| SstReader::new(t)).collect();
+		let out = compact(readers, SstWriter::new(), max_len as usize + 1, |_| drop).unwrap();
+		out.iter().all(|t| !t.is_empty()) && read(&out) == want.into_iter().collect::<Vec<_>>()
+	}
+
+	#[test]
+	fn splits_by_size() {
+		let run: Run = (0u8..10).map(|k| (vec![k], Some(vec![k; 10]))).collect();
+		let one = table(&run.iter().take(1).map(|(k, v)| (k.clone(), v.clone())).collect());
+		let t = table(&run);
+		let out = compact(vec![SstReader::new(&t)], SstWriter::new(), one.len() * 3, |_| false).unwrap();
+		assert_eq!(out.len(), 4);
+		assert!(out.iter().all(|t| t.len() <= one.len() * 3));
+		assert_eq!(read(&out).len(), 10);
+
+		let mut bad = t.clone();
+		bad[8] ^= 1;
+		assert_eq!(compact(vec![SstReader::new(&bad)], SstWriter::new(), usize::MAX, |_| false),
+			Err(DecodeError::Malformed("record checksum mismatch")));
+	}
+}
@@ -0,0 +1,139 @@
+//! The `integer-encoding` crate's traits, backed by this crate's formats.
+//!
+//! `U64` and `I64` wrap a value together with a `VarintFormat` and
+//! implement `integer_encoding::VarInt`, so code written against that
+//! crate can switch encodings by changing a type:
+//!
+//! ```
+//! use integer_encoding::{VarInt, VarIntReader, VarIntWriter};
+//! use rust_varint::compat::{Reader, U64};
+//! use rust_varint::formats::Ordered;
+//!
+//! let mut buf = Vec::new();
+//! buf.write_varint(U64(300, Ordered)).unwrap();
+//! assert_eq!(buf, rust_varint::pack_uint(300));
+//! assert_eq!(Reader(&buf[..]).read_varint::<U64>().unwrap().0, 300);
+//! ```
+//!
+//! `VarIntWriter` works on any `Write` as usual. `VarIntReader` is
+//! implemented for any `Read` by that crate itself, by looking for a byte
+//! with the high bit clear, which only suits LEB128; wrap the reader in
+//! `Reader` to read other formats.
+
+use std::io::{self, Read};
+
+use integer_encoding::{VarInt, VarIntReader};
+
+use crate::formats::{Ordered, VarintFormat};
+
+/// An unsigned value encoded with `F`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct U64<F = Ordered>(pub u64, pub F);
+
+/// A signed value encoded with `F`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct I64<F = Ordered>(pub i64, pub F);
+
+impl<F: Default> U64<F> {
+	pub fn new(v: u64) -> U64<F> {
+		U64(v, F::default())
+	}
+}
+
+impl<F: Default> I64<F> {
+	pub fn new(v: i64) -> I64<F> {
+		I64(v, F::default())
+	}
+}
+
+// Writes `encode` into `dst`, which the trait requires to be long enough.
+fn copy_encoded(dst: &mut [u8], encode: impl FnOnce(&mut Vec<u8>)) -> usize {
+	let mut out = Vec::with_capacity(10);
+	encode(&mut out);
+	dst[..out.len()].copy_from_slice(&out);
+	out.len()
+}
+
+impl<F: VarintFormat + Copy + Default> VarInt for U64<F> {
+	fn required_space(self) -> usize {
+		let mut out = Vec::with_capacity(10);
+		self.1.encode_into(&mut out, self.0);
+		out.len()
+	}
+
+	fn decode_var(src: &[u8]) -> Option<(Self, usize)> {
+		let (v, used) = F::default().decode_from(src).ok()?;
+		Some((U64::new(v), used))
+	}
+
+	fn encode_var(self, dst: &mut [u8]) -> usize {
+		copy_encoded(dst, |out| self.1.encode_into(out, self.0))
+	}
+}
+
+impl<F: VarintFormat + Copy + Default> VarInt for I64<F> {
+	fn required_space(self) -> usize {
+		let mut out = Vec::with_capacity(10);
+		self.1.encode_i64_into(&mut out, self.0);
+		out.len()
+	}
+
+	fn decode_var(src: &[u8]) -> Option<(Self, usize)> {
+		let (v, used) = F::default().decode_i64_from(src).ok()?;
+		Some((I64::new(v), used))
+	}
+
+	fn encode_var(self, dst: &mut [u8]) -> usize {
+		copy_encoded(dst, |out| self.1.encode_i64_into(out, self.0))
+	}
+}
+
+/// Reads values of any `VarInt` from `R` a byte at a time, stopping as
+/// soon as the bytes so far decode.
+#[derive(Debug)]
+pub struct Reader<R>(pub R);
+
+impl<R: Read> VarIntReader for Reader<R> {
+	fn read_varint<VI: VarInt>(&mut self) -> io::Result<VI> {
+		let mut buf = [0u8; 10];
+		for len in 1..=buf.len() {
+			if self.0.read(&mut buf[len - 1..len])? == 0 {
+				return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "reached the end of the input"));
+			}
+			if let Some((v, _)) = VI::decode_var(&buf[..len]) {
+				return Ok(v);
+			}
+		}
+		Err(io::Error::new(io::ErrorKind::InvalidData, "invalid varint"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::formats::{cassandra::Cassandra, fast_le::FastLE, leb128::Leb128};
+	use integer_encoding::VarIntWriter;
+
+	fn round_trip<F: VarintFormat + Copy + Default>(x: u64, y: i64) -> bool {
+		let mut buf = Vec::new();
+		let a = buf.write_varint(U64::<F>::new(x)).unwrap();
+		let b = buf.write_varint(I64::<F>::new(y)).unwrap();
+		let mut r = Reader(&buf[..]);
+		a == U64::<F>::new(x).required_space() && b + a == buf.len() &&
+			r.read_varint::<U64<F>>().unwrap().0 == x && r.read_varint::<I64<F>>().unwrap().0 == y
+	}
+
+	#[quickcheck]
+	fn round_trips_through_the_traits(x: u64, y: i64) -> bool {
+		round_trip::<Ordered>(x, y) && round_trip::<Cassandra>(x, y) && round_trip::<Leb128>(x, y) &&
+			round_trip::<FastLE>(x, y)
+	}
+
+	#[test]
+	fn matches_the_crate_encoding() {
+		assert_eq!(I64::<Ordered>::new(-1).encode_var_vec(), crate::pack_int(-1));
+		assert_eq!(U64::<Leb128>::new(300).encode_var_vec(), 300u64.encode_var_vec());
+		assert_eq!(Reader(&[0xe4u8][..]).read_varint::<U64>().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+		assert_eq!(Reader(&[0xffu8; 12][..]).read_varint::<U64>().unwrap_err().kind(), io::ErrorKind::InvalidData);
+	}
+}
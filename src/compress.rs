@@ -0,0 +1,231 @@
+//! General-purpose compression of sealed blocks, for cold storage.
+//!
+//! A compressed block is self-delimiting, like the block it wraps:
+//!
+//! ```text
+//! [codec: uint][uncompressed length: uint][compressed length: uint][payload ...]
+//! ```
+//!
+//! Codec ids are stable across builds; a block written with a codec that
+//! is not compiled in fails to decode with a `Malformed` error.
+//!
+//! The uncompressed length is checked before anything is allocated for
+//! it: it may not be above `MAX_UNCOMPRESSED_LEN`, nor more than the
+//! payload could expand to with its codec.
+
+use crate::block;
+use crate::{decode_uint, pack_uint, DecodeError};
+
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Largest block `compress` accepts and `parse` will decompress, 1 GiB.
+pub const MAX_UNCOMPRESSED_LEN: usize = 1 << 30;
+
+/// Compression codecs, each behind the cargo feature of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+	#[cfg(feature = "lz4")]
+	Lz4,
+	#[cfg(feature = "zstd")]
+	Zstd,
+}
+
+impl Codec {
+	fn id(self) -> u64 {
+		match self {
+			#[cfg(feature = "lz4")]
+			Codec::Lz4 => 1,
+			#[cfg(feature = "zstd")]
+			Codec::Zstd => 2,
+		}
+	}
+
+	fn from_id(id: u64) -> Result<Codec, DecodeError> {
+		match id {
+			#[cfg(feature = "lz4")]
+			1 => Ok(Codec::Lz4),
+			#[cfg(feature = "zstd")]
+			2 => Ok(Codec::Zstd),
+			_ => Err(DecodeError::Malformed("compressed block codec is not supported")),
+		}
+	}
+
+	// Bounds the output of one payload byte: an LZ4 length byte adds at
+	// most 255, and a 4-byte zstd RLE block stands for at most 128 KiB.
+	fn max_ratio(self) -> usize {
+		match self {
+			#[cfg(feature = "lz4")]
+			Codec::Lz4 => 255,
+			#[cfg(feature = "zstd")]
+			Codec::Zstd => 32 * 1024,
+		}
+	}
+
+	fn compress(self, data: &[u8]) -> Vec<u8> {
+		match self {
+			#[cfg(feature = "lz4")]
+			Codec::Lz4 => lz4_flex::block::compress(data),
+			#[cfg(feature = "zstd")]
+			Codec::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL).expect("zstd compression into a Vec cannot fail"),
+		}
+	}
+
+	fn decompress(self, data: &[u8], len: usize) -> Result<Vec<u8>, DecodeError> {
+		let out = match self {
+			#[cfg(feature = "lz4")]
+			Codec::Lz4 => lz4_flex::block::decompress(data, len).ok(),
+			#[cfg(feature = "zstd")]
+			Codec::Zstd => zstd::bulk::decompress(data, len).ok(),
+		};
+		match out {
+			Some(out) if out.len() == len => Ok(out),
+			_ => Err(DecodeError::Malformed("compressed block payload is corrupt")),
+		}
+	}
+}
+
+/// A compressed block parsed from a buffer, not yet decompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedBlock<'a> {
+	codec: Codec,
+	uncompressed_len: usize,
+	payload: &'a [u8],
+}
+
+impl<'a> CompressedBlock<'a> {
+	/// Compresses a sealed block, e.g. the output of `block::encode_block`.
+	/// Panics if it is longer than `MAX_UNCOMPRESSED_LEN`.
+	pub fn compress(block: &[u8], codec: Codec) -> Vec<u8> {
+		assert!(block.len() <= MAX_UNCOMPRESSED_LEN, "block of {} bytes is too long to compress", block.len());
+		let payload = codec.compress(block);
+		let mut out = pack_uint(codec.id());
+		out.extend_from_slice(&pack_uint(block.len() as u64));
+		out.extend_from_slice(&pack_uint(payload.len() as u64));
+		out.extend_from_slice(&payload);
+		out
+	}
+
+	/// Parses the header at the front of `buf`. Returns the block and its
+	/// total size, so the caller can step to the next. An uncompressed
+	/// length the payload cannot have is `Malformed`.
+	pub fn parse(buf: &'a [u8]) -> Result<(CompressedBlock<'a>, usize), DecodeError> {
+		let (id, n1) = decode_uint(buf)?;
+		let (uncompressed_len, n2) = decode_uint(&buf[n1..])?;
+		let (len, n3) = decode_uint(&buf[n1 + n2..])?;
+		let start = n1 + n2 + n3;
+		let end = start.checked_add(len as usize).ok_or(DecodeError::Overflow)?;
+		if buf.len() < end {
+			return Err(DecodeError::Truncated { needed: end });
+		}
+		let codec = Codec::from_id(id)?;
+		let payload = &buf[start..end];
+		let limit = MAX_UNCOMPRESSED_LEN.min(payload.len().saturating_mul(codec.max_ratio()));
+		if uncompressed_len > limit as u64 {
+			return Err(DecodeError::Malformed("compressed block length is more than its payload can hold"));
+		}
+		let block = CompressedBlock { codec, uncompressed_len: uncompressed_len as usize, payload };
+		Ok((block, end))
+	}
+
+	pub fn codec(&self) -> Codec {
+		self.codec
+	}
+
+	pub fn uncompressed_len(&self) -> usize {
+		self.uncompressed_len
+	}
+
+	/// Returns the original sealed block bytes.
+	pub fn decompress(&self) -> Result<Vec<u8>, DecodeError> {
+		self.codec.decompress(self.payload, self.uncompressed_len)
+	}
+
+	/// Decompresses and decodes the values of the wrapped block.
+	pub fn decode(&self) -> Result<Vec<i64>, DecodeError> {
+		let raw = self.decompress()?;
+		let (values, used) = block::decode_block(&raw)?;
+		if used != raw.len() {
+			return Err(DecodeError::Malformed("trailing bytes after the compressed block"));
+		}
+		Ok(values)
+	}
+}
+
+/// Packs `values` into a sealed block and compresses it.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(values = values.len(), ?codec)))]
+pub fn encode_compressed_block(values: &[i64], codec: Codec) -> Vec<u8> {
+	CompressedBlock::compress(&block::encode_block(values), codec)
+}
+
+/// Decodes the compressed block at the front of `buf`, returning its
+/// values and the number of bytes it occupied.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = buf.len()), err))]
+pub fn decode_compressed_block(buf: &[u8]) -> Result<(Vec<i64>, usize), DecodeError> {
+	let (block, used) = CompressedBlock::parse(buf)?;
+	trace!(codec = ?block.codec(), compressed = used, uncompressed = block.uncompressed_len(), "parsed compressed block");
+	Ok((block.decode()?, used))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn codecs() -> Vec<Codec> {
+		vec![
+			#[cfg(feature = "lz4")]
+			Codec::Lz4,
+			// zstd is a C library, which Miri cannot run
+			#[cfg(all(feature = "zstd", not(miri)))]
+			Codec::Zstd,
+		]
+	}
+
+	#[quickcheck]
+	fn round_trip(values: Vec<i64>) -> bool {
+		codecs().into_iter().all(|codec| {
+			let buf = encode_compressed_block(&values, codec);
+			decode_compressed_block(&buf) == Ok((values.clone(), buf.len()))
+		})
+	}
+
+	#[test]
+	fn shrinks_repetitive_blocks() {
+		let values: Vec<i64> = (0..4096).map(|i| i % 16).collect();
+		let raw = block::encode_block(&values);
+		for codec in codecs() {
+			let buf = CompressedBlock::compress(&raw, codec);
+			let (block, used) = CompressedBlock::parse(&buf).unwrap();
+			assert!(buf.len() < raw.len() / 4);
+			assert_eq!((block.codec(), block.uncompressed_len(), used), (codec, raw.len(), buf.len()));
+			assert_eq!(block.decompress().unwrap(), raw);
+		}
+	}
+
+	#[test]
+	fn rejects_bad_headers() {
+		for codec in codecs() {
+			let mut buf = encode_compressed_block(&[1, 2, 3], codec);
+			assert!(decode_compressed_block(&buf[..buf.len() - 1]).is_err());
+			buf[0] = pack_uint(9)[0];
+			assert_eq!(decode_compressed_block(&buf),
+				Err(DecodeError::Malformed("compressed block codec is not supported")));
+		}
+	}
+
+	#[test]
+	fn rejects_impossible_lengths() {
+		let too_long = DecodeError::Malformed("compressed block length is more than its payload can hold");
+		for codec in codecs() {
+			let payload = codec.compress(&[0; 64]);
+			for &len in &[u64::MAX, MAX_UNCOMPRESSED_LEN as u64 + 1, (payload.len() * codec.max_ratio()) as u64 + 1] {
+				let mut buf = pack_uint(codec.id());
+				buf.extend_from_slice(&pack_uint(len));
+				buf.extend_from_slice(&pack_uint(payload.len() as u64));
+				buf.extend_from_slice(&payload);
+				assert_eq!(CompressedBlock::parse(&buf), Err(too_long));
+				assert_eq!(decode_compressed_block(&buf), Err(too_long));
+			}
+		}
+	}
+}
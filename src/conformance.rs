@@ -0,0 +1,145 @@
+//! Checks every `VarintFormat` should pass, for in-tree and third-party
+//! implementations alike. Call `check_format` from a test; it panics with
+//! the failing value on the first violation.
+
+use crate::formats::VarintFormat;
+use crate::DecodeError;
+
+// Every power of two, its neighbours, and a deterministic spread of
+// xorshift values, kept short under Miri.
+fn unsigned_vectors() -> Vec<u64> {
+	let mut out = vec![0, u64::MAX];
+	for shift in 0..64 {
+		let p = 1u64 << shift;
+		out.extend_from_slice(&[p - 1, p, p + 1]);
+	}
+	let mut x = 0x9e37_79b9_7f4a_7c15u64;
+	for _ in 0..if cfg!(miri) { 50 } else { 1000 } {
+		x ^= x << 13;
+		x ^= x >> 7;
+		x ^= x << 17;
+		out.push(x >> (x % 64));
+	}
+	out.sort_unstable();
+	out.dedup();
+	out
+}
+
+fn signed_vectors() -> Vec<i64> {
+	let mut out: Vec<i64> = unsigned_vectors().into_iter().flat_map(|v| vec![v as i64, (v as i64).wrapping_neg()]).collect();
+	out.extend_from_slice(&[i64::MIN, i64::MAX]);
+	out.sort_unstable();
+	out.dedup();
+	out
+}
+
+fn check_one<T, F>(name: &str, v: T, buf: &[u8], max_len: usize, decode: F)
+where
+	T: Copy + PartialEq + std::fmt::Debug,
+	F: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+{
+	assert!(!buf.is_empty() && buf.len() <= max_len, "{}: {:?} encodes to {} bytes", name, v, buf.len());
+	assert_eq!(decode(buf), Ok((v, buf.len())), "{}: {:?} does not round trip", name, v);
+
+	let mut padded = buf.to_vec();
+	padded.extend_from_slice(&[0xa5; 16]);
+	assert_eq!(decode(&padded), Ok((v, buf.len())), "{}: {:?} reads past its end", name, v);
+
+	for cut in 0..buf.len() {
+		assert!(
+			matches!(decode(&buf[..cut]), Err(DecodeError::Truncated { .. })),
+			"{}: {:?} cut to {} bytes is not reported as truncated",
+			name,
+			v,
+			cut
+		);
+	}
+}
+
+/// Runs the round trip, truncation, canonical form, boundary vector and,
+/// when `format.preserves_order()`, ordering suites against `format`.
+pub fn check_format<F: VarintFormat>(format: F) {
+	let mut previous: Option<Vec<u8>> = None;
+	for v in unsigned_vectors() {
+		let mut buf = Vec::new();
+		format.encode_into(&mut buf, v);
+		check_one("unsigned", v, &buf, format.max_len(), |b| format.decode_from(b));
+		assert!(format.is_canonical(&buf), "unsigned: encoding of {} is not canonical", v);
+		if format.preserves_order() {
+			if let Some(prev) = &previous {
+				assert!(prev < &buf, "unsigned: {} does not sort after its predecessor", v);
+			}
+		}
+		previous = Some(buf);
+	}
+
+	let mut previous: Option<Vec<u8>> = None;
+	for v in signed_vectors() {
+		let mut buf = Vec::new();
+		format.encode_i64_into(&mut buf, v);
+		check_one("signed", v, &buf, format.max_len(), |b| format.decode_i64_from(b));
+		if format.preserves_order() {
+			if let Some(prev) = &previous {
+				assert!(prev < &buf, "signed: {} does not sort after its predecessor", v);
+			}
+		}
+		previous = Some(buf);
+	}
+
+	assert!(format.decode_from(&[]).is_err(), "empty input decodes");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::formats::bitcoin::CompactSize;
+	use crate::formats::{cassandra::Cassandra, fast_le::FastLE, git::GitOffset, leb128::Leb128, prefix_varint::PrefixVarint};
+	use crate::formats::{sqlite::Sqlite, Format, Ordered};
+
+	#[test]
+	fn built_in_formats_conform() {
+		check_format(Ordered);
+		check_format(Cassandra);
+		check_format(PrefixVarint);
+		check_format(Leb128);
+		check_format(FastLE);
+		check_format(Sqlite);
+		check_format(GitOffset);
+		check_format(CompactSize);
+		let formats = [Format::Ordered, Format::Cassandra, Format::PrefixVarint, Format::Leb128, Format::FastLE,
+			Format::Sqlite, Format::GitOffset, Format::CompactSize];
+		for &f in formats.iter() {
+			check_format(f);
+		}
+	}
+
+	// claims to preserve order but is little-endian
+	struct Broken;
+
+	impl VarintFormat for Broken {
+		fn max_len(&self) -> usize {
+			8
+		}
+
+		fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+			out.extend_from_slice(&v.to_le_bytes());
+		}
+
+		fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+			let bytes = buf.get(..8).ok_or(DecodeError::Truncated { needed: 8 })?;
+			let mut v = [0; 8];
+			v.copy_from_slice(bytes);
+			Ok((u64::from_le_bytes(v), 8))
+		}
+
+		fn preserves_order(&self) -> bool {
+			true
+		}
+	}
+
+	#[test]
+	#[should_panic(expected = "does not sort after its predecessor")]
+	fn catches_ordering_violations() {
+		check_format(Broken);
+	}
+}
@@ -0,0 +1,410 @@
+//! Build integer columns from CSV or newline-delimited JSON records.
+//!
+//! Rows that cannot be converted are skipped and reported, so a handful
+//! of bad lines does not abort a large import.
+//!
+//! ```
+//! use rust_varint::convert::{Converter, Source};
+//! use rust_varint::column::ColumnReader;
+//!
+//! let rows = vec!["id,name", "17,a", "x,b", "-4,c"];
+//! let out = Converter::new(Source::Csv { index: 0, delimiter: b',' })
+//!     .skip_rows(1)
+//!     .run(rows);
+//!
+//! assert_eq!(out.values, 2);
+//! assert_eq!(out.errors[0].row, 2);
+//! let values: Result<Vec<i64>, _> = ColumnReader::new(&out.column).collect();
+//! assert_eq!(values.unwrap(), vec![17, -4]);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::column::{ColumnWriter, DEFAULT_BLOCK_LEN};
+use crate::DecodeError;
+
+// Arrays and objects nested deeper than this inside a record are
+// rejected rather than risk the stack.
+const MAX_JSON_DEPTH: usize = 128;
+
+const BAD_JSON: DecodeError = DecodeError::Malformed("malformed JSON record");
+
+/// Where to find the integer in each record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source<'a> {
+	/// Zero-based field of a delimiter-separated record. Quoted fields
+	/// follow RFC 4180, with `""` standing for a literal quote.
+	Csv { index: usize, delimiter: u8 },
+	/// Top-level member of a JSON object, one object per record.
+	Ndjson { field: &'a str },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowErrorKind {
+	/// The record could not be parsed as CSV or JSON.
+	Malformed,
+	/// The record has no such field, or it is JSON `null`.
+	MissingField,
+	/// The field is present but is not a 64-bit integer.
+	NotAnInteger(String),
+}
+
+/// A record that was skipped; `row` counts from zero over the input,
+/// including any skipped leading rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+	pub row: u64,
+	pub kind: RowErrorKind,
+}
+
+impl fmt::Display for RowError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.kind {
+			RowErrorKind::Malformed => write!(f, "row {}: malformed record", self.row),
+			RowErrorKind::MissingField => write!(f, "row {}: field missing", self.row),
+			RowErrorKind::NotAnInteger(ref s) => write!(f, "row {}: {:?} is not an integer", self.row, s),
+		}
+	}
+}
+
+/// Result of a conversion run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversion {
+	/// Column bytes, readable with `column::ColumnReader`.
+	pub column: Vec<u8>,
+	/// Number of values written to the column.
+	pub values: u64,
+	pub errors: Vec<RowError>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Converter<'a> {
+	source: Source<'a>,
+	block_len: usize,
+	skip_rows: u64,
+}
+
+impl<'a> Converter<'a> {
+	pub fn new(source: Source<'a>) -> Converter<'a> {
+		Converter { source, block_len: DEFAULT_BLOCK_LEN, skip_rows: 0 }
+	}
+
+	/// Values per column block. Panics if `block_len` is zero.
+	pub fn block_len(mut self, block_len: usize) -> Converter<'a> {
+		assert!(block_len > 0, "block length must be positive");
+		self.block_len = block_len;
+		self
+	}
+
+	/// Ignores the first `n` records, e.g. a CSV header line.
+	pub fn skip_rows(mut self, n: u64) -> Converter<'a> {
+		self.skip_rows = n;
+		self
+	}
+
+	pub fn run<I, S>(&self, records: I) -> Conversion
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<str>,
+	{
+		let mut writer = ColumnWriter::with_block_len(self.block_len);
+		let mut errors = Vec::new();
+
+		for (row, record) in records.into_iter().enumerate().skip(self.skip_rows as usize) {
+			let record = record.as_ref().trim_end_matches(['\r', '\n']);
+			let field = match self.source {
+				Source::Csv { index, delimiter } => csv_field(record, index, delimiter),
+				Source::Ndjson { field } => json_field(record, field).map_err(|_| ()),
+			};
+			let kind = match field {
+				Ok(Some(text)) => match text.trim().parse::<i64>() {
+					Ok(v) => {
+						writer.push(v);
+						continue;
+					}
+					Err(_) => RowErrorKind::NotAnInteger(text.into_owned()),
+				},
+				Ok(None) => RowErrorKind::MissingField,
+				Err(()) => RowErrorKind::Malformed,
+			};
+			errors.push(RowError { row: row as u64, kind });
+		}
+
+		let values = writer.len();
+		Conversion { column: writer.finish(), values, errors }
+	}
+}
+
+fn csv_field(record: &str, index: usize, delimiter: u8) -> Result<Option<Cow<'_, str>>, ()> {
+	let bytes = record.as_bytes();
+	let mut pos = 0;
+	let mut field = 0;
+
+	loop {
+		let value: Cow<str>;
+		if bytes.get(pos) == Some(&b'"') {
+			let mut unquoted = String::new();
+			let mut start = pos + 1;
+			loop {
+				let close = start + record[start..].find('"').ok_or(())?;
+				unquoted.push_str(&record[start..close]);
+				if bytes.get(close + 1) == Some(&b'"') {
+					unquoted.push('"');
+					start = close + 2;
+				} else {
+					pos = close + 1;
+					break;
+				}
+			}
+			if pos < bytes.len() && bytes[pos] != delimiter {
+				return Err(());
+			}
+			value = Cow::Owned(unquoted);
+		} else {
+			let end = bytes[pos..].iter().position(|&b| b == delimiter).map_or(bytes.len(), |n| pos + n);
+			value = Cow::Borrowed(&record[pos..end]);
+			pos = end;
+		}
+
+		if field == index {
+			return Ok(Some(value));
+		}
+		if pos >= bytes.len() {
+			return Ok(None);
+		}
+		pos += 1;
+		field += 1;
+	}
+}
+
+// Finds a top-level member of a JSON object and returns its number token
+// (or string contents). Other members are skipped without being
+// materialized, but the whole record must still be well-formed.
+fn json_field<'r>(record: &'r str, name: &str) -> Result<Option<Cow<'r, str>>, DecodeError> {
+	let mut p = JsonScanner { s: record.as_bytes(), pos: 0, depth: 0 };
+	let mut found = None;
+	p.expect(b'{')?;
+	if p.peek() == Some(b'}') {
+		p.pos += 1;
+	} else {
+		loop {
+			let key = p.string()?;
+			p.expect(b':')?;
+			p.skip_ws();
+			let start = p.pos;
+			if key == name && p.s[start..].first() == Some(&b'"') {
+				found = Some(Cow::Owned(p.string()?));
+			} else {
+				p.skip_value()?;
+				if key == name && &p.s[start..p.pos] != b"null" {
+					found = Some(Cow::Borrowed(&record[start..p.pos]));
+				}
+			}
+			match p.next_token() {
+				Some(b',') => continue,
+				Some(b'}') => break,
+				_ => return Err(BAD_JSON),
+			}
+		}
+	}
+	if p.peek().is_some() {
+		return Err(BAD_JSON);
+	}
+	Ok(found)
+}
+
+struct JsonScanner<'a> {
+	s: &'a [u8],
+	pos: usize,
+	// arrays and objects `skip_value` is inside of
+	depth: usize,
+}
+
+impl<'a> JsonScanner<'a> {
+	fn skip_ws(&mut self) {
+		while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<u8> {
+		self.skip_ws();
+		self.s.get(self.pos).cloned()
+	}
+
+	fn next_token(&mut self) -> Option<u8> {
+		let b = self.peek()?;
+		self.pos += 1;
+		Some(b)
+	}
+
+	fn expect(&mut self, b: u8) -> Result<(), DecodeError> {
+		if self.next_token() == Some(b) { Ok(()) } else { Err(BAD_JSON) }
+	}
+
+	fn string(&mut self) -> Result<String, DecodeError> {
+		self.expect(b'"')?;
+		let mut out = Vec::new();
+		loop {
+			let b = *self.s.get(self.pos).ok_or(BAD_JSON)?;
+			self.pos += 1;
+			match b {
+				b'"' => return String::from_utf8(out).map_err(|_| BAD_JSON),
+				b'\\' => {
+					let esc = *self.s.get(self.pos).ok_or(BAD_JSON)?;
+					self.pos += 1;
+					let c = match esc {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => self.unicode_escape()?,
+						_ => return Err(BAD_JSON),
+					};
+					let mut tmp = [0u8; 4];
+					out.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+				}
+				_ => out.push(b),
+			}
+		}
+	}
+
+	fn hex4(&mut self) -> Result<u32, DecodeError> {
+		let digits = self.s.get(self.pos..self.pos + 4).ok_or(BAD_JSON)?;
+		let digits = std::str::from_utf8(digits).map_err(|_| BAD_JSON)?;
+		self.pos += 4;
+		u32::from_str_radix(digits, 16).map_err(|_| BAD_JSON)
+	}
+
+	fn unicode_escape(&mut self) -> Result<char, DecodeError> {
+		let hi = self.hex4()?;
+		if (0xd800..0xdc00).contains(&hi) {
+			if self.s.get(self.pos..self.pos + 2) != Some(b"\\u") {
+				return Err(BAD_JSON);
+			}
+			self.pos += 2;
+			let lo = self.hex4()?;
+			if !(0xdc00..0xe000).contains(&lo) {
+				return Err(BAD_JSON);
+			}
+			return char::from_u32(0x10000 + ((hi - 0xd800) << 10) + (lo - 0xdc00)).ok_or(BAD_JSON);
+		}
+		char::from_u32(hi).ok_or(BAD_JSON)
+	}
+
+	fn skip_value(&mut self) -> Result<(), DecodeError> {
+		match self.peek().ok_or(BAD_JSON)? {
+			b'"' => self.string().map(|_| ()),
+			open @ b'{' | open @ b'[' => {
+				if self.depth == MAX_JSON_DEPTH {
+					return Err(DecodeError::Malformed("nesting too deep"));
+				}
+				self.depth += 1;
+				let skipped = self.skip_members(open);
+				self.depth -= 1;
+				skipped
+			}
+			_ => {
+				let start = self.pos;
+				while self.pos < self.s.len() && !b",}] \t\r\n".contains(&self.s[self.pos]) {
+					self.pos += 1;
+				}
+				if self.pos == start { Err(BAD_JSON) } else { Ok(()) }
+			}
+		}
+	}
+
+	// The rest of the array or object opened by `open`.
+	fn skip_members(&mut self, open: u8) -> Result<(), DecodeError> {
+		let close = if open == b'{' { b'}' } else { b']' };
+		self.pos += 1;
+		if self.peek() == Some(close) {
+			self.pos += 1;
+			return Ok(());
+		}
+		loop {
+			if open == b'{' {
+				self.string()?;
+				self.expect(b':')?;
+			}
+			self.skip_value()?;
+			match self.next_token() {
+				Some(b',') => continue,
+				Some(b) if b == close => return Ok(()),
+				_ => return Err(BAD_JSON),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::column::ColumnReader;
+
+	fn values(c: &Conversion) -> Vec<i64> {
+		ColumnReader::new(&c.column).map(|v| v.unwrap()).collect()
+	}
+
+	#[test]
+	fn csv_with_quotes_and_errors() {
+		let rows = [
+			"a,b,c",
+			"1,\"x, \"\"quoted\"\"\",10\r",
+			"2,y",
+			"3,\"z\",abc",
+			"4,\"unterminated,5",
+			"5,w, -7 ",
+		];
+		let out = Converter::new(Source::Csv { index: 2, delimiter: b',' }).skip_rows(1).run(rows.iter());
+		assert_eq!(values(&out), vec![10, -7]);
+		assert_eq!(out.errors, vec![
+			RowError { row: 2, kind: RowErrorKind::MissingField },
+			RowError { row: 3, kind: RowErrorKind::NotAnInteger("abc".to_string()) },
+			RowError { row: 4, kind: RowErrorKind::Malformed },
+		]);
+	}
+
+	#[test]
+	fn ndjson_finds_top_level_member() {
+		let rows = [
+			r#"{"nested": {"id": 99}, "tags": ["id", {"id": 5}], "id": 12}"#,
+			r#"{"id": "34", "name": "é\"x"}"#,
+			r#"{"id": null}"#,
+			r#"{"other": 1}"#,
+			r#"{"id": 1.5}"#,
+			r#"{"id": 7"#,
+			r#"{"id": -8}"#,
+		];
+		let out = Converter::new(Source::Ndjson { field: "id" }).block_len(2).run(rows.iter());
+		assert_eq!(values(&out), vec![12, 34, -8]);
+		let kinds: Vec<_> = out.errors.iter().map(|e| (e.row, e.kind.clone())).collect();
+		assert_eq!(kinds, vec![
+			(2, RowErrorKind::MissingField),
+			(3, RowErrorKind::MissingField),
+			(4, RowErrorKind::NotAnInteger("1.5".to_string())),
+			(5, RowErrorKind::Malformed),
+		]);
+	}
+
+	#[test]
+	fn ndjson_limits_nesting() {
+		let nested = |depth| format!(r#"{{"id": 1, "deep": {}{}}}"#, "[".repeat(depth), "]".repeat(depth));
+		assert_eq!(json_field(&nested(MAX_JSON_DEPTH), "id"), Ok(Some(Cow::Borrowed("1"))));
+		assert_eq!(json_field(&nested(MAX_JSON_DEPTH + 1), "id"), Err(DecodeError::Malformed("nesting too deep")));
+		let hostile = format!(r#"{{"id": 1, "deep": {}}}"#, "[".repeat(1 << 20));
+		let out = Converter::new(Source::Ndjson { field: "id" }).run([hostile]);
+		assert_eq!(out.errors, vec![RowError { row: 0, kind: RowErrorKind::Malformed }]);
+	}
+
+	#[test]
+	#[should_panic(expected = "block length must be positive")]
+	fn rejects_empty_blocks() {
+		let _ = Converter::new(Source::Ndjson { field: "id" }).block_len(0);
+	}
+}
@@ -0,0 +1,38 @@
+// CRC-32 (IEEE 802.3, as in zlib and PNG), table driven.
+
+const fn build_table() -> [u32; 256] {
+	let mut table = [0u32; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut c = i as u32;
+		let mut k = 0;
+		while k < 8 {
+			c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+			k += 1;
+		}
+		table[i] = c;
+		i += 1;
+	}
+	table
+}
+
+static TABLE: [u32; 256] = build_table();
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+	let mut c = !0u32;
+	for &b in bytes {
+		c = TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+	}
+	!c
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn check_value() {
+		assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+		assert_eq!(crc32(b""), 0);
+	}
+}
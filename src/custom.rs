@@ -0,0 +1,133 @@
+//! Single-byte tags of your own in the marker ranges the encoding leaves
+//! free, `0x00..=0x0f` and `0xf0..=0xff`, mixed into a stream of values.
+//!
+//! ```
+//! use rust_varint::custom::{CustomMarkers, Marked};
+//! use rust_varint::pack_uint_into_array;
+//!
+//! const TOMBSTONE: u8 = 0xf8;
+//! let markers = CustomMarkers::new().with(TOMBSTONE);
+//! let mut buf = [0; 9];
+//! let len = pack_uint_into_array(7, &mut buf);
+//! assert_eq!(markers.decode_uint(&buf[..len]), Ok((Marked::Value(7), len)));
+//! assert_eq!(markers.decode_uint(&[TOMBSTONE]), Ok((Marked::Custom(TOMBSTONE), 1)));
+//! ```
+//!
+//! Other parts of the crate claim some of these bytes: `tagged` leads
+//! streams with `0x01..=0x0f`, `wide` encodes 128-bit values with
+//! `0x08..=0x0f` and `0xf0`, `bigint` wider ones with `0x07` and `0xf1`,
+//! the sentinels are `0x00` and `0xff`, and `pack_opt_u64` writes `None`
+//! as `0x01` or `0xfe`. A tag is only unambiguous in streams that use none
+//! of those.
+
+use crate::{decode_int, decode_uint, DecodeError};
+
+/// Whether `b` is a first byte no encoded 64-bit value starts with.
+pub fn is_free_marker(b: u8) -> bool {
+	!(0x10..0xf0).contains(&b)
+}
+
+/// One item of a stream with custom tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marked<T> {
+	Value(T),
+	/// A registered tag, which occupies its one byte.
+	Custom(u8),
+}
+
+/// The set of tags a stream may contain. Free markers not in the set
+/// still fail to decode with `InvalidMarker`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CustomMarkers(u32);
+
+// Bit of `tag` in the set: the low range takes bits 0 to 15, the high
+// range 16 to 31.
+fn bit(tag: u8) -> u32 {
+	1 << ((tag & 0xf) + if tag >= 0xf0 { 16 } else { 0 })
+}
+
+impl CustomMarkers {
+	pub const fn new() -> CustomMarkers {
+		CustomMarkers(0)
+	}
+
+	/// Adds `tag` to the set. Panics if `tag` is not a free marker.
+	pub fn with(mut self, tag: u8) -> CustomMarkers {
+		self.register(tag);
+		self
+	}
+
+	/// Adds `tag` to the set in place, see `with`.
+	pub fn register(&mut self, tag: u8) {
+		assert!(is_free_marker(tag), "custom tag 0x{:02x} is not a free marker", tag);
+		self.0 |= bit(tag);
+	}
+
+	pub fn contains(&self, tag: u8) -> bool {
+		is_free_marker(tag) && self.0 & bit(tag) != 0
+	}
+
+	/// Decodes the unsigned value or registered tag at the front of `buf`,
+	/// returning it with the number of bytes it occupied.
+	pub fn decode_uint(&self, buf: &[u8]) -> Result<(Marked<u64>, usize), DecodeError> {
+		match buf.first() {
+			Some(&tag) if self.contains(tag) => Ok((Marked::Custom(tag), 1)),
+			_ => decode_uint(buf).map(|(x, used)| (Marked::Value(x), used)),
+		}
+	}
+
+	/// Signed counterpart of `decode_uint`.
+	pub fn decode_int(&self, buf: &[u8]) -> Result<(Marked<i64>, usize), DecodeError> {
+		match buf.first() {
+			Some(&tag) if self.contains(tag) => Ok((Marked::Custom(tag), 1)),
+			_ => decode_int(buf).map(|(x, used)| (Marked::Value(x), used)),
+		}
+	}
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+	use crate::pack_int_append;
+
+	#[quickcheck]
+	fn values_and_tags_interleave(items: Vec<(i64, u8, bool)>) -> bool {
+		let markers = CustomMarkers::new().with(0x03).with(0xf0).with(0xfe);
+		let tags = [0x03, 0xf0, 0xfe];
+		let items: Vec<Marked<i64>> = items.into_iter()
+			.map(|(v, t, tag)| if tag { Marked::Custom(tags[t as usize % 3]) } else { Marked::Value(v << (t % 64)) })
+			.collect();
+		let mut buf = Vec::new();
+		for item in &items {
+			match *item {
+				Marked::Value(v) => pack_int_append(v, &mut buf),
+				Marked::Custom(tag) => buf.push(tag),
+			}
+		}
+		let mut pos = 0;
+		let mut out = Vec::new();
+		while pos < buf.len() {
+			let (item, used) = markers.decode_int(&buf[pos..]).unwrap();
+			out.push(item);
+			pos += used;
+		}
+		out == items
+	}
+
+	#[test]
+	fn only_registered_tags_decode() {
+		let mut markers = CustomMarkers::new();
+		markers.register(0x0f);
+		assert!(markers.contains(0x0f) && !markers.contains(0xff) && !markers.contains(0x80));
+		assert_eq!(markers.decode_uint(&[0x0f, 0x81]), Ok((Marked::Custom(0x0f), 1)));
+		assert_eq!(markers.decode_uint(&[0xff]), Err(DecodeError::InvalidMarker(0xff)));
+		assert_eq!(markers.decode_uint(&[]), Err(DecodeError::Truncated { needed: 1 }));
+		assert!((0..=255).filter(|&b| is_free_marker(b)).eq((0x00..0x10).chain(0xf0..=0xff)));
+	}
+
+	#[test]
+	#[should_panic(expected = "custom tag 0x80 is not a free marker")]
+	fn rejects_tags_that_are_markers() {
+		CustomMarkers::new().with(0x80);
+	}
+}
@@ -0,0 +1,173 @@
+//! Decimal numbers as keys that sort numerically, for amounts that must
+//! not round-trip through `f64`.
+//!
+//! A decimal is `mantissa * 10^-scale`. Written as `0.d1 d2 .. dn * 10^e`
+//! with `d1` non-zero and no trailing zeros, it is encoded as
+//!
+//! ```text
+//! zero:     [0x80]
+//! positive: [0xc0][e: int][digit pairs ...][0x00]
+//! negative: [0x40] then the bytes of the positive form, complemented
+//! ```
+//!
+//! Each pair of digits `ab` is the byte `2 * (10a + b) + 1`, the last pair
+//! padded with a zero digit, so every pair byte is above the terminator.
+//! Equal values encode alike whatever their scale: `1.50` and `1.5` give
+//! the same bytes, and decode with the smallest scale that holds them.
+//!
+//! ```
+//! use rust_varint::decimal::{pack_decimal, unpack_decimal};
+//!
+//! let price = pack_decimal(1999, 2); // 19.99
+//! assert!(pack_decimal(-5, 0) < pack_decimal(1, 3));
+//! assert!(pack_decimal(195, 1) < price); // 19.5
+//! assert_eq!(unpack_decimal(&price), Ok(((1999, 2), price.len())));
+//! ```
+//!
+//! With the `rust_decimal` feature, `pack_rust_decimal` and
+//! `unpack_rust_decimal` take `rust_decimal::Decimal` directly.
+
+use std::convert::TryFrom;
+
+use crate::{decode_int, pack_int_append, DecodeError, MAX_ENCODED_LEN};
+
+const NEGATIVE: u8 = 0x40;
+const ZERO: u8 = 0x80;
+const POSITIVE: u8 = 0xc0;
+const TERMINATOR: u8 = 0x00;
+
+/// Encodes `mantissa * 10^-scale`.
+pub fn pack_decimal(mantissa: i128, scale: u32) -> Vec<u8> {
+	if mantissa == 0 {
+		return vec![ZERO];
+	}
+	let mut digits = mantissa.unsigned_abs().to_string().into_bytes();
+	let exponent = digits.len() as i64 - scale as i64;
+	while digits.last() == Some(&b'0') {
+		digits.pop();
+	}
+	let mut out = vec![if mantissa < 0 { NEGATIVE } else { POSITIVE }];
+	pack_int_append(exponent, &mut out);
+	for pair in digits.chunks(2) {
+		let hi = pair[0] - b'0';
+		let lo = pair.get(1).map_or(0, |d| d - b'0');
+		out.push(2 * (10 * hi + lo) + 1);
+	}
+	out.push(TERMINATOR);
+	if mantissa < 0 {
+		out[1..].iter_mut().for_each(|b| *b = !*b);
+	}
+	out
+}
+
+/// Decodes a value written by `pack_decimal` as its mantissa and scale,
+/// returning them with the number of bytes the value occupied. Fails with
+/// `Overflow` when the mantissa does not fit an `i128`.
+pub fn unpack_decimal(buf: &[u8]) -> Result<((i128, u32), usize), DecodeError> {
+	let negative = match buf.first() {
+		Some(&ZERO) => return Ok(((0, 0), 1)),
+		Some(&NEGATIVE) => true,
+		Some(&POSITIVE) => false,
+		Some(_) => return Err(DecodeError::Malformed("invalid decimal sign byte")),
+		None => return Err(DecodeError::Truncated { needed: 1 }),
+	};
+	// undo the complement of a negative value as it is read
+	let flip = if negative { 0xff } else { 0x00 };
+	let head: Vec<u8> = buf[1..].iter().take(MAX_ENCODED_LEN).map(|&b| b ^ flip).collect();
+	let (exponent, len) = decode_int(&head).map_err(|e| match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: needed + 1 },
+		e => e,
+	})?;
+	let start = 1 + len;
+	let end = match buf[start..].iter().position(|&b| b ^ flip == TERMINATOR) {
+		Some(0) => return Err(DecodeError::Malformed("decimal has no digits")),
+		Some(i) => start + i + 1,
+		None => return Err(DecodeError::Truncated { needed: buf.len() + 1 }),
+	};
+
+	let pairs = &buf[start..end - 1];
+	let mut mantissa: u128 = 0;
+	let mut n: i64 = 0;
+	for (i, &b) in pairs.iter().enumerate() {
+		let pair = (b ^ flip) as u128;
+		if pair & 1 == 0 || pair > 199 {
+			return Err(DecodeError::Malformed("invalid decimal digit pair"));
+		}
+		let pair = pair / 2;
+		// the zero digit padding the last pair is not part of the value
+		let (digits, place) = if i == pairs.len() - 1 && pair.is_multiple_of(10) { (pair / 10, 10) } else { (pair, 100) };
+		mantissa = mantissa.checked_mul(place).and_then(|m| m.checked_add(digits)).ok_or(DecodeError::Overflow)?;
+		n += if place == 10 { 1 } else { 2 };
+	}
+
+	let shift = exponent.checked_sub(n).ok_or(DecodeError::Overflow)?;
+	let (mantissa, scale) = if shift >= 0 {
+		let m = u32::try_from(shift).ok().and_then(|s| 10u128.checked_pow(s)).and_then(|p| mantissa.checked_mul(p));
+		(m.ok_or(DecodeError::Overflow)?, 0)
+	} else {
+		(mantissa, u32::try_from(-shift).map_err(|_| DecodeError::Overflow)?)
+	};
+	let mantissa = i128::try_from(mantissa).map_err(|_| DecodeError::Overflow)?;
+	Ok(((if negative { -mantissa } else { mantissa }, scale), end))
+}
+
+#[cfg(feature = "rust_decimal")]
+pub fn pack_rust_decimal(d: rust_decimal::Decimal) -> Vec<u8> {
+	pack_decimal(d.mantissa(), d.scale())
+}
+
+/// Decodes a value written by `pack_rust_decimal`. Fails with `Overflow`
+/// for values outside `Decimal`'s 96-bit mantissa and scale of 28.
+#[cfg(feature = "rust_decimal")]
+pub fn unpack_rust_decimal(buf: &[u8]) -> Result<(rust_decimal::Decimal, usize), DecodeError> {
+	let ((mantissa, scale), used) = unpack_decimal(buf)?;
+	let d = rust_decimal::Decimal::try_from_i128_with_scale(mantissa, scale).map_err(|_| DecodeError::Overflow)?;
+	Ok((d, used))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cmp::Ordering;
+
+	// Compares two small decimals exactly by bringing them to one scale.
+	fn cmp(a: (i64, u32), b: (i64, u32)) -> Ordering {
+		let scale = a.1.max(b.1);
+		(a.0 as i128 * 10i128.pow(scale - a.1)).cmp(&(b.0 as i128 * 10i128.pow(scale - b.1)))
+	}
+
+	#[quickcheck]
+	fn decimals_sort_numerically(a: (i64, u8, u8), b: (i64, u8, u8)) -> bool {
+		let d = |(m, s, shift): (i64, u8, u8)| (m.wrapping_mul(10i64.pow(shift as u32 % 19)), s as u32 % 20);
+		let (x, y) = (d(a), d(b));
+		let (px, py) = (pack_decimal(x.0 as i128, x.1), pack_decimal(y.0 as i128, y.1));
+		let ((m, s), used) = unpack_decimal(&px).unwrap();
+		px.cmp(&py) == cmp(x, y) && used == px.len() &&
+			(m * 10i128.pow(x.1.saturating_sub(s))) == x.0 as i128 * 10i128.pow(s.saturating_sub(x.1))
+	}
+
+	#[test]
+	fn normalizes_and_rejects() {
+		assert_eq!(pack_decimal(150, 2), pack_decimal(15, 1));
+		assert_eq!(pack_decimal(0, 9), [0x80]);
+		assert_eq!(unpack_decimal(&pack_decimal(1200, 0)), Ok(((1200, 0), 4)));
+		assert_eq!(unpack_decimal(&pack_decimal(-7, 30)), Ok(((-7, 30), 4)));
+		assert_eq!(unpack_decimal(&pack_decimal(i128::MIN + 1, 0)).unwrap().0, (i128::MIN + 1, 0));
+		assert_eq!(unpack_decimal(&[0xc0, 0x81, 0x02, 0x00]), Err(DecodeError::Malformed("invalid decimal digit pair")));
+		assert_eq!(unpack_decimal(&[0xc0, 0x81, 0x03]), Err(DecodeError::Truncated { needed: 4 }));
+		assert_eq!(unpack_decimal(&[0x07]), Err(DecodeError::Malformed("invalid decimal sign byte")));
+		assert_eq!(unpack_decimal(&[0xc0, 0x81, 0x00]), Err(DecodeError::Malformed("decimal has no digits")));
+		assert_eq!(unpack_decimal(&[0x40, 0x3f]), Err(DecodeError::Truncated { needed: 3 }));
+		// 1 * 10^100 does not fit
+		assert_eq!(unpack_decimal(&[0xc0, 0xc0, 0x25, 0x15, 0x00]), Err(DecodeError::Overflow));
+	}
+
+	#[cfg(feature = "rust_decimal")]
+	#[test]
+	fn converts_rust_decimals() {
+		let d = rust_decimal::Decimal::new(-123_456, 3);
+		assert_eq!(unpack_rust_decimal(&pack_rust_decimal(d)), Ok((d, pack_rust_decimal(d).len())));
+		assert!(pack_rust_decimal(d) < pack_rust_decimal(rust_decimal::Decimal::new(-123_455, 3)));
+		assert_eq!(unpack_rust_decimal(&pack_decimal(1, 40)), Err(DecodeError::Overflow));
+	}
+}
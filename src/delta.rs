@@ -0,0 +1,94 @@
+//! Non-decreasing sequences, such as posting lists and timestamp columns,
+//! stored as the gaps between neighbouring values.
+//!
+//! The result is a sealed block (see `block`) whose payload is the first
+//! value and then each gap, all packed as unsigned:
+//!
+//! ```text
+//! [count: uint][payload length: uint][first: uint][gap: uint ...]
+//! ```
+//!
+//! Gaps below 64 take one byte and below 8256 two, however large the
+//! values themselves are.
+//!
+//! ```
+//! use rust_varint::delta::{pack_sorted, unpack_sorted};
+//!
+//! let ids = [1_000_000_000, 1_000_000_003, 1_000_000_003, 1_000_000_090];
+//! let packed = pack_sorted(&ids);
+//! assert_eq!(packed.len(), 2 + 5 + 1 + 1 + 2);
+//! assert_eq!(unpack_sorted(&packed), Ok((ids.to_vec(), packed.len())));
+//! ```
+
+use crate::block::{seal, unseal};
+use crate::{decode_uint_at, pack_uint_append, DecodeError};
+
+/// Packs `values` as a sealed block of gaps. Panics if a value is less
+/// than the one before it; sort them first.
+pub fn pack_sorted(values: &[u64]) -> Vec<u8> {
+	let mut payload = Vec::with_capacity(values.len() + 8);
+	let mut prev = 0;
+	for &v in values {
+		assert!(v >= prev, "sorted values must not decrease");
+		pack_uint_append(v - prev, &mut payload);
+		prev = v;
+	}
+	seal(values.len(), payload)
+}
+
+/// Decodes the block at the front of `buf`, returning its values and the
+/// number of bytes it occupied. Fails with `Overflow` if the gaps add up
+/// past `u64::MAX`.
+pub fn unpack_sorted(buf: &[u8]) -> Result<(Vec<u64>, usize), DecodeError> {
+	let (count, payload, used) = unseal(buf)?;
+	let mut values = Vec::with_capacity(count as usize);
+	let (mut pos, mut prev) = (0, 0u64);
+	for _ in 0..count {
+		let gap = decode_uint_at(payload, &mut pos)?;
+		prev = prev.checked_add(gap).ok_or(DecodeError::Overflow)?;
+		values.push(prev);
+	}
+	if pos != payload.len() {
+		return Err(DecodeError::Malformed("trailing bytes after the last block value"));
+	}
+	Ok((values, used))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(mut values: Vec<u64>) -> bool {
+		values.sort_unstable();
+		let packed = pack_sorted(&values);
+		unpack_sorted(&packed) == Ok((values, packed.len()))
+	}
+
+	#[quickcheck]
+	fn small_gaps_take_one_byte(start: u64, gaps: Vec<u8>) -> bool {
+		let values: Vec<u64> = gaps.iter().scan(start >> 1, |v, &g| {
+			*v += (g % 64) as u64;
+			Some(*v)
+		}).collect();
+		let packed = pack_sorted(&values);
+		let (_, payload, _) = unseal(&packed).unwrap();
+		values.is_empty() || payload.len() == crate::encoded_len_u64(values[0]) + values.len() - 1
+	}
+
+	#[test]
+	fn rejects_overflowing_gaps() {
+		let packed = pack_sorted(&[u64::MAX - 1, u64::MAX]);
+		assert_eq!(unpack_sorted(&packed), Ok((vec![u64::MAX - 1, u64::MAX], packed.len())));
+		let mut bad = packed.clone();
+		*bad.last_mut().unwrap() = 0x82;
+		assert_eq!(unpack_sorted(&bad), Err(DecodeError::Overflow));
+		assert_eq!(unpack_sorted(&pack_sorted(&[])), Ok((vec![], 2)));
+	}
+
+	#[test]
+	#[should_panic(expected = "must not decrease")]
+	fn requires_sorted_input() {
+		pack_sorted(&[3, 2]);
+	}
+}
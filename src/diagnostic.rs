@@ -0,0 +1,154 @@
+//! Detailed reports for values that fail to decode, for investigating
+//! corrupted pages.
+
+use std::error;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::{decode_uint, int_len_from_marker, DecodeError};
+
+// bytes of context shown on each side of the value
+const CONTEXT: usize = 8;
+
+/// Encoding class announced by a first byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarkerClass {
+	/// `0x00..=0x0f` and `0xf0..=0xff`, not assigned.
+	Reserved,
+	NegativeMulti,
+	NegativeTwoByte,
+	NegativeOneByte,
+	PositiveOneByte,
+	PositiveTwoByte,
+	PositiveMulti,
+}
+
+impl MarkerClass {
+	pub fn of(first: u8) -> MarkerClass {
+		match first {
+			0x10..=0x1f => MarkerClass::NegativeMulti,
+			0x20..=0x3f => MarkerClass::NegativeTwoByte,
+			0x40..=0x7f => MarkerClass::NegativeOneByte,
+			0x80..=0xbf => MarkerClass::PositiveOneByte,
+			0xc0..=0xdf => MarkerClass::PositiveTwoByte,
+			0xe0..=0xef => MarkerClass::PositiveMulti,
+			_ => MarkerClass::Reserved,
+		}
+	}
+}
+
+/// Everything known about a value that failed to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub error: DecodeError,
+	/// Offset of the value in the inspected buffer.
+	pub offset: usize,
+	/// `None` when the value starts at or past the end of the buffer.
+	pub first_byte: Option<u8>,
+	pub class: Option<MarkerClass>,
+	/// Length the first byte claims, if it claims a valid one.
+	pub expected_len: Option<usize>,
+	/// Bytes left in the buffer from `offset`.
+	pub available: usize,
+	/// Hex dump around the value, with the value's claimed bytes in
+	/// brackets.
+	pub context: String,
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at offset {}", self.error, self.offset)?;
+		if let (Some(first), Some(class)) = (self.first_byte, self.class) {
+			write!(f, ": first byte 0x{:02x} ({:?})", first, class)?;
+		}
+		if let Some(len) = self.expected_len {
+			write!(f, ", expects {} bytes", len)?;
+		}
+		write!(f, ", {} available\n{}", self.available, self.context)
+	}
+}
+
+impl error::Error for Diagnostic {}
+
+fn render_context(buf: &[u8], offset: usize, len: usize) -> String {
+	let start = offset.saturating_sub(CONTEXT);
+	let end = buf.len().min(offset + len.max(1) + CONTEXT);
+	let value_end = buf.len().min(offset + len.max(1));
+	let mut out = String::new();
+	write!(out, "{:08x}:", start).unwrap();
+	for (i, b) in buf.iter().enumerate().take(end).skip(start) {
+		let open = if i == offset { "[" } else { " " };
+		write!(out, "{}{:02x}", open, b).unwrap();
+		if i + 1 == value_end && offset < value_end {
+			out.push(']');
+		}
+	}
+	if offset >= buf.len() {
+		out.push_str(" []");
+	}
+	out
+}
+
+/// `decode_uint` at the front of `buf`, with a `Diagnostic` on failure.
+pub fn decode_debug(buf: &[u8]) -> Result<u64, Diagnostic> {
+	decode_debug_at(buf, 0)
+}
+
+/// Decodes the unsigned value at `offset` of a larger buffer such as a
+/// page, so the context shows the bytes around it.
+pub fn decode_debug_at(buf: &[u8], offset: usize) -> Result<u64, Diagnostic> {
+	let rest = buf.get(offset..).unwrap_or(&[]);
+	decode_uint(rest).map(|(v, _)| v).map_err(|error| {
+		let first_byte = rest.first().copied();
+		let expected_len = first_byte.and_then(|b| int_len_from_marker(b).ok());
+		Diagnostic {
+			error,
+			offset,
+			first_byte,
+			class: first_byte.map(MarkerClass::of),
+			expected_len,
+			available: rest.len(),
+			context: render_context(buf, offset, expected_len.unwrap_or(1)),
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn decodes_like_decode_uint(buf: Vec<u8>) -> bool {
+		decode_debug(&buf).map_err(|d| d.error) == decode_uint(&buf).map(|(v, _)| v)
+	}
+
+	#[test]
+	fn reports_truncation() {
+		let page = [0x81, 0x82, 0x83, 0xe3, 0x01];
+		let d = decode_debug_at(&page, 3).unwrap_err();
+		assert_eq!(d, Diagnostic {
+			error: DecodeError::Truncated { needed: 4 },
+			offset: 3,
+			first_byte: Some(0xe3),
+			class: Some(MarkerClass::PositiveMulti),
+			expected_len: Some(4),
+			available: 2,
+			context: "00000000: 81 82 83[e3 01]".to_string(),
+		});
+		assert_eq!(d.to_string(), "truncated input, value needs 4 bytes at offset 3: first byte 0xe3 \
+			(PositiveMulti), expects 4 bytes, 2 available\n00000000: 81 82 83[e3 01]");
+	}
+
+	#[test]
+	fn reports_bad_markers() {
+		let mut page = vec![0x80; 20];
+		page[10] = 0x05;
+		let d = decode_debug_at(&page, 10).unwrap_err();
+		assert_eq!((d.error, d.class, d.expected_len), (DecodeError::InvalidMarker(0x05), Some(MarkerClass::Reserved), None));
+		assert_eq!(d.context, "00000002: 80 80 80 80 80 80 80 80[05] 80 80 80 80 80 80 80 80");
+
+		let d = decode_debug_at(&page, 25).unwrap_err();
+		assert_eq!((d.first_byte, d.available, d.context.as_str()), (None, 0, "00000011: 80 80 80 []"));
+		assert_eq!(decode_debug(&[0x40]).unwrap_err().class, Some(MarkerClass::NegativeOneByte));
+	}
+}
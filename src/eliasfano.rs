@@ -0,0 +1,281 @@
+//! Elias–Fano coding of non-decreasing `u64` sequences, which answers
+//! `select` and `next_geq` without decompressing.
+//!
+//! Each value is split into its low `l` bits, stored at a fixed width,
+//! and the rest, its bucket. The buckets go into a bitvector in unary: the
+//! `i`th value sets bit `bucket + i`. Choosing `l` near `log2(last / n)`
+//! keeps the whole sequence within about `2 + log2(last / n)` bits per
+//! value.
+//!
+//! ```
+//! use rust_varint::eliasfano::EliasFano;
+//!
+//! let postings = [3, 9, 9, 120, 4_000, 4_001, 90_000];
+//! let ef = EliasFano::new(&postings);
+//! assert_eq!(ef.select(3), Some(120));
+//! assert_eq!(ef.next_geq(121), Some((4, 4_000)));
+//! assert_eq!(ef.next_geq(90_001), None);
+//!
+//! let bytes = ef.to_bytes();
+//! assert_eq!(EliasFano::from_bytes(&bytes), Ok((ef, bytes.len())));
+//! ```
+
+use crate::bitvec::{BitVec, BitVecBuilder};
+use crate::{decode_uint, pack_uint, DecodeError};
+
+/// A compressed, immutable sorted sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EliasFano {
+	len: usize,
+	low_bits: u32,
+	low: Vec<u64>,
+	high: BitVec,
+}
+
+impl EliasFano {
+	/// Panics if a value is less than the one before it; sort them first.
+	pub fn new(values: &[u64]) -> EliasFano {
+		let len = values.len();
+		let last = values.last().cloned().unwrap_or(0);
+		let ratio = if len == 0 { 0 } else { last / len as u64 };
+		let low_bits = if ratio == 0 { 0 } else { 63 - ratio.leading_zeros() };
+		let mut low = vec![0; (len * low_bits as usize).div_ceil(64)];
+		let mut high = BitVecBuilder::with_len(len + (last >> low_bits) as usize + 1);
+		let mut prev = 0;
+		for (i, &v) in values.iter().enumerate() {
+			assert!(v >= prev, "sorted values must not decrease");
+			prev = v;
+			set_low(&mut low, low_bits, i, v);
+			high.set((v >> low_bits) as usize + i);
+		}
+		EliasFano { len, low_bits, low, high: high.build() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// The `i`th value (counting from zero), if there is one.
+	pub fn select(&self, i: usize) -> Option<u64> {
+		let pos = self.high.select1(i)?;
+		Some(self.value(i, pos))
+	}
+
+	/// The first value not less than `x`, with its index.
+	pub fn next_geq(&self, x: u64) -> Option<(usize, u64)> {
+		let bucket = x >> self.low_bits;
+		let buckets = self.high.count_zeros() as u64;
+		if bucket >= buckets {
+			return None;
+		}
+		// the bucket's values are the ones between the zeros around it
+		let start = if bucket == 0 { 0 } else { self.high.select0(bucket as usize - 1)? + 1 };
+		let end = self.high.select0(bucket as usize)?;
+		let first = start - bucket as usize;
+		// they share their high bits, so search their low bits; past them
+		// the answer is the first value of a later bucket
+		let (mut lo, mut hi) = (first, first + (end - start));
+		while lo < hi {
+			let mid = lo + (hi - lo) / 2;
+			if self.value(mid, start + (mid - first)) < x {
+				lo = mid + 1;
+			} else {
+				hi = mid;
+			}
+		}
+		self.select(lo).map(|v| (lo, v))
+	}
+
+	/// Iterates the values in order.
+	pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+		Values { ef: self, i: 0, pos: 0 }.map(|(_, v)| v)
+	}
+
+	// Value `i`, whose unary bit is at `pos`.
+	fn value(&self, i: usize, pos: usize) -> u64 {
+		((pos - i) as u64) << self.low_bits | get_low(&self.low, self.low_bits, i)
+	}
+
+	/// Serializes as `[len][low width][low words][high bitvector]`, with the
+	/// length and width packed as varints, the words little-endian and the
+	/// bitvector as `BitVec::to_bytes` writes it.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = pack_uint(self.len as u64);
+		out.extend_from_slice(&pack_uint(self.low_bits as u64));
+		for w in &self.low {
+			out.extend_from_slice(&w.to_le_bytes());
+		}
+		out.extend_from_slice(&self.high.to_bytes());
+		out
+	}
+
+	/// Parses the output of `to_bytes`, returning the sequence and the
+	/// number of bytes consumed. The parts are checked against each other
+	/// and the values for order.
+	pub fn from_bytes(buf: &[u8]) -> Result<(EliasFano, usize), DecodeError> {
+		let (len, n1) = decode_uint(buf)?;
+		let (low_bits, n2) = decode_uint(&buf[n1..])?;
+		if low_bits > 63 {
+			return Err(DecodeError::Malformed("Elias-Fano low width is over 63 bits"));
+		}
+		let words = (len as u128 * low_bits as u128).div_ceil(64);
+		let start = n1 + n2;
+		let end = (start as u128 + words * 8).min(usize::MAX as u128) as usize;
+		let low_bytes = buf.get(start..end).ok_or(DecodeError::Truncated { needed: end })?;
+		let low: Vec<u64> = low_bytes.chunks(8).map(|c| {
+			let mut b = [0u8; 8];
+			b.copy_from_slice(c);
+			u64::from_le_bytes(b)
+		}).collect();
+		let (high, used) = BitVec::from_bytes(&buf[end..]).map_err(|e| match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: end + needed },
+			e => e,
+		})?;
+		if high.count_ones() as u64 != len {
+			return Err(DecodeError::Malformed("Elias-Fano length does not match its buckets"));
+		}
+		let ef = EliasFano { len: len as usize, low_bits: low_bits as u32, low, high };
+		// the bitvector ends right after the bucket of the last value
+		let last = ef.len.checked_sub(1).and_then(|i| ef.high.select1(i).map(|p| (i, p)));
+		if ef.high.len() - last.map_or(0, |(_, p)| p + 1) != 1 {
+			return Err(DecodeError::Malformed("Elias-Fano buckets end past the last value"));
+		}
+		// the last bucket is the highest, so if it fits every value does
+		if let Some((i, p)) = last {
+			let bucket = (p - i) as u64;
+			if bucket.checked_shl(ef.low_bits).filter(|v| v >> ef.low_bits == bucket).is_none() {
+				return Err(DecodeError::Malformed("Elias-Fano values overflow 64 bits"));
+			}
+		}
+		if ef.iter().zip(ef.iter().skip(1)).any(|(a, b)| a > b) {
+			return Err(DecodeError::Malformed("Elias-Fano values are out of order"));
+		}
+		Ok((ef, end + used))
+	}
+}
+
+// Yields the values from `i` on; `pos` is where value `i`'s unary bit is,
+// or an earlier bucket's zero.
+struct Values<'a> {
+	ef: &'a EliasFano,
+	i: usize,
+	pos: usize,
+}
+
+impl<'a> Iterator for Values<'a> {
+	type Item = (usize, u64);
+
+	fn next(&mut self) -> Option<(usize, u64)> {
+		if self.i == self.ef.len {
+			return None;
+		}
+		// jump over empty buckets rather than walk their zeros
+		if !self.ef.high.get(self.pos) {
+			self.pos = self.ef.high.select1(self.i)?;
+		}
+		let item = (self.i, self.ef.value(self.i, self.pos));
+		self.i += 1;
+		self.pos += 1;
+		Some(item)
+	}
+}
+
+fn set_low(words: &mut [u64], width: u32, i: usize, v: u64) {
+	if width == 0 {
+		return;
+	}
+	let v = v & ((1 << width) - 1);
+	let bit = i * width as usize;
+	let (w, off) = (bit / 64, bit % 64);
+	words[w] |= v << off;
+	if off + width as usize > 64 {
+		words[w + 1] |= v >> (64 - off);
+	}
+}
+
+fn get_low(words: &[u64], width: u32, i: usize) -> u64 {
+	if width == 0 {
+		return 0;
+	}
+	let bit = i * width as usize;
+	let (w, off) = (bit / 64, bit % 64);
+	let mut v = words[w] >> off;
+	if off + width as usize > 64 {
+		v |= words[w + 1] << (64 - off);
+	}
+	v & ((1 << width) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn selects_and_seeks(mut values: Vec<u64>, shift: u8, probes: Vec<u64>) -> bool {
+		values.iter_mut().for_each(|v| *v >>= shift % 64);
+		values.sort_unstable();
+		let ef = EliasFano::new(&values);
+		let bytes = ef.to_bytes();
+		ef.iter().eq(values.iter().cloned()) &&
+			(0..=values.len()).all(|i| ef.select(i) == values.get(i).cloned()) &&
+			probes.iter().map(|&x| x >> (shift % 64)).chain(values.iter().cloned()).all(|x| {
+				let i = values.partition_point(|&v| v < x);
+				ef.next_geq(x) == values.get(i).map(|&v| (i, v))
+			}) &&
+			EliasFano::from_bytes(&bytes) == Ok((ef, bytes.len()))
+	}
+
+	#[test]
+	fn stays_compact() {
+		let values: Vec<u64> = (0..10_000).map(|i| i * 1000 + i % 7).collect();
+		let ef = EliasFano::new(&values);
+		assert_eq!(ef.low_bits, 9);
+		// under 13 bits per value, against 64 raw
+		assert!(ef.to_bytes().len() < 10_000 * 13 / 8);
+		assert_eq!(ef.next_geq(5_000_003), Some((5001, 5_001_003)));
+		assert_eq!(EliasFano::new(&[u64::MAX, u64::MAX]).select(1), Some(u64::MAX));
+		assert_eq!(EliasFano::new(&[]).next_geq(0), None);
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let bytes = EliasFano::new(&[5, 17, 300, 301]).to_bytes();
+		assert_eq!(EliasFano::from_bytes(&bytes[..bytes.len() - 1]).map(|_| ()), Err(DecodeError::Truncated { needed: bytes.len() }));
+		assert_eq!(EliasFano::from_bytes(&bytes[..6]).map(|_| ()), Err(DecodeError::Truncated { needed: 10 }));
+		let mut bad = bytes.clone();
+		bad[0] = 0x85;
+		assert_eq!(EliasFano::from_bytes(&bad).map(|_| ()), Err(DecodeError::Malformed("Elias-Fano length does not match its buckets")));
+		let mut bad = bytes.clone();
+		// swap the lowest bits of 300 and 301, which share a bucket and sit
+		// at bits 12 and 18 of the low word
+		bad[2 + 1] ^= 1 << 4;
+		bad[2 + 2] ^= 1 << 2;
+		assert_eq!(EliasFano::from_bytes(&bad).map(|_| ()), Err(DecodeError::Malformed("Elias-Fano values are out of order")));
+
+		// one value in bucket 2, which with 63 low bits is past u64::MAX
+		let mut high = BitVecBuilder::with_len(4);
+		high.set(2);
+		let wide = EliasFano { len: 1, low_bits: 63, low: vec![0], high: high.build() }.to_bytes();
+		assert_eq!(EliasFano::from_bytes(&wide).map(|_| ()), Err(DecodeError::Malformed("Elias-Fano values overflow 64 bits")));
+	}
+
+	#[test]
+	fn seeks_across_empty_buckets() {
+		let values = [1, 1, 1, 1 << 40, (1 << 40) + 1];
+		let ef = EliasFano::new(&values);
+		assert_eq!(ef.next_geq(2), Some((3, 1 << 40)));
+		assert_eq!(ef.next_geq(1), Some((0, 1)));
+		assert_eq!(ef.next_geq((1 << 40) + 1), Some((4, (1 << 40) + 1)));
+		assert_eq!(ef.iter().collect::<Vec<_>>(), values);
+	}
+
+	#[test]
+	#[should_panic(expected = "must not decrease")]
+	fn requires_sorted_input() {
+		EliasFano::new(&[3, 2]);
+	}
+}
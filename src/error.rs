@@ -0,0 +1,28 @@
+use core::fmt;
+
+/// Reasons a byte buffer could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+	/// The first byte falls in a marker range the format does not assign.
+	InvalidMarker(u8),
+	/// The input ended early; `needed` is the full length the value claims.
+	Truncated { needed: usize },
+	/// The encoded value does not fit the target integer type.
+	Overflow,
+	/// A container header or framing field is inconsistent with its payload.
+	Malformed(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			DecodeError::InvalidMarker(b) => write!(f, "invalid marker byte 0x{:02x}", b),
+			DecodeError::Truncated { needed } => write!(f, "truncated input, value needs {} bytes", needed),
+			DecodeError::Overflow => write!(f, "encoded value overflows the target type"),
+			DecodeError::Malformed(what) => write!(f, "malformed input: {}", what),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
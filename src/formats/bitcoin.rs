@@ -0,0 +1,191 @@
+//! Bitcoin's CompactSize, the length prefix of transactions, scripts and
+//! the other variable-length parts of blocks and P2P messages.
+//!
+//! Values below `0xfd` are one byte. Larger ones are the byte `0xfd`,
+//! `0xfe` or `0xff` followed by the value as a little-endian `u16`, `u32`
+//! or `u64`. Consensus code requires the shortest of these forms, as
+//! `decode_u64_strict` and `read_u64_strict` do; the plain decoders also
+//! accept longer ones. Signed values are ZigZag mapped first, which
+//! Bitcoin itself never needs.
+//!
+//! Bitcoin Core's other varint, the `VARINT` of its UTXO database, is
+//! git's offset varint, see `formats::git`.
+//!
+//! ```
+//! use rust_varint::formats::bitcoin;
+//! use rust_varint::DecodeError;
+//!
+//! let mut out = Vec::new();
+//! bitcoin::encode_u64(&mut out, 515);
+//! assert_eq!(out, [0xfd, 0x03, 0x02]);
+//! assert_eq!(bitcoin::decode_u64_strict(&out), Ok((515, 3)));
+//! assert_eq!(bitcoin::decode_u64_strict(&[0xfd, 0x10, 0x00]),
+//!     Err(DecodeError::Malformed("non-canonical CompactSize")));
+//! ```
+
+use std::io::{self, Read, Write};
+
+use super::VarintFormat;
+use crate::{at_offset, DecodeError};
+
+/// CompactSize as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CompactSize;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 9;
+
+/// Encoded length of `v`.
+pub fn encoded_len_u64(v: u64) -> usize {
+	match v {
+		0..=0xfc => 1,
+		0xfd..=0xffff => 3,
+		0x1_0000..=0xffff_ffff => 5,
+		_ => 9,
+	}
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	let len = encoded_len_u64(v);
+	match len {
+		1 => return out.push(v as u8),
+		3 => out.push(0xfd),
+		5 => out.push(0xfe),
+		_ => out.push(0xff),
+	}
+	out.extend_from_slice(&v.to_le_bytes()[..len - 1]);
+}
+
+// Bytes after the first.
+fn width(first: u8) -> usize {
+	match first {
+		0xfd => 2,
+		0xfe => 4,
+		0xff => 8,
+		_ => 0,
+	}
+}
+
+/// Decodes one value, in any of its forms.
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let n = width(first);
+	if n == 0 {
+		return Ok((first as u64, 1));
+	}
+	let bytes = buf.get(1..1 + n).ok_or(DecodeError::Truncated { needed: 1 + n })?;
+	let mut le = [0; 8];
+	le[..n].copy_from_slice(bytes);
+	Ok((u64::from_le_bytes(le), 1 + n))
+}
+
+/// Decodes one value, rejecting any but the shortest form with
+/// `Malformed`, as Bitcoin Core's `ReadCompactSize` does.
+pub fn decode_u64_strict(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let (v, len) = decode_u64(buf)?;
+	if len != encoded_len_u64(v) {
+		return Err(DecodeError::Malformed("non-canonical CompactSize"));
+	}
+	Ok((v, len))
+}
+
+/// Decodes the value at `*pos` in its shortest form and moves `pos` past
+/// it. `Truncated` counts from the start of `buf`, and `pos` is left
+/// alone on error.
+pub fn decode_u64_at(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+	let (v, used) = decode_u64_strict(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(v)
+}
+
+fn read_with<R: Read + ?Sized>(r: &mut R, strict: bool) -> io::Result<u64> {
+	let mut buf = [0; MAX_LEN];
+	r.read_exact(&mut buf[..1])?;
+	let n = width(buf[0]);
+	r.read_exact(&mut buf[1..1 + n])?;
+	let decoded = if strict { decode_u64_strict(&buf[..1 + n]) } else { decode_u64(&buf[..1 + n]) };
+	decoded.map(|(v, _)| v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads one value, in any of its forms, never past its end.
+pub fn read_u64<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+	read_with(r, false)
+}
+
+/// `read_u64` in the shortest form only.
+pub fn read_u64_strict<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+	read_with(r, true)
+}
+
+pub fn write_u64<W: Write + ?Sized>(w: &mut W, v: u64) -> io::Result<()> {
+	let mut out = Vec::with_capacity(MAX_LEN);
+	encode_u64(&mut out, v);
+	w.write_all(&out)
+}
+
+impl VarintFormat for CompactSize {
+	fn max_len(&self) -> usize {
+		MAX_LEN
+	}
+
+	fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+		encode_u64(out, v);
+	}
+
+	fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+		decode_u64(buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, tail: Vec<u8>) -> bool {
+		let x = x >> (shift % 64);
+		let mut b = enc(x);
+		let len = b.len();
+		b.extend_from_slice(&tail);
+		let mut rd = &b[..];
+		len == encoded_len_u64(x) && decode_u64_strict(&b) == Ok((x, len)) &&
+			read_u64_strict(&mut rd).unwrap() == x && rd.len() == tail.len()
+	}
+
+	#[test]
+	fn matches_bitcoin() {
+		assert_eq!(enc(0), [0x00]);
+		assert_eq!(enc(0xfc), [0xfc]);
+		assert_eq!(enc(0xfd), [0xfd, 0xfd, 0x00]);
+		assert_eq!(enc(0xffff), [0xfd, 0xff, 0xff]);
+		assert_eq!(enc(0x1_0000), [0xfe, 0x00, 0x00, 0x01, 0x00]);
+		assert_eq!(enc(0xffff_ffff), [0xfe, 0xff, 0xff, 0xff, 0xff]);
+		assert_eq!(enc(0x1_0000_0000), [0xff, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+		assert_eq!(enc(u64::MAX), [0xff; 9]);
+	}
+
+	#[test]
+	fn strict_forms_reject_longer_encodings() {
+		let long = [&[0xfd, 0xfc, 0x00][..], &[0xfe, 0xff, 0xff, 0x00, 0x00], &[0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]];
+		for buf in &long {
+			let (v, len) = decode_u64(buf).unwrap();
+			assert_eq!(len, buf.len());
+			assert!(!CompactSize.is_canonical(buf));
+			assert_eq!(decode_u64_strict(buf), Err(DecodeError::Malformed("non-canonical CompactSize")));
+			assert_eq!(read_u64(&mut &buf[..]).unwrap(), v);
+			assert_eq!(read_u64_strict(&mut &buf[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+		}
+		assert_eq!(decode_u64(&[]), Err(DecodeError::Truncated { needed: 1 }));
+		assert_eq!(decode_u64(&[0xfe, 0x00, 0x00]), Err(DecodeError::Truncated { needed: 5 }));
+		let mut pos = 1;
+		assert_eq!(decode_u64_at(&[0x01, 0xff, 0x00], &mut pos), Err(DecodeError::Truncated { needed: 10 }));
+		assert_eq!(pos, 1);
+		assert_eq!(read_u64(&mut &[0xfd, 0x00][..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+	}
+}
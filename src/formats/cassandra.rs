@@ -0,0 +1,118 @@
+//! Cassandra's `vint` encoding (`org.apache.cassandra.utils.vint.VIntCoding`).
+//!
+//! The number of leading one bits in the first byte gives the number of
+//! extra bytes that follow; the remaining bits of the first byte and the
+//! extra bytes hold the value big-endian. A first byte of `0xff` is
+//! followed by all eight bytes of the value. Signed values are ZigZag
+//! mapped first.
+//!
+//! Unsigned encodings happen to sort in numeric order; the ZigZag mapping
+//! means signed ones do not.
+
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::DecodeError;
+
+/// Cassandra's `vint` as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Cassandra;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 9;
+
+/// Encoded length of `v`, including the first byte.
+pub fn encoded_len_u64(v: u64) -> usize {
+	let magnitude = (v | 1).leading_zeros() as usize;
+	(639 - magnitude * 9) >> 6
+}
+
+pub fn encoded_len_i64(v: i64) -> usize {
+	encoded_len_u64(zigzag_encode(v))
+}
+
+/// Total encoded length announced by a first byte.
+pub fn len_from_first_byte(first: u8) -> usize {
+	first.leading_ones() as usize + 1
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	let len = encoded_len_u64(v);
+	if len == 1 {
+		out.push(v as u8);
+		return;
+	}
+	let extra = len - 1;
+	if extra == 8 {
+		out.push(0xff);
+		out.extend_from_slice(&v.to_be_bytes());
+	} else {
+		let start = out.len();
+		out.extend_from_slice(&v.to_be_bytes()[7 - extra..]);
+		out[start] |= !(0xffu8 >> extra);
+	}
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, zigzag_encode(v));
+}
+
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = len_from_first_byte(first);
+	if buf.len() < len {
+		return Err(DecodeError::Truncated { needed: len });
+	}
+	let extra = len - 1;
+	let mask = if extra == 8 { 0 } else { 0xffu8 >> extra };
+	let mut v = (first & mask) as u64;
+	for &b in &buf[1..len] {
+		v = (v << 8) | b as u64;
+	}
+	Ok((v, len))
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (zigzag_decode(v), len))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, y: i64) -> bool {
+		let x = x.wrapping_shl(shift as u32 % 64);
+		let b = enc(x);
+		let mut s = Vec::new();
+		encode_i64(&mut s, y);
+		b.len() == encoded_len_u64(x) && len_from_first_byte(b[0]) == b.len() &&
+			decode_u64(&b) == Ok((x, b.len())) && decode_i64(&s) == Ok((y, s.len()))
+	}
+
+	#[test]
+	fn matches_cassandra_vectors() {
+		assert_eq!(enc(0), vec![0x00]);
+		assert_eq!(enc(127), vec![0x7f]);
+		assert_eq!(enc(128), vec![0x80, 0x80]);
+		assert_eq!(enc(16383), vec![0xbf, 0xff]);
+		assert_eq!(enc(16384), vec![0xc0, 0x40, 0x00]);
+		assert_eq!(enc(1 << 56), vec![0xff, 0x01, 0, 0, 0, 0, 0, 0, 0]);
+		assert_eq!(enc(u64::MAX), vec![0xff; 9]);
+
+		let mut out = Vec::new();
+		encode_i64(&mut out, -1);
+		encode_i64(&mut out, -65);
+		assert_eq!(out, vec![0x01, 0x80, 0x81]);
+	}
+
+	#[test]
+	fn reports_truncation() {
+		assert_eq!(decode_u64(&[0xc0, 0x40]), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(decode_u64(&[]), Err(DecodeError::Truncated { needed: 1 }));
+	}
+}
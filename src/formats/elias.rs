@@ -0,0 +1,221 @@
+//! Elias gamma and delta codes, universal codes for positive integers of
+//! no fixed size.
+//!
+//! Gamma writes a value of `n + 1` significant bits as `n` zeros and then
+//! the value itself, most significant bit first. Delta instead writes
+//! `n + 1` in gamma and then the value without its leading one, which is
+//! shorter from 32 upwards. Both use `bits::BitWriter`.
+//!
+//! As `BlockCodec`s, `EliasGamma` and `EliasDelta` code each value plus
+//! one, so that zero and `u64::MAX` are representable too.
+//!
+//! ```
+//! use rust_varint::bits::{BitReader, BitWriter};
+//! use rust_varint::formats::elias::{read_delta, read_gamma, write_delta, write_gamma};
+//!
+//! let mut w = BitWriter::default();
+//! write_gamma(&mut w, 5);
+//! write_delta(&mut w, 1_000_000);
+//! assert_eq!(w.bit_len(), 5 + 28);
+//!
+//! let bytes = w.finish();
+//! let mut r = BitReader::new(&bytes);
+//! assert_eq!(read_gamma(&mut r), Some(Ok(5)));
+//! assert_eq!(read_delta(&mut r), Some(Ok(1_000_000)));
+//! ```
+
+use std::convert::TryFrom;
+
+use super::BlockCodec;
+use crate::bits::{BitReader, BitWriter};
+use crate::DecodeError;
+
+/// Elias gamma as a `BlockCodec`, coding `v + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct EliasGamma;
+
+/// Elias delta as a `BlockCodec`, coding `v + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct EliasDelta;
+
+// Values run up to 2^64, so that the codecs can add one to any u64.
+fn bit_len(v: u128) -> u32 {
+	128 - v.leading_zeros()
+}
+
+fn write_wide(w: &mut BitWriter, v: u128, n: u32) {
+	if n > 64 {
+		w.write_bits((v >> 64) as u64, n - 64);
+		w.write_bits(v as u64, 64);
+	} else {
+		w.write_bits(v as u64, n);
+	}
+}
+
+fn gamma(w: &mut BitWriter, v: u128) {
+	let n = bit_len(v) - 1;
+	w.write_bits(0, n);
+	write_wide(w, v, n + 1);
+}
+
+fn delta(w: &mut BitWriter, v: u128) {
+	let n = bit_len(v) - 1;
+	gamma(w, n as u128 + 1);
+	write_wide(w, v, n);
+}
+
+// Reads `n` zeros and then `n` more bits after the one that ends them.
+fn read_gamma_wide(r: &mut BitReader) -> Option<Result<u128, DecodeError>> {
+	let mut n = 0;
+	while !r.read_bit()? {
+		n += 1;
+		if n > 64 {
+			return Some(Err(DecodeError::Overflow));
+		}
+	}
+	let low = if n == 0 { 0 } else { r.read_bits(n)? };
+	Some(Ok(1 << n | low as u128))
+}
+
+fn read_delta_wide(r: &mut BitReader) -> Option<Result<u128, DecodeError>> {
+	let n = match read_gamma_wide(r)? {
+		Ok(len) if len <= 65 => len as u32 - 1,
+		Ok(_) | Err(_) => return Some(Err(DecodeError::Overflow)),
+	};
+	let low = if n == 0 { 0 } else { r.read_bits(n)? };
+	Some(Ok(1 << n | low as u128))
+}
+
+fn narrow(v: Option<Result<u128, DecodeError>>, offset: u128) -> Option<Result<u64, DecodeError>> {
+	Some(v?.and_then(|v| u64::try_from(v - offset).map_err(|_| DecodeError::Overflow)))
+}
+
+/// Writes `v` in gamma. Panics if `v` is zero.
+pub fn write_gamma(w: &mut BitWriter, v: u64) {
+	assert!(v > 0, "Elias codes start at one");
+	gamma(w, v as u128);
+}
+
+/// Reads one gamma value, or `None` if the bits run out first.
+pub fn read_gamma(r: &mut BitReader) -> Option<Result<u64, DecodeError>> {
+	narrow(read_gamma_wide(r), 0)
+}
+
+/// Writes `v` in delta. Panics if `v` is zero.
+pub fn write_delta(w: &mut BitWriter, v: u64) {
+	assert!(v > 0, "Elias codes start at one");
+	delta(w, v as u128);
+}
+
+/// Reads one delta value, or `None` if the bits run out first.
+pub fn read_delta(r: &mut BitReader) -> Option<Result<u64, DecodeError>> {
+	narrow(read_delta_wide(r), 0)
+}
+
+// The values are padded with zeros to a whole byte.
+fn encode_with(out: &mut Vec<u8>, values: &[u64], code: fn(&mut BitWriter, u128)) {
+	let mut w = BitWriter::default();
+	for &v in values {
+		code(&mut w, v as u128 + 1);
+	}
+	out.extend_from_slice(&w.finish());
+}
+
+type ReadWide = fn(&mut BitReader) -> Option<Result<u128, DecodeError>>;
+
+fn decode_with(buf: &[u8], count: usize, out: &mut Vec<u64>, read: ReadWide) -> Result<usize, DecodeError> {
+	let mut r = BitReader::new(buf);
+	for _ in 0..count {
+		let v = narrow(read(&mut r), 1).ok_or(DecodeError::Truncated { needed: buf.len() + 1 })??;
+		out.push(v);
+	}
+	Ok(r.bit_pos().div_ceil(8) as usize)
+}
+
+impl BlockCodec for EliasGamma {
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]) {
+		encode_with(out, values, gamma);
+	}
+
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+		decode_with(buf, count, out, read_gamma_wide)
+	}
+}
+
+impl BlockCodec for EliasDelta {
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]) {
+		encode_with(out, values, delta);
+	}
+
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+		decode_with(buf, count, out, read_delta_wide)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(values: Vec<(u64, u8)>, tail: Vec<u8>) -> bool {
+		let values: Vec<u64> = values.into_iter().map(|(v, s)| v >> (s % 64)).collect();
+		let codecs: [&dyn BlockCodec; 2] = [&EliasGamma, &EliasDelta];
+		codecs.iter().all(|codec| {
+			let mut buf = Vec::new();
+			codec.encode_u64s(&mut buf, &values);
+			let len = buf.len();
+			buf.extend_from_slice(&tail);
+			let mut out = Vec::new();
+			codec.decode_u64s(&buf, values.len(), &mut out) == Ok(len) && out == values
+		})
+	}
+
+	#[quickcheck]
+	fn lengths(v: u64) -> bool {
+		let v = v.max(1);
+		let n = 63 - v.leading_zeros() as u64;
+		let (mut g, mut d) = (BitWriter::default(), BitWriter::default());
+		write_gamma(&mut g, v);
+		write_delta(&mut d, v);
+		let l = 63 - (n + 1).leading_zeros() as u64;
+		g.bit_len() == 2 * n + 1 && d.bit_len() == n + 2 * l + 1
+	}
+
+	#[test]
+	fn layout() {
+		let mut w = BitWriter::default();
+		for v in 1..=5 {
+			write_gamma(&mut w, v);
+		}
+		// 1, 010, 011, 00100, 00101
+		assert_eq!(w.finish(), vec![0b1010_0110, 0b0100_0010, 0b1000_0000]);
+		let mut w = BitWriter::default();
+		write_delta(&mut w, 1);
+		write_delta(&mut w, 17);
+		// 1, then 00101 for a length of 5 and 0001
+		assert_eq!(w.finish(), vec![0b1001_0100, 0b0100_0000]);
+	}
+
+	#[test]
+	fn covers_the_whole_range() {
+		let mut buf = Vec::new();
+		EliasDelta.encode_u64s(&mut buf, &[u64::MAX, 0]);
+		// the gamma of 65 is 13 bits, then 64 and a one for zero
+		assert_eq!(buf.len(), (13 + 64 + 1usize).div_ceil(8));
+		let mut out = Vec::new();
+		assert_eq!(EliasDelta.decode_u64s(&buf, 2, &mut out), Ok(buf.len()));
+		assert_eq!(out, [u64::MAX, 0]);
+
+		let mut r = BitReader::new(&buf);
+		assert_eq!(read_delta(&mut r), Some(Err(DecodeError::Overflow)));
+		let zeros = [0; 9];
+		assert_eq!(read_gamma(&mut BitReader::new(&zeros)), Some(Err(DecodeError::Overflow)));
+		assert_eq!(EliasGamma.decode_u64s(&[0, 0xff], 1, &mut out), Err(DecodeError::Truncated { needed: 3 }));
+	}
+
+	#[test]
+	#[should_panic(expected = "start at one")]
+	fn rejects_zero() {
+		write_gamma(&mut BitWriter::default(), 0);
+	}
+}
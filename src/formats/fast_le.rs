@@ -0,0 +1,82 @@
+//! FastLE: a length byte followed by the value little-endian.
+//!
+//! The length byte counts the value bytes, 0 to 8, with high zero bytes
+//! left out, so zero is the single byte `0x00`. Unlike the crate's own
+//! encoding the bytes do not sort in value order; in return encoding is a
+//! single store and decoding a single load on little-endian machines. It
+//! suits values, not keys. Signed values are ZigZag mapped first.
+
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::DecodeError;
+
+/// FastLE as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FastLE;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 9;
+
+/// Encoded length of `v`, including the length byte.
+pub fn encoded_len_u64(v: u64) -> usize {
+	1 + (64 - v.leading_zeros() as usize).div_ceil(8)
+}
+
+pub fn encoded_len_i64(v: i64) -> usize {
+	encoded_len_u64(zigzag_encode(v))
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	let len = encoded_len_u64(v) - 1;
+	out.push(len as u8);
+	out.extend_from_slice(&v.to_le_bytes()[..len]);
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, zigzag_encode(v));
+}
+
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = first as usize;
+	if len > 8 {
+		return Err(DecodeError::InvalidMarker(first));
+	}
+	let bytes = buf.get(1..1 + len).ok_or(DecodeError::Truncated { needed: 1 + len })?;
+	let mut word = [0u8; 8];
+	word[..len].copy_from_slice(bytes);
+	Ok((u64::from_le_bytes(word), 1 + len))
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (zigzag_decode(v), len))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, y: i64) -> bool {
+		let x = x.wrapping_shr(shift as u32 % 64);
+		let b = enc(x);
+		let mut s = Vec::new();
+		encode_i64(&mut s, y);
+		b.len() == encoded_len_u64(x) && decode_u64(&b) == Ok((x, b.len())) && decode_i64(&s) == Ok((y, s.len()))
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(enc(0), vec![0x00]);
+		assert_eq!(enc(255), vec![0x01, 0xff]);
+		assert_eq!(enc(300), vec![0x02, 0x2c, 0x01]);
+		assert_eq!(enc(u64::MAX), [&[0x08][..], &[0xff; 8]].concat());
+		assert_eq!(decode_u64(&[0x02, 0x2c]), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(decode_u64(&[0x09]), Err(DecodeError::InvalidMarker(0x09)));
+	}
+}
@@ -0,0 +1,191 @@
+//! varint-G8IU: group varint for `u32`s with 8 data bytes per group
+//! (Stepanov et al., "SIMD-Based Decoding of Posting Lists").
+//!
+//! Each group is a descriptor byte followed by exactly 8 data bytes. Values
+//! are stored little-endian in 1 to 4 bytes and never straddle groups; bit
+//! `i` of the descriptor is clear when data byte `i` is the last byte of a
+//! value. Bytes left over at the end of a group keep their descriptor bits
+//! set and are ignored.
+//!
+//! With the `simd` feature on x86-64, decoding uses SSSE3 shuffles driven
+//! by a table indexed by the descriptor, when the CPU supports them.
+
+use crate::DecodeError;
+
+const GROUP_LEN: usize = 9;
+
+fn byte_len(v: u32) -> usize {
+	(32 - (v | 1).leading_zeros() as usize).div_ceil(8)
+}
+
+/// Appends `values` as whole groups.
+pub fn encode(out: &mut Vec<u8>, values: &[u32]) {
+	let mut i = 0;
+	while i < values.len() {
+		let start = out.len();
+		out.extend_from_slice(&[0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+		let mut used = 0;
+		while let Some(&v) = values.get(i) {
+			let n = byte_len(v);
+			if used + n > 8 {
+				break;
+			}
+			out[start + 1 + used..start + 1 + used + n].copy_from_slice(&v.to_le_bytes()[..n]);
+			used += n;
+			out[start] &= !(1 << (used - 1));
+			i += 1;
+		}
+	}
+}
+
+/// Decodes every group in `buf`, appending the values to `out`.
+pub fn decode(buf: &[u8], out: &mut Vec<u32>) -> Result<(), DecodeError> {
+	#[allow(unused_mut)]
+	let mut pos = 0;
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	#[allow(unsafe_code)]
+	{
+		if is_x86_feature_detected!("ssse3") {
+			// SAFETY: SSSE3 support was just checked.
+			pos = unsafe { simd::decode_groups(buf, out)? };
+		}
+	}
+	decode_scalar(buf, pos, out)
+}
+
+fn decode_scalar(buf: &[u8], mut pos: usize, out: &mut Vec<u32>) -> Result<(), DecodeError> {
+	while pos < buf.len() {
+		let group = buf
+			.get(pos..pos + GROUP_LEN)
+			.ok_or(DecodeError::Truncated { needed: pos + GROUP_LEN })?;
+		let (mut v, mut len) = (0u32, 0);
+		for (i, &b) in group[1..].iter().enumerate() {
+			if len < 4 {
+				v |= (b as u32) << (8 * len);
+			}
+			len += 1;
+			if group[0] & (1 << i) == 0 {
+				if len > 4 {
+					return Err(DecodeError::Malformed("g8iu value longer than 4 bytes"));
+				}
+				out.push(v);
+				v = 0;
+				len = 0;
+			}
+		}
+		pos += GROUP_LEN;
+	}
+	Ok(())
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[allow(unsafe_code)]
+mod simd {
+	use std::arch::x86_64::*;
+
+	use super::GROUP_LEN;
+	use crate::DecodeError;
+
+	#[derive(Clone, Copy)]
+	struct Entry {
+		// pshufb masks for the first and second four output values
+		shuffle: [[u8; 16]; 2],
+		count: u8,
+		valid: bool,
+	}
+
+	const fn build_table() -> [Entry; 256] {
+		let mut table = [Entry { shuffle: [[0x80; 16]; 2], count: 0, valid: true }; 256];
+		let mut desc = 0;
+		while desc < 256 {
+			let entry = &mut table[desc];
+			let (mut start, mut i) = (0, 0);
+			while i < 8 {
+				if desc & (1 << i) == 0 {
+					let len = i + 1 - start;
+					if len > 4 {
+						entry.valid = false;
+					} else {
+						let k = entry.count as usize;
+						let mut j = 0;
+						while j < len {
+							entry.shuffle[k / 4][(k % 4) * 4 + j] = (start + j) as u8;
+							j += 1;
+						}
+					}
+					entry.count += 1;
+					start = i + 1;
+				}
+				i += 1;
+			}
+			desc += 1;
+		}
+		table
+	}
+
+	static TABLE: [Entry; 256] = build_table();
+
+	/// Decodes whole groups while 16 bytes can be loaded past each
+	/// descriptor, returning how many bytes were consumed.
+	#[target_feature(enable = "ssse3")]
+	pub(super) unsafe fn decode_groups(buf: &[u8], out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+		let mut pos = 0;
+		let mut values = [0u32; 8];
+		while pos + 1 + 16 <= buf.len() {
+			let entry = &TABLE[buf[pos] as usize];
+			if !entry.valid {
+				return Err(DecodeError::Malformed("g8iu value longer than 4 bytes"));
+			}
+			let data = _mm_loadu_si128(buf.as_ptr().add(pos + 1) as *const __m128i);
+			let lo = _mm_shuffle_epi8(data, _mm_loadu_si128(entry.shuffle[0].as_ptr() as *const __m128i));
+			let hi = _mm_shuffle_epi8(data, _mm_loadu_si128(entry.shuffle[1].as_ptr() as *const __m128i));
+			_mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, lo);
+			_mm_storeu_si128(values.as_mut_ptr().add(4) as *mut __m128i, hi);
+			out.extend_from_slice(&values[..entry.count as usize]);
+			pos += GROUP_LEN;
+		}
+		Ok(pos)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(values: &[u32]) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode(&mut out, values);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(values: Vec<(u32, u8)>) -> bool {
+		let values: Vec<u32> = values.into_iter().map(|(v, s)| v >> (s % 32)).collect();
+		let buf = enc(&values);
+		let mut scalar = Vec::new();
+		let mut out = Vec::new();
+		buf.len().is_multiple_of(GROUP_LEN) &&
+			decode_scalar(&buf, 0, &mut scalar).is_ok() &&
+			decode(&buf, &mut out).is_ok() && out == values && scalar == values
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(enc(&[1, 0x1234, 0x0fffff, 7]),
+			vec![0b1001_1010, 0x01, 0x34, 0x12, 0xff, 0xff, 0x0f, 0x07, 0x00]);
+		assert_eq!(enc(&[u32::MAX, u32::MAX, 5]),
+			vec![0b0111_0111, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+				0b1111_1110, 0x05, 0, 0, 0, 0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn rejects_bad_groups() {
+		let mut out = Vec::new();
+		assert_eq!(decode(&[0xff; 10], &mut out), Err(DecodeError::Truncated { needed: 18 }));
+		let mut long = vec![0b0000_1111];
+		long.extend_from_slice(&[0; 8 + 9 * 2]);
+		let err = Err(DecodeError::Malformed("g8iu value longer than 4 bytes"));
+		assert_eq!(decode(&long, &mut out), err);
+		assert_eq!(decode(&long[..9], &mut out), err);
+	}
+}
@@ -0,0 +1,159 @@
+//! Git's offset varint, which packfiles use for the base offset of an
+//! `OFS_DELTA` object and the v4 index for its path prefix lengths.
+//!
+//! Values are big-endian, 7 bits a byte with the high bit set on every
+//! byte but the last, like a big-endian LEB128 except that each
+//! continuation also adds one to the groups before it. That removes the
+//! redundant forms: every value has exactly one encoding, and two bytes
+//! reach 16511 rather than 16383. Signed values are ZigZag mapped first,
+//! which git itself never needs.
+//!
+//! ```
+//! use rust_varint::formats::git;
+//!
+//! let mut out = Vec::new();
+//! git::encode_u64(&mut out, 128);
+//! assert_eq!(out, [0x80, 0x00]);
+//! assert_eq!(git::decode_u64(&out), Ok((128, 2)));
+//! ```
+
+use std::io::{self, Read, Write};
+
+use super::VarintFormat;
+use crate::{at_offset, DecodeError};
+
+/// Git's offset varint as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct GitOffset;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 10;
+
+/// Encoded length of `v`.
+pub fn encoded_len_u64(mut v: u64) -> usize {
+	let mut len = 1;
+	v >>= 7;
+	while v != 0 {
+		v = (v - 1) >> 7;
+		len += 1;
+	}
+	len
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, mut v: u64) {
+	let mut buf = [0; MAX_LEN];
+	let mut pos = MAX_LEN - 1;
+	buf[pos] = v as u8 & 0x7f;
+	v >>= 7;
+	while v != 0 {
+		v -= 1;
+		pos -= 1;
+		buf[pos] = v as u8 | 0x80;
+		v >>= 7;
+	}
+	out.extend_from_slice(&buf[pos..]);
+}
+
+/// Decodes one value. A value past `u64::MAX` is an overflow, which is
+/// also how a run of continuation bytes longer than any value ends.
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let mut v = 0u64;
+	for (i, &b) in buf.iter().enumerate() {
+		if i > 0 {
+			v = v.checked_add(1).and_then(|v| v.checked_mul(128)).ok_or(DecodeError::Overflow)?;
+		}
+		v |= (b & 0x7f) as u64;
+		if b & 0x80 == 0 {
+			return Ok((v, i + 1));
+		}
+	}
+	Err(DecodeError::Truncated { needed: buf.len() + 1 })
+}
+
+/// Decodes the value at `*pos` and moves `pos` past it. `Truncated`
+/// counts from the start of `buf`, and `pos` is left alone on error.
+pub fn decode_u64_at(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+	let (v, used) = decode_u64(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(v)
+}
+
+/// Reads one value a byte at a time, never past its end.
+pub fn read_u64<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+	let mut buf = [0; MAX_LEN];
+	for n in 0..MAX_LEN {
+		r.read_exact(&mut buf[n..n + 1])?;
+		if buf[n] & 0x80 == 0 {
+			break;
+		}
+	}
+	decode_u64(&buf).map(|(v, _)| v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_u64<W: Write + ?Sized>(w: &mut W, v: u64) -> io::Result<()> {
+	let mut out = Vec::with_capacity(MAX_LEN);
+	encode_u64(&mut out, v);
+	w.write_all(&out)
+}
+
+impl VarintFormat for GitOffset {
+	fn max_len(&self) -> usize {
+		MAX_LEN
+	}
+
+	fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+		encode_u64(out, v);
+	}
+
+	fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+		decode_u64(buf)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, tail: Vec<u8>) -> bool {
+		let x = x >> (shift % 64);
+		let mut b = enc(x);
+		let len = b.len();
+		b.extend_from_slice(&tail);
+		let mut rd = &b[..];
+		len == encoded_len_u64(x) && decode_u64(&b) == Ok((x, len)) && GitOffset.is_canonical(&b) &&
+			read_u64(&mut rd).unwrap() == x && rd.len() == tail.len()
+	}
+
+	#[test]
+	fn matches_git() {
+		// the first value of each length is 0x80 ... 0x80 0x00
+		let mut first = 0u64;
+		for n in 1..MAX_LEN {
+			assert_eq!(enc(first), [vec![0x80; n - 1], vec![0x00]].concat());
+			assert_eq!(enc(first.wrapping_sub(1)).len(), if n == 1 { MAX_LEN } else { n - 1 });
+			first = (first + 1) * 128;
+		}
+		assert_eq!(first, 9_295_997_013_522_923_648);
+		assert_eq!(enc(127), [0x7f]);
+		assert_eq!(enc(16511), [0xff, 0x7f]);
+		assert_eq!(enc(u64::MAX), [0x80, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0x7f]);
+	}
+
+	#[test]
+	fn rejects_bad_input() {
+		assert_eq!(decode_u64(&[0x80, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xfe, 0xff, 0x00]), Err(DecodeError::Overflow));
+		assert_eq!(decode_u64(&[0x80; 12]), Err(DecodeError::Overflow));
+		assert_eq!(decode_u64(&[0x80; 3]), Err(DecodeError::Truncated { needed: 4 }));
+		let mut pos = 1;
+		assert_eq!(decode_u64_at(&[0x00, 0x81], &mut pos), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(pos, 1);
+		assert_eq!(read_u64(&mut &[0x80; 12][..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,173 @@
+//! Golomb coding, for gaps that are roughly geometrically distributed, such
+//! as sorted hashes in a Bloom filter replacement or posting lists.
+//!
+//! With parameter `m`, a value is written as its quotient by `m` in unary
+//! (that many one bits and a zero) followed by the remainder in truncated
+//! binary, most significant bit first. When `m` is a power of two `2^k`,
+//! which is Rice coding, the remainder is just the low `k` bits; this is
+//! the layout of the Golomb-coded sets of BIP 158.
+//!
+//! ```
+//! use rust_varint::formats::golomb::{optimal_rice_parameter, Golomb};
+//! use rust_varint::formats::BlockCodec;
+//!
+//! let gaps = [700, 1500, 180, 2900, 960, 40, 1300, 610];
+//! let k = optimal_rice_parameter(&gaps);
+//! assert_eq!(k, 9);
+//!
+//! let mut packed = Vec::new();
+//! Golomb::rice(k).encode_u64s(&mut packed, &gaps);
+//! assert_eq!(packed.len(), 12);
+//! let mut out = Vec::new();
+//! Golomb::rice(k).decode_u64s(&packed, gaps.len(), &mut out).unwrap();
+//! assert_eq!(out, gaps);
+//! ```
+
+use super::BlockCodec;
+use crate::bits::{BitReader, BitWriter};
+use crate::DecodeError;
+
+/// Longest quotient a value may have, which bounds its unary part.
+pub const MAX_QUOTIENT: u64 = 1 << 16;
+
+/// Golomb coding with a fixed parameter, as a `BlockCodec`. The values
+/// are padded with zeros to a whole byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Golomb {
+	m: u64,
+	// bits of the long remainders, and how many remainders take one fewer
+	bits: u32,
+	short: u64,
+}
+
+impl Golomb {
+	/// Panics if `m` is zero.
+	pub fn new(m: u64) -> Golomb {
+		assert!(m > 0, "Golomb parameter must be positive");
+		let bits = 64 - (m - 1).leading_zeros();
+		let short = if bits == 64 { 0u64.wrapping_sub(m) } else { (1 << bits) - m };
+		Golomb { m, bits, short }
+	}
+
+	/// Rice coding, with `m = 2^k`. Panics if `k > 63`.
+	pub fn rice(k: u32) -> Golomb {
+		assert!(k < 64, "Rice parameter must be below 64");
+		Golomb::new(1 << k)
+	}
+
+	pub fn m(&self) -> u64 {
+		self.m
+	}
+
+	/// Writes one value. Panics if its quotient is over `MAX_QUOTIENT`;
+	/// pick a larger parameter for such values.
+	pub fn write(&self, w: &mut BitWriter, v: u64) {
+		let (q, r) = (v / self.m, v % self.m);
+		assert!(q <= MAX_QUOTIENT, "Golomb quotient of {} by {} is too long", v, self.m);
+		w.write_unary(q);
+		if r < self.short {
+			w.write_bits(r, self.bits - 1);
+		} else {
+			w.write_bits(r + self.short, self.bits);
+		}
+	}
+
+	/// Reads one value, or `None` if the bits run out first.
+	pub fn read(&self, r: &mut BitReader) -> Option<Result<u64, DecodeError>> {
+		let q = r.read_unary()?;
+		let mut rem = 0;
+		if self.bits > 0 {
+			rem = r.read_bits(self.bits - 1)?;
+			if rem >= self.short {
+				rem = (rem << 1 | r.read_bits(1)?) - self.short;
+			}
+		}
+		Some(q.checked_mul(self.m).and_then(|v| v.checked_add(rem)).ok_or(DecodeError::Overflow))
+	}
+}
+
+impl BlockCodec for Golomb {
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]) {
+		let mut w = BitWriter::default();
+		for &v in values {
+			self.write(&mut w, v);
+		}
+		out.extend_from_slice(&w.finish());
+	}
+
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+		let mut r = BitReader::new(buf);
+		for _ in 0..count {
+			let v = self.read(&mut r).ok_or(DecodeError::Truncated { needed: buf.len() + 1 })??;
+			out.push(v);
+		}
+		Ok(r.bit_pos().div_ceil(8) as usize)
+	}
+}
+
+/// The Rice parameter `k` that codes `sample` in the fewest bits, keeping
+/// every quotient within `MAX_QUOTIENT`.
+pub fn optimal_rice_parameter(sample: &[u64]) -> u32 {
+	let max = sample.iter().cloned().max().unwrap_or(0);
+	(0..64).filter(|&k| max >> k <= MAX_QUOTIENT).min_by_key(|&k| {
+		sample.iter().map(|&v| (v >> k) as u128 + 1 + k as u128).sum::<u128>()
+	}).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(g: Golomb, values: &[u64]) -> Vec<u8> {
+		let mut out = Vec::new();
+		g.encode_u64s(&mut out, values);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(values: Vec<u16>, m: u16, tail: Vec<u8>) -> bool {
+		let g = Golomb::new(m as u64 + 1);
+		let values: Vec<u64> = values.into_iter().map(u64::from).collect();
+		let mut buf = enc(g, &values);
+		let len = buf.len();
+		buf.extend_from_slice(&tail);
+		let mut out = Vec::new();
+		g.decode_u64s(&buf, values.len(), &mut out) == Ok(len) && out == values
+	}
+
+	#[quickcheck]
+	fn optimal_parameter_is_optimal(sample: Vec<u16>) -> bool {
+		let sample: Vec<u64> = sample.into_iter().map(u64::from).collect();
+		let k = optimal_rice_parameter(&sample);
+		let bits = |k: u32| sample.iter().map(|&v| (v >> k) + 1 + k as u64).sum::<u64>();
+		(0..20).all(|j| bits(k) <= bits(j))
+	}
+
+	#[test]
+	fn layout() {
+		// quotient 2, then the remainder 5 in 3 bits
+		assert_eq!(enc(Golomb::rice(3), &[21]), vec![0b1101_0100]);
+		// m = 5 writes remainders 0..3 in 2 bits and 3..5 in 3
+		assert_eq!(enc(Golomb::new(5), &[2, 3, 9]), vec![0b0100_1101, 0b0111_0000]);
+		assert_eq!(enc(Golomb::new(1), &[3]), vec![0b1110_0000]);
+		let big = Golomb::new(u64::MAX);
+		let mut out = Vec::new();
+		assert_eq!(big.decode_u64s(&enc(big, &[u64::MAX - 1, 7]), 2, &mut out), Ok(17));
+		assert_eq!(out, [u64::MAX - 1, 7]);
+	}
+
+	#[test]
+	fn rejects_short_input() {
+		let buf = enc(Golomb::rice(4), &[1000, 3]);
+		let mut out = Vec::new();
+		assert_eq!(Golomb::rice(4).decode_u64s(&buf[..8], 2, &mut out), Err(DecodeError::Truncated { needed: 9 }));
+		assert_eq!(Golomb::rice(4).decode_u64s(&[0xff; 4], 1, &mut out), Err(DecodeError::Truncated { needed: 5 }));
+		assert_eq!(Golomb::rice(63).decode_u64s(&[0xc0, 0, 0, 0, 0, 0, 0, 0, 0], 1, &mut out), Err(DecodeError::Overflow));
+	}
+
+	#[test]
+	#[should_panic(expected = "too long")]
+	fn rejects_long_quotients() {
+		enc(Golomb::rice(0), &[MAX_QUOTIENT + 1]);
+	}
+}
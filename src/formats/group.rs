@@ -0,0 +1,128 @@
+//! Group varint for `u32`s, as described by Jeff Dean ("Challenges in
+//! Building Large-Scale Information Retrieval Systems", WSDM 2009).
+//!
+//! Values go in groups of four: a control byte, then each value
+//! little-endian in 1 to 4 bytes. Bits `2i` and `2i + 1` of the control
+//! byte hold the byte length of value `i`, minus one. A final group with
+//! fewer values leaves the control bits of the missing ones clear and
+//! writes no bytes for them, so decoding needs the number of values.
+//!
+//! Decoding looks each control byte up in a table of lengths and reads
+//! whole words, without branching on the individual values.
+
+use crate::DecodeError;
+
+const MASKS: [u32; 4] = [0xff, 0xffff, 0xff_ffff, 0xffff_ffff];
+
+// Byte lengths of the four values behind each control byte.
+const fn build_lengths() -> [[u8; 4]; 256] {
+	let mut table = [[0; 4]; 256];
+	let mut control = 0;
+	while control < 256 {
+		let mut i = 0;
+		while i < 4 {
+			table[control][i] = ((control >> (2 * i)) & 3) as u8 + 1;
+			i += 1;
+		}
+		control += 1;
+	}
+	table
+}
+
+static LENGTHS: [[u8; 4]; 256] = build_lengths();
+
+fn byte_len(v: u32) -> usize {
+	(32 - (v | 1).leading_zeros() as usize).div_ceil(8)
+}
+
+/// Appends `values` as groups of four, the last possibly shorter.
+pub fn encode(out: &mut Vec<u8>, values: &[u32]) {
+	for group in values.chunks(4) {
+		let control = out.len();
+		out.push(0);
+		for (i, &v) in group.iter().enumerate() {
+			let n = byte_len(v);
+			out[control] |= ((n - 1) << (2 * i)) as u8;
+			out.extend_from_slice(&v.to_le_bytes()[..n]);
+		}
+	}
+}
+
+/// Decodes `count` values from the front of `buf`, appending them to
+/// `out`, and returns the number of bytes they occupied.
+pub fn decode(buf: &[u8], count: usize, out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+	// every value takes at least a byte, so a bogus count cannot
+	// reserve more than the buffer could hold
+	out.reserve(count.min(buf.len()));
+	let mut pos = 0;
+	let mut left = count;
+	// whole groups with room to load four bytes for every value
+	while left >= 4 && pos + 1 + 16 <= buf.len() {
+		let lens = &LENGTHS[buf[pos] as usize];
+		let mut at = pos + 1;
+		for &n in lens {
+			let word = u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]]);
+			out.push(word & MASKS[n as usize - 1]);
+			at += n as usize;
+		}
+		pos = at;
+		left -= 4;
+	}
+	while left > 0 {
+		let control = *buf.get(pos).ok_or(DecodeError::Truncated { needed: pos + 1 })?;
+		let lens = &LENGTHS[control as usize][..left.min(4)];
+		let end = pos + 1 + lens.iter().map(|&n| n as usize).sum::<usize>();
+		if buf.len() < end {
+			return Err(DecodeError::Truncated { needed: end });
+		}
+		let mut at = pos + 1;
+		for &n in lens {
+			let mut word = [0; 4];
+			word[..n as usize].copy_from_slice(&buf[at..at + n as usize]);
+			out.push(u32::from_le_bytes(word));
+			at += n as usize;
+		}
+		pos = end;
+		left -= lens.len();
+	}
+	Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(values: &[u32]) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode(&mut out, values);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(values: Vec<(u32, u8)>, tail: Vec<u8>) -> bool {
+		let values: Vec<u32> = values.into_iter().map(|(v, s)| v >> (s % 32)).collect();
+		let mut buf = enc(&values);
+		let len = buf.len();
+		buf.extend_from_slice(&tail);
+		let mut out = Vec::new();
+		decode(&buf, values.len(), &mut out) == Ok(len) && out == values
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(enc(&[1, 0x1234, 0x0f_ffff, u32::MAX, 7]),
+			vec![0b11_10_01_00, 0x01, 0x34, 0x12, 0xff, 0xff, 0x0f, 0xff, 0xff, 0xff, 0xff, 0b00, 0x07]);
+		assert!(enc(&[]).is_empty());
+	}
+
+	#[test]
+	fn rejects_truncated_groups() {
+		let buf = enc(&[1, 2, 3, 0x1_0000, 5]);
+		let mut out = Vec::new();
+		assert_eq!(decode(&buf[..buf.len() - 1], 5, &mut out), Err(DecodeError::Truncated { needed: 9 }));
+		assert_eq!(decode(&buf[..6], 4, &mut out), Err(DecodeError::Truncated { needed: 7 }));
+		assert_eq!(decode(&buf, 6, &mut out), Err(DecodeError::Truncated { needed: 10 }));
+		assert_eq!(decode(&[], usize::MAX, &mut out), Err(DecodeError::Truncated { needed: 1 }));
+		assert_eq!(decode(&buf, usize::MAX, &mut out), Err(DecodeError::Truncated { needed: 12 }));
+	}
+}
@@ -0,0 +1,394 @@
+//! LEB128, the varint of protobuf, DWARF and WebAssembly.
+//!
+//! Each byte holds 7 bits of the value, least significant group first,
+//! with the high bit set on every byte but the last. Signed values are
+//! ZigZag mapped first, as protobuf does for `sint64`; `encode_int64` is
+//! protobuf's plain `int64` and `int32`, which write the two's complement
+//! bits instead.
+//!
+//! Like the crate's own encoding, values can be decoded from a slice
+//! (`decode_u64`), walked with a cursor (`decode_u64_at`), or read from
+//! and written to `std::io` streams (`read_u64`, `write_u64`).
+//!
+//! `decode_u32s` decodes a packed run of values, such as a protobuf packed
+//! repeated field. With the `simd` feature on x86-64 it uses the
+//! masked-VByte technique (Plaisance, Kurz and Lemire): the continuation
+//! bits of 16 input bytes are gathered into a mask, and a table indexed by
+//! the low 12 bits of that mask supplies a shuffle that lines up a batch of
+//! values for decoding at once.
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::{at_offset, DecodeError};
+
+/// LEB128 as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Leb128;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 10;
+
+/// Encoded length of `v`.
+pub fn encoded_len_u64(v: u64) -> usize {
+	(64 - (v | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+pub fn encoded_len_i64(v: i64) -> usize {
+	encoded_len_u64(zigzag_encode(v))
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, mut v: u64) {
+	while v >= 0x80 {
+		out.push(v as u8 | 0x80);
+		v >>= 7;
+	}
+	out.push(v as u8);
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, zigzag_encode(v));
+}
+
+/// Decodes one value. More than ten bytes, or bits beyond the 64th, are
+/// an overflow; redundant trailing `0x80` groups are accepted, as in
+/// protobuf.
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let mut v = 0u64;
+	for (i, &b) in buf.iter().enumerate().take(MAX_LEN) {
+		if i == MAX_LEN - 1 && b > 1 {
+			return Err(DecodeError::Overflow);
+		}
+		v |= ((b & 0x7f) as u64) << (7 * i);
+		if b & 0x80 == 0 {
+			return Ok((v, i + 1));
+		}
+	}
+	if buf.len() >= MAX_LEN {
+		return Err(DecodeError::Overflow);
+	}
+	Err(DecodeError::Truncated { needed: buf.len() + 1 })
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (zigzag_decode(v), len))
+}
+
+/// Protobuf's `int64`: the two's complement bits as unsigned, so every
+/// negative value takes ten bytes. An `int32` is written as its `i64`.
+pub fn encode_int64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, v as u64);
+}
+
+pub fn decode_int64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (v as i64, len))
+}
+
+/// Decodes the value at `*pos` and moves `pos` past it. `Truncated`
+/// counts from the start of `buf`, and `pos` is left alone on error.
+pub fn decode_u64_at(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+	let (v, used) = decode_u64(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(v)
+}
+
+/// Signed counterpart of `decode_u64_at`.
+pub fn decode_i64_at(buf: &[u8], pos: &mut usize) -> Result<i64, DecodeError> {
+	decode_u64_at(buf, pos).map(zigzag_decode)
+}
+
+/// Reads one value a byte at a time, never past its end.
+pub fn read_u64<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+	let mut buf = [0; MAX_LEN];
+	for n in 0..MAX_LEN {
+		r.read_exact(&mut buf[n..n + 1])?;
+		if buf[n] & 0x80 == 0 {
+			break;
+		}
+	}
+	decode_u64(&buf).map(|(v, _)| v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn read_i64<R: Read + ?Sized>(r: &mut R) -> io::Result<i64> {
+	read_u64(r).map(zigzag_decode)
+}
+
+pub fn write_u64<W: Write + ?Sized>(w: &mut W, v: u64) -> io::Result<()> {
+	let mut out = Vec::with_capacity(MAX_LEN);
+	encode_u64(&mut out, v);
+	w.write_all(&out)
+}
+
+pub fn write_i64<W: Write + ?Sized>(w: &mut W, v: i64) -> io::Result<()> {
+	write_u64(w, zigzag_encode(v))
+}
+
+fn decode_u32(buf: &[u8]) -> Result<(u32, usize), DecodeError> {
+	let (v, len) = decode_u64(buf)?;
+	u32::try_from(v).map(|v| (v, len)).map_err(|_| DecodeError::Overflow)
+}
+
+/// Decodes every value in `buf` as a `u32`, appending them to `out`.
+pub fn decode_u32s(buf: &[u8], out: &mut Vec<u32>) -> Result<(), DecodeError> {
+	#[allow(unused_mut)]
+	let mut pos = 0;
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	#[allow(unsafe_code)]
+	{
+		if is_x86_feature_detected!("ssse3") {
+			// SAFETY: SSSE3 support was just checked.
+			pos = unsafe { masked::decode_batches(buf, out)? };
+		}
+	}
+	while pos < buf.len() {
+		let (v, used) = decode_u32(&buf[pos..]).map_err(|e| match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+			e => e,
+		})?;
+		out.push(v);
+		pos += used;
+	}
+	Ok(())
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[allow(unsafe_code)]
+mod masked {
+	use std::arch::x86_64::*;
+
+	use std::convert::TryFrom;
+
+	use super::decode_u32;
+	use crate::DecodeError;
+
+	#[derive(Clone, Copy)]
+	struct Entry {
+		shuffle: [u8; 16],
+		// values in the batch; zero when the first value is too long
+		count: u8,
+		consumed: u8,
+		// bytes per lane: 2, 4, or 8 for values of up to 2, 4 or 5 bytes
+		lane: u8,
+	}
+
+	// Lengths of the values that end within the first 12 bytes.
+	const fn lengths(mask: usize) -> ([usize; 12], usize) {
+		let mut lens = [0; 12];
+		let (mut n, mut start, mut i) = (0, 0, 0);
+		while i < 12 {
+			if mask & (1 << i) == 0 {
+				lens[n] = i + 1 - start;
+				n += 1;
+				start = i + 1;
+			}
+			i += 1;
+		}
+		(lens, n)
+	}
+
+	const fn entry(mask: usize) -> Entry {
+		let (lens, n) = lengths(mask);
+		// take the lane width that decodes the most leading values
+		let (mut count, mut lane) = (0, 2);
+		let mut width = 2;
+		while width <= 8 {
+			let max_len = if width == 8 { 5 } else { width };
+			let mut fit = 0;
+			while fit < n && fit < 16 / width && lens[fit] <= max_len {
+				fit += 1;
+			}
+			if fit > count {
+				count = fit;
+				lane = width;
+			}
+			width *= 2;
+		}
+		let mut e = Entry { shuffle: [0x80; 16], count: count as u8, consumed: 0, lane: lane as u8 };
+		let (mut k, mut start) = (0, 0);
+		while k < count {
+			let mut j = 0;
+			while j < lens[k] {
+				e.shuffle[k * lane + j] = (start + j) as u8;
+				j += 1;
+			}
+			start += lens[k];
+			k += 1;
+		}
+		e.consumed = start as u8;
+		e
+	}
+
+	const fn build_table() -> [Entry; 4096] {
+		let mut table = [Entry { shuffle: [0; 16], count: 0, consumed: 0, lane: 0 }; 4096];
+		let mut mask = 0;
+		while mask < 4096 {
+			table[mask] = entry(mask);
+			mask += 1;
+		}
+		table
+	}
+
+	static TABLE: [Entry; 4096] = build_table();
+
+	/// Decodes while 16 bytes remain, returning the bytes consumed. Values
+	/// longer than 4 bytes are decoded one at a time.
+	#[target_feature(enable = "ssse3")]
+	pub(super) unsafe fn decode_batches(buf: &[u8], out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+		let mut pos = 0;
+		let mut values = [0u32; 16];
+		let zero = _mm_setzero_si128();
+		while pos + 16 <= buf.len() {
+			let data = _mm_loadu_si128(buf.as_ptr().add(pos) as *const __m128i);
+			let mask = _mm_movemask_epi8(data) as usize;
+			if mask == 0 {
+				let lo = _mm_unpacklo_epi8(data, zero);
+				let hi = _mm_unpackhi_epi8(data, zero);
+				let lanes = [
+					_mm_unpacklo_epi16(lo, zero),
+					_mm_unpackhi_epi16(lo, zero),
+					_mm_unpacklo_epi16(hi, zero),
+					_mm_unpackhi_epi16(hi, zero),
+				];
+				for (i, lane) in lanes.iter().enumerate() {
+					_mm_storeu_si128(values.as_mut_ptr().add(4 * i) as *mut __m128i, *lane);
+				}
+				out.extend_from_slice(&values);
+				pos += 16;
+				continue;
+			}
+
+			let entry = &TABLE[mask & 0xfff];
+			if entry.count == 0 {
+				let (v, used) = decode_u32(&buf[pos..])?;
+				out.push(v);
+				pos += used;
+				continue;
+			}
+			let shuffle = _mm_loadu_si128(entry.shuffle.as_ptr() as *const __m128i);
+			let x = _mm_shuffle_epi8(data, shuffle);
+			match entry.lane {
+				2 => {
+					let lo = _mm_and_si128(x, _mm_set1_epi16(0x007f));
+					let hi = _mm_srli_epi16(_mm_and_si128(x, _mm_set1_epi16(0x7f00)), 1);
+					let v = _mm_or_si128(lo, hi);
+					_mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, _mm_unpacklo_epi16(v, zero));
+					_mm_storeu_si128(values.as_mut_ptr().add(4) as *mut __m128i, _mm_unpackhi_epi16(v, zero));
+				}
+				4 => {
+					let b0 = _mm_and_si128(x, _mm_set1_epi32(0x0000_007f));
+					let b1 = _mm_srli_epi32(_mm_and_si128(x, _mm_set1_epi32(0x0000_7f00)), 1);
+					let b2 = _mm_srli_epi32(_mm_and_si128(x, _mm_set1_epi32(0x007f_0000)), 2);
+					let b3 = _mm_srli_epi32(_mm_and_si128(x, _mm_set1_epi32(0x7f00_0000)), 3);
+					let v = _mm_or_si128(_mm_or_si128(b0, b1), _mm_or_si128(b2, b3));
+					_mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, v);
+				}
+				_ => {
+					let group = |k: i64| _mm_and_si128(x, _mm_set1_epi64x(0x7f << (8 * k)));
+					let v = _mm_or_si128(
+						_mm_or_si128(group(0), _mm_srli_epi64(group(1), 1)),
+						_mm_or_si128(_mm_srli_epi64(group(2), 2), _mm_srli_epi64(group(3), 3)),
+					);
+					let v = _mm_or_si128(v, _mm_srli_epi64(group(4), 4));
+					let mut wide = [0u64; 2];
+					_mm_storeu_si128(wide.as_mut_ptr() as *mut __m128i, v);
+					for (slot, &w) in values.iter_mut().zip(&wide[..entry.count as usize]) {
+						*slot = u32::try_from(w).map_err(|_| DecodeError::Overflow)?;
+					}
+				}
+			}
+			out.extend_from_slice(&values[..entry.count as usize]);
+			pos += entry.consumed as usize;
+		}
+		Ok(pos)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, y: i64) -> bool {
+		let x = x.wrapping_shl(shift as u32 % 64);
+		let b = enc(x);
+		let mut s = Vec::new();
+		encode_i64(&mut s, y);
+		b.len() == encoded_len_u64(x) && decode_u64(&b) == Ok((x, b.len())) &&
+			decode_i64(&s) == Ok((y, s.len())) && s.len() == encoded_len_i64(y)
+	}
+
+	#[quickcheck]
+	fn bulk_round_trip(values: Vec<(u32, u8)>) -> bool {
+		let values: Vec<u32> = values.into_iter().map(|(v, s)| v >> (s % 33).min(31)).collect();
+		let mut buf = Vec::new();
+		for &v in &values {
+			encode_u64(&mut buf, v as u64);
+		}
+		let mut out = Vec::new();
+		decode_u32s(&buf, &mut out).is_ok() && out == values
+	}
+
+	#[test]
+	fn matches_protobuf_vectors() {
+		assert_eq!(enc(1), vec![0x01]);
+		assert_eq!(enc(150), vec![0x96, 0x01]);
+		assert_eq!(enc(300), vec![0xac, 0x02]);
+		assert_eq!(enc(u64::MAX), vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+		let mut out = Vec::new();
+		encode_i64(&mut out, -1);
+		encode_i64(&mut out, 1);
+		assert_eq!(out, vec![0x01, 0x02]);
+		assert_eq!(decode_u64(&[0x80, 0x00]), Ok((0, 2)));
+
+		out.clear();
+		encode_int64(&mut out, -1);
+		assert_eq!(out, enc(u64::MAX));
+		assert_eq!(decode_int64(&out), Ok((-1, 10)));
+		out.clear();
+		encode_int64(&mut out, i32::MIN as i64);
+		assert_eq!(out, vec![0x80, 0x80, 0x80, 0x80, 0xf8, 0xff, 0xff, 0xff, 0xff, 0x01]);
+	}
+
+	#[quickcheck]
+	fn streams_and_cursors_agree(values: Vec<i64>) -> bool {
+		let mut buf = Vec::new();
+		for &v in &values {
+			write_i64(&mut buf, v).unwrap();
+		}
+		let mut rd = &buf[..];
+		let mut pos = 0;
+		values.iter().all(|&v| read_i64(&mut rd).unwrap() == v && decode_i64_at(&buf, &mut pos) == Ok(v)) &&
+			rd.is_empty() && pos == buf.len()
+	}
+
+	#[test]
+	fn rejects_bad_input() {
+		assert_eq!(decode_u64(&[0x96]), Err(DecodeError::Truncated { needed: 2 }));
+		assert_eq!(decode_u64(&[0xff; 9]), Err(DecodeError::Truncated { needed: 10 }));
+		assert_eq!(decode_u64(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]),
+			Err(DecodeError::Overflow));
+		assert_eq!(decode_u64(&[0x80; 11]), Err(DecodeError::Overflow));
+		let mut pos = 1;
+		assert_eq!(decode_u64_at(&[0x01, 0x96], &mut pos), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(pos, 1);
+		assert_eq!(read_u64(&mut &[0x80; 11][..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+		assert_eq!(read_u64(&mut &[0x96][..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+		let mut out = Vec::new();
+		let mut buf = vec![1; 20];
+		buf.extend_from_slice(&enc(1 << 32));
+		assert_eq!(decode_u32s(&buf, &mut out), Err(DecodeError::Overflow));
+		buf.rotate_right(5);
+		assert_eq!(decode_u32s(&buf, &mut out), Err(DecodeError::Overflow));
+		let mut buf = vec![1; 20];
+		buf.push(0x80);
+		assert_eq!(decode_u32s(&buf, &mut out), Err(DecodeError::Truncated { needed: 22 }));
+	}
+}
@@ -0,0 +1,232 @@
+//! MySQL-style memcomparable key encoding, as used by TiKV/TiDB.
+//!
+//! Every encoding here compares with `memcmp` in the same order as the
+//! original values, and the `_desc` variants in reverse order (every byte
+//! complemented).
+//!
+//! * `u64`: 8 bytes big-endian.
+//! * `i64`: 8 bytes big-endian with the sign bit flipped.
+//! * `f64`: IEEE bits, sign bit set for positives and all bits flipped for
+//!   negatives. As in MySQL's `change_double_for_sort`, `-0.0` is stored
+//!   as `0.0`.
+//! * bytes: groups of 8 bytes, each followed by a marker `0xff - padding`,
+//!   where the final group is zero-padded (TiKV's `encode_bytes`). Strings
+//!   are encoded as their UTF-8 bytes, which matches binary collations;
+//!   other collations need MySQL weight strings first.
+
+use crate::sortable::{i64_to_sortable_u64, sortable_u64_to_i64};
+use crate::DecodeError;
+
+const SIGN_MARK: u64 = 1 << 63;
+const GROUP_SIZE: usize = 8;
+const MARKER: u8 = 0xff;
+const PAD: u8 = 0;
+
+fn read_u64(buf: &[u8]) -> Result<u64, DecodeError> {
+	if buf.len() < 8 {
+		return Err(DecodeError::Truncated { needed: 8 });
+	}
+	let mut b = [0u8; 8];
+	b.copy_from_slice(&buf[..8]);
+	Ok(u64::from_be_bytes(b))
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	out.extend_from_slice(&v.to_be_bytes());
+}
+
+pub fn encode_u64_desc(out: &mut Vec<u8>, v: u64) {
+	encode_u64(out, !v);
+}
+
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	read_u64(buf).map(|v| (v, 8))
+}
+
+pub fn decode_u64_desc(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	read_u64(buf).map(|v| (!v, 8))
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, i64_to_sortable_u64(v));
+}
+
+pub fn encode_i64_desc(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, !i64_to_sortable_u64(v));
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	read_u64(buf).map(|v| (sortable_u64_to_i64(v), 8))
+}
+
+pub fn decode_i64_desc(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	read_u64(buf).map(|v| (sortable_u64_to_i64(!v), 8))
+}
+
+fn f64_to_cmp(v: f64) -> u64 {
+	let v = if v == 0.0 { 0.0 } else { v };
+	let u = v.to_bits();
+	if u & SIGN_MARK == 0 { u | SIGN_MARK } else { !u }
+}
+
+fn cmp_to_f64(u: u64) -> f64 {
+	let u = if u & SIGN_MARK != 0 { u & !SIGN_MARK } else { !u };
+	f64::from_bits(u)
+}
+
+pub fn encode_f64(out: &mut Vec<u8>, v: f64) {
+	encode_u64(out, f64_to_cmp(v));
+}
+
+pub fn encode_f64_desc(out: &mut Vec<u8>, v: f64) {
+	encode_u64(out, !f64_to_cmp(v));
+}
+
+pub fn decode_f64(buf: &[u8]) -> Result<(f64, usize), DecodeError> {
+	read_u64(buf).map(|u| (cmp_to_f64(u), 8))
+}
+
+pub fn decode_f64_desc(buf: &[u8]) -> Result<(f64, usize), DecodeError> {
+	read_u64(buf).map(|u| (cmp_to_f64(!u), 8))
+}
+
+/// Exact length of `encode_bytes` for an input of `len` bytes.
+pub fn encoded_bytes_len(len: usize) -> usize {
+	(len / GROUP_SIZE + 1) * (GROUP_SIZE + 1)
+}
+
+fn encode_bytes_with(out: &mut Vec<u8>, data: &[u8], flip: u8) {
+	out.reserve(encoded_bytes_len(data.len()));
+	let mut chunks = data.chunks_exact(GROUP_SIZE);
+	for group in &mut chunks {
+		out.extend(group.iter().map(|b| b ^ flip));
+		out.push(MARKER ^ flip);
+	}
+	let rest = chunks.remainder();
+	let pad = GROUP_SIZE - rest.len();
+	out.extend(rest.iter().map(|b| b ^ flip));
+	out.extend(std::iter::repeat_n(PAD ^ flip, pad));
+	out.push((MARKER - pad as u8) ^ flip);
+}
+
+fn decode_bytes_with(buf: &[u8], flip: u8) -> Result<(Vec<u8>, usize), DecodeError> {
+	let mut out = Vec::new();
+	let mut pos = 0;
+	loop {
+		let end = pos + GROUP_SIZE + 1;
+		if buf.len() < end {
+			return Err(DecodeError::Truncated { needed: end });
+		}
+		let group = &buf[pos..end - 1];
+		let marker = buf[end - 1] ^ flip;
+		pos = end;
+		if marker == MARKER {
+			out.extend(group.iter().map(|b| b ^ flip));
+			continue;
+		}
+		let pad = (MARKER - marker) as usize;
+		if pad > GROUP_SIZE {
+			return Err(DecodeError::Malformed("memcomparable group marker out of range"));
+		}
+		let (data, padding) = group.split_at(GROUP_SIZE - pad);
+		if padding.iter().any(|&b| b ^ flip != PAD) {
+			return Err(DecodeError::Malformed("memcomparable group padding is not zero"));
+		}
+		out.extend(data.iter().map(|b| b ^ flip));
+		return Ok((out, pos));
+	}
+}
+
+pub fn encode_bytes(out: &mut Vec<u8>, data: &[u8]) {
+	encode_bytes_with(out, data, 0);
+}
+
+pub fn encode_bytes_desc(out: &mut Vec<u8>, data: &[u8]) {
+	encode_bytes_with(out, data, 0xff);
+}
+
+pub fn decode_bytes(buf: &[u8]) -> Result<(Vec<u8>, usize), DecodeError> {
+	decode_bytes_with(buf, 0)
+}
+
+pub fn decode_bytes_desc(buf: &[u8]) -> Result<(Vec<u8>, usize), DecodeError> {
+	decode_bytes_with(buf, 0xff)
+}
+
+pub fn encode_str(out: &mut Vec<u8>, s: &str) {
+	encode_bytes(out, s.as_bytes());
+}
+
+pub fn encode_str_desc(out: &mut Vec<u8>, s: &str) {
+	encode_bytes_desc(out, s.as_bytes());
+}
+
+pub fn decode_str(buf: &[u8]) -> Result<(String, usize), DecodeError> {
+	let (bytes, used) = decode_bytes(buf)?;
+	String::from_utf8(bytes)
+		.map(|s| (s, used))
+		.map_err(|_| DecodeError::Malformed("memcomparable string is not UTF-8"))
+}
+
+pub fn decode_str_desc(buf: &[u8]) -> Result<(String, usize), DecodeError> {
+	let (bytes, used) = decode_bytes_desc(buf)?;
+	String::from_utf8(bytes)
+		.map(|s| (s, used))
+		.map_err(|_| DecodeError::Malformed("memcomparable string is not UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc<T: Copy>(f: fn(&mut Vec<u8>, T), v: T) -> Vec<u8> {
+		let mut out = Vec::new();
+		f(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn int_order(x: i64, y: i64) -> bool {
+		x.cmp(&y) == enc(encode_i64, x).cmp(&enc(encode_i64, y)) &&
+			y.cmp(&x) == enc(encode_i64_desc, x).cmp(&enc(encode_i64_desc, y)) &&
+			decode_i64(&enc(encode_i64, x)) == Ok((x, 8)) &&
+			decode_i64_desc(&enc(encode_i64_desc, x)) == Ok((x, 8))
+	}
+
+	#[quickcheck]
+	fn float_order(x: f64, y: f64) -> bool {
+		let (a, b) = (enc(encode_f64, x), enc(encode_f64, y));
+		x.partial_cmp(&y) == Some(a.cmp(&b)) && decode_f64(&a).unwrap().0 == x
+	}
+
+	#[quickcheck]
+	fn bytes_order(x: Vec<u8>, y: Vec<u8>) -> bool {
+		let (a, b) = (enc(encode_bytes, &x[..]), enc(encode_bytes, &y[..]));
+		let (da, db) = (enc(encode_bytes_desc, &x[..]), enc(encode_bytes_desc, &y[..]));
+		x.cmp(&y) == a.cmp(&b) && y.cmp(&x) == da.cmp(&db) &&
+			a.len() == encoded_bytes_len(x.len()) &&
+			decode_bytes(&a) == Ok((x.clone(), a.len())) &&
+			decode_bytes_desc(&da) == Ok((x, da.len()))
+	}
+
+	#[test]
+	fn matches_tikv_vectors() {
+		assert_eq!(enc(encode_bytes, &b""[..]), vec![0, 0, 0, 0, 0, 0, 0, 0, 247]);
+		assert_eq!(enc(encode_bytes, &[1u8, 2, 3][..]), vec![1, 2, 3, 0, 0, 0, 0, 0, 250]);
+		assert_eq!(enc(encode_bytes, &[1u8, 2, 3, 4, 5, 6, 7, 8][..]),
+			vec![1, 2, 3, 4, 5, 6, 7, 8, 255, 0, 0, 0, 0, 0, 0, 0, 0, 247]);
+		assert_eq!(enc(encode_bytes_desc, &[1u8, 2, 3][..]),
+			vec![254, 253, 252, 255, 255, 255, 255, 255, 5]);
+		assert_eq!(enc(encode_i64, -1), vec![0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+		assert_eq!(enc(encode_f64, -0.0), enc(encode_f64, 0.0));
+		assert_eq!(enc(encode_f64, 1.0), vec![0xbf, 0xf0, 0, 0, 0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn rejects_bad_groups() {
+		assert!(decode_bytes(&[1, 2, 3, 0, 0, 0, 0, 0]).is_err());
+		assert!(decode_bytes(&[1, 2, 3, 0, 0, 0, 0, 7, 250]).is_err());
+		assert!(decode_bytes(&[1, 2, 3, 0, 0, 0, 0, 0, 200]).is_err());
+		assert!(decode_str(&enc(encode_bytes, &[0xffu8][..])).is_err());
+	}
+}
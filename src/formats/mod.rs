@@ -0,0 +1,543 @@
+//! Encodings used by other systems, for interoperability with their
+//! on-disk and wire formats.
+
+pub mod bitcoin;
+pub mod cassandra;
+pub mod elias;
+pub mod fast_le;
+pub mod g8iu;
+pub mod git;
+pub mod golomb;
+pub mod group;
+pub mod leb128;
+pub mod memcomparable;
+pub mod orc;
+pub mod orderedbytes;
+pub mod prefix_varint;
+pub mod simple8b;
+pub mod sqlite;
+pub mod streamvbyte;
+
+use crate::metrics::CodecMetrics;
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::{decode_int, decode_uint, pack_int_append, pack_uint_append, DecodeError, MAX_ENCODED_LEN};
+
+/// A single-value integer encoding. The higher layers (`transcode`,
+/// `Transcoder`, `block::encode_block_with`) are generic over it, so a
+/// downstream crate can plug in its own format.
+///
+/// The signed methods default to ZigZag over the unsigned ones.
+pub trait VarintFormat {
+	/// Longest encoding of any 64-bit value.
+	fn max_len(&self) -> usize;
+
+	fn encode_into(&self, out: &mut Vec<u8>, v: u64);
+
+	/// Decodes one value from the front of `buf`, returning it with the
+	/// number of bytes it occupied.
+	fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError>;
+
+	fn encode_i64_into(&self, out: &mut Vec<u8>, v: i64) {
+		self.encode_into(out, zigzag_encode(v));
+	}
+
+	fn decode_i64_from(&self, buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+		self.decode_from(buf).map(|(v, used)| (zigzag_decode(v), used))
+	}
+
+	/// Whether encodings compare bytewise in the order of their values,
+	/// both unsigned and signed.
+	fn preserves_order(&self) -> bool {
+		false
+	}
+
+	/// Whether `buf` starts with the one encoding this format writes for
+	/// its value, rather than a longer form that merely decodes to it.
+	fn is_canonical(&self, buf: &[u8]) -> bool {
+		match self.decode_from(buf) {
+			Ok((v, used)) => {
+				let mut out = Vec::with_capacity(used);
+				self.encode_into(&mut out, v);
+				out[..] == buf[..used]
+			}
+			Err(_) => false,
+		}
+	}
+}
+
+/// The crate's own order-preserving encoding as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Ordered;
+
+impl VarintFormat for Ordered {
+	fn max_len(&self) -> usize {
+		MAX_ENCODED_LEN
+	}
+
+	fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+		pack_uint_append(v, out);
+	}
+
+	fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+		decode_uint(buf)
+	}
+
+	fn encode_i64_into(&self, out: &mut Vec<u8>, v: i64) {
+		pack_int_append(v, out);
+	}
+
+	fn decode_i64_from(&self, buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+		decode_int(buf)
+	}
+
+	fn preserves_order(&self) -> bool {
+		true
+	}
+}
+
+macro_rules! module_format {
+	($format:ty, $module:ident) => {
+		impl VarintFormat for $format {
+			fn max_len(&self) -> usize {
+				$module::MAX_LEN
+			}
+
+			fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+				$module::encode_u64(out, v);
+			}
+
+			fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+				$module::decode_u64(buf)
+			}
+
+			fn encode_i64_into(&self, out: &mut Vec<u8>, v: i64) {
+				$module::encode_i64(out, v);
+			}
+
+			fn decode_i64_from(&self, buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+				$module::decode_i64(buf)
+			}
+		}
+	};
+}
+
+module_format!(cassandra::Cassandra, cassandra);
+module_format!(prefix_varint::PrefixVarint, prefix_varint);
+module_format!(leb128::Leb128, leb128);
+module_format!(fast_le::FastLE, fast_le);
+module_format!(sqlite::Sqlite, sqlite);
+
+/// Single-value integer encodings that can be chosen at run time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+	/// The crate's own order-preserving encoding (`pack_uint`/`pack_int`).
+	Ordered,
+	/// Cassandra's `vint`, see `formats::cassandra`.
+	Cassandra,
+	/// See `formats::prefix_varint`.
+	PrefixVarint,
+	/// Protobuf-style LEB128, see `formats::leb128`.
+	Leb128,
+	/// Length byte and little-endian value, not order-preserving, see
+	/// `formats::fast_le`.
+	FastLE,
+	/// The SQLite 3 varint, see `formats::sqlite`.
+	Sqlite,
+	/// Git's offset varint, see `formats::git`.
+	GitOffset,
+	/// Bitcoin's CompactSize, see `formats::bitcoin`.
+	CompactSize,
+}
+
+impl Format {
+	/// Longest encoding of any 64-bit value.
+	pub fn max_len(self) -> usize {
+		match self {
+			Format::Ordered => Ordered.max_len(),
+			Format::Cassandra => cassandra::MAX_LEN,
+			Format::PrefixVarint => prefix_varint::MAX_LEN,
+			Format::Leb128 => leb128::MAX_LEN,
+			Format::FastLE => fast_le::MAX_LEN,
+			Format::Sqlite => sqlite::MAX_LEN,
+			Format::GitOffset => git::MAX_LEN,
+			Format::CompactSize => bitcoin::MAX_LEN,
+		}
+	}
+
+	pub fn encode_u64(self, out: &mut Vec<u8>, v: u64) {
+		match self {
+			Format::Ordered => pack_uint_append(v, out),
+			Format::Cassandra => cassandra::encode_u64(out, v),
+			Format::PrefixVarint => prefix_varint::encode_u64(out, v),
+			Format::Leb128 => leb128::encode_u64(out, v),
+			Format::FastLE => fast_le::encode_u64(out, v),
+			Format::Sqlite => sqlite::encode_u64(out, v),
+			Format::GitOffset => git::encode_u64(out, v),
+			Format::CompactSize => bitcoin::encode_u64(out, v),
+		}
+	}
+
+	pub fn encode_i64(self, out: &mut Vec<u8>, v: i64) {
+		match self {
+			Format::Ordered => pack_int_append(v, out),
+			Format::Cassandra => cassandra::encode_i64(out, v),
+			Format::PrefixVarint => prefix_varint::encode_i64(out, v),
+			Format::Leb128 => leb128::encode_i64(out, v),
+			Format::FastLE => fast_le::encode_i64(out, v),
+			Format::Sqlite => sqlite::encode_i64(out, v),
+			Format::GitOffset => git::GitOffset.encode_i64_into(out, v),
+			Format::CompactSize => bitcoin::CompactSize.encode_i64_into(out, v),
+		}
+	}
+
+	pub fn decode_u64(self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+		match self {
+			Format::Ordered => decode_uint(buf),
+			Format::Cassandra => cassandra::decode_u64(buf),
+			Format::PrefixVarint => prefix_varint::decode_u64(buf),
+			Format::Leb128 => leb128::decode_u64(buf),
+			Format::FastLE => fast_le::decode_u64(buf),
+			Format::Sqlite => sqlite::decode_u64(buf),
+			Format::GitOffset => git::decode_u64(buf),
+			Format::CompactSize => bitcoin::decode_u64(buf),
+		}
+	}
+
+	pub fn decode_i64(self, buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+		match self {
+			Format::Ordered => decode_int(buf),
+			Format::Cassandra => cassandra::decode_i64(buf),
+			Format::PrefixVarint => prefix_varint::decode_i64(buf),
+			Format::Leb128 => leb128::decode_i64(buf),
+			Format::FastLE => fast_le::decode_i64(buf),
+			Format::Sqlite => sqlite::decode_i64(buf),
+			Format::GitOffset => git::GitOffset.decode_i64_from(buf),
+			Format::CompactSize => bitcoin::CompactSize.decode_i64_from(buf),
+		}
+	}
+}
+
+impl VarintFormat for Format {
+	fn max_len(&self) -> usize {
+		Format::max_len(*self)
+	}
+
+	fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+		self.encode_u64(out, v);
+	}
+
+	fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+		self.decode_u64(buf)
+	}
+
+	fn encode_i64_into(&self, out: &mut Vec<u8>, v: i64) {
+		self.encode_i64(out, v);
+	}
+
+	fn decode_i64_from(&self, buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+		self.decode_i64(buf)
+	}
+
+	fn preserves_order(&self) -> bool {
+		*self == Format::Ordered
+	}
+}
+
+/// An encoding of whole runs of `u64`s, so that a column can switch
+/// between packing its values one at a time and bit-packing them.
+///
+/// Every `VarintFormat` is one, writing the values back to back.
+pub trait BlockCodec {
+	/// Appends `values` to `out`. Panics if the codec cannot represent one.
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]);
+
+	/// Decodes `count` values from the front of `buf`, appending them to
+	/// `out`, and returns the number of bytes they occupied.
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError>;
+}
+
+impl<F: VarintFormat> BlockCodec for F {
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]) {
+		for &v in values {
+			self.encode_into(out, v);
+		}
+	}
+
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+		let mut pos = 0;
+		for _ in 0..count {
+			let (v, used) = self.decode_from(&buf[pos..]).map_err(|e| offset_error(e, pos))?;
+			out.push(v);
+			pos += used;
+		}
+		Ok(pos)
+	}
+}
+
+/// Re-encodes a buffer of concatenated unsigned values from `src` to `dst`.
+pub fn transcode<S: VarintFormat, D: VarintFormat>(src: S, dst: D, buf: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	let mut out = Vec::with_capacity(buf.len());
+	let used = transcode_prefix(&src, &dst, buf, &mut out, false, &())?;
+	if used < buf.len() {
+		// the tail stopped transcoding because it ran out of bytes
+		src.decode_from(&buf[used..]).map_err(|e| offset_error(e, used))?;
+	}
+	Ok(out)
+}
+
+fn offset_error(e: DecodeError, pos: usize) -> DecodeError {
+	match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+		e => e,
+	}
+}
+
+// Transcodes whole values from the front of `buf`, stopping before a
+// truncated one. Returns how many bytes were consumed.
+fn transcode_prefix<S, D, M>(
+	src: &S,
+	dst: &D,
+	buf: &[u8],
+	out: &mut Vec<u8>,
+	canonical: bool,
+	metrics: &M,
+) -> Result<usize, DecodeError>
+where
+	S: VarintFormat,
+	D: VarintFormat,
+	M: CodecMetrics,
+{
+	let (start, mut pos, mut values) = (out.len(), 0, 0);
+	let result = loop {
+		if pos == buf.len() {
+			break Ok(pos);
+		}
+		match src.decode_from(&buf[pos..]) {
+			Ok(_) if canonical && !src.is_canonical(&buf[pos..]) => {
+				metrics.canonicality_rejections(1);
+				break Err(DecodeError::Malformed("non-canonical encoding"));
+			}
+			Ok((v, used)) => {
+				dst.encode_into(out, v);
+				pos += used;
+				values += 1;
+			}
+			Err(DecodeError::Truncated { .. }) => break Ok(pos),
+			Err(e) => {
+				metrics.decode_errors(&e);
+				break Err(e);
+			}
+		}
+	};
+	metrics.values_encoded(values);
+	metrics.bytes_written((out.len() - start) as u64);
+	result
+}
+
+/// Streaming `transcode` for input that arrives in chunks split at
+/// arbitrary byte boundaries.
+///
+/// An error is sticky: the values before the bad one are written out,
+/// but where the next value would start is unknown, so every later
+/// `feed` and `finish` returns the same error.
+#[derive(Debug, Clone)]
+pub struct Transcoder<S, D, M = ()> {
+	src: S,
+	dst: D,
+	pending: Vec<u8>,
+	canonical: bool,
+	failed: Option<DecodeError>,
+	metrics: M,
+}
+
+impl<S: VarintFormat, D: VarintFormat> Transcoder<S, D> {
+	pub fn new(src: S, dst: D) -> Transcoder<S, D> {
+		Transcoder { src, dst, pending: Vec::new(), canonical: false, failed: None, metrics: () }
+	}
+}
+
+impl<S: VarintFormat, D: VarintFormat, M: CodecMetrics> Transcoder<S, D, M> {
+	/// Reports values written, bytes written and errors to `metrics`.
+	pub fn with_metrics<N: CodecMetrics>(self, metrics: N) -> Transcoder<S, D, N> {
+		let Transcoder { src, dst, pending, canonical, failed, .. } = self;
+		Transcoder { src, dst, pending, canonical, failed, metrics }
+	}
+
+	/// Fails on source values that are not in canonical form (see
+	/// `VarintFormat::is_canonical`) instead of transcoding them.
+	pub fn require_canonical(mut self, canonical: bool) -> Transcoder<S, D, M> {
+		self.canonical = canonical;
+		self
+	}
+
+	/// Transcodes every value completed by `chunk` into `out`, holding
+	/// back a trailing partial value until the next call.
+	pub fn feed(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<(), DecodeError> {
+		if let Some(e) = self.failed {
+			return Err(e);
+		}
+		let result = if self.pending.is_empty() {
+			transcode_prefix(&self.src, &self.dst, chunk, out, self.canonical, &self.metrics)
+				.map(|used| self.pending.extend_from_slice(&chunk[used..]))
+		} else {
+			self.pending.extend_from_slice(chunk);
+			transcode_prefix(&self.src, &self.dst, &self.pending, out, self.canonical, &self.metrics)
+				.map(|used| {
+					self.pending.drain(..used);
+				})
+		};
+		if let Err(e) = result {
+			self.failed = Some(e);
+			self.pending = Vec::new();
+		}
+		result
+	}
+
+	/// Ends the stream, failing if it stopped part way through a value or
+	/// an earlier `feed` failed.
+	pub fn finish(self) -> Result<(), DecodeError> {
+		if let Some(e) = self.failed {
+			return Err(e);
+		}
+		if !self.pending.is_empty() {
+			if let Err(e) = self.src.decode_from(&self.pending) {
+				self.metrics.decode_errors(&e);
+				return Err(e);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_uint;
+
+	const ALL: [Format; 8] = [Format::Ordered, Format::Cassandra, Format::PrefixVarint, Format::Leb128, Format::FastLE,
+		Format::Sqlite, Format::GitOffset, Format::CompactSize];
+
+	#[quickcheck]
+	fn round_trip(x: u64, y: i64) -> bool {
+		ALL.iter().all(|&f| {
+			let mut out = Vec::new();
+			f.encode_u64(&mut out, x);
+			let split = out.len();
+			f.encode_i64(&mut out, y);
+			split <= f.max_len() && out.len() - split <= f.max_len() &&
+				f.decode_u64(&out) == Ok((x, split)) &&
+				f.decode_i64(&out[split..]) == Ok((y, out.len() - split))
+		})
+	}
+
+	#[quickcheck]
+	fn transcodes_between_all_formats(values: Vec<u64>, split: usize) -> bool {
+		ALL.iter().all(|&src| ALL.iter().all(|&dst| {
+			let (mut buf, mut want) = (Vec::new(), Vec::new());
+			for &v in &values {
+				src.encode_u64(&mut buf, v);
+				dst.encode_u64(&mut want, v);
+			}
+
+			let mut t = Transcoder::new(src, dst);
+			let mut out = Vec::new();
+			let split = split % (buf.len() + 1);
+			transcode(src, dst, &buf).as_ref() == Ok(&want) &&
+				t.feed(&buf[..split], &mut out).is_ok() && t.feed(&buf[split..], &mut out).is_ok() &&
+				t.finish().is_ok() && out == want
+		}))
+	}
+
+	#[quickcheck]
+	fn structs_match_the_enum(x: u64, y: i64) -> bool {
+		fn same<F: VarintFormat>(f: F, g: Format, x: u64, y: i64) -> bool {
+			let (mut a, mut b) = (Vec::new(), Vec::new());
+			f.encode_into(&mut a, x);
+			f.encode_i64_into(&mut a, y);
+			g.encode_u64(&mut b, x);
+			g.encode_i64(&mut b, y);
+			a == b && f.max_len() == g.max_len() && f.is_canonical(&a) && g.is_canonical(&b)
+		}
+		same(Ordered, Format::Ordered, x, y) && same(cassandra::Cassandra, Format::Cassandra, x, y) &&
+			same(prefix_varint::PrefixVarint, Format::PrefixVarint, x, y) && same(leb128::Leb128, Format::Leb128, x, y) &&
+			same(fast_le::FastLE, Format::FastLE, x, y) && same(sqlite::Sqlite, Format::Sqlite, x, y) &&
+			same(git::GitOffset, Format::GitOffset, x, y) && same(bitcoin::CompactSize, Format::CompactSize, x, y)
+	}
+
+	// A format defined outside the crate: fixed 8 bytes little-endian.
+	struct Fixed;
+
+	impl VarintFormat for Fixed {
+		fn max_len(&self) -> usize {
+			8
+		}
+
+		fn encode_into(&self, out: &mut Vec<u8>, v: u64) {
+			out.extend_from_slice(&v.to_le_bytes());
+		}
+
+		fn decode_from(&self, buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+			let bytes = buf.get(..8).ok_or(DecodeError::Truncated { needed: 8 })?;
+			let mut v = [0; 8];
+			v.copy_from_slice(bytes);
+			Ok((u64::from_le_bytes(v), 8))
+		}
+	}
+
+	#[test]
+	fn plugs_in_custom_formats() {
+		let fixed = transcode(Format::Ordered, Fixed, &pack_uint(300)).unwrap();
+		assert_eq!(fixed, 300u64.to_le_bytes());
+		assert_eq!(transcode(Fixed, Ordered, &fixed), Ok(pack_uint(300)));
+		assert!(!leb128::Leb128.is_canonical(&[0x81, 0x00]));
+		assert!(!Ordered.is_canonical(&[0x05]));
+	}
+
+	#[test]
+	fn transcode_rejects_truncated_input() {
+		let mut buf = Vec::new();
+		Format::Leb128.encode_u64(&mut buf, 1);
+		Format::Leb128.encode_u64(&mut buf, 300);
+		assert_eq!(transcode(Format::Leb128, Format::Ordered, &buf[..2]), Err(DecodeError::Truncated { needed: 3 }));
+
+		let mut t = Transcoder::new(Format::Leb128, Format::Ordered);
+		let mut out = Vec::new();
+		t.feed(&buf[..2], &mut out).unwrap();
+		assert_eq!(out, pack_uint(1));
+		assert!(t.finish().is_err());
+	}
+
+	#[test]
+	fn transcoder_reports_metrics() {
+		use crate::metrics::Counters;
+		use std::sync::atomic::Ordering;
+
+		let counters = Counters::default();
+		let mut t = Transcoder::new(Format::Leb128, Format::Ordered).with_metrics(&counters).require_canonical(true);
+		let mut out = Vec::new();
+		t.feed(&[0x01, 0xac, 0x02], &mut out).unwrap();
+		assert_eq!(t.feed(&[0x81, 0x00], &mut out), Err(DecodeError::Malformed("non-canonical encoding")));
+		assert_eq!(counters.values_encoded.load(Ordering::Relaxed), 2);
+		assert_eq!(counters.bytes_written.load(Ordering::Relaxed), out.len() as u64);
+		assert_eq!(counters.canonicality_rejections.load(Ordering::Relaxed), 1);
+
+		let mut t = Transcoder::new(Format::Leb128, Format::Ordered).with_metrics(&counters);
+		t.feed(&[0x81], &mut out).unwrap();
+		assert!(t.finish().is_err());
+		assert_eq!(counters.decode_errors.load(Ordering::Relaxed), 1);
+	}
+
+	#[test]
+	fn transcoder_errors_are_sticky() {
+		let mut t = Transcoder::new(Format::Leb128, Format::Ordered).require_canonical(true);
+		let mut out = Vec::new();
+		t.feed(&[0x01, 0x81], &mut out).unwrap();
+		let bad = Err(DecodeError::Malformed("non-canonical encoding"));
+		assert_eq!(t.feed(&[0x00, 0x02], &mut out), bad);
+		assert_eq!(out, pack_uint(1));
+		// neither the bytes after the error nor any later chunk is transcoded
+		assert_eq!(t.feed(&[0x03], &mut out), bad);
+		assert_eq!(out, pack_uint(1));
+		assert_eq!(t.finish(), bad);
+	}
+}
@@ -0,0 +1,681 @@
+//! HBase's `OrderedBytes` encodings (`org.apache.hadoop.hbase.util.OrderedBytes`).
+//!
+//! Every value starts with a header byte naming its type, so encoded values
+//! of different types still sort consistently next to each other in a
+//! compound key. `Order::Descending` complements every byte, header
+//! included.
+//!
+//! * numeric: the SQLite4 key encoding. A value is written as
+//!   `0.M × 100^E`, where each centimal digit `d` of the mantissa is stored
+//!   as `2d + 1` and the last one as `2d`. The header says the sign and the
+//!   size of `E`; large and small exponents follow the header as SQLite4
+//!   varints.
+//! * fixed width: `int8` to `int64` as big-endian with the sign bit
+//!   flipped, and `float32`/`float64` as IEEE bits flipped the same way as
+//!   HBase does.
+//! * text: UTF-8 bytes followed by a `0x00` terminator, so strings may not
+//!   contain NUL.
+//! * blob-var: 7 bits of the input per output byte, the high bit set on all
+//!   but the last byte. As in HBase, that continuation bit is compared
+//!   before the data bits, so blob-var does not always sort by content
+//!   (`[0, 0]` sorts after `[1]`). blob-copy stores the bytes verbatim and
+//!   can only be the last field of an ascending key.
+
+use std::convert::TryFrom;
+
+use crate::DecodeError;
+
+const NULL: u8 = 0x05;
+const NEG_INF: u8 = 0x07;
+const NEG_LARGE: u8 = 0x08;
+const NEG_MED_MIN: u8 = 0x09;
+const NEG_MED_MAX: u8 = 0x13;
+const NEG_SMALL: u8 = 0x14;
+const ZERO: u8 = 0x15;
+const POS_SMALL: u8 = 0x16;
+const POS_MED_MIN: u8 = 0x17;
+const POS_MED_MAX: u8 = 0x21;
+const POS_LARGE: u8 = 0x22;
+const POS_INF: u8 = 0x23;
+const NAN: u8 = 0x25;
+const FIXED_INT8: u8 = 0x29;
+const FIXED_INT16: u8 = 0x2a;
+const FIXED_INT32: u8 = 0x2b;
+const FIXED_INT64: u8 = 0x2c;
+const FIXED_FLOAT32: u8 = 0x30;
+const FIXED_FLOAT64: u8 = 0x31;
+const TEXT: u8 = 0x34;
+const BLOB_VAR: u8 = 0x37;
+const BLOB_COPY: u8 = 0x38;
+const TERM: u8 = 0x00;
+
+/// Sort direction of an encoded value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+	Ascending,
+	Descending,
+}
+
+impl Order {
+	fn mask(self) -> u8 {
+		match self {
+			Order::Ascending => 0,
+			Order::Descending => 0xff,
+		}
+	}
+
+	fn apply(self, out: &mut [u8]) {
+		if self == Order::Descending {
+			for b in out {
+				*b = !*b;
+			}
+		}
+	}
+}
+
+// Reads bytes with the order mask already removed.
+struct Input<'a> {
+	buf: &'a [u8],
+	pos: usize,
+	mask: u8,
+}
+
+impl<'a> Input<'a> {
+	fn new(buf: &'a [u8], order: Order) -> Input<'a> {
+		Input { buf, pos: 0, mask: order.mask() }
+	}
+
+	fn byte(&mut self) -> Result<u8, DecodeError> {
+		let b = *self.buf.get(self.pos).ok_or(DecodeError::Truncated { needed: self.pos + 1 })?;
+		self.pos += 1;
+		Ok(b ^ self.mask)
+	}
+
+	fn header(&mut self, expected: u8) -> Result<(), DecodeError> {
+		match self.byte()? {
+			h if h == expected => Ok(()),
+			h => Err(DecodeError::InvalidMarker(h ^ self.mask)),
+		}
+	}
+
+	fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+		let mut out = [0u8; N];
+		for b in out.iter_mut() {
+			*b = self.byte()?;
+		}
+		Ok(out)
+	}
+}
+
+/// Type of an encoded value, as named by its header byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+	Null,
+	Numeric,
+	Int8,
+	Int16,
+	Int32,
+	Int64,
+	Float32,
+	Float64,
+	Text,
+	BlobVar,
+	BlobCopy,
+}
+
+/// Reads the header at the front of `buf`, returning the type and the
+/// order of the value that follows. Ascending and descending headers do
+/// not overlap, so values of mixed order can be told apart.
+pub fn kind_of(buf: &[u8]) -> Result<(Kind, Order), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let (header, order) = if first < 0x80 { (first, Order::Ascending) } else { (!first, Order::Descending) };
+	let kind = match header {
+		NULL => Kind::Null,
+		NEG_INF..=POS_INF | NAN => Kind::Numeric,
+		FIXED_INT8 => Kind::Int8,
+		FIXED_INT16 => Kind::Int16,
+		FIXED_INT32 => Kind::Int32,
+		FIXED_INT64 => Kind::Int64,
+		FIXED_FLOAT32 => Kind::Float32,
+		FIXED_FLOAT64 => Kind::Float64,
+		TEXT => Kind::Text,
+		BLOB_VAR => Kind::BlobVar,
+		BLOB_COPY => Kind::BlobCopy,
+		_ => return Err(DecodeError::InvalidMarker(first)),
+	};
+	Ok((kind, order))
+}
+
+/// Whether the value at the front of `buf` is an encoded null.
+pub fn is_null(buf: &[u8], order: Order) -> bool {
+	buf.first() == Some(&(NULL ^ order.mask()))
+}
+
+pub fn encode_null(out: &mut Vec<u8>, order: Order) {
+	out.push(NULL ^ order.mask());
+}
+
+// SQLite4 varint, complemented when `complement` is set.
+fn put_varuint(out: &mut Vec<u8>, v: u64, complement: bool) {
+	let start = out.len();
+	if v < 241 {
+		out.push(v as u8);
+	} else if v < 2288 {
+		let y = v - 240;
+		out.push((y / 256 + 241) as u8);
+		out.push(y as u8);
+	} else if v < 67824 {
+		out.push(249);
+		out.extend_from_slice(&((v - 2288) as u16).to_be_bytes());
+	} else {
+		let n = (8 - v.leading_zeros() as usize / 8).max(3);
+		out.push(247 + n as u8);
+		out.extend_from_slice(&v.to_be_bytes()[8 - n..]);
+	}
+	if complement {
+		for b in &mut out[start..] {
+			*b = !*b;
+		}
+	}
+}
+
+fn get_varuint(input: &mut Input, complement: bool) -> Result<u64, DecodeError> {
+	let flip = if complement { 0xff } else { 0 };
+	let b0 = input.byte()? ^ flip;
+	let mut tail = |n: usize| -> Result<u64, DecodeError> {
+		let mut v = 0u64;
+		for _ in 0..n {
+			v = (v << 8) | (input.byte()? ^ flip) as u64;
+		}
+		Ok(v)
+	};
+	Ok(match b0 {
+		0..=240 => b0 as u64,
+		241..=248 => 240 + 256 * (b0 as u64 - 241) + tail(1)?,
+		249 => 2288 + tail(2)?,
+		_ => tail(b0 as usize - 247)?,
+	})
+}
+
+// A nonzero finite magnitude as 0.c1..cn × 100^e, with c1 and cn nonzero.
+struct Centimal {
+	digits: Vec<u8>,
+	e: i64,
+}
+
+impl Centimal {
+	// `decimal` is the ASCII digits of 0.D × 10^exp10, without leading zeros.
+	fn from_decimal(decimal: &[u8], mut exp10: i64) -> Centimal {
+		let mut d: Vec<u8> = decimal.iter().map(|c| c - b'0').collect();
+		while d.last() == Some(&0) {
+			d.pop();
+		}
+		if exp10 % 2 != 0 {
+			d.insert(0, 0);
+			exp10 += 1;
+		}
+		if !d.len().is_multiple_of(2) {
+			d.push(0);
+		}
+		let digits = d.chunks(2).map(|p| p[0] * 10 + p[1]).collect();
+		Centimal { digits, e: exp10 / 2 }
+	}
+}
+
+fn put_numeric(out: &mut Vec<u8>, negative: bool, c: &Centimal, order: Order) {
+	let start = out.len();
+	if c.e > 10 {
+		out.push(if negative { NEG_LARGE } else { POS_LARGE });
+		put_varuint(out, c.e as u64, negative);
+	} else if c.e > 0 {
+		out.push(if negative { NEG_MED_MAX - c.e as u8 } else { POS_MED_MIN + c.e as u8 });
+	} else {
+		out.push(if negative { NEG_SMALL } else { POS_SMALL });
+		put_varuint(out, c.e.unsigned_abs(), !negative);
+	}
+	let m = out.len();
+	out.extend(c.digits.iter().map(|d| 2 * d + 1));
+	*out.last_mut().unwrap() &= 0xfe;
+	if negative {
+		for b in &mut out[m..] {
+			*b = !*b;
+		}
+	}
+	order.apply(&mut out[start..]);
+}
+
+/// Encodes `v` as a variable-length numeric.
+pub fn encode_numeric_i64(out: &mut Vec<u8>, v: i64, order: Order) {
+	if v == 0 {
+		out.push(ZERO ^ order.mask());
+		return;
+	}
+	let digits = v.unsigned_abs().to_string();
+	let c = Centimal::from_decimal(digits.as_bytes(), digits.len() as i64);
+	put_numeric(out, v < 0, &c, order);
+}
+
+/// Encodes `v` as a variable-length numeric, from the shortest decimal
+/// that round-trips (HBase goes through `BigDecimal.valueOf`). `-0.0` is
+/// stored as zero.
+pub fn encode_numeric_f64(out: &mut Vec<u8>, v: f64, order: Order) {
+	let header = if v.is_nan() {
+		NAN
+	} else if v == 0.0 {
+		ZERO
+	} else if v == f64::INFINITY {
+		POS_INF
+	} else if v == f64::NEG_INFINITY {
+		NEG_INF
+	} else {
+		let text = format!("{:e}", v.abs());
+		let (mantissa, exp) = text.split_at(text.find('e').unwrap());
+		let digits: Vec<u8> = mantissa.bytes().filter(|&c| c != b'.').collect();
+		let exp10 = exp[1..].parse::<i64>().unwrap() + 1;
+		put_numeric(out, v < 0.0, &Centimal::from_decimal(&digits, exp10), order);
+		return;
+	};
+	out.push(header ^ order.mask());
+}
+
+// An exponent read from the input, which may not fit an `i64`.
+fn exponent(v: u64, negate: bool) -> Result<i64, DecodeError> {
+	let e = i64::try_from(v).map_err(|_| DecodeError::Overflow)?;
+	if negate { e.checked_neg().ok_or(DecodeError::Overflow) } else { Ok(e) }
+}
+
+enum Numeric {
+	Zero,
+	NaN,
+	Infinite { negative: bool },
+	Finite { negative: bool, e: i64, digits: Vec<u8> },
+}
+
+fn decode_numeric(buf: &[u8], order: Order) -> Result<(Numeric, usize), DecodeError> {
+	let mut input = Input::new(buf, order);
+	let header = input.byte()?;
+	let (negative, e) = match header {
+		ZERO => return Ok((Numeric::Zero, 1)),
+		NAN => return Ok((Numeric::NaN, 1)),
+		NEG_INF => return Ok((Numeric::Infinite { negative: true }, 1)),
+		POS_INF => return Ok((Numeric::Infinite { negative: false }, 1)),
+		NEG_LARGE => (true, exponent(get_varuint(&mut input, true)?, false)?),
+		NEG_MED_MIN..=NEG_MED_MAX => (true, (NEG_MED_MAX - header) as i64),
+		NEG_SMALL => (true, exponent(get_varuint(&mut input, false)?, true)?),
+		POS_SMALL => (false, exponent(get_varuint(&mut input, true)?, true)?),
+		POS_MED_MIN..=POS_MED_MAX => (false, (header - POS_MED_MIN) as i64),
+		POS_LARGE => (false, exponent(get_varuint(&mut input, false)?, false)?),
+		_ => return Err(DecodeError::InvalidMarker(header ^ order.mask())),
+	};
+	let mut digits = Vec::new();
+	loop {
+		let b = input.byte()? ^ if negative { 0xff } else { 0 };
+		if b >> 1 > 99 {
+			return Err(DecodeError::Malformed("orderedbytes centimal digit out of range"));
+		}
+		digits.push(b >> 1);
+		if b & 1 == 0 {
+			break;
+		}
+	}
+	Ok((Numeric::Finite { negative, e, digits }, input.pos))
+}
+
+/// Decodes a numeric that holds an integer in `i64` range.
+pub fn decode_numeric_i64(buf: &[u8], order: Order) -> Result<(i64, usize), DecodeError> {
+	match decode_numeric(buf, order)? {
+		(Numeric::Zero, used) => Ok((0, used)),
+		(Numeric::Finite { negative, e, digits }, used) => {
+			if e < digits.len() as i64 {
+				return Err(DecodeError::Malformed("orderedbytes numeric is not an integer"));
+			}
+			if e > 10 {
+				return Err(DecodeError::Overflow);
+			}
+			let mut m = 0i128;
+			for i in 0..e as usize {
+				m = m * 100 + *digits.get(i).unwrap_or(&0) as i128;
+			}
+			let v = if negative { -m } else { m };
+			i64::try_from(v).map(|v| (v, used)).map_err(|_| DecodeError::Overflow)
+		}
+		_ => Err(DecodeError::Malformed("orderedbytes numeric is not finite")),
+	}
+}
+
+/// Decodes any numeric, rounding to the nearest `f64`. An exponent of
+/// 100 too large to write as a power of 10 is an `Overflow`.
+pub fn decode_numeric_f64(buf: &[u8], order: Order) -> Result<(f64, usize), DecodeError> {
+	let (n, used) = decode_numeric(buf, order)?;
+	let v = match n {
+		Numeric::Zero => 0.0,
+		Numeric::NaN => f64::NAN,
+		Numeric::Infinite { negative: true } => f64::NEG_INFINITY,
+		Numeric::Infinite { negative: false } => f64::INFINITY,
+		Numeric::Finite { negative, e, digits } => {
+			let mut text = String::from("0.");
+			for d in digits {
+				text.push_str(&format!("{:02}", d));
+			}
+			text.push_str(&format!("e{}", e.checked_mul(2).ok_or(DecodeError::Overflow)?));
+			let v: f64 = text.parse().unwrap();
+			if negative { -v } else { v }
+		}
+	};
+	Ok((v, used))
+}
+
+fn put_fixed(out: &mut Vec<u8>, header: u8, be: &[u8], order: Order) {
+	let start = out.len();
+	out.push(header);
+	out.extend_from_slice(be);
+	out[start + 1] ^= 0x80;
+	order.apply(&mut out[start..]);
+}
+
+fn get_fixed<const N: usize>(buf: &[u8], header: u8, order: Order) -> Result<([u8; N], usize), DecodeError> {
+	let mut input = Input::new(buf, order);
+	input.header(header)?;
+	let mut be = input.array::<N>()?;
+	be[0] ^= 0x80;
+	Ok((be, N + 1))
+}
+
+pub fn encode_int8(out: &mut Vec<u8>, v: i8, order: Order) {
+	put_fixed(out, FIXED_INT8, &v.to_be_bytes(), order);
+}
+
+pub fn encode_int16(out: &mut Vec<u8>, v: i16, order: Order) {
+	put_fixed(out, FIXED_INT16, &v.to_be_bytes(), order);
+}
+
+pub fn encode_int32(out: &mut Vec<u8>, v: i32, order: Order) {
+	put_fixed(out, FIXED_INT32, &v.to_be_bytes(), order);
+}
+
+pub fn encode_int64(out: &mut Vec<u8>, v: i64, order: Order) {
+	put_fixed(out, FIXED_INT64, &v.to_be_bytes(), order);
+}
+
+pub fn decode_int8(buf: &[u8], order: Order) -> Result<(i8, usize), DecodeError> {
+	get_fixed(buf, FIXED_INT8, order).map(|(b, n)| (i8::from_be_bytes(b), n))
+}
+
+pub fn decode_int16(buf: &[u8], order: Order) -> Result<(i16, usize), DecodeError> {
+	get_fixed(buf, FIXED_INT16, order).map(|(b, n)| (i16::from_be_bytes(b), n))
+}
+
+pub fn decode_int32(buf: &[u8], order: Order) -> Result<(i32, usize), DecodeError> {
+	get_fixed(buf, FIXED_INT32, order).map(|(b, n)| (i32::from_be_bytes(b), n))
+}
+
+pub fn decode_int64(buf: &[u8], order: Order) -> Result<(i64, usize), DecodeError> {
+	get_fixed(buf, FIXED_INT64, order).map(|(b, n)| (i64::from_be_bytes(b), n))
+}
+
+// Negative floats have every bit flipped; `put_fixed` flips the sign bit
+// back afterwards, so positives end up with only the sign bit set.
+pub fn encode_float32(out: &mut Vec<u8>, v: f32, order: Order) {
+	let bits = v.to_bits();
+	let bits = if bits >> 31 == 1 { !bits ^ (1 << 31) } else { bits };
+	put_fixed(out, FIXED_FLOAT32, &bits.to_be_bytes(), order);
+}
+
+pub fn encode_float64(out: &mut Vec<u8>, v: f64, order: Order) {
+	let bits = v.to_bits();
+	let bits = if bits >> 63 == 1 { !bits ^ (1 << 63) } else { bits };
+	put_fixed(out, FIXED_FLOAT64, &bits.to_be_bytes(), order);
+}
+
+pub fn decode_float32(buf: &[u8], order: Order) -> Result<(f32, usize), DecodeError> {
+	let (b, n) = get_fixed(buf, FIXED_FLOAT32, order)?;
+	let bits = u32::from_be_bytes(b);
+	let bits = if bits >> 31 == 1 { !bits ^ (1 << 31) } else { bits };
+	Ok((f32::from_bits(bits), n))
+}
+
+pub fn decode_float64(buf: &[u8], order: Order) -> Result<(f64, usize), DecodeError> {
+	let (b, n) = get_fixed(buf, FIXED_FLOAT64, order)?;
+	let bits = u64::from_be_bytes(b);
+	let bits = if bits >> 63 == 1 { !bits ^ (1 << 63) } else { bits };
+	Ok((f64::from_bits(bits), n))
+}
+
+/// Panics if `s` contains a NUL character, which HBase also rejects.
+pub fn encode_str(out: &mut Vec<u8>, s: &str, order: Order) {
+	assert!(!s.contains('\0'), "orderedbytes text cannot contain NUL");
+	let start = out.len();
+	out.push(TEXT);
+	out.extend_from_slice(s.as_bytes());
+	out.push(TERM);
+	order.apply(&mut out[start..]);
+}
+
+pub fn decode_str(buf: &[u8], order: Order) -> Result<(String, usize), DecodeError> {
+	let mut input = Input::new(buf, order);
+	input.header(TEXT)?;
+	let mut bytes = Vec::new();
+	loop {
+		match input.byte()? {
+			TERM => break,
+			b => bytes.push(b),
+		}
+	}
+	String::from_utf8(bytes)
+		.map(|s| (s, input.pos))
+		.map_err(|_| DecodeError::Malformed("orderedbytes text is not UTF-8"))
+}
+
+/// Exact length of `encode_blob_var` for an input of `len` bytes.
+pub fn blob_var_encoded_len(len: usize) -> usize {
+	if len == 0 { 2 } else { 1 + (len * 8).div_ceil(7) }
+}
+
+pub fn encode_blob_var(out: &mut Vec<u8>, data: &[u8], order: Order) {
+	let start = out.len();
+	out.reserve(blob_var_encoded_len(data.len()));
+	out.push(BLOB_VAR);
+	if data.is_empty() {
+		out.push(TERM);
+	} else {
+		// `acc` holds `bits` not yet written, right-aligned.
+		let (mut acc, mut bits) = (0u16, 0u32);
+		for &b in data {
+			acc = (acc << 8) | b as u16;
+			bits += 8;
+			while bits >= 7 {
+				bits -= 7;
+				out.push(0x80 | (acc >> bits) as u8 & 0x7f);
+			}
+		}
+		if bits > 0 {
+			out.push((acc << (7 - bits)) as u8 & 0x7f);
+		} else {
+			*out.last_mut().unwrap() &= 0x7f;
+		}
+	}
+	order.apply(&mut out[start..]);
+}
+
+pub fn decode_blob_var(buf: &[u8], order: Order) -> Result<(Vec<u8>, usize), DecodeError> {
+	let mut input = Input::new(buf, order);
+	input.header(BLOB_VAR)?;
+	let mut out = Vec::new();
+	if buf.get(1).map(|b| b ^ order.mask()) == Some(TERM) {
+		return Ok((out, 2));
+	}
+	let (mut acc, mut bits) = (0u16, 0u32);
+	loop {
+		let b = input.byte()?;
+		acc = (acc << 7) | (b & 0x7f) as u16;
+		bits += 7;
+		if bits >= 8 {
+			bits -= 8;
+			out.push((acc >> bits) as u8);
+		}
+		if b & 0x80 == 0 {
+			return Ok((out, input.pos));
+		}
+	}
+}
+
+/// Stores `data` verbatim. Descending keys need a terminator, so there
+/// `data` may not contain `0x00`; this panics if it does, as HBase would
+/// throw.
+pub fn encode_blob_copy(out: &mut Vec<u8>, data: &[u8], order: Order) {
+	let start = out.len();
+	out.push(BLOB_COPY);
+	out.extend_from_slice(data);
+	if order == Order::Descending {
+		assert!(!data.contains(&TERM), "descending orderedbytes blob-copy cannot contain 0x00");
+		out.push(TERM);
+	}
+	order.apply(&mut out[start..]);
+}
+
+/// Decodes a blob-copy. Ascending ones run to the end of `buf`.
+pub fn decode_blob_copy(buf: &[u8], order: Order) -> Result<(Vec<u8>, usize), DecodeError> {
+	let mut input = Input::new(buf, order);
+	input.header(BLOB_COPY)?;
+	match order {
+		Order::Ascending => Ok((buf[1..].to_vec(), buf.len())),
+		Order::Descending => {
+			let mut out = Vec::new();
+			loop {
+				match input.byte()? {
+					TERM => return Ok((out, input.pos)),
+					b => out.push(b),
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cmp::Ordering;
+
+	fn enc<T>(f: fn(&mut Vec<u8>, T, Order), v: T, order: Order) -> Vec<u8> {
+		let mut out = Vec::new();
+		f(&mut out, v, order);
+		out
+	}
+
+	#[quickcheck]
+	fn numeric_int_order(x: i64, y: i64, shift: u8) -> bool {
+		let x = x >> (shift % 64);
+		let (a, b) = (enc(encode_numeric_i64, x, Order::Ascending), enc(encode_numeric_i64, y, Order::Ascending));
+		let (da, db) = (enc(encode_numeric_i64, x, Order::Descending), enc(encode_numeric_i64, y, Order::Descending));
+		x.cmp(&y) == a.cmp(&b) && y.cmp(&x) == da.cmp(&db) &&
+			decode_numeric_i64(&a, Order::Ascending) == Ok((x, a.len())) &&
+			decode_numeric_i64(&da, Order::Descending) == Ok((x, da.len())) &&
+			decode_numeric_f64(&a, Order::Ascending).unwrap().0 == x as f64
+	}
+
+	#[quickcheck]
+	fn numeric_float_order(x: f64, y: f64, scale: i16) -> bool {
+		let x = x * 10f64.powi(scale as i32 % 300);
+		let (a, b) = (enc(encode_numeric_f64, x, Order::Ascending), enc(encode_numeric_f64, y, Order::Ascending));
+		let ordered = match x.partial_cmp(&y) {
+			Some(Ordering::Equal) | None => true,
+			Some(o) => o == a.cmp(&b),
+		};
+		let back = decode_numeric_f64(&a, Order::Ascending).unwrap();
+		ordered && (back.0 == x || x.is_nan() && back.0.is_nan()) && back.1 == a.len()
+	}
+
+	#[quickcheck]
+	fn fixed_width_order(x: i64, y: i64, f: f64, g: f64) -> bool {
+		let (a, b) = (enc(encode_int64, x, Order::Ascending), enc(encode_int64, y, Order::Ascending));
+		let (fa, fb) = (enc(encode_float64, f, Order::Descending), enc(encode_float64, g, Order::Descending));
+		let floats = match f.partial_cmp(&g) {
+			Some(Ordering::Equal) | None => true,
+			Some(o) => o.reverse() == fa.cmp(&fb),
+		};
+		let small = enc(encode_int16, x as i16, Order::Descending);
+		x.cmp(&y) == a.cmp(&b) && floats &&
+			decode_int64(&a, Order::Ascending) == Ok((x, 9)) &&
+			decode_int16(&small, Order::Descending) == Ok((x as i16, 3)) &&
+			decode_float64(&fa, Order::Descending).unwrap().0.to_bits() == f.to_bits()
+	}
+
+	#[quickcheck]
+	fn blob_and_text_round_trip(x: Vec<u8>, s: String, t: String) -> bool {
+		let a = enc(encode_blob_var, &x[..], Order::Ascending);
+		let da = enc(encode_blob_var, &x[..], Order::Descending);
+		let (s, t) = (s.replace('\0', ""), t.replace('\0', ""));
+		let (es, et) = (enc(encode_str, &s[..], Order::Descending), enc(encode_str, &t[..], Order::Descending));
+		t.cmp(&s) == es.cmp(&et) && a.len() == blob_var_encoded_len(x.len()) &&
+			decode_blob_var(&a, Order::Ascending) == Ok((x.clone(), a.len())) &&
+			decode_blob_var(&da, Order::Descending) == Ok((x, da.len())) &&
+			decode_str(&es, Order::Descending) == Ok((s, es.len()))
+	}
+
+	#[test]
+	fn matches_hbase_layout() {
+		let asc = Order::Ascending;
+		assert_eq!(enc(encode_numeric_i64, 0, asc), vec![0x15]);
+		assert_eq!(enc(encode_numeric_i64, 1, asc), vec![0x18, 0x02]);
+		assert_eq!(enc(encode_numeric_i64, 100, asc), vec![0x19, 0x02]);
+		assert_eq!(enc(encode_numeric_i64, 12345, asc), vec![0x1a, 0x03, 0x2f, 0x5a]);
+		assert_eq!(enc(encode_numeric_i64, -1, asc), vec![0x12, 0xfd]);
+		assert_eq!(enc(encode_numeric_f64, 0.5, asc), vec![0x16, 0xff, 0x64]);
+		assert_eq!(enc(encode_numeric_f64, 1e30, asc), vec![0x22, 16, 0x02]);
+		assert_eq!(enc(encode_numeric_f64, f64::NAN, Order::Descending), vec![!0x25]);
+		assert_eq!(enc(encode_int32, 1, asc), vec![0x2b, 0x80, 0, 0, 1]);
+		assert_eq!(enc(encode_str, "a", asc), vec![0x34, 0x61, 0x00]);
+		assert_eq!(enc(encode_str, "a", Order::Descending), vec![0xcb, 0x9e, 0xff]);
+		assert_eq!(enc(encode_blob_var, &[0xffu8][..], asc), vec![0x37, 0xff, 0x40]);
+		assert_eq!(enc(encode_blob_var, &b""[..], asc), vec![0x37, 0x00]);
+		assert_eq!(enc(encode_blob_copy, &[0x00u8, 0x01][..], asc), vec![0x38, 0x00, 0x01]);
+		assert_eq!(enc(encode_blob_copy, &[0x01u8][..], Order::Descending), vec![0xc7, 0xfe, 0xff]);
+
+		let mut null = Vec::new();
+		encode_null(&mut null, asc);
+		assert!(is_null(&null, asc) && !is_null(&null, Order::Descending));
+		assert_eq!(kind_of(&null), Ok((Kind::Null, asc)));
+		assert_eq!(kind_of(&enc(encode_numeric_i64, -1, Order::Descending)), Ok((Kind::Numeric, Order::Descending)));
+		assert_eq!(kind_of(&enc(encode_float32, 1.0, asc)), Ok((Kind::Float32, asc)));
+		assert_eq!(kind_of(&[0x40]), Err(DecodeError::InvalidMarker(0x40)));
+	}
+
+	#[test]
+	fn sqlite4_varint_boundaries() {
+		for &v in &[0u64, 240, 241, 2287, 2288, 67823, 67824, 1 << 24, 1 << 40, u64::MAX] {
+			for &complement in &[false, true] {
+				let mut out = Vec::new();
+				put_varuint(&mut out, v, complement);
+				let mut input = Input::new(&out, Order::Ascending);
+				assert_eq!(get_varuint(&mut input, complement), Ok(v));
+				assert_eq!(input.pos, out.len());
+			}
+		}
+	}
+
+	#[test]
+	fn rejects_mismatched_input() {
+		let asc = Order::Ascending;
+		assert_eq!(decode_int64(&enc(encode_int32, 1, asc), asc), Err(DecodeError::InvalidMarker(0x2b)));
+		assert_eq!(decode_numeric_i64(&enc(encode_numeric_f64, 0.5, asc), asc),
+			Err(DecodeError::Malformed("orderedbytes numeric is not an integer")));
+		assert_eq!(decode_numeric_i64(&enc(encode_numeric_f64, 1e30, asc), asc), Err(DecodeError::Overflow));
+		assert_eq!(decode_str(&[0x34, 0x61], asc), Err(DecodeError::Truncated { needed: 3 }));
+	}
+
+	#[test]
+	fn rejects_out_of_range_exponents() {
+		let asc = Order::Ascending;
+		// exponents of 2^63, which do not fit an i64 on either side of zero
+		for buf in &[[0x14, 0xff, 0x80, 0, 0, 0, 0, 0, 0, 0, 0xfd], [0x22, 0xff, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x02],
+			[0x16, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02], [0x08, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfd]]
+		{
+			assert_eq!(decode_numeric_i64(buf, asc), Err(DecodeError::Overflow));
+			assert_eq!(decode_numeric_f64(buf, asc), Err(DecodeError::Overflow));
+		}
+		// 2^62 fits, but its power of 10 does not
+		let buf = [0x22, 0xff, 0x40, 0, 0, 0, 0, 0, 0, 0, 0x02];
+		assert_eq!(decode_numeric_f64(&buf, asc), Err(DecodeError::Overflow));
+		assert_eq!(decode_numeric_f64(&[0x22, 0xfa, 0x01, 0x00, 0x00, 0x02], asc), Ok((f64::INFINITY, 6)));
+	}
+}
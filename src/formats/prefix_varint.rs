@@ -0,0 +1,100 @@
+//! PrefixVarint: a varint whose whole length prefix sits in the first byte.
+//!
+//! The value is stored little-endian, shifted left by the encoded length
+//! `n`, with bit `n - 1` set, so the number of trailing zero bits in the
+//! first byte is `n - 1`. Lengths 1 to 8 hold 7 bits per byte; a first
+//! byte of `0x00` announces all eight bytes of the value after it. As in
+//! UTF-8, a decoder learns the length from the first byte alone instead of
+//! testing a continuation bit in every byte. Signed values are ZigZag
+//! mapped first.
+
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::DecodeError;
+
+/// PrefixVarint as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PrefixVarint;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 9;
+
+/// Encoded length of `v`, including the first byte.
+pub fn encoded_len_u64(v: u64) -> usize {
+	let bits = 64 - (v | 1).leading_zeros() as usize;
+	if bits > 56 { MAX_LEN } else { bits.div_ceil(7) }
+}
+
+pub fn encoded_len_i64(v: i64) -> usize {
+	encoded_len_u64(zigzag_encode(v))
+}
+
+/// Total encoded length announced by a first byte.
+pub fn len_from_first_byte(first: u8) -> usize {
+	if first == 0 { MAX_LEN } else { first.trailing_zeros() as usize + 1 }
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	let len = encoded_len_u64(v);
+	if len == MAX_LEN {
+		out.push(0);
+		out.extend_from_slice(&v.to_le_bytes());
+	} else {
+		let word = (v << len) | (1 << (len - 1));
+		out.extend_from_slice(&word.to_le_bytes()[..len]);
+	}
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, zigzag_encode(v));
+}
+
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = len_from_first_byte(first);
+	if buf.len() < len {
+		return Err(DecodeError::Truncated { needed: len });
+	}
+	let mut word = [0u8; 8];
+	if len == MAX_LEN {
+		word.copy_from_slice(&buf[1..MAX_LEN]);
+		return Ok((u64::from_le_bytes(word), MAX_LEN));
+	}
+	word[..len].copy_from_slice(&buf[..len]);
+	Ok((u64::from_le_bytes(word) >> len, len))
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (zigzag_decode(v), len))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, y: i64) -> bool {
+		let x = x.wrapping_shl(shift as u32 % 64);
+		let b = enc(x);
+		let mut s = Vec::new();
+		encode_i64(&mut s, y);
+		b.len() == encoded_len_u64(x) && len_from_first_byte(b[0]) == b.len() &&
+			decode_u64(&b) == Ok((x, b.len())) && decode_i64(&s) == Ok((y, s.len()))
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(enc(0), vec![0x01]);
+		assert_eq!(enc(127), vec![0xff]);
+		assert_eq!(enc(128), vec![0x02, 0x02]);
+		assert_eq!(enc(300), vec![0xb2, 0x04]);
+		assert_eq!(enc((1 << 56) - 1), vec![0x80, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+		assert_eq!(enc(1 << 56), vec![0x00, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+		assert_eq!(decode_u64(&[0x04, 0x00]), Err(DecodeError::Truncated { needed: 3 }));
+	}
+}
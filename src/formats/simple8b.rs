@@ -0,0 +1,150 @@
+//! Simple-8b (Anh and Moffat, "Index compression using 64-bit words",
+//! 2010), which bit-packs as many values as fit into each 64-bit word.
+//!
+//! The top 4 bits of a word select how the other 60 are split: from 60
+//! one-bit values down to a single 60-bit value. Selectors 0 and 1 stand
+//! for runs of 240 and 120 ones, the common gap in dense posting lists and
+//! regular timestamps. Values are packed from the least significant bits
+//! and words are stored little-endian. Values of 2^60 and above cannot be
+//! represented.
+//!
+//! ```
+//! use rust_varint::formats::simple8b::Simple8b;
+//! use rust_varint::formats::leb128::Leb128;
+//! use rust_varint::formats::BlockCodec;
+//!
+//! let gaps: Vec<u64> = (0..1000).map(|i| i % 5).collect();
+//! let (mut packed, mut bytewise) = (Vec::new(), Vec::new());
+//! Simple8b.encode_u64s(&mut packed, &gaps);
+//! Leb128.encode_u64s(&mut bytewise, &gaps);
+//! assert_eq!((packed.len(), bytewise.len()), (400, 1000));
+//!
+//! let mut out = Vec::new();
+//! assert_eq!(Simple8b.decode_u64s(&packed, gaps.len(), &mut out), Ok(packed.len()));
+//! assert_eq!(out, gaps);
+//! ```
+
+use super::BlockCodec;
+use crate::DecodeError;
+
+/// Simple-8b as a `BlockCodec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Simple8b;
+
+/// Largest value a word can hold.
+pub const MAX_VALUE: u64 = (1 << 60) - 1;
+
+// (values, bits per value) for each selector
+const SELECTORS: [(usize, u32); 16] = [
+	(240, 0), (120, 0), (60, 1), (30, 2), (20, 3), (15, 4), (12, 5), (10, 6),
+	(8, 7), (7, 8), (6, 10), (5, 12), (4, 15), (3, 20), (2, 30), (1, 60),
+];
+
+/// Appends `values` as words. Panics if a value is over `MAX_VALUE`.
+pub fn encode(out: &mut Vec<u8>, values: &[u64]) {
+	let mut rest = values;
+	while !rest.is_empty() {
+		// the selector packing the most of the values left
+		let (selector, n, bits) = SELECTORS.iter().enumerate().find_map(|(s, &(n, bits))| {
+			let fits = rest.len() >= n && rest[..n].iter().all(|&v| match bits {
+				0 => v == 1,
+				_ => v >> bits == 0,
+			});
+			if fits { Some((s as u64, n, bits)) } else { None }
+		}).unwrap_or_else(|| panic!("Simple-8b values must be below 2^60, got {}", rest[0]));
+		let mut word = selector << 60;
+		if bits > 0 {
+			for (i, &v) in rest[..n].iter().enumerate() {
+				word |= v << (i as u32 * bits);
+			}
+		}
+		out.extend_from_slice(&word.to_le_bytes());
+		rest = &rest[n..];
+	}
+}
+
+/// Decodes `count` values from the front of `buf`, appending them to
+/// `out`, and returns the number of bytes they occupied.
+pub fn decode(buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+	// no word holds more than the 240 of selector 0, so a bogus count
+	// cannot reserve more than the buffer could hold
+	out.reserve(count.min(buf.len() / 8 * SELECTORS[0].0));
+	let (mut pos, mut left) = (0, count);
+	while left > 0 {
+		let bytes = buf.get(pos..pos + 8).ok_or(DecodeError::Truncated { needed: pos + 8 })?;
+		let mut word = [0; 8];
+		word.copy_from_slice(bytes);
+		let word = u64::from_le_bytes(word);
+		let (n, bits) = SELECTORS[(word >> 60) as usize];
+		if n > left {
+			return Err(DecodeError::Malformed("Simple-8b word holds more values than were asked for"));
+		}
+		if bits == 0 {
+			out.extend(std::iter::repeat_n(1, n));
+		} else {
+			let mask = (1 << bits) - 1;
+			out.extend((0..n as u32).map(|i| word >> (i * bits) & mask));
+		}
+		pos += 8;
+		left -= n;
+	}
+	Ok(pos)
+}
+
+impl BlockCodec for Simple8b {
+	fn encode_u64s(&self, out: &mut Vec<u8>, values: &[u64]) {
+		encode(out, values);
+	}
+
+	fn decode_u64s(&self, buf: &[u8], count: usize, out: &mut Vec<u64>) -> Result<usize, DecodeError> {
+		decode(buf, count, out)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::formats::{leb128::Leb128, Format};
+
+	#[quickcheck]
+	fn round_trips_through_every_codec(values: Vec<(u64, u8)>, tail: Vec<u8>) -> bool {
+		let values: Vec<u64> = values.into_iter().map(|(v, s)| (v >> 4) >> (s % 64)).collect();
+		let codecs: [&dyn BlockCodec; 3] = [&Simple8b, &Leb128, &Format::Ordered];
+		codecs.iter().all(|codec| {
+			let mut buf = Vec::new();
+			codec.encode_u64s(&mut buf, &values);
+			let len = buf.len();
+			buf.extend_from_slice(&tail);
+			let mut out = Vec::new();
+			codec.decode_u64s(&buf, values.len(), &mut out) == Ok(len) && out == values
+		})
+	}
+
+	#[test]
+	fn picks_the_densest_selector() {
+		let mut out = Vec::new();
+		encode(&mut out, &[1; 360]);
+		assert_eq!(out, [[0; 8], 0x1000_0000_0000_0000u64.to_le_bytes()].concat());
+		out.clear();
+		encode(&mut out, &[3, 0, 2, MAX_VALUE]);
+		assert_eq!(out, [0xd000_0200_0000_0003u64.to_le_bytes(), (0xf000_0000_0000_0000 | MAX_VALUE).to_le_bytes()].concat());
+	}
+
+	#[test]
+	fn rejects_short_and_overfull_input() {
+		let mut buf = Vec::new();
+		encode(&mut buf, &[7; 20]);
+		let mut out = Vec::new();
+		assert_eq!(decode(&buf[..7], 20, &mut out), Err(DecodeError::Truncated { needed: 8 }));
+		assert_eq!(decode(&buf, 21, &mut out), Err(DecodeError::Truncated { needed: 16 }));
+		assert_eq!(decode(&buf, 19, &mut out), Err(DecodeError::Malformed("Simple-8b word holds more values than were asked for")));
+		assert_eq!(decode(&[], usize::MAX, &mut out), Err(DecodeError::Truncated { needed: 8 }));
+		assert_eq!(decode(&buf, usize::MAX, &mut out), Err(DecodeError::Truncated { needed: 16 }));
+	}
+
+	#[test]
+	#[should_panic(expected = "below 2^60")]
+	fn rejects_wide_values() {
+		encode(&mut Vec::new(), &[1 << 60]);
+	}
+}
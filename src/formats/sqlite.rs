@@ -0,0 +1,181 @@
+//! The varint of the SQLite 3 database file format, used in its b-tree
+//! pages, record headers and WAL.
+//!
+//! Values are big-endian, 7 bits a byte with the high bit set on every
+//! byte but the last, except that a ninth byte carries 8 bits, so any
+//! value fits in 9 bytes. Signed values are their two's complement bits,
+//! as SQLite stores rowids, so every negative value takes 9 bytes. This
+//! is a different encoding from the SQLite 4 varint in `orderedbytes`.
+//!
+//! ```
+//! use rust_varint::formats::sqlite;
+//!
+//! let mut out = Vec::new();
+//! sqlite::encode_u64(&mut out, 240);
+//! assert_eq!(out, [0x81, 0x70]);
+//! assert_eq!(sqlite::decode_u64(&out), Ok((240, 2)));
+//! ```
+
+use std::io::{self, Read, Write};
+
+use crate::{at_offset, DecodeError};
+
+/// The SQLite varint as a `VarintFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Sqlite;
+
+/// Largest encoding of a 64-bit value.
+pub const MAX_LEN: usize = 9;
+
+/// Encoded length of `v`.
+pub fn encoded_len_u64(v: u64) -> usize {
+	if v >> 56 != 0 {
+		MAX_LEN
+	} else {
+		(64 - (v | 1).leading_zeros() as usize).div_ceil(7)
+	}
+}
+
+pub fn encoded_len_i64(v: i64) -> usize {
+	encoded_len_u64(v as u64)
+}
+
+pub fn encode_u64(out: &mut Vec<u8>, v: u64) {
+	if v >> 56 != 0 {
+		// eight 7-bit groups of the high 56 bits, then the low byte whole
+		out.extend((1..=8).rev().map(|i| (v >> (1 + 7 * i)) as u8 | 0x80));
+		out.push(v as u8);
+		return;
+	}
+	let n = encoded_len_u64(v);
+	out.extend((1..n).rev().map(|i| (v >> (7 * i)) as u8 | 0x80));
+	out.push(v as u8 & 0x7f);
+}
+
+pub fn encode_i64(out: &mut Vec<u8>, v: i64) {
+	encode_u64(out, v as u64);
+}
+
+/// Decodes one value. Every sequence of bytes is some value, so the only
+/// error is running out of them; leading `0x80` bytes are accepted, as
+/// SQLite does.
+pub fn decode_u64(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let mut v = 0u64;
+	for (i, &b) in buf.iter().enumerate().take(MAX_LEN) {
+		if i == MAX_LEN - 1 {
+			return Ok((v << 8 | b as u64, MAX_LEN));
+		}
+		v = v << 7 | (b & 0x7f) as u64;
+		if b & 0x80 == 0 {
+			return Ok((v, i + 1));
+		}
+	}
+	Err(DecodeError::Truncated { needed: buf.len() + 1 })
+}
+
+pub fn decode_i64(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_u64(buf).map(|(v, len)| (v as i64, len))
+}
+
+/// Decodes the value at `*pos` and moves `pos` past it. `Truncated`
+/// counts from the start of `buf`, and `pos` is left alone on error.
+pub fn decode_u64_at(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+	let (v, used) = decode_u64(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(v)
+}
+
+/// Signed counterpart of `decode_u64_at`.
+pub fn decode_i64_at(buf: &[u8], pos: &mut usize) -> Result<i64, DecodeError> {
+	decode_u64_at(buf, pos).map(|v| v as i64)
+}
+
+/// Reads one value a byte at a time, never past its end.
+pub fn read_u64<R: Read + ?Sized>(r: &mut R) -> io::Result<u64> {
+	let mut buf = [0; MAX_LEN];
+	for n in 0..MAX_LEN {
+		r.read_exact(&mut buf[n..n + 1])?;
+		if buf[n] & 0x80 == 0 {
+			break;
+		}
+	}
+	decode_u64(&buf).map(|(v, _)| v).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn read_i64<R: Read + ?Sized>(r: &mut R) -> io::Result<i64> {
+	read_u64(r).map(|v| v as i64)
+}
+
+pub fn write_u64<W: Write + ?Sized>(w: &mut W, v: u64) -> io::Result<()> {
+	let mut out = Vec::with_capacity(MAX_LEN);
+	encode_u64(&mut out, v);
+	w.write_all(&out)
+}
+
+pub fn write_i64<W: Write + ?Sized>(w: &mut W, v: i64) -> io::Result<()> {
+	write_u64(w, v as u64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(v: u64) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode_u64(&mut out, v);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(x: u64, shift: u8, tail: Vec<u8>) -> bool {
+		let x = x >> (shift % 64);
+		let mut b = enc(x);
+		let len = b.len();
+		b.extend_from_slice(&tail);
+		let mut rd = &b[..];
+		len == encoded_len_u64(x) && decode_u64(&b) == Ok((x, len)) &&
+			read_u64(&mut rd).unwrap() == x && rd.len() == tail.len()
+	}
+
+	#[test]
+	fn every_length_boundary() {
+		for n in 1..8 {
+			let max = (1u64 << (7 * n)) - 1;
+			assert_eq!(enc(max), [vec![0xff; n as usize - 1], vec![0x7f]].concat());
+			assert_eq!(enc(max + 1), [vec![0x81], vec![0x80; n as usize - 1], vec![0x00]].concat());
+		}
+		assert_eq!(enc((1 << 56) - 1), [vec![0xff; 7], vec![0x7f]].concat());
+		assert_eq!(enc(1 << 56), [0x80, 0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]);
+		assert_eq!(enc(u64::MAX), [0xff; 9]);
+		for n in 1..=64 {
+			let v = u64::MAX >> (64 - n);
+			assert_eq!(decode_u64(&enc(v)), Ok((v, encoded_len_u64(v))));
+		}
+	}
+
+	#[test]
+	fn matches_sqlite_patterns() {
+		assert_eq!(enc(0), [0x00]);
+		assert_eq!(enc(0x4000), [0x81, 0x80, 0x00]);
+		let mut out = Vec::new();
+		encode_i64(&mut out, -1);
+		assert_eq!(out, [0xff; 9]);
+		encode_i64(&mut out, i64::MIN);
+		assert_eq!(out[9..], [0xc0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]);
+		let mut pos = 0;
+		assert_eq!((decode_i64_at(&out, &mut pos), decode_i64_at(&out, &mut pos)), (Ok(-1), Ok(i64::MIN)));
+		// the ninth byte keeps its high bit as data
+		assert_eq!(decode_u64(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xff, 0x01]), Ok((0xff, 9)));
+		assert_eq!(decode_u64(&[0x80, 0x05]), Ok((5, 2)));
+	}
+
+	#[test]
+	fn rejects_short_input() {
+		assert_eq!(decode_u64(&[]), Err(DecodeError::Truncated { needed: 1 }));
+		assert_eq!(decode_u64(&[0xff; 8]), Err(DecodeError::Truncated { needed: 9 }));
+		let mut pos = 2;
+		assert_eq!(decode_u64_at(&[0x01, 0x02, 0x81], &mut pos), Err(DecodeError::Truncated { needed: 4 }));
+		assert_eq!(pos, 2);
+		assert_eq!(read_u64(&mut &[0xff; 5][..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+	}
+}
@@ -0,0 +1,221 @@
+//! Stream VByte for `u32`s (Lemire, Kurz and Rupp, "Stream VByte: Faster
+//! Byte-Oriented Integer Compression", 2017).
+//!
+//! The control bits of group varint are moved out of the data into a
+//! stream of their own: first one 2-bit length per value, four to a
+//! control byte and least significant bits first, then every value
+//! little-endian in 1 to 4 bytes. Decoding needs the number of values,
+//! which fixes where the control stream ends.
+//!
+//! With the `simd` feature, decoding shuffles four values at a time into
+//! place, with SSSE3 on x86-64 when the CPU supports it and NEON on
+//! AArch64.
+
+use crate::DecodeError;
+
+// The shuffle behind each control byte, for the SIMD decoders.
+#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod shuffle {
+	#[derive(Clone, Copy)]
+	pub(super) struct Entry {
+		// byte of the data each output byte comes from, 0x80 for a zero
+		pub(super) shuffle: [u8; 16],
+		pub(super) len: u8,
+	}
+
+	const fn build_table() -> [Entry; 256] {
+		let mut table = [Entry { shuffle: [0x80; 16], len: 0 }; 256];
+		let mut control = 0;
+		while control < 256 {
+			let entry = &mut table[control];
+			let mut i = 0;
+			while i < 4 {
+				let n = ((control >> (2 * i)) & 3) as u8 + 1;
+				let mut j = 0;
+				while j < n {
+					entry.shuffle[4 * i + j as usize] = entry.len + j;
+					j += 1;
+				}
+				entry.len += n;
+				i += 1;
+			}
+			control += 1;
+		}
+		table
+	}
+
+	pub(super) static TABLE: [Entry; 256] = build_table();
+}
+
+fn byte_len(v: u32) -> usize {
+	(32 - (v | 1).leading_zeros() as usize).div_ceil(8)
+}
+
+/// Length of the control stream for `count` values.
+pub fn control_len(count: usize) -> usize {
+	count.div_ceil(4)
+}
+
+/// Appends `values` as a control stream followed by a data stream.
+pub fn encode(out: &mut Vec<u8>, values: &[u32]) {
+	let control = out.len();
+	out.resize(control + control_len(values.len()), 0);
+	for (i, &v) in values.iter().enumerate() {
+		let n = byte_len(v);
+		out[control + i / 4] |= ((n - 1) << (2 * (i % 4))) as u8;
+		out.extend_from_slice(&v.to_le_bytes()[..n]);
+	}
+}
+
+/// Decodes `count` values from the front of `buf`, appending them to
+/// `out`, and returns the number of bytes they occupied.
+pub fn decode(buf: &[u8], count: usize, out: &mut Vec<u32>) -> Result<usize, DecodeError> {
+	let controls = control_len(count);
+	if buf.len() < controls {
+		return Err(DecodeError::Truncated { needed: controls });
+	}
+	out.reserve(count);
+	let (control, data) = buf.split_at(controls);
+	#[allow(unused_mut)]
+	let (mut groups, mut pos) = (0, 0);
+	#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+	#[allow(unsafe_code)]
+	{
+		if is_x86_feature_detected!("ssse3") {
+			// SAFETY: SSSE3 support was just checked.
+			(groups, pos) = unsafe { simd::decode_groups(&control[..count / 4], data, out) };
+		}
+	}
+	#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+	#[allow(unsafe_code)]
+	{
+		if std::arch::is_aarch64_feature_detected!("neon") {
+			// SAFETY: NEON support was just checked.
+			(groups, pos) = unsafe { neon::decode_groups(&control[..count / 4], data, out) };
+		}
+	}
+	let end = decode_scalar(control, data, groups * 4, count, pos, out).map_err(|e| match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: controls + needed },
+		e => e,
+	})?;
+	Ok(controls + end)
+}
+
+// Decodes values `from..count` starting at byte `pos` of the data stream,
+// returning where they end.
+fn decode_scalar(control: &[u8], data: &[u8], from: usize, count: usize, mut pos: usize, out: &mut Vec<u32>)
+	-> Result<usize, DecodeError> {
+	for i in from..count {
+		let n = ((control[i / 4] >> (2 * (i % 4))) & 3) as usize + 1;
+		let bytes = data.get(pos..pos + n).ok_or(DecodeError::Truncated { needed: pos + n })?;
+		let mut word = [0; 4];
+		word[..n].copy_from_slice(bytes);
+		out.push(u32::from_le_bytes(word));
+		pos += n;
+	}
+	Ok(pos)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[allow(unsafe_code)]
+mod simd {
+	use std::arch::x86_64::*;
+
+	use super::shuffle::TABLE;
+
+	/// Decodes the groups of four behind `control` while 16 bytes of data
+	/// can be loaded, returning how many groups and data bytes were used.
+	#[target_feature(enable = "ssse3")]
+	pub(super) unsafe fn decode_groups(control: &[u8], data: &[u8], out: &mut Vec<u32>) -> (usize, usize) {
+		let (mut groups, mut pos) = (0, 0);
+		let mut values = [0u32; 4];
+		for &c in control {
+			if pos + 16 > data.len() {
+				break;
+			}
+			let entry = &TABLE[c as usize];
+			let bytes = _mm_loadu_si128(data.as_ptr().add(pos) as *const __m128i);
+			let mask = _mm_loadu_si128(entry.shuffle.as_ptr() as *const __m128i);
+			_mm_storeu_si128(values.as_mut_ptr() as *mut __m128i, _mm_shuffle_epi8(bytes, mask));
+			out.extend_from_slice(&values);
+			groups += 1;
+			pos += entry.len as usize;
+		}
+		(groups, pos)
+	}
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+#[allow(unsafe_code)]
+mod neon {
+	use std::arch::aarch64::*;
+
+	use super::shuffle::TABLE;
+
+	/// The NEON counterpart of the SSSE3 decoder; table lookups past the
+	/// 16 loaded bytes give zero, as `pshufb` does for 0x80.
+	#[target_feature(enable = "neon")]
+	pub(super) unsafe fn decode_groups(control: &[u8], data: &[u8], out: &mut Vec<u32>) -> (usize, usize) {
+		let (mut groups, mut pos) = (0, 0);
+		let mut values = [0u32; 4];
+		for &c in control {
+			if pos + 16 > data.len() {
+				break;
+			}
+			let entry = &TABLE[c as usize];
+			let bytes = vld1q_u8(data.as_ptr().add(pos));
+			let shuffled = vqtbl1q_u8(bytes, vld1q_u8(entry.shuffle.as_ptr()));
+			vst1q_u8(values.as_mut_ptr() as *mut u8, shuffled);
+			out.extend_from_slice(&values);
+			groups += 1;
+			pos += entry.len as usize;
+		}
+		(groups, pos)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn enc(values: &[u32]) -> Vec<u8> {
+		let mut out = Vec::new();
+		encode(&mut out, values);
+		out
+	}
+
+	#[quickcheck]
+	fn round_trip(values: Vec<(u32, u8)>, tail: Vec<u8>) -> bool {
+		let values: Vec<u32> = values.into_iter().map(|(v, s)| v >> (s % 32)).collect();
+		let mut buf = enc(&values);
+		let len = buf.len();
+		buf.extend_from_slice(&tail);
+		let (mut out, mut scalar) = (Vec::new(), Vec::new());
+		let controls = control_len(values.len());
+		decode(&buf, values.len(), &mut out) == Ok(len) && out == values &&
+			decode_scalar(&buf[..controls], &buf[controls..], 0, values.len(), 0, &mut scalar) == Ok(len - controls) &&
+			scalar == values
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(enc(&[1, 0x1234, 0x0f_ffff, u32::MAX, 7]),
+			vec![0b11_10_01_00, 0b00, 0x01, 0x34, 0x12, 0xff, 0xff, 0x0f, 0xff, 0xff, 0xff, 0xff, 0x07]);
+		#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+		{
+			use super::shuffle::TABLE;
+			assert_eq!(TABLE[0b11_10_01_00].shuffle[..8], [0, 0x80, 0x80, 0x80, 1, 2, 0x80, 0x80]);
+			assert_eq!(TABLE[0xff].len, 16);
+		}
+	}
+
+	#[test]
+	fn rejects_truncated_streams() {
+		let values: Vec<u32> = (0..40).map(|i| 1 << (i % 32)).collect();
+		let buf = enc(&values);
+		let mut out = Vec::new();
+		assert_eq!(decode(&buf[..buf.len() - 1], 40, &mut out), Err(DecodeError::Truncated { needed: buf.len() }));
+		assert_eq!(decode(&buf[..5], 40, &mut out), Err(DecodeError::Truncated { needed: 10 }));
+		assert!(decode(&[], 0, &mut out) == Ok(0));
+	}
+}
@@ -0,0 +1,355 @@
+//! Reading and writing single values on `std::io` streams, in the style
+//! of the `byteorder` crate: the format is a type parameter.
+//!
+//! ```
+//! use rust_varint::formats::{leb128::Leb128, Ordered};
+//! use rust_varint::io::{ReadVarintExt, WriteVarintExt};
+//!
+//! let mut buf = Vec::new();
+//! buf.write_uvarint::<Ordered>(300).unwrap();
+//! buf.write_ivarint::<Leb128>(-2).unwrap();
+//!
+//! let mut rd = &buf[..];
+//! assert_eq!(rd.read_uvarint::<Ordered>().unwrap(), 300);
+//! assert_eq!(rd.read_ivarint::<Leb128>().unwrap(), -2);
+//! ```
+//!
+//! `read_varint_u64` and the like are shorthands for the crate's own
+//! encoding, which read the marker byte and then exactly the bytes it
+//! calls for.
+//!
+//! Reads never go past the end of the value, so the stream can be shared
+//! with other readers. They ask for the bytes a value still needs, which
+//! is several small reads for some formats; buffer slow streams.
+//!
+//! `encode_iter_to_writer` streams a whole sequence of values out, e.g.
+//! ids from a database cursor. The `tokio` feature adds an async
+//! counterpart, and `async_io` the shorthands for async streams.
+
+use std::io::{self, Read, Write};
+
+use crate::formats::VarintFormat;
+use crate::{
+	decode_int, decode_uint, int_len_from_marker, pack_int_into_array, pack_uint, pack_uint_into_array,
+	uint_len_from_marker, DecodeError, MAX_ENCODED_LEN,
+};
+
+/// Bytes buffered by `encode_iter_to_writer` between writes.
+pub const CHUNK_LEN: usize = 64 * 1024;
+
+// Reads one value, growing the buffer to what each truncated attempt says
+// it needs.
+fn read_value<R, T, D>(r: &mut R, max_len: usize, decode: D) -> io::Result<T>
+where
+	R: Read + ?Sized,
+	D: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+{
+	let mut buf = Vec::with_capacity(max_len);
+	loop {
+		match decode(&buf) {
+			Ok((v, _)) => return Ok(v),
+			Err(DecodeError::Truncated { needed }) if needed > buf.len() && needed <= max_len => {
+				let have = buf.len();
+				buf.resize(needed, 0);
+				r.read_exact(&mut buf[have..])?;
+			}
+			Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+		}
+	}
+}
+
+// Reads one value of the crate's own encoding: the marker byte, then the
+// rest of the length it gives.
+fn read_ordered<R, T, L, D>(r: &mut R, len_from_marker: L, decode: D) -> io::Result<T>
+where
+	R: Read + ?Sized,
+	L: Fn(u8) -> Result<usize, DecodeError>,
+	D: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+{
+	let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+	let mut buf = [0; MAX_ENCODED_LEN];
+	r.read_exact(&mut buf[..1])?;
+	let len = len_from_marker(buf[0]).map_err(invalid)?;
+	r.read_exact(&mut buf[1..len])?;
+	decode(&buf[..len]).map(|(v, _)| v).map_err(invalid)
+}
+
+/// Extends `Read` with varint reads.
+pub trait ReadVarintExt: Read {
+	fn read_uvarint<F: VarintFormat + Default>(&mut self) -> io::Result<u64> {
+		let f = F::default();
+		read_value(self, f.max_len(), |buf| f.decode_from(buf))
+	}
+
+	fn read_ivarint<F: VarintFormat + Default>(&mut self) -> io::Result<i64> {
+		let f = F::default();
+		read_value(self, f.max_len(), |buf| f.decode_i64_from(buf))
+	}
+
+	fn read_varint_u64(&mut self) -> io::Result<u64> {
+		read_ordered(self, uint_len_from_marker, decode_uint)
+	}
+
+	fn read_varint_i64(&mut self) -> io::Result<i64> {
+		read_ordered(self, int_len_from_marker, decode_int)
+	}
+}
+
+impl<R: Read + ?Sized> ReadVarintExt for R {}
+
+/// Extends `Write` with varint writes.
+pub trait WriteVarintExt: Write {
+	fn write_uvarint<F: VarintFormat + Default>(&mut self, v: u64) -> io::Result<()> {
+		let mut out = Vec::with_capacity(16);
+		F::default().encode_into(&mut out, v);
+		self.write_all(&out)
+	}
+
+	fn write_ivarint<F: VarintFormat + Default>(&mut self, v: i64) -> io::Result<()> {
+		let mut out = Vec::with_capacity(16);
+		F::default().encode_i64_into(&mut out, v);
+		self.write_all(&out)
+	}
+
+	fn write_varint_u64(&mut self, v: u64) -> io::Result<()> {
+		let mut out = [0; MAX_ENCODED_LEN];
+		let len = pack_uint_into_array(v, &mut out);
+		self.write_all(&out[..len])
+	}
+
+	fn write_varint_i64(&mut self, v: i64) -> io::Result<()> {
+		let mut out = [0; MAX_ENCODED_LEN];
+		let len = pack_int_into_array(v, &mut out);
+		self.write_all(&out[..len])
+	}
+}
+
+impl<W: Write + ?Sized> WriteVarintExt for W {}
+
+/// Packs each value of `iter` with `pack_uint` and writes them to `w`,
+/// holding at most about `CHUNK_LEN` bytes at a time. Returns the number
+/// of bytes written.
+pub fn encode_iter_to_writer<I, W>(iter: I, mut w: W) -> io::Result<u64>
+where
+	I: IntoIterator<Item = u64>,
+	W: Write,
+{
+	let mut chunk = Vec::with_capacity(CHUNK_LEN + 9);
+	let mut written = 0;
+	for v in iter {
+		chunk.extend_from_slice(&pack_uint(v));
+		if chunk.len() >= CHUNK_LEN {
+			w.write_all(&chunk)?;
+			written += chunk.len() as u64;
+			chunk.clear();
+		}
+	}
+	w.write_all(&chunk)?;
+	w.flush()?;
+	Ok(written + chunk.len() as u64)
+}
+
+/// Async counterpart of `encode_iter_to_writer`.
+#[cfg(feature = "tokio")]
+pub async fn encode_iter_to_async_writer<I, W>(iter: I, mut w: W) -> io::Result<u64>
+where
+	I: IntoIterator<Item = u64>,
+	W: tokio::io::AsyncWrite + Unpin,
+{
+	use tokio::io::AsyncWriteExt;
+
+	let mut chunk = Vec::with_capacity(CHUNK_LEN + 9);
+	let mut written = 0;
+	for v in iter {
+		chunk.extend_from_slice(&pack_uint(v));
+		if chunk.len() >= CHUNK_LEN {
+			w.write_all(&chunk).await?;
+			written += chunk.len() as u64;
+			chunk.clear();
+		}
+	}
+	w.write_all(&chunk).await?;
+	w.flush().await?;
+	Ok(written + chunk.len() as u64)
+}
+
+/// The `read_varint_*` and `write_varint_*` shorthands for tokio streams.
+/// They live apart from the blocking traits since types like `Vec<u8>`
+/// are both kinds of stream.
+#[cfg(feature = "tokio")]
+pub mod async_io {
+	use std::future::Future;
+	use std::io;
+
+	use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+	use crate::{
+		decode_int, decode_uint, int_len_from_marker, pack_int_into_array, pack_uint_into_array, uint_len_from_marker,
+		DecodeError, MAX_ENCODED_LEN,
+	};
+
+	// Async counterpart of `read_ordered`.
+	async fn read_ordered_async<R, T, L, D>(r: &mut R, len_from_marker: L, decode: D) -> io::Result<T>
+	where
+		R: AsyncRead + Unpin + ?Sized,
+		L: Fn(u8) -> Result<usize, DecodeError>,
+		D: Fn(&[u8]) -> Result<(T, usize), DecodeError>,
+	{
+		let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+		let mut buf = [0; MAX_ENCODED_LEN];
+		r.read_exact(&mut buf[..1]).await?;
+		let len = len_from_marker(buf[0]).map_err(invalid)?;
+		r.read_exact(&mut buf[1..len]).await?;
+		decode(&buf[..len]).map(|(v, _)| v).map_err(invalid)
+	}
+
+	/// Extends `tokio::io::AsyncRead` with reads of the crate's own encoding.
+	pub trait AsyncReadVarintExt: AsyncRead + Unpin {
+		fn read_varint_u64(&mut self) -> impl Future<Output = io::Result<u64>> + '_ {
+			read_ordered_async(self, uint_len_from_marker, decode_uint)
+		}
+
+		fn read_varint_i64(&mut self) -> impl Future<Output = io::Result<i64>> + '_ {
+			read_ordered_async(self, int_len_from_marker, decode_int)
+		}
+	}
+
+	impl<R: AsyncRead + Unpin + ?Sized> AsyncReadVarintExt for R {}
+
+	/// Extends `tokio::io::AsyncWrite` with writes of the crate's own encoding.
+	pub trait AsyncWriteVarintExt: AsyncWrite + Unpin {
+		fn write_varint_u64(&mut self, v: u64) -> impl Future<Output = io::Result<()>> + '_ {
+			let mut out = [0; MAX_ENCODED_LEN];
+			let len = pack_uint_into_array(v, &mut out);
+			async move { self.write_all(&out[..len]).await }
+		}
+
+		fn write_varint_i64(&mut self, v: i64) -> impl Future<Output = io::Result<()>> + '_ {
+			let mut out = [0; MAX_ENCODED_LEN];
+			let len = pack_int_into_array(v, &mut out);
+			async move { self.write_all(&out[..len]).await }
+		}
+	}
+
+	impl<W: AsyncWrite + Unpin + ?Sized> AsyncWriteVarintExt for W {}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn async_shorthands_round_trip() {
+			let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+			rt.block_on(async {
+				let mut buf = Vec::new();
+				for &(x, y) in &[(0, -1), (300, i64::MIN), (u64::MAX, 8000)] {
+					buf.write_varint_u64(x).await.unwrap();
+					buf.write_varint_i64(y).await.unwrap();
+				}
+				assert_eq!(buf[..3], [0x80, 0x7f, 0xc0]);
+				let mut rd = &buf[..];
+				for &(x, y) in &[(0, -1), (300, i64::MIN), (u64::MAX, 8000)] {
+					assert_eq!(rd.read_varint_u64().await.unwrap(), x);
+					assert_eq!(rd.read_varint_i64().await.unwrap(), y);
+				}
+				let err = (&[0xe4u8, 1][..]).read_varint_u64().await.unwrap_err();
+				assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+				let err = (&[0x00u8][..]).read_varint_i64().await.unwrap_err();
+				assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::formats::{cassandra::Cassandra, fast_le::FastLE, leb128::Leb128, prefix_varint::PrefixVarint, Ordered};
+
+	fn round_trip<F: VarintFormat + Default>(x: u64, y: i64) -> bool {
+		let mut buf = Vec::new();
+		buf.write_uvarint::<F>(x).unwrap();
+		buf.write_ivarint::<F>(y).unwrap();
+		buf.push(0x42);
+		let mut rd = &buf[..];
+		rd.read_uvarint::<F>().unwrap() == x && rd.read_ivarint::<F>().unwrap() == y && rd == [0x42]
+	}
+
+	// hands out one byte per read and counts the reads
+	struct Trickle<'a>(&'a [u8], usize);
+
+	impl Read for Trickle<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			self.1 += 1;
+			let n = self.0.len().min(buf.len()).min(1);
+			buf[..n].copy_from_slice(&self.0[..n]);
+			self.0 = &self.0[n..];
+			Ok(n)
+		}
+	}
+
+	#[quickcheck]
+	fn round_trips_ordered_shorthands(x: u64, y: i64) -> bool {
+		let mut buf = Vec::new();
+		buf.write_varint_u64(x).unwrap();
+		buf.write_varint_i64(y).unwrap();
+		let mut rd = Trickle(&buf, 0);
+		rd.read_varint_u64().unwrap() == x && rd.read_varint_i64().unwrap() == y && rd.1 == buf.len()
+	}
+
+	#[quickcheck]
+	fn round_trips_every_format(x: u64, y: i64) -> bool {
+		round_trip::<Ordered>(x, y) && round_trip::<Cassandra>(x, y) && round_trip::<PrefixVarint>(x, y) &&
+			round_trip::<Leb128>(x, y) && round_trip::<FastLE>(x, y)
+	}
+
+	// counts the writes it receives
+	struct Writes(Vec<u8>, usize);
+
+	impl Write for Writes {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.extend_from_slice(buf);
+			self.1 += 1;
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn streams_iterators_in_chunks() {
+		// more than one chunk even at the smaller size Miri runs
+		let n = if cfg!(miri) { 40_000 } else { 100_000u64 };
+		let want: Vec<u8> = (0..n).flat_map(pack_uint).collect();
+		let mut w = Writes(Vec::new(), 0);
+		assert!(want.len() > CHUNK_LEN);
+		assert_eq!(encode_iter_to_writer(0..n, &mut w).unwrap(), want.len() as u64);
+		assert_eq!(w.0, want);
+		assert_eq!(w.1, want.len().div_ceil(CHUNK_LEN));
+	}
+
+	#[cfg(feature = "tokio")]
+	#[test]
+	fn streams_to_async_writers() {
+		let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+		let mut out = Vec::new();
+		let n = rt.block_on(encode_iter_to_async_writer(vec![1, 500, u64::MAX], &mut out)).unwrap();
+		assert_eq!((n, out), (12, [pack_uint(1), pack_uint(500), pack_uint(u64::MAX)].concat()));
+	}
+
+	#[test]
+	fn reports_bad_streams() {
+		let err = (&[0xe4u8, 1][..]).read_uvarint::<Ordered>().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+		let err = (&[0x00u8][..]).read_uvarint::<Ordered>().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		let err = (&[][..]).read_ivarint::<Leb128>().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+		let err = (&[0x10u8, 0xff][..]).read_varint_i64().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+		let err = (&[0xf0u8, 0][..]).read_varint_u64().unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+	}
+}
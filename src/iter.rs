@@ -0,0 +1,105 @@
+//! Iterating over values packed back to back.
+//!
+//! ```
+//! use rust_varint::iter::decode_iter;
+//! use rust_varint::DecodeError;
+//!
+//! let buf = [0x81, 0xc1, 0x00, 0xe2];
+//! let mut values = decode_iter(&buf);
+//! assert_eq!(values.next(), Some(Ok(1)));
+//! assert_eq!(values.next(), Some(Ok(320)));
+//! assert_eq!(values.next(), Some(Err(DecodeError::Truncated { needed: 6 })));
+//! assert_eq!(values.next(), None);
+//! ```
+
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+use crate::varint::VarInt;
+use crate::DecodeError;
+
+/// Decodes consecutive `T` values from a slice.
+///
+/// Ends at the end of the slice, or after yielding the first error.
+/// `Truncated` counts from the start of the slice.
+#[derive(Debug, Clone)]
+pub struct VarintIter<'a, T = u64> {
+	buf: &'a [u8],
+	pos: usize,
+	failed: bool,
+	_type: PhantomData<T>,
+}
+
+impl<'a, T: VarInt> VarintIter<'a, T> {
+	pub fn new(buf: &'a [u8]) -> VarintIter<'a, T> {
+		VarintIter { buf, pos: 0, failed: false, _type: PhantomData }
+	}
+
+	/// Bytes consumed by the values yielded so far.
+	pub fn position(&self) -> usize {
+		self.pos
+	}
+}
+
+/// Iterates over the unsigned values packed in `buf`.
+pub fn decode_iter(buf: &[u8]) -> VarintIter<'_, u64> {
+	VarintIter::new(buf)
+}
+
+/// Iterates over the signed values packed in `buf`.
+pub fn decode_int_iter(buf: &[u8]) -> VarintIter<'_, i64> {
+	VarintIter::new(buf)
+}
+
+impl<T: VarInt> Iterator for VarintIter<'_, T> {
+	type Item = Result<T, DecodeError>;
+
+	fn next(&mut self) -> Option<Result<T, DecodeError>> {
+		if self.failed || self.pos == self.buf.len() {
+			return None;
+		}
+		match T::decode(&self.buf[self.pos..]) {
+			Ok((v, used)) => {
+				self.pos += used;
+				Some(Ok(v))
+			}
+			Err(e) => {
+				self.failed = true;
+				Some(Err(match e {
+					DecodeError::Truncated { needed } => DecodeError::Truncated { needed: self.pos + needed },
+					e => e,
+				}))
+			}
+		}
+	}
+}
+
+impl<T: VarInt> FusedIterator for VarintIter<'_, T> {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+	use crate::{pack_int, pack_uint};
+
+	#[quickcheck]
+	fn yields_every_value(xs: Vec<u64>, ys: Vec<i64>) -> bool {
+		let a: Vec<u8> = xs.iter().flat_map(|&x| pack_uint(x)).collect();
+		let b: Vec<u8> = ys.iter().flat_map(|&y| pack_int(y)).collect();
+		let mut it = decode_iter(&a);
+		let got: Result<Vec<u64>, _> = it.by_ref().collect();
+		got == Ok(xs) && it.position() == a.len() && decode_int_iter(&b).collect::<Result<Vec<_>, _>>() == Ok(ys)
+	}
+
+	#[test]
+	fn stops_after_an_error() {
+		let mut it = VarintIter::<u8>::new(&[0x81, 0xe2, 0x01, 0x00, 0x81]);
+		assert_eq!(it.next(), Some(Ok(1)));
+		assert_eq!(it.next(), Some(Err(DecodeError::Overflow)));
+		assert_eq!(it.next(), None);
+		let mut it = decode_int_iter(&[0x7f, 0x00, 0x7f]);
+		assert_eq!(it.next(), Some(Ok(-1)));
+		assert_eq!(it.next(), Some(Err(DecodeError::InvalidMarker(0x00))));
+		assert_eq!(it.position(), 1);
+		assert_eq!(it.next(), None);
+	}
+}
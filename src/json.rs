@@ -0,0 +1,164 @@
+//! Schema-less decoding into `serde_json::Value`, for inspection and ETL
+//! tools that dump keys and records without knowing their layout.
+//!
+//! Two kinds of input describe themselves well enough:
+//!
+//! * OrderedBytes values (`formats::orderedbytes`), whose header names
+//!   their type. `decode_tagged_tuple` reads a compound key of them, each
+//!   ascending or descending.
+//! * Runs of packed integers, such as the keys built by `keyspace` or
+//!   `snowflake`. `decode_packed_tuple` reads them as signed where they
+//!   fit and unsigned otherwise.
+//!
+//! Integers become JSON numbers and text becomes strings. Numbers JSON
+//! cannot hold (NaN and the infinities) become the strings `"NaN"`,
+//! `"Infinity"` and `"-Infinity"`, and bytes become strings as chosen by
+//! `BytesAs`.
+
+use serde_json::{Number, Value};
+
+use crate::formats::orderedbytes::{self, Kind};
+use crate::text::encode_hex;
+use crate::{decode_int, decode_uint, DecodeError};
+
+/// How byte strings are written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BytesAs {
+	/// Lowercase hex.
+	Hex,
+	/// Standard base64 with padding.
+	Base64,
+}
+
+impl BytesAs {
+	fn string(self, bytes: &[u8]) -> Value {
+		Value::String(match self {
+			BytesAs::Hex => encode_hex(bytes),
+			BytesAs::Base64 => base64(bytes),
+		})
+	}
+}
+
+fn base64(bytes: &[u8]) -> String {
+	const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+	for chunk in bytes.chunks(3) {
+		let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+		for i in 0..4 {
+			if i <= chunk.len() {
+				out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+fn float(v: f64) -> Value {
+	match Number::from_f64(v) {
+		Some(n) => Value::Number(n),
+		None if v.is_nan() => Value::String("NaN".to_string()),
+		None if v > 0.0 => Value::String("Infinity".to_string()),
+		None => Value::String("-Infinity".to_string()),
+	}
+}
+
+/// Decodes the OrderedBytes value at the front of `buf`, returning it and
+/// the number of bytes it occupied.
+///
+/// A numeric that holds an integer becomes an integer, any other a float.
+pub fn decode_tagged(buf: &[u8], bytes: BytesAs) -> Result<(Value, usize), DecodeError> {
+	let (kind, order) = orderedbytes::kind_of(buf)?;
+	let value = match kind {
+		Kind::Null => (Value::Null, 1),
+		Kind::Numeric => match orderedbytes::decode_numeric_i64(buf, order) {
+			Ok((v, used)) => (Value::from(v), used),
+			Err(e @ DecodeError::Truncated { .. }) => return Err(e),
+			Err(_) => orderedbytes::decode_numeric_f64(buf, order).map(|(v, used)| (float(v), used))?,
+		},
+		Kind::Int8 => orderedbytes::decode_int8(buf, order).map(|(v, used)| (Value::from(v), used))?,
+		Kind::Int16 => orderedbytes::decode_int16(buf, order).map(|(v, used)| (Value::from(v), used))?,
+		Kind::Int32 => orderedbytes::decode_int32(buf, order).map(|(v, used)| (Value::from(v), used))?,
+		Kind::Int64 => orderedbytes::decode_int64(buf, order).map(|(v, used)| (Value::from(v), used))?,
+		Kind::Float32 => orderedbytes::decode_float32(buf, order).map(|(v, used)| (float(v as f64), used))?,
+		Kind::Float64 => orderedbytes::decode_float64(buf, order).map(|(v, used)| (float(v), used))?,
+		Kind::Text => orderedbytes::decode_str(buf, order).map(|(v, used)| (Value::String(v), used))?,
+		Kind::BlobVar => orderedbytes::decode_blob_var(buf, order).map(|(v, used)| (bytes.string(&v), used))?,
+		Kind::BlobCopy => orderedbytes::decode_blob_copy(buf, order).map(|(v, used)| (bytes.string(&v), used))?,
+	};
+	Ok(value)
+}
+
+// Decodes values with `decode` until `buf` runs out, as a JSON array.
+fn tuple<D>(buf: &[u8], mut decode: D) -> Result<Value, DecodeError>
+where
+	D: FnMut(&[u8]) -> Result<(Value, usize), DecodeError>,
+{
+	let mut out = Vec::new();
+	let mut pos = 0;
+	while pos < buf.len() {
+		let (v, used) = decode(&buf[pos..]).map_err(|e| match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+			e => e,
+		})?;
+		out.push(v);
+		pos += used;
+	}
+	Ok(Value::Array(out))
+}
+
+/// Decodes all of `buf` as a run of OrderedBytes values.
+pub fn decode_tagged_tuple(buf: &[u8], bytes: BytesAs) -> Result<Value, DecodeError> {
+	tuple(buf, |b| decode_tagged(b, bytes))
+}
+
+/// Decodes all of `buf` as a run of packed integers.
+pub fn decode_packed_tuple(buf: &[u8]) -> Result<Value, DecodeError> {
+	tuple(buf, |b| match decode_int(b) {
+		Ok((v, used)) => Ok((Value::from(v), used)),
+		Err(DecodeError::Overflow) => decode_uint(b).map(|(v, used)| (Value::from(v), used)),
+		Err(e) => Err(e),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::formats::orderedbytes::Order;
+	use crate::{pack_int, pack_uint};
+	use serde_json::json;
+
+	#[quickcheck]
+	fn packed_tuples_match(values: Vec<i64>, big: u64) -> bool {
+		let mut buf: Vec<u8> = values.iter().flat_map(|&v| pack_int(v)).collect();
+		buf.extend_from_slice(&pack_uint(big));
+		let mut want: Vec<Value> = values.into_iter().map(Value::from).collect();
+		want.push(Value::from(big));
+		decode_packed_tuple(&buf) == Ok(Value::Array(want))
+	}
+
+	#[test]
+	fn dumps_tagged_tuples() {
+		let (asc, desc) = (Order::Ascending, Order::Descending);
+		let mut key = Vec::new();
+		orderedbytes::encode_numeric_i64(&mut key, -12345, asc);
+		orderedbytes::encode_numeric_f64(&mut key, 0.5, desc);
+		orderedbytes::encode_numeric_f64(&mut key, f64::NEG_INFINITY, asc);
+		orderedbytes::encode_null(&mut key, desc);
+		orderedbytes::encode_int16(&mut key, -2, desc);
+		orderedbytes::encode_float64(&mut key, 2.5, asc);
+		orderedbytes::encode_str(&mut key, "héllo", desc);
+		orderedbytes::encode_blob_var(&mut key, b"\x00\xffab", asc);
+		orderedbytes::encode_blob_copy(&mut key, b"xyz", asc);
+
+		let want = json!([-12345, 0.5, "-Infinity", null, -2, 2.5, "héllo", "00ff6162", "78797a"]);
+		assert_eq!(decode_tagged_tuple(&key, BytesAs::Hex), Ok(want));
+		let tail = decode_tagged_tuple(&key, BytesAs::Base64).unwrap();
+		assert_eq!(tail.as_array().unwrap()[7..], [json!("AP9hYg=="), json!("eHl6")]);
+
+		assert_eq!(decode_tagged_tuple(&key[..2], BytesAs::Hex), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(decode_packed_tuple(&[0x81, 0xe4, 0x01]), Err(DecodeError::Truncated { needed: 6 }));
+		assert_eq!(base64(b"a"), "YQ==");
+	}
+}
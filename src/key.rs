@@ -0,0 +1,816 @@
+//! Composite keys: several fields in one byte string that compares with
+//! `memcmp` like the tuple of its fields.
+//!
+//! ```
+//! use rust_varint::key::{KeyBuilder, KeyReader};
+//!
+//! let mut a = KeyBuilder::new();
+//! a.append_str("users").append_u64(42).append_i64(-1);
+//! let b = KeyBuilder::new().append_str("users").append_u64(42).append_i64(7).finish();
+//! assert!(a.as_bytes() < &b[..]);
+//!
+//! let mut r = KeyReader::new(a.as_bytes());
+//! assert_eq!(r.read_str().unwrap(), "users");
+//! assert_eq!(r.read_u64(), Ok(42));
+//! assert_eq!(r.read_i64(), Ok(-1));
+//! assert!(r.is_empty());
+//! ```
+//!
+//! Integers are packed as by `pack_uint` and `pack_int`. Byte strings have
+//! each zero byte escaped as `0x00 0x01` and end with `0x00 0x00`, so a
+//! string sorts before every longer string it is a prefix of;
+//! `pack_bytes` and `unpack_bytes` give this encoding on its own, for keys
+//! laid out by hand. Floats take 8 bytes, see `pack_f64`.
+//!
+//! Each field may instead be appended descending, as with
+//! `append_u64_desc`, which stores it complemented, so keys sort like
+//! `ORDER BY a ASC, b DESC`. Fields carry neither type nor direction: a
+//! key must be read back with the schema it was built with, field by
+//! field or all at once with `decode_key`.
+//!
+//! With the `derive` feature, `#[derive(OrderedKey)]` writes that code
+//! for a struct whose fields are all `KeyField`s, in declaration order.
+//! A field marked `#[key(desc)]` sorts descending, and an `Option` field
+//! puts `None` first unless marked `#[key(nulls_last)]`:
+//!
+//! ```
+//! # #[cfg(feature = "derive")] {
+//! use rust_varint::key::OrderedKey;
+//!
+//! #[derive(OrderedKey, Debug, PartialEq)]
+//! struct Event {
+//!     tenant: String,
+//!     #[key(desc)]
+//!     at: i64,
+//!     #[key(nulls_last)]
+//!     parent: Option<u64>,
+//! }
+//!
+//! let newer = Event { tenant: "a".into(), at: 20, parent: None };
+//! let older = Event { tenant: "a".into(), at: 10, parent: Some(3) };
+//! assert!(newer.encode_key() < older.encode_key());
+//! assert_eq!(Event::decode_key(&older.encode_key()), Ok(older));
+//! # }
+//! ```
+
+use std::convert::TryFrom;
+
+use crate::{decode_int_at, decode_uint_at, pack_int_append, pack_uint_append, DecodeError, NullPlacement};
+
+#[cfg(feature = "derive")]
+pub use rust_varint_derive::OrderedKey;
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ZERO: u8 = 0x01;
+const TERMINATOR: u8 = 0x00;
+// Lead an `Option` field, uncomplemented whatever its direction so that
+// `None` stays where it was placed.
+const NONE_FIRST: u8 = 0x01;
+const SOME: u8 = 0x02;
+const NONE_LAST: u8 = 0xfe;
+
+/// Escapes `data` as a self-delimiting field, see the module docs. Any
+/// value may follow it in a key without changing its order.
+pub fn pack_bytes(data: &[u8]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(data.len() + 2);
+	pack_bytes_append(data, &mut out);
+	out
+}
+
+/// Escapes `data` onto the end of `out`.
+pub fn pack_bytes_append(data: &[u8], out: &mut Vec<u8>) {
+	for &b in data {
+		out.push(b);
+		if b == ESCAPE {
+			out.push(ESCAPED_ZERO);
+		}
+	}
+	out.extend_from_slice(&[ESCAPE, TERMINATOR]);
+}
+
+/// Unescapes the byte string at the front of `buf`, returning it with the
+/// number of bytes it occupied. A missing terminator is `Truncated`, an
+/// escape other than the two the encoding uses `Malformed`.
+pub fn unpack_bytes(buf: &[u8]) -> Result<(Vec<u8>, usize), DecodeError> {
+	let mut data = Vec::new();
+	let mut i = 0;
+	while let Some(&b) = buf.get(i) {
+		if b != ESCAPE {
+			data.push(b);
+			i += 1;
+			continue;
+		}
+		match buf.get(i + 1) {
+			Some(&TERMINATOR) => return Ok((data, i + 2)),
+			Some(&ESCAPED_ZERO) => data.push(0),
+			Some(_) => return Err(DecodeError::Malformed("invalid escape in key bytes")),
+			None => break,
+		}
+		i += 2;
+	}
+	Err(DecodeError::Truncated { needed: i + 2 })
+}
+
+/// Where `pack_f64` and `pack_f32` sort NaN, which has no place among the
+/// other values. Every NaN packs to the same bytes and unpacks to a NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NanPlacement {
+	/// Before negative infinity.
+	First,
+	/// After positive infinity, as in PostgreSQL.
+	Last,
+}
+
+/// Packs `v` as 8 big-endian bytes comparing like `f64::total_cmp`: the
+/// sign bit is set for positive values and every bit flipped for negative
+/// ones, so `-0.0` sorts just before `0.0`.
+pub fn pack_f64(v: f64, nan: NanPlacement) -> [u8; 8] {
+	let bits = match nan {
+		_ if !v.is_nan() => sortable_bits(v.to_bits(), 1 << 63),
+		NanPlacement::First => 0,
+		NanPlacement::Last => u64::MAX,
+	};
+	bits.to_be_bytes()
+}
+
+/// Packs `v` as 4 bytes, see `pack_f64`.
+pub fn pack_f32(v: f32, nan: NanPlacement) -> [u8; 4] {
+	let bits = match nan {
+		_ if !v.is_nan() => sortable_bits(v.to_bits() as u64, 1 << 31) as u32,
+		NanPlacement::First => 0,
+		NanPlacement::Last => u32::MAX,
+	};
+	bits.to_be_bytes()
+}
+
+// Order-preserving form of IEEE `bits` whose sign bit is `sign`.
+fn sortable_bits(bits: u64, sign: u64) -> u64 {
+	if bits & sign == 0 { bits | sign } else { !bits & (sign << 1).wrapping_sub(1) }
+}
+
+// Inverse of `sortable_bits`; the two packed NaNs come back as NaNs.
+fn ieee_bits(bits: u64, sign: u64) -> u64 {
+	if bits & sign != 0 { bits & !sign } else { !bits & (sign << 1).wrapping_sub(1) }
+}
+
+/// Decodes a value packed by `pack_f64`, returning it with the 8 bytes it
+/// occupied.
+pub fn unpack_f64(buf: &[u8]) -> Result<(f64, usize), DecodeError> {
+	let mut b = [0; 8];
+	b.copy_from_slice(buf.get(..8).ok_or(DecodeError::Truncated { needed: 8 })?);
+	Ok((f64::from_bits(ieee_bits(u64::from_be_bytes(b), 1 << 63)), 8))
+}
+
+/// Decodes a value packed by `pack_f32`.
+pub fn unpack_f32(buf: &[u8]) -> Result<(f32, usize), DecodeError> {
+	let mut b = [0; 4];
+	b.copy_from_slice(buf.get(..4).ok_or(DecodeError::Truncated { needed: 4 })?);
+	Ok((f32::from_bits(ieee_bits(u32::from_be_bytes(b) as u64, 1 << 31) as u32), 4))
+}
+
+/// Sort direction of one key field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+	Asc,
+	/// Stored complemented, so the field sorts in reverse.
+	Desc,
+}
+
+/// Type of one key field, for reading keys back by schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+	U64,
+	I64,
+	F64,
+	U128Fixed,
+	Uuid,
+	Bytes,
+	Str,
+}
+
+/// One field read back by `KeyReader::read_field`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	U64(u64),
+	I64(i64),
+	F64(f64),
+	U128(u128),
+	Uuid([u8; 16]),
+	Bytes(Vec<u8>),
+	Str(String),
+}
+
+/// Reads every field of `key` as `schema` lays them out, failing if bytes
+/// are left after the last one.
+pub fn decode_key(key: &[u8], schema: &[(Field, Direction)]) -> Result<Vec<Value>, DecodeError> {
+	let mut r = KeyReader::new(key);
+	let values = schema.iter().map(|&(field, dir)| r.read_field(field, dir)).collect::<Result<Vec<_>, _>>()?;
+	r.finish()?;
+	Ok(values)
+}
+
+/// A type a composite key can hold as one field.
+pub trait KeyField: Sized {
+	/// Appends `self` ascending.
+	fn append_to(&self, key: &mut KeyBuilder);
+
+	/// Reads back a field appended by `append_to`. Running out of bytes
+	/// must be `Truncated`, which descending reads rely on.
+	fn read_from(r: &mut KeyReader<'_>) -> Result<Self, DecodeError>;
+}
+
+/// A type that is a whole key, usually by `#[derive(OrderedKey)]`.
+pub trait OrderedKey: Sized {
+	fn encode_key(&self) -> Vec<u8>;
+
+	/// Decodes a key written by `encode_key`, failing if bytes are left
+	/// after it.
+	fn decode_key(key: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl KeyField for u64 {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_u64(*self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<u64, DecodeError> {
+		r.read_u64()
+	}
+}
+
+impl KeyField for i64 {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_i64(*self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<i64, DecodeError> {
+		r.read_i64()
+	}
+}
+
+// Narrower integers are stored as their 64-bit counterparts.
+macro_rules! narrow_key_field {
+	($($t:ty => $wide:ty),*) => {$(
+		impl KeyField for $t {
+			fn append_to(&self, key: &mut KeyBuilder) {
+				(*self as $wide).append_to(key);
+			}
+
+			fn read_from(r: &mut KeyReader<'_>) -> Result<$t, DecodeError> {
+				let start = r.pos;
+				<$t>::try_from(<$wide>::read_from(r)?).map_err(|_| {
+					r.pos = start;
+					DecodeError::Overflow
+				})
+			}
+		}
+	)*};
+}
+
+narrow_key_field!(u8 => u64, u16 => u64, u32 => u64, i8 => i64, i16 => i64, i32 => i64);
+
+impl KeyField for f64 {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_f64(*self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<f64, DecodeError> {
+		r.read_f64()
+	}
+}
+
+/// Fixed-width, as by `append_u128_fixed`.
+impl KeyField for u128 {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_u128_fixed(*self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<u128, DecodeError> {
+		r.read_u128_fixed()
+	}
+}
+
+/// A UUID, as by `append_uuid`.
+impl KeyField for [u8; 16] {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_uuid(*self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<[u8; 16], DecodeError> {
+		r.read_uuid()
+	}
+}
+
+impl KeyField for Vec<u8> {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_bytes(self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<Vec<u8>, DecodeError> {
+		r.read_bytes()
+	}
+}
+
+impl KeyField for String {
+	fn append_to(&self, key: &mut KeyBuilder) {
+		key.append_str(self);
+	}
+
+	fn read_from(r: &mut KeyReader<'_>) -> Result<String, DecodeError> {
+		r.read_str()
+	}
+}
+
+/// Builds a composite key one field at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyBuilder {
+	buf: Vec<u8>,
+}
+
+impl KeyBuilder {
+	pub fn new() -> KeyBuilder {
+		KeyBuilder { buf: Vec::new() }
+	}
+
+	pub fn append_u64(&mut self, v: u64) -> &mut KeyBuilder {
+		pack_uint_append(v, &mut self.buf);
+		self
+	}
+
+	pub fn append_i64(&mut self, v: i64) -> &mut KeyBuilder {
+		pack_int_append(v, &mut self.buf);
+		self
+	}
+
+	pub fn append_bytes(&mut self, data: &[u8]) -> &mut KeyBuilder {
+		pack_bytes_append(data, &mut self.buf);
+		self
+	}
+
+	pub fn append_str(&mut self, s: &str) -> &mut KeyBuilder {
+		self.append_bytes(s.as_bytes())
+	}
+
+	/// Appends `v` as by `pack_f64`, with NaN last.
+	pub fn append_f64(&mut self, v: f64) -> &mut KeyBuilder {
+		self.buf.extend_from_slice(&pack_f64(v, NanPlacement::Last));
+		self
+	}
+
+	/// Appends `v` as 16 big-endian bytes. Unlike `wide::pack_u128` every
+	/// value takes the full width, which suits ids such as ULIDs whose
+	/// values are all large.
+	pub fn append_u128_fixed(&mut self, v: u128) -> &mut KeyBuilder {
+		self.buf.extend_from_slice(&v.to_be_bytes());
+		self
+	}
+
+	/// Appends a UUID as its 16 bytes, so UUIDv7 keys sort by time.
+	pub fn append_uuid(&mut self, uuid: [u8; 16]) -> &mut KeyBuilder {
+		self.buf.extend_from_slice(&uuid);
+		self
+	}
+
+	pub fn append_u64_desc(&mut self, v: u64) -> &mut KeyBuilder {
+		self.descending(|k| k.append_u64(v))
+	}
+
+	pub fn append_i64_desc(&mut self, v: i64) -> &mut KeyBuilder {
+		self.descending(|k| k.append_i64(v))
+	}
+
+	pub fn append_bytes_desc(&mut self, data: &[u8]) -> &mut KeyBuilder {
+		self.descending(|k| k.append_bytes(data))
+	}
+
+	pub fn append_str_desc(&mut self, s: &str) -> &mut KeyBuilder {
+		self.descending(|k| k.append_str(s))
+	}
+
+	pub fn append_f64_desc(&mut self, v: f64) -> &mut KeyBuilder {
+		self.descending(|k| k.append_f64(v))
+	}
+
+	pub fn append_u128_fixed_desc(&mut self, v: u128) -> &mut KeyBuilder {
+		self.descending(|k| k.append_u128_fixed(v))
+	}
+
+	pub fn append_uuid_desc(&mut self, uuid: [u8; 16]) -> &mut KeyBuilder {
+		self.descending(|k| k.append_uuid(uuid))
+	}
+
+	/// Appends any `KeyField` in direction `dir`.
+	pub fn append<T: KeyField>(&mut self, v: &T, dir: Direction) -> &mut KeyBuilder {
+		match dir {
+			Direction::Asc => {
+				v.append_to(self);
+				self
+			}
+			Direction::Desc => self.descending(|k| {
+				v.append_to(k);
+				k
+			}),
+		}
+	}
+
+	/// Appends a nullable field. `None` sorts where `nulls` puts it in
+	/// either direction, like `DESC NULLS LAST`; `Some` takes a byte more
+	/// than its value.
+	pub fn append_opt<T: KeyField>(&mut self, v: &Option<T>, dir: Direction, nulls: NullPlacement) -> &mut KeyBuilder {
+		match v {
+			None => {
+				self.buf.push(if nulls == NullPlacement::First { NONE_FIRST } else { NONE_LAST });
+				self
+			}
+			Some(v) => {
+				self.buf.push(SOME);
+				self.append(v, dir)
+			}
+		}
+	}
+
+	// Every field encoding is self-delimiting, so complementing one
+	// reverses its order without disturbing the fields after it.
+	fn descending<F: FnOnce(&mut KeyBuilder) -> &mut KeyBuilder>(&mut self, append: F) -> &mut KeyBuilder {
+		let start = self.buf.len();
+		append(self);
+		self.buf[start..].iter_mut().for_each(|b| *b = !*b);
+		self
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.buf
+	}
+
+	/// The finished key. Takes the fields out, leaving the builder empty
+	/// for the next key.
+	pub fn finish(&mut self) -> Vec<u8> {
+		std::mem::take(&mut self.buf)
+	}
+}
+
+/// Reads the fields of a composite key back in the order they were
+/// appended. `Truncated` counts from the start of the key, and a failed
+/// read leaves the reader where it was.
+#[derive(Debug, Clone)]
+pub struct KeyReader<'a> {
+	buf: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> KeyReader<'a> {
+	pub fn new(buf: &'a [u8]) -> KeyReader<'a> {
+		KeyReader { buf, pos: 0 }
+	}
+
+	pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+		decode_uint_at(self.buf, &mut self.pos)
+	}
+
+	pub fn read_i64(&mut self) -> Result<i64, DecodeError> {
+		decode_int_at(self.buf, &mut self.pos)
+	}
+
+	pub fn read_bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
+		let (data, used) = unpack_bytes(&self.buf[self.pos..]).map_err(|e| self.at_pos(e))?;
+		self.pos += used;
+		Ok(data)
+	}
+
+	pub fn read_f64(&mut self) -> Result<f64, DecodeError> {
+		let (v, used) = unpack_f64(&self.buf[self.pos..]).map_err(|e| self.at_pos(e))?;
+		self.pos += used;
+		Ok(v)
+	}
+
+	pub fn read_u128_fixed(&mut self) -> Result<u128, DecodeError> {
+		self.read_fixed().map(u128::from_be_bytes)
+	}
+
+	pub fn read_uuid(&mut self) -> Result<[u8; 16], DecodeError> {
+		self.read_fixed()
+	}
+
+	fn read_fixed(&mut self) -> Result<[u8; 16], DecodeError> {
+		let mut out = [0; 16];
+		let end = self.pos + out.len();
+		out.copy_from_slice(self.buf.get(self.pos..end).ok_or(DecodeError::Truncated { needed: end })?);
+		self.pos = end;
+		Ok(out)
+	}
+
+	pub fn read_str(&mut self) -> Result<String, DecodeError> {
+		let start = self.pos;
+		let data = self.read_bytes()?;
+		String::from_utf8(data).map_err(|_| {
+			self.pos = start;
+			DecodeError::Malformed("key string is not UTF-8")
+		})
+	}
+
+	/// Reads the next field as `field`, stored in direction `dir`.
+	pub fn read_field(&mut self, field: Field, dir: Direction) -> Result<Value, DecodeError> {
+		self.directed(dir, |r| r.read_asc(field))
+	}
+
+	/// Reads the next field as a `T` appended by `KeyBuilder::append`.
+	pub fn read<T: KeyField>(&mut self, dir: Direction) -> Result<T, DecodeError> {
+		self.directed(dir, T::read_from)
+	}
+
+	/// Reads a field appended by `KeyBuilder::append_opt`.
+	pub fn read_opt<T: KeyField>(&mut self, dir: Direction, nulls: NullPlacement) -> Result<Option<T>, DecodeError> {
+		let none = if nulls == NullPlacement::First { NONE_FIRST } else { NONE_LAST };
+		match self.buf.get(self.pos) {
+			Some(&b) if b == none => {
+				self.pos += 1;
+				Ok(None)
+			}
+			Some(&SOME) => {
+				self.pos += 1;
+				self.read(dir).map(Some).inspect_err(|_| self.pos -= 1)
+			}
+			Some(_) => Err(DecodeError::Malformed("invalid null tag in key")),
+			None => Err(DecodeError::Truncated { needed: self.pos + 1 }),
+		}
+	}
+
+	// A descending field is read from a complemented copy of the bytes
+	// it may span. The copy starts short and grows while the field is
+	// truncated in it, so each field costs its own length rather than
+	// that of the rest of the key.
+	fn directed<T, F: FnMut(&mut KeyReader<'_>) -> Result<T, DecodeError>>(&mut self, dir: Direction, mut read: F)
+		-> Result<T, DecodeError> {
+		if dir == Direction::Asc {
+			return read(self);
+		}
+		let rest = &self.buf[self.pos..];
+		let mut len = rest.len().min(32);
+		loop {
+			let window: Vec<u8> = rest[..len].iter().map(|&b| !b).collect();
+			let mut r = KeyReader::new(&window);
+			match read(&mut r) {
+				Err(DecodeError::Truncated { needed }) if len < rest.len() => len = rest.len().min(needed.max(2 * len)),
+				v => {
+					let v = v.map_err(|e| self.at_pos(e))?;
+					self.pos += r.pos;
+					return Ok(v);
+				}
+			}
+		}
+	}
+
+	fn read_asc(&mut self, field: Field) -> Result<Value, DecodeError> {
+		match field {
+			Field::U64 => self.read_u64().map(Value::U64),
+			Field::I64 => self.read_i64().map(Value::I64),
+			Field::F64 => self.read_f64().map(Value::F64),
+			Field::U128Fixed => self.read_u128_fixed().map(Value::U128),
+			Field::Uuid => self.read_uuid().map(Value::Uuid),
+			Field::Bytes => self.read_bytes().map(Value::Bytes),
+			Field::Str => self.read_str().map(Value::Str),
+		}
+	}
+
+	fn at_pos(&self, e: DecodeError) -> DecodeError {
+		match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: self.pos + needed },
+			e => e,
+		}
+	}
+
+	/// Bytes of the key read so far.
+	pub fn position(&self) -> usize {
+		self.pos
+	}
+
+	/// Whether every field has been read.
+	pub fn is_empty(&self) -> bool {
+		self.pos == self.buf.len()
+	}
+
+	/// Fails if bytes are left after the fields read so far.
+	pub fn finish(&self) -> Result<(), DecodeError> {
+		if !self.is_empty() {
+			return Err(DecodeError::Malformed("trailing bytes after the last key field"));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(t: &(u64, i64, Vec<u8>, String)) -> Vec<u8> {
+		KeyBuilder::new().append_u64(t.0).append_i64(t.1).append_bytes(&t.2).append_str(&t.3).finish()
+	}
+
+	#[quickcheck]
+	fn keys_sort_like_tuples(a: (u64, i64, Vec<u8>, String), b: (u64, i64, Vec<u8>, String)) -> bool {
+		let (ka, kb) = (key(&a), key(&b));
+		let mut r = KeyReader::new(&ka);
+		ka.cmp(&kb) == a.cmp(&b) && r.read_u64() == Ok(a.0) && r.read_i64() == Ok(a.1) &&
+			r.read_bytes() == Ok(a.2) && r.read_str() == Ok(a.3) && r.is_empty()
+	}
+
+	// draws bytes mostly from the escape bytes and their neighbours
+	fn escapy(data: &[u8]) -> Vec<u8> {
+		data.iter().map(|&b| [0x00, 0x01, 0x02, 0xff, b][b as usize % 5]).collect()
+	}
+
+	#[quickcheck]
+	fn packed_bytes_sort_and_round_trip(a: Vec<u8>, b: Vec<u8>, x: i64, y: i64) -> bool {
+		let (a, b) = (escapy(&a), escapy(&b));
+		let (mut ka, mut kb) = (pack_bytes(&a), pack_bytes(&b));
+		let used = ka.len();
+		pack_int_append(x, &mut ka);
+		pack_int_append(y, &mut kb);
+		ka.cmp(&kb) == (&a, x).cmp(&(&b, y)) && unpack_bytes(&ka) == Ok((a, used))
+	}
+
+	#[quickcheck]
+	fn floats_sort_in_total_order(x: f64, y: f64, shift: i8) -> bool {
+		// spread over the exponents, which quickcheck keeps small
+		let (x, y) = (x * 2f64.powi(shift as i32 * 8), y / 2f64.powi(shift as i32 * 8));
+		let (a, b) = (pack_f64(x, NanPlacement::First), pack_f64(y, NanPlacement::First));
+		let (fx, fy) = (x as f32, y as f32);
+		let (fa, fb) = (pack_f32(fx, NanPlacement::Last), pack_f32(fy, NanPlacement::Last));
+		a.cmp(&b) == x.total_cmp(&y) && unpack_f64(&a) == Ok((x, 8)) &&
+			fa.cmp(&fb) == fx.total_cmp(&fy) && unpack_f32(&fa) == Ok((fx, 4))
+	}
+
+	#[test]
+	fn places_nan_and_zeros() {
+		let order = [f64::NEG_INFINITY, f64::MIN, -1.0, -f64::MIN_POSITIVE, -0.0, 0.0, 1e-300, f64::MAX, f64::INFINITY];
+		let last = order.iter().map(|&v| pack_f64(v, NanPlacement::Last)).collect::<Vec<_>>();
+		assert!(last.windows(2).all(|w| w[0] < w[1]));
+		assert!(pack_f64(f64::NAN, NanPlacement::First) < pack_f64(f64::NEG_INFINITY, NanPlacement::First));
+		assert!(pack_f64(-f64::NAN, NanPlacement::Last) > pack_f64(f64::INFINITY, NanPlacement::Last));
+		assert!(unpack_f64(&pack_f64(f64::NAN, NanPlacement::First)).unwrap().0.is_nan());
+		assert!(unpack_f64(&pack_f64(f64::NAN, NanPlacement::Last)).unwrap().0.is_nan());
+		assert!(unpack_f32(&pack_f32(f32::NAN, NanPlacement::First)).unwrap().0.is_nan());
+		assert_eq!(unpack_f32(&[0x80, 0]), Err(DecodeError::Truncated { needed: 4 }));
+
+		let key = KeyBuilder::new().append_u64(1).append_f64(-2.5).finish();
+		let mut r = KeyReader::new(&key[..6]);
+		assert_eq!(r.read_u64(), Ok(1));
+		assert_eq!(r.read_f64(), Err(DecodeError::Truncated { needed: 9 }));
+		assert_eq!(KeyReader::new(&key[1..]).read_f64(), Ok(-2.5));
+	}
+
+	#[quickcheck]
+	fn fixed_ids_sort_and_round_trip(a: (u64, u64, u8), b: (u64, u64, u8)) -> bool {
+		let id = |(hi, lo, shift): (u64, u64, u8)| ((hi as u128) << 64 | (lo as u128) << (shift % 64)) << (shift % 3);
+		let (x, y) = (id(a), id(b));
+		let (kx, ky) = (KeyBuilder::new().append_u128_fixed(x).append_u64(1).finish(),
+			KeyBuilder::new().append_u128_fixed(y).append_u64(0).finish());
+		let mut r = KeyReader::new(&kx);
+		kx.cmp(&ky) == (x, 1).cmp(&(y, 0)) && kx.len() == 17 && r.read_u128_fixed() == Ok(x) && r.read_u64() == Ok(1)
+	}
+
+	#[test]
+	fn reads_uuids() {
+		let uuid = [0x01, 0x8f, 0x3c, 0x2a, 0x7b, 0x10, 0x70, 0x00, 0x80, 0x00, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc];
+		let key = KeyBuilder::new().append_str("t").append_uuid(uuid).finish();
+		let mut r = KeyReader::new(&key);
+		assert_eq!(r.read_str().unwrap(), "t");
+		assert_eq!(r.read_uuid(), Ok(uuid));
+		assert!(r.is_empty());
+		let mut r = KeyReader::new(&key[..key.len() - 1]);
+		r.read_str().unwrap();
+		assert_eq!(r.read_uuid(), Err(DecodeError::Truncated { needed: 19 }));
+		assert_eq!(r.position(), 3);
+	}
+
+	#[quickcheck]
+	fn directions_sort_like_order_by(a: (u64, String, i64), b: (u64, String, i64)) -> bool {
+		use std::cmp::Reverse;
+		// ORDER BY 0 ASC, 1 DESC, 2 DESC
+		let key = |t: &(u64, String, i64)| KeyBuilder::new().append_u64(t.0).append_str_desc(&t.1).append_i64_desc(t.2).finish();
+		let schema = [(Field::U64, Direction::Asc), (Field::Str, Direction::Desc), (Field::I64, Direction::Desc)];
+		let (ka, kb) = (key(&a), key(&b));
+		ka.cmp(&kb) == (a.0, Reverse(&a.1), Reverse(a.2)).cmp(&(b.0, Reverse(&b.1), Reverse(b.2))) &&
+			decode_key(&ka, &schema) == Ok(vec![Value::U64(a.0), Value::Str(a.1.clone()), Value::I64(a.2)])
+	}
+
+	#[test]
+	fn decodes_every_field_type_by_schema() {
+		let uuid = [7; 16];
+		let key = KeyBuilder::new().append_f64_desc(-0.5).append_bytes_desc(b"a\0").append_u128_fixed_desc(9)
+			.append_uuid(uuid).append_uuid_desc(uuid).append_u64_desc(3).finish();
+		let schema = [(Field::F64, Direction::Desc), (Field::Bytes, Direction::Desc), (Field::U128Fixed, Direction::Desc),
+			(Field::Uuid, Direction::Asc), (Field::Uuid, Direction::Desc), (Field::U64, Direction::Desc)];
+		assert_eq!(decode_key(&key, &schema), Ok(vec![Value::F64(-0.5), Value::Bytes(b"a\0".to_vec()), Value::U128(9),
+			Value::Uuid(uuid), Value::Uuid(uuid), Value::U64(3)]));
+		assert!(KeyBuilder::new().append_f64_desc(1.0).finish() < KeyBuilder::new().append_f64_desc(0.5).finish());
+
+		assert_eq!(decode_key(&key, &schema[..5]), Err(DecodeError::Malformed("trailing bytes after the last key field")));
+		let mut r = KeyReader::new(&key[..key.len() - 1]);
+		for &(field, dir) in &schema[..5] {
+			r.read_field(field, dir).unwrap();
+		}
+		assert_eq!(r.read_field(Field::U64, Direction::Desc), Err(DecodeError::Truncated { needed: key.len() }));
+	}
+
+	#[test]
+	fn reads_long_descending_fields() {
+		let long: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+		let mut k = KeyBuilder::new();
+		for i in 0..100 {
+			k.append_u64_desc(i << 40).append_bytes_desc(&long[..i as usize * 10]);
+		}
+		let key = k.finish();
+		let mut r = KeyReader::new(&key);
+		for i in 0..100 {
+			assert_eq!(r.read_field(Field::U64, Direction::Desc), Ok(Value::U64(i << 40)));
+			assert_eq!(r.read_field(Field::Bytes, Direction::Desc), Ok(Value::Bytes(long[..i as usize * 10].to_vec())));
+		}
+		assert!(r.is_empty());
+		let mut r = KeyReader::new(&key[..key.len() - 1]);
+		for _ in 0..99 {
+			r.read_field(Field::U64, Direction::Desc).unwrap();
+			r.read_field(Field::Bytes, Direction::Desc).unwrap();
+		}
+		r.read_field(Field::U64, Direction::Desc).unwrap();
+		assert_eq!(r.read_field(Field::Bytes, Direction::Desc), Err(DecodeError::Truncated { needed: key.len() }));
+	}
+
+	#[test]
+	fn strings_sort_before_their_extensions() {
+		let k = |s: &[u8]| KeyBuilder::new().append_bytes(s).append_u64(0).finish();
+		assert!(k(b"a") < k(b"a\0") && k(b"a\0") < k(b"a\x01") && k(b"a\x01") < k(b"ab"));
+		assert_eq!(k(b"a\0b"), [b'a', 0x00, 0x01, b'b', 0x00, 0x00, 0x80]);
+		assert_eq!(KeyReader::new(&k(b"\0\0")).read_bytes(), Ok(vec![0, 0]));
+	}
+
+	#[test]
+	fn reports_bad_fields() {
+		let mut r = KeyReader::new(&[0x81, b'a', 0x00]);
+		assert_eq!(r.read_u64(), Ok(1));
+		assert_eq!(r.read_bytes(), Err(DecodeError::Truncated { needed: 4 }));
+		assert_eq!(r.position(), 1);
+		assert_eq!(KeyReader::new(&[0x00, 0x02]).read_bytes(),
+			Err(DecodeError::Malformed("invalid escape in key bytes")));
+		let mut r = KeyReader::new(&[0xff, 0x00, 0x00]);
+		assert_eq!(r.read_str(), Err(DecodeError::Malformed("key string is not UTF-8")));
+		assert_eq!(r.position(), 0);
+	}
+
+	#[quickcheck]
+	fn typed_fields_sort_with_their_nulls(a: (i32, Option<String>, Option<u8>), b: (i32, Option<String>, Option<u8>)) -> bool {
+		use std::cmp::Reverse;
+		let key = |t: &(i32, Option<String>, Option<u8>)| KeyBuilder::new().append(&t.0, Direction::Asc)
+			.append_opt(&t.1, Direction::Desc, NullPlacement::First).append_opt(&t.2, Direction::Asc, NullPlacement::Last).finish();
+		// None before every Some in the second field, after them in the third
+		let order = |t: &(i32, Option<String>, Option<u8>)| (t.0, t.1.clone().map(Reverse), t.2.is_none(), t.2);
+		let (ka, kb) = (key(&a), key(&b));
+		let mut r = KeyReader::new(&ka);
+		ka.cmp(&kb) == order(&a).cmp(&order(&b)) && r.read(Direction::Asc) == Ok(a.0) &&
+			r.read_opt(Direction::Desc, NullPlacement::First) == Ok(a.1.clone()) &&
+			r.read_opt(Direction::Asc, NullPlacement::Last) == Ok(a.2) && r.finish().is_ok()
+	}
+
+	#[test]
+	fn rejects_bad_typed_fields() {
+		let key = KeyBuilder::new().append_u64(300).finish();
+		let mut r = KeyReader::new(&key);
+		assert_eq!(r.read::<u8>(Direction::Asc), Err(DecodeError::Overflow));
+		assert_eq!(r.position(), 0);
+		assert_eq!(r.read_opt::<u64>(Direction::Asc, NullPlacement::Last), Err(DecodeError::Malformed("invalid null tag in key")));
+		assert_eq!(KeyReader::new(&[0x01]).read_opt::<u64>(Direction::Asc, NullPlacement::Last),
+			Err(DecodeError::Malformed("invalid null tag in key")));
+		let mut r = KeyReader::new(&[0x02]);
+		assert_eq!(r.read_opt::<u64>(Direction::Asc, NullPlacement::Last), Err(DecodeError::Truncated { needed: 2 }));
+		assert_eq!(r.position(), 0);
+	}
+
+	#[cfg(feature = "derive")]
+	#[test]
+	fn derives_ordered_keys() {
+		#[derive(OrderedKey, Debug, Clone, PartialEq)]
+		struct Row {
+			table: String,
+			#[key(desc, nulls_last)]
+			version: Option<i64>,
+			id: [u8; 16],
+		}
+
+		#[derive(OrderedKey, Debug, PartialEq)]
+		struct Pair(u32, #[key(desc)] Vec<u8>);
+
+		let row = |v| Row { table: "t".into(), version: v, id: [1; 16] };
+		let rows = [row(Some(3)), row(Some(-1)), row(None)];
+		for pair in rows.windows(2) {
+			assert!(pair[0].encode_key() < pair[1].encode_key());
+			assert_eq!(Row::decode_key(&pair[0].encode_key()), Ok(pair[0].clone()));
+		}
+		assert_eq!(row(Some(3)).encode_key()[3..5], [SOME, !0x83]);
+		assert_eq!(row(None).encode_key()[3], NONE_LAST);
+		let pair = Pair(7, b"ab".to_vec()).encode_key();
+		assert!(Pair(7, b"ac".to_vec()).encode_key() < pair);
+		assert_eq!(Pair::decode_key(&pair), Ok(Pair(7, b"ab".to_vec())));
+		assert_eq!(Pair::decode_key(&[&pair[..], &[0x80]].concat()),
+			Err(DecodeError::Malformed("trailing bytes after the last key field")));
+	}
+}
@@ -0,0 +1,291 @@
+//! Blocks of sorted keys that store each key as the part it does not
+//! share with the one before, like LevelDB's data blocks.
+//!
+//! Every `restart_interval`th key is stored whole, and the block starts
+//! with where those restarts are, so a lookup can binary search them
+//! before scanning at most one interval:
+//!
+//! ```text
+//! [count: uint][restarts: uint][restart gap: uint ...][payload length: uint]
+//! [shared: uint][rest length: uint][rest ...] for each key
+//! ```
+//!
+//! A restart gap is the distance in payload bytes from the previous
+//! restart, or from the start for the first. Packed keys that sort near
+//! each other share their leading bytes, so sorted runs of them compress
+//! well:
+//!
+//! ```
+//! use rust_varint::keyblock::{KeyBlock, KeyBlockWriter};
+//! use rust_varint::pack_uint;
+//!
+//! let mut w = KeyBlockWriter::new(16);
+//! for id in 1_000_000..1_000_100u64 {
+//!     w.add(&[&b"user/"[..], &pack_uint(id)].concat());
+//! }
+//! let block = w.finish();
+//! assert!(block.len() < 100 * 4);
+//!
+//! let (keys, _) = KeyBlock::new(&block).unwrap();
+//! let target = [&b"user/"[..], &pack_uint(1_000_042)].concat();
+//! assert_eq!(keys.seek(&target).unwrap().next(), Some(Ok(target)));
+//! ```
+
+use std::convert::TryFrom;
+
+use crate::{decode_uint_at, pack_uint_append, DecodeError};
+
+/// Builds a block in memory.
+#[derive(Debug, Clone)]
+pub struct KeyBlockWriter {
+	interval: usize,
+	payload: Vec<u8>,
+	restarts: Vec<usize>,
+	last: Vec<u8>,
+	count: usize,
+}
+
+impl KeyBlockWriter {
+	/// Panics if `restart_interval` is zero.
+	pub fn new(restart_interval: usize) -> KeyBlockWriter {
+		assert!(restart_interval > 0, "restart interval must be positive");
+		KeyBlockWriter { interval: restart_interval, payload: Vec::new(), restarts: Vec::new(), last: Vec::new(), count: 0 }
+	}
+
+	/// Panics unless `key` sorts after every key added so far.
+	pub fn add(&mut self, key: &[u8]) {
+		if self.count > 0 {
+			assert!(key > &self.last[..], "block keys must be strictly increasing");
+		}
+		let shared = if self.count.is_multiple_of(self.interval) {
+			self.restarts.push(self.payload.len());
+			0
+		} else {
+			self.last.iter().zip(key).take_while(|(a, b)| a == b).count()
+		};
+		pack_uint_append(shared as u64, &mut self.payload);
+		pack_uint_append((key.len() - shared) as u64, &mut self.payload);
+		self.payload.extend_from_slice(&key[shared..]);
+		self.last.clear();
+		self.last.extend_from_slice(key);
+		self.count += 1;
+	}
+
+	/// Number of keys added.
+	pub fn len(&self) -> usize {
+		self.count
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count == 0
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(self.payload.len() + 3 * self.restarts.len() + 8);
+		pack_uint_append(self.count as u64, &mut out);
+		pack_uint_append(self.restarts.len() as u64, &mut out);
+		let mut prev = 0;
+		for &r in &self.restarts {
+			pack_uint_append((r - prev) as u64, &mut out);
+			prev = r;
+		}
+		pack_uint_append(self.payload.len() as u64, &mut out);
+		out.extend_from_slice(&self.payload);
+		out
+	}
+}
+
+/// A parsed block header, for iterating and seeking its keys.
+#[derive(Debug, Clone)]
+pub struct KeyBlock<'a> {
+	payload: &'a [u8],
+	restarts: Vec<usize>,
+	count: u64,
+}
+
+impl<'a> KeyBlock<'a> {
+	/// Parses the block at the front of `buf`. Returns it and the total
+	/// size of the block, so the caller can step to the next.
+	pub fn new(buf: &'a [u8]) -> Result<(KeyBlock<'a>, usize), DecodeError> {
+		let mut pos = 0;
+		let count = decode_uint_at(buf, &mut pos)?;
+		let n = decode_uint_at(buf, &mut pos)?;
+		// every restart gap but the first takes a key's two length bytes
+		if n > count || n.saturating_sub(1).saturating_mul(2) > buf.len() as u64 || (count > 0 && n == 0) {
+			return Err(DecodeError::Malformed("key block restart count does not match its keys"));
+		}
+		let mut restarts = Vec::with_capacity(n as usize);
+		let mut at: u64 = 0;
+		for _ in 0..n {
+			at = at.checked_add(decode_uint_at(buf, &mut pos)?).ok_or(DecodeError::Overflow)?;
+			restarts.push(at as usize);
+		}
+		let len = decode_uint_at(buf, &mut pos)?;
+		let end = usize::try_from(len).ok().and_then(|len| pos.checked_add(len)).ok_or(DecodeError::Overflow)?;
+		let payload = buf.get(pos..end).ok_or(DecodeError::Truncated { needed: end })?;
+		if restarts.first().is_some_and(|&r| r != 0) || restarts.windows(2).any(|w| w[0] >= w[1]) ||
+			restarts.last().is_some_and(|&r| r >= payload.len()) {
+			return Err(DecodeError::Malformed("key block restart is outside its payload"));
+		}
+		Ok((KeyBlock { payload, restarts, count }, end))
+	}
+
+	/// Number of keys in the block.
+	pub fn len(&self) -> u64 {
+		self.count
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.count == 0
+	}
+
+	/// Iterates every key in order.
+	pub fn iter(&self) -> Keys<'a> {
+		Keys { payload: self.payload, pos: 0, key: Vec::new(), started: false, failed: false }
+	}
+
+	/// Iterates the keys from the first that is not less than `target`.
+	pub fn seek(&self, target: &[u8]) -> Result<Keys<'a>, DecodeError> {
+		// the last restart whose key is below the target, if any
+		let (mut lo, mut hi) = (0, self.restarts.len());
+		while lo < hi {
+			let mid = (lo + hi) / 2;
+			let mut keys = Keys { pos: self.restarts[mid], ..self.iter() };
+			keys.step()?;
+			if &keys.key[..] < target {
+				lo = mid + 1;
+			} else {
+				hi = mid;
+			}
+		}
+		let pos = if lo == 0 { 0 } else { self.restarts[lo - 1] };
+		let mut keys = Keys { pos, ..self.iter() };
+		loop {
+			let before = keys.clone();
+			match keys.next() {
+				Some(Ok(key)) if &key[..] < target => continue,
+				Some(Err(e)) => return Err(e),
+				// step back so the iterator yields the key it just read
+				Some(Ok(_)) => return Ok(before),
+				None => return Ok(keys),
+			}
+		}
+	}
+}
+
+/// The keys of a block, from `KeyBlock::iter` or `seek`.
+#[derive(Debug, Clone)]
+pub struct Keys<'a> {
+	payload: &'a [u8],
+	pos: usize,
+	key: Vec<u8>,
+	started: bool,
+	failed: bool,
+}
+
+impl<'a> Keys<'a> {
+	// Reads the next entry into `key`. Starting from a restart, `key` is
+	// empty, so its entry cannot share a prefix.
+	fn step(&mut self) -> Result<(), DecodeError> {
+		let mut pos = self.pos;
+		let shared = decode_uint_at(self.payload, &mut pos)?;
+		let len = decode_uint_at(self.payload, &mut pos)?;
+		if shared > self.key.len() as u64 {
+			return Err(DecodeError::Malformed("key shares more bytes than the key before it"));
+		}
+		let end = usize::try_from(len).ok().and_then(|len| pos.checked_add(len)).ok_or(DecodeError::Overflow)?;
+		let rest = self.payload.get(pos..end).ok_or(DecodeError::Truncated { needed: end })?;
+		self.key.truncate(shared as usize);
+		self.key.extend_from_slice(rest);
+		self.pos = end;
+		Ok(())
+	}
+}
+
+impl<'a> Iterator for Keys<'a> {
+	type Item = Result<Vec<u8>, DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.failed || self.pos == self.payload.len() {
+			return None;
+		}
+		let prev = self.key.clone();
+		let started = std::mem::replace(&mut self.started, true);
+		Some(match self.step() {
+			Ok(()) if started && self.key <= prev => {
+				self.failed = true;
+				Err(DecodeError::Malformed("block keys are out of order"))
+			}
+			Ok(()) => Ok(self.key.clone()),
+			Err(e) => {
+				self.failed = true;
+				Err(e)
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_int;
+	use std::collections::BTreeSet;
+
+	fn block(keys: &BTreeSet<Vec<u8>>, interval: u8) -> Vec<u8> {
+		let mut w = KeyBlockWriter::new(interval as usize % 8 + 1);
+		keys.iter().for_each(|k| w.add(k));
+		w.finish()
+	}
+
+	#[quickcheck]
+	fn round_trips_and_seeks(keys: BTreeSet<Vec<u8>>, interval: u8, target: Vec<u8>) -> bool {
+		let buf = block(&keys, interval);
+		let (b, used) = KeyBlock::new(&buf).unwrap();
+		let all: Vec<Vec<u8>> = b.iter().collect::<Result<_, _>>().unwrap();
+		let from: Vec<Vec<u8>> = b.seek(&target).unwrap().collect::<Result<_, _>>().unwrap();
+		used == buf.len() && b.len() == keys.len() as u64 && all.iter().eq(&keys) &&
+			from.iter().eq(keys.range(target..))
+	}
+
+	#[quickcheck]
+	fn shares_the_prefixes_of_packed_keys(values: BTreeSet<i64>) -> bool {
+		let keys: BTreeSet<Vec<u8>> = values.iter().map(|&v| [&b"tenant/7/"[..], &pack_int(v)].concat()).collect();
+		let buf = block(&keys, 7);
+		let (b, _) = KeyBlock::new(&buf).unwrap();
+		b.seek(b"tenant/7/").unwrap().map(Result::unwrap).eq(keys.iter().cloned()) &&
+			(keys.len() < 8 || buf.len() < keys.iter().map(Vec::len).sum::<usize>())
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let mut w = KeyBlockWriter::new(2);
+		for k in [&b"apple"[..], b"apricot", b"banana"] {
+			w.add(k);
+		}
+		let buf = w.finish();
+		// count, 2 restarts, gaps 0 and 14, payload length
+		assert_eq!(buf[..5], [0x83, 0x82, 0x80, 0x8e, 0x96]);
+		let (b, _) = KeyBlock::new(&buf).unwrap();
+		assert_eq!(b.seek(b"b").unwrap().next(), Some(Ok(b"banana".to_vec())));
+		assert_eq!(b.seek(b"z").unwrap().next(), None);
+
+		let mut bad = buf.clone();
+		bad[5 + 7] = 0x86; // "apricot" shares more than "apple" has
+		let (b, _) = KeyBlock::new(&bad).unwrap();
+		assert_eq!(b.iter().nth(1), Some(Err(DecodeError::Malformed("key shares more bytes than the key before it"))));
+		let mut bad = buf.clone();
+		bad[3] = 0xa0;
+		assert_eq!(KeyBlock::new(&bad).map(|_| ()), Err(DecodeError::Malformed("key block restart is outside its payload")));
+		assert_eq!(KeyBlock::new(&buf[..buf.len() - 1]).map(|_| ()), Err(DecodeError::Truncated { needed: buf.len() }));
+		assert_eq!(KeyBlock::new(&[0x81, 0x80, 0x80]).map(|_| ()),
+			Err(DecodeError::Malformed("key block restart count does not match its keys")));
+	}
+
+	#[test]
+	#[should_panic(expected = "strictly increasing")]
+	fn writer_enforces_order() {
+		let mut w = KeyBlockWriter::new(1);
+		w.add(b"b");
+		w.add(b"a");
+	}
+}
@@ -0,0 +1,308 @@
+//! Laying encoded keys out over a key space.
+//!
+//! A sharded key is the shard number, packed as an unsigned value, followed
+//! by the original key. Keys of the same shard keep their relative order,
+//! so a global range scan becomes one scan of the same range per shard.
+//!
+//! `split_range` cuts a range into pieces of similar width for parallel
+//! scans, without looking at the data.
+//!
+//! `successor` and `predecessor` step the last value of a key of packed
+//! values, to turn inclusive range bounds into exclusive ones and back,
+//! and `shortest_separator` picks a short key between two others for the
+//! inner nodes of a B-tree.
+
+use crate::{decode_int, decode_int_at, decode_uint, decode_uint_at, pack_int, pack_uint, DecodeError,
+	SENTINEL_AFTER_ALL, SENTINEL_BEFORE_ALL};
+
+// 64-bit FNV-1a, stable across builds and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+	let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+	for &b in bytes {
+		h ^= b as u64;
+		h = h.wrapping_mul(0x0100_0000_01b3);
+	}
+	h
+}
+
+/// Shard that `field` hashes to, out of `shards`.
+pub fn shard_of(field: &[u8], shards: u16) -> u16 {
+	assert!(shards > 0, "shard count must be positive");
+	(fnv1a(field) % shards as u64) as u16
+}
+
+/// Prepends the shard that the key itself hashes to.
+pub fn shard_key(encoded: &[u8], shards: u16) -> Vec<u8> {
+	shard_key_by(encoded, shards, encoded)
+}
+
+/// Prepends the shard that `field` hashes to, e.g. a tenant id, so that
+/// related keys land on the same shard.
+pub fn shard_key_by(encoded: &[u8], shards: u16, field: &[u8]) -> Vec<u8> {
+	with_shard(shard_of(field, shards), encoded)
+}
+
+fn with_shard(shard: u16, key: &[u8]) -> Vec<u8> {
+	let mut out = pack_uint(shard as u64);
+	out.extend_from_slice(key);
+	out
+}
+
+/// Splits a sharded key into its shard and the original key.
+pub fn unshard_key(sharded: &[u8]) -> Result<(u16, &[u8]), DecodeError> {
+	let (shard, used) = decode_uint(sharded)?;
+	if shard > u16::MAX as u64 {
+		return Err(DecodeError::Overflow);
+	}
+	Ok((shard as u16, &sharded[used..]))
+}
+
+/// Per-shard `[start, end)` ranges that together cover the global range
+/// `[start, end)` of unsharded keys.
+pub fn shard_ranges(start: &[u8], end: &[u8], shards: u16) -> Vec<(Vec<u8>, Vec<u8>)> {
+	(0..shards).map(|s| (with_shard(s, start), with_shard(s, end))).collect()
+}
+
+// A key that is exactly one packed value, widened so that signed and
+// unsigned keys interpolate on the same line.
+fn as_value(key: &[u8]) -> Option<i128> {
+	match decode_int(key) {
+		Ok((v, used)) if used == key.len() => Some(v as i128),
+		_ => match decode_uint(key) {
+			Ok((v, used)) if used == key.len() => Some(v as i128),
+			_ => None,
+		},
+	}
+}
+
+fn pack_value(v: i128) -> Vec<u8> {
+	if v > i64::MAX as i128 {
+		pack_uint(v as u64)
+	} else {
+		pack_int(v as i64)
+	}
+}
+
+// Where each value of a run of packed values starts, and the value,
+// widened like `as_value`.
+fn values(key: &[u8]) -> Result<Vec<(usize, i128)>, DecodeError> {
+	let mut out = Vec::new();
+	let mut pos = 0;
+	while pos < key.len() || out.is_empty() {
+		let start = pos;
+		let v = match key.get(pos) {
+			Some(&b) if b >= 0x80 => decode_uint_at(key, &mut pos)? as i128,
+			_ => decode_int_at(key, &mut pos)? as i128,
+		};
+		out.push((start, v));
+	}
+	Ok(out)
+}
+
+// The bytes before the last value of a run of packed values, and that
+// value.
+fn split_last(key: &[u8]) -> Result<(&[u8], i128), DecodeError> {
+	let (start, v) = *values(key)?.last().unwrap();
+	Ok((&key[..start], v))
+}
+
+/// The key right after `encoded`, a run of packed values, among keys of
+/// the same number of values: the last one plus one, so `[a, successor(b))`
+/// holds the same keys as `[a, b]`. After `u64::MAX` comes the prefix and
+/// `SENTINEL_AFTER_ALL`, which is above every value.
+pub fn successor(encoded: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	let (prefix, v) = split_last(encoded)?;
+	let next = if v == u64::MAX as i128 { SENTINEL_AFTER_ALL.to_vec() } else { pack_value(v + 1) };
+	Ok([prefix, &next].concat())
+}
+
+/// The key right before `encoded`, the last value minus one. Before
+/// `i64::MIN` comes the prefix and `SENTINEL_BEFORE_ALL`.
+pub fn predecessor(encoded: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	let (prefix, v) = split_last(encoded)?;
+	let prev = if v == i64::MIN as i128 { SENTINEL_BEFORE_ALL.to_vec() } else { pack_value(v - 1) };
+	Ok([prefix, &prev].concat())
+}
+
+/// The shortest key `s` with `a < s <= b`, for runs of packed values `a`
+/// and `b`. It is the values the two share, then the shortest value from
+/// past `a`'s next one up to `b`'s, so it decodes like any other key.
+/// Keys that differ only by a longer, non-canonical encoding of the same
+/// values are separated by `b` itself. Panics unless `a < b`.
+pub fn shortest_separator(a: &[u8], b: &[u8]) -> Result<Vec<u8>, DecodeError> {
+	assert!(a < b, "a separator needs a < b");
+	let (va, vb) = (if a.is_empty() { Vec::new() } else { values(a)? }, values(b)?);
+	let shared = va.iter().zip(&vb).take_while(|(x, y)| x.1 == y.1).count();
+	let (start, hi) = match vb.get(shared) {
+		Some(&last) => last,
+		None => return Ok(b.to_vec()),
+	};
+	// `a` ends at the shared values when it has no value left
+	let lo = va.get(shared).map(|&(_, v)| v);
+	let v = match lo {
+		Some(lo) if lo >= 0 => lo + 1,
+		_ if hi >= 0 => 0,
+		_ => hi,
+	};
+	Ok([&b[..start], &pack_value(v)[..]].concat())
+}
+
+// Eight bytes after `from`, zero padded, as a big-endian number.
+fn window(key: &[u8], from: usize) -> u64 {
+	let mut bytes = [0; 8];
+	let tail = &key[from.min(key.len())..];
+	let n = tail.len().min(8);
+	bytes[..n].copy_from_slice(&tail[..n]);
+	u64::from_be_bytes(bytes)
+}
+
+/// Boundaries of `n` subranges of similar width covering `[start, end)`:
+/// `start`, the split points, then `end`. Keys that are single packed
+/// values are split by value, other keys by interpolating the bytes after
+/// their common prefix. Narrow ranges yield fewer subranges.
+pub fn split_range(start: &[u8], end: &[u8], n: usize) -> Vec<Vec<u8>> {
+	assert!(n > 0, "subrange count must be positive");
+	if start >= end {
+		return Vec::new();
+	}
+
+	let points: Vec<Vec<u8>> = match (as_value(start), as_value(end)) {
+		(Some(a), Some(b)) => (1..n).map(|i| pack_value(a + (b - a) * i as i128 / n as i128)).collect(),
+		_ => {
+			let prefix = start.iter().zip(end).take_while(|(x, y)| x == y).count();
+			let (a, b) = (window(start, prefix) as u128, window(end, prefix) as u128);
+			(1..n)
+				.map(|i| {
+					let p = (a + (b - a) * i as u128 / n as u128) as u64;
+					let bytes = p.to_be_bytes();
+					let len = 8 - bytes.iter().rev().take_while(|&&b| b == 0).count();
+					[&start[..prefix], &bytes[..len]].concat()
+				})
+				.collect()
+		}
+	};
+
+	let mut out = vec![start.to_vec()];
+	for p in points {
+		if p > *out.last().unwrap() && p.as_slice() < end {
+			out.push(p);
+		}
+	}
+	out.push(end.to_vec());
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn keeps_order_within_a_shard(x: i64, y: i64, tenant: u32, shards: u16) -> bool {
+		let shards = shards.max(1);
+		let tenant = tenant.to_be_bytes();
+		let (a, b) = (shard_key_by(&pack_int(x), shards, &tenant), shard_key_by(&pack_int(y), shards, &tenant));
+		let (sa, ka) = unshard_key(&a).unwrap();
+		sa < shards && ka == &pack_int(x)[..] && x.cmp(&y) == a.cmp(&b)
+	}
+
+	#[test]
+	fn ranges_cover_every_shard() {
+		let (start, end) = (pack_int(-10), pack_int(10));
+		let ranges = shard_ranges(&start, &end, 300);
+		assert_eq!(ranges.len(), 300);
+		for v in -10..10 {
+			let key = shard_key(&pack_int(v), 300);
+			let (shard, _) = unshard_key(&key).unwrap();
+			let (lo, hi) = &ranges[shard as usize];
+			assert!(lo <= &key && &key < hi);
+		}
+		assert_eq!(shard_of(b"tenant-7", 16), shard_of(b"tenant-7", 16));
+	}
+
+	#[quickcheck]
+	fn split_points_are_ordered(start: Vec<u8>, end: Vec<u8>, x: i64, y: i64, n: u8) -> bool {
+		let n = n as usize % 16 + 1;
+		let ordered = |bounds: Vec<Vec<u8>>, lo: &[u8], hi: &[u8]| {
+			bounds.is_empty() && lo >= hi ||
+				bounds.len() <= n + 1 && bounds[0] == lo && bounds[bounds.len() - 1] == hi &&
+					bounds.windows(2).all(|w| w[0] < w[1])
+		};
+		ordered(split_range(&start, &end, n), &start, &end) &&
+			ordered(split_range(&pack_int(x), &pack_int(y), n), &pack_int(x), &pack_int(y))
+	}
+
+	#[test]
+	fn splits_values_evenly() {
+		assert_eq!(split_range(&pack_int(-100), &pack_uint(u64::MAX), 1), vec![pack_int(-100), pack_uint(u64::MAX)]);
+		let bounds: Vec<i64> = split_range(&pack_int(-100), &pack_int(300), 4)
+			.iter()
+			.map(|k| decode_int(k).unwrap().0)
+			.collect();
+		assert_eq!(bounds, vec![-100, 0, 100, 200, 300]);
+		assert_eq!(split_range(b"user/a", b"user/c", 2), vec![b"user/a".to_vec(), b"user/b".to_vec(), b"user/c".to_vec()]);
+		assert_eq!(split_range(&pack_int(1), &pack_int(2), 8).len(), 2);
+	}
+
+	#[quickcheck]
+	fn steps_to_the_neighbouring_key(prefix: Vec<i64>, x: i64, wide: bool) -> bool {
+		let mut key: Vec<u8> = prefix.iter().flat_map(|&v| pack_int(v)).collect();
+		let len = key.len();
+		let v = if wide { x as u64 | 1 << 63 } else { x as u64 };
+		key.extend(if wide { pack_uint(v) } else { pack_int(x) });
+		let (next, prev) = (successor(&key).unwrap(), predecessor(&key).unwrap());
+		let value = |k: &[u8]| as_value(&k[len..]).unwrap();
+		let v = value(&key);
+		prev < key && key < next && next[..len] == key[..len] && prev[..len] == key[..len] &&
+			(v == u64::MAX as i128 || value(&next) == v + 1) && (v == i64::MIN as i128 || value(&prev) == v - 1)
+	}
+
+	#[test]
+	fn steps_across_classes_and_into_the_sentinels() {
+		// 63 is the largest one-byte value, 8255 the largest two-byte one
+		assert_eq!(successor(&pack_uint(63)), Ok(pack_uint(64)));
+		assert_eq!(predecessor(&pack_uint(8256)), Ok(pack_uint(8255)));
+		assert_eq!(predecessor(&pack_int(0)), Ok(pack_int(-1)));
+		assert_eq!(successor(&pack_int(i64::MAX)), Ok(pack_uint(1 << 63)));
+		let key = [pack_int(7), pack_uint(u64::MAX)].concat();
+		assert_eq!(successor(&key), Ok([&pack_int(7)[..], &SENTINEL_AFTER_ALL].concat()));
+		assert_eq!(predecessor(&pack_int(i64::MIN)), Ok(SENTINEL_BEFORE_ALL.to_vec()));
+		assert_eq!(successor(&[0x81, 0xc0]), Err(DecodeError::Truncated { needed: 3 }));
+		assert_eq!(predecessor(&[]), Err(DecodeError::Truncated { needed: 1 }));
+		assert_eq!(successor(&[0xff]), Err(DecodeError::InvalidMarker(0xff)));
+	}
+
+	#[quickcheck]
+	fn separators_are_short_and_between(a: Vec<i64>, b: Vec<i64>, shared: Vec<i64>) -> bool {
+		let key = |vs: &[i64]| shared.iter().chain(vs).flat_map(|&v| pack_int(v)).collect::<Vec<u8>>();
+		let (a, b) = (key(&a).min(key(&b)), key(&a).max(key(&b)));
+		if a == b {
+			return true;
+		}
+		let s = shortest_separator(&a, &b).unwrap();
+		let (vs, vb) = (values(&s).unwrap(), values(&b).unwrap());
+		// the last value takes no more bytes than `b`'s in its place
+		let b_end = vb.get(vs.len()).map_or(b.len(), |&(start, _)| start);
+		a < s && s <= b && s.len() <= b_end
+	}
+
+	#[test]
+	fn separates_by_the_shortest_value() {
+		let key = |vs: &[i64]| vs.iter().flat_map(|&v| pack_int(v)).collect::<Vec<u8>>();
+		// zero is the shortest value of all
+		assert_eq!(shortest_separator(&key(&[1, -5000, 9]), &key(&[1, 5000])), Ok(key(&[1, 0])));
+		assert_eq!(shortest_separator(&key(&[1, 5000, 9]), &key(&[1, 90_000])), Ok(key(&[1, 5001])));
+		assert_eq!(shortest_separator(&key(&[-90_000]), &key(&[-5000, 3])), Ok(key(&[-5000])));
+		assert_eq!(shortest_separator(&key(&[7]), &key(&[7, -90_000, 2])), Ok(key(&[7, -90_000])));
+		assert_eq!(shortest_separator(&[], &key(&[300])), Ok(key(&[0])));
+		assert_eq!(shortest_separator(&key(&[7]), &[0x87, 0xc0]), Err(DecodeError::Truncated { needed: 3 }));
+		// both decode to 8256, the second from a longer encoding
+		assert_eq!(shortest_separator(&[0xe1, 0x00], &[0xe2, 0x00, 0x00]), Ok(vec![0xe2, 0x00, 0x00]));
+		assert_eq!(shortest_separator(&key(&[8256, 1]), &[0xe2, 0x00, 0x00]), Ok(vec![0xe2, 0x00, 0x00]));
+	}
+
+	#[test]
+	#[should_panic(expected = "a separator needs a < b")]
+	fn separators_need_ordered_keys() {
+		let _ = shortest_separator(&pack_int(2), &pack_int(1));
+	}
+}
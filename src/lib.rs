@@ -1,10 +1,131 @@
-#[cfg(test)]
+//! Order-preserving variable-length integers.
+//!
+//! Without the default `std` feature the crate is `no_std` and keeps only
+//! the core codec, which never allocates: `pack_uint_into_array`,
+//! `pack_uint_into_slice`, `decode_uint`, their signed counterparts and
+//! the length functions. The `alloc` feature adds the `Vec` conveniences
+//! such as `pack_uint`; everything else needs `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+// The only unsafe code is in the simd decoders and in mapping files.
+#![cfg_attr(not(any(feature = "simd", feature = "mmap")), forbid(unsafe_code))]
+#![cfg_attr(any(feature = "simd", feature = "mmap"), deny(unsafe_code))]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+// Lets the code `#[derive(OrderedKey)]` generates name this crate from
+// inside it.
+#[cfg(feature = "derive")]
+extern crate self as rust_varint;
+
+#[cfg(all(test, feature = "alloc"))]
 extern crate quickcheck;
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 #[macro_use(quickcheck)]
 extern crate quickcheck_macros;
 
-use std::mem;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::mem;
+
+#[cfg(feature = "std")]
+#[macro_use]
+mod trace;
+mod error;
+#[cfg(feature = "std")]
+mod crc32;
+#[cfg(feature = "std")]
+pub mod array;
+#[cfg(feature = "num-bigint")]
+pub mod bigint;
+#[cfg(feature = "std")]
+pub mod bits;
+#[cfg(feature = "std")]
+pub mod bitvec;
+#[cfg(feature = "std")]
+pub mod block;
+#[cfg(feature = "bytes")]
+pub mod buf;
+#[cfg(feature = "std")]
+pub mod column;
+#[cfg(feature = "tokio-util")]
+pub mod codec;
+#[cfg(feature = "integer-encoding-compat")]
+pub mod compat;
+#[cfg(feature = "std")]
+pub mod compact;
+#[cfg(feature = "std")]
+pub mod conformance;
+#[cfg(any(feature = "lz4", feature = "zstd"))]
+pub mod compress;
+#[cfg(feature = "std")]
+pub mod convert;
+pub mod custom;
+#[cfg(feature = "std")]
+pub mod decimal;
+#[cfg(feature = "std")]
+pub mod delta;
+#[cfg(feature = "std")]
+pub mod diagnostic;
+#[cfg(feature = "std")]
+pub mod eliasfano;
+#[cfg(feature = "std")]
+pub mod formats;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod iter;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod key;
+#[cfg(feature = "std")]
+pub mod keyblock;
+#[cfg(feature = "std")]
+pub mod keyspace;
+#[cfg(feature = "std")]
+pub mod log;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "std")]
+pub mod narrow;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod record;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(feature = "std")]
+pub mod snowflake;
+#[cfg(feature = "std")]
+pub mod sortable;
+#[cfg(feature = "std")]
+pub mod sparse;
+#[cfg(feature = "std")]
+pub mod sst;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod tagged;
+#[cfg(feature = "std")]
+pub mod text;
+#[cfg(feature = "std")]
+pub mod timeseries;
+#[cfg(feature = "std")]
+pub mod timestamp;
+pub mod varint;
+#[cfg(feature = "std")]
+pub mod version;
+pub mod wide;
+pub mod zigzag;
+
+pub use error::DecodeError;
+pub use zigzag::{zigzag_decode, zigzag_encode};
+#[cfg(feature = "serde")]
+pub use serialize::{from_slice, serde_i128, serde_i64, serde_u128, serde_u64, to_vec};
 
 /*
  * Variable-length integer encoding.
@@ -33,43 +154,35 @@ const POS_1BYTE_MARKER:u8 = 0x80;
 const POS_2BYTE_MARKER:u8 = 0xc0;
 const POS_MULTI_MARKER:u8 = 0xe0;
 
-const NEG_1BYTE_MIN:i64 = (-(1 << 6));
-const NEG_2BYTE_MIN:i64 = (-(1 << 13) + NEG_1BYTE_MIN);
-const POS_1BYTE_MAX:u64 = ((1 << 6) - 1);
-const POS_2BYTE_MAX:u64 = ((1 << 13) + POS_1BYTE_MAX);
+const NEG_1BYTE_MIN:i64 = -(1 << 6);
+const NEG_2BYTE_MIN:i64 = -(1 << 13) + NEG_1BYTE_MIN;
+const POS_1BYTE_MAX:u64 = (1 << 6) - 1;
+const POS_2BYTE_MAX:u64 = (1 << 13) + POS_1BYTE_MAX;
 
 const INTPACK64_MAXSIZE:usize = mem::size_of::<u64>() + 1;
 
-fn get_posint_bits(x: u64, start: usize, end: usize) -> u8 {
-	return ((x & ((1u64 << (start)) - 1u64)) >> (end)) as u8
-}
-
-fn get_negint_bits(x: i64, start: usize, end: usize) -> u8 {
-	return ((x & ((1i64 << (start)) - 1i64)) >> (end)) as u8
-}
-
-fn pack_posint_into(x: u64, res: &mut Vec<u8>) {
-
-	let mut len = size_posint(x);
-	let mut shift = (len - 1) << 3;
+/// Longest encoding of any value, in bytes.
+pub const MAX_ENCODED_LEN: usize = INTPACK64_MAXSIZE;
 
-	res[0] |= (len & 0xf) as u8;
-	let mut index = 1;
-
-	loop {
-		res[index] = (x >> shift) as u8;
+/// A key that sorts before the encoding of every value, in either order
+/// and in `wide`, taken from the free range below the markers. Useful as
+/// the lower bound of a range scan. Decoding it fails with
+/// `InvalidMarker`.
+pub const SENTINEL_BEFORE_ALL: [u8; 1] = [0x00];
 
-		// update loop variable
-	    shift -= 8;
-	    index += 1;
-	    len -= 1;
+/// A key that sorts after the encoding of every value, see
+/// `SENTINEL_BEFORE_ALL`.
+pub const SENTINEL_AFTER_ALL: [u8; 1] = [0xff];
 
-	    if len == 0 { break; }
-	}
+fn get_posint_bits(x: u64, start: usize, end: usize) -> u8 {
+	((x & ((1u64 << (start)) - 1u64)) >> (end)) as u8
+}
 
+fn get_negint_bits(x: i64, start: usize, end: usize) -> u8 {
+	((x & ((1i64 << (start)) - 1i64)) >> (end)) as u8
 }
 
-fn unpack_posint_from(res: &Vec<u8>) -> u64 {
+fn unpack_posint_from(res: &[u8]) -> u64 {
 	let mut x: u64 = 0;
 	let mut len = res[0] & 0xf;
 	let mut index = 1;
@@ -81,35 +194,13 @@ fn unpack_posint_from(res: &Vec<u8>) -> u64 {
 		if len == 0 { break; }
 	}
 
-	return x;
-}
-
-fn pack_negint_into(x: i64, res: &mut Vec<u8>) {
-
-	let lz = lz_negint(x);
-	let mut len = size_negint(x);
-	let mut shift = (len - 1) << 3;
-
-	res[0] |= (lz & 0xf) as u8;
-	let mut index = 1;
-
-	loop {
-		res[index] = (x >> shift) as u8;
-
-		// update loop variable
-	    shift -= 8;
-	    index += 1;
-	    len -= 1;
-
-	    if len == 0 { break; }
-	}
-
+	x
 }
 
-fn unpack_negint_from(res: &Vec<u8>) -> i64 {
+fn unpack_negint_from(res: &[u8]) -> i64 {
 	let mut len = mem::size_of::<u64>() as u8 - (res[0] & 0xf);
 	let mut index = 1;
-	let mut x: u64 = std::u64::MAX;
+	let mut x: u64 = u64::MAX;
 
 	loop {
 		x = (x << 8) | res[index] as u64;
@@ -118,10 +209,11 @@ fn unpack_negint_from(res: &Vec<u8>) -> i64 {
 		if len == 0 { break; }
 	}
 
-	unsafe { *(&x as *const u64 as *const i64) }
+	x as i64
 }
 
-pub fn unpack_uint(res: &Vec<u8>) -> u64 {
+// Decodes a value whose marker and length have already been checked.
+fn unpack_uint_from(res: &[u8]) -> u64 {
 	let mut x: u64;
 	let marker = res[0] & 0xf0;
 
@@ -140,13 +232,13 @@ pub fn unpack_uint(res: &Vec<u8>) -> u64 {
 		x = unpack_posint_from(res);
 		x += POS_2BYTE_MAX + 1;
 	} else {
-		unimplemented!()
+		unreachable!("marker checked by uint_len_from_marker")
 	}
 
-	return x;
+	x
 }
 
-pub fn unpack_int(res: &Vec<u8>) -> i64 {
+fn unpack_int_from(res: &[u8]) -> i64 {
 	let mut x: i64;
 	let marker = res[0] & 0xf0;
 
@@ -166,93 +258,590 @@ pub fn unpack_int(res: &Vec<u8>) -> i64 {
 		x = NEG_1BYTE_MIN + get_negint_bits(res[0] as i64, 6, 0) as i64;
 	}
 	else {
-		let y = unpack_uint(res);
-		x = unsafe { *(&y as *const u64 as *const i64) }
+		x = unpack_uint_from(res) as i64;
 	}
 
-	return x;
+	x
 }
 
-pub fn pack_uint(x: u64) -> Vec<u8> {
+// Encoded length of an unsigned value, derived from its first byte.
+pub(crate) fn uint_len_from_marker(first: u8) -> Result<usize, DecodeError> {
+	match first & 0xf0 {
+		0x80 | 0x90 | 0xa0 | 0xb0 => Ok(1),
+		0xc0 | 0xd0 => Ok(2),
+		POS_MULTI_MARKER => match (first & 0xf) as usize {
+			0 => Err(DecodeError::InvalidMarker(first)),
+			len if len > mem::size_of::<u64>() => Err(DecodeError::Overflow),
+			len => Ok(len + 1),
+		},
+		_ => Err(DecodeError::InvalidMarker(first)),
+	}
+}
 
-	let len = size_uint(x);
-	let mut res: Vec<u8> = vec![0; len];
+// Encoded length of a signed value, derived from its first byte.
+pub(crate) fn int_len_from_marker(first: u8) -> Result<usize, DecodeError> {
+	match first & 0xf0 {
+		NEG_MULTI_MARKER => match (first & 0xf) as usize {
+			lz if lz >= mem::size_of::<u64>() => Err(DecodeError::InvalidMarker(first)),
+			lz => Ok(INTPACK64_MAXSIZE - lz),
+		},
+		0x20 | 0x30 => Ok(2),
+		0x40 | 0x50 | 0x60 | 0x70 => Ok(1),
+		_ => uint_len_from_marker(first),
+	}
+}
 
-	if x <= POS_1BYTE_MAX {
-		res[0] = POS_1BYTE_MARKER | get_posint_bits(x, 6, 0);
+/// Full encoded length of the value whose first byte is `first`, for
+/// framing code that has read only that byte. Signed and unsigned values
+/// share their markers, so this holds for either.
+///
+/// Fails with `InvalidMarker` for the free markers and `Overflow` for a
+/// length past eight payload bytes.
+pub fn encoded_len_from_marker(first: u8) -> Result<usize, DecodeError> {
+	int_len_from_marker(first)
+}
+
+fn check_len(buf: &[u8], len: usize) -> Result<(), DecodeError> {
+	if buf.len() < len {
+		return Err(DecodeError::Truncated { needed: len });
 	}
-	else if x <= POS_2BYTE_MAX {
-		let mut y = x;
-		y -= POS_1BYTE_MAX + 1;
-		res[0] = POS_2BYTE_MARKER | get_posint_bits(y, 13, 8);
-		res[1] = get_posint_bits(y, 8, 0);
+	Ok(())
+}
+
+/// Decodes one unsigned value from the front of `buf`, returning it with
+/// the number of bytes it occupied. Never panics on malformed input: the
+/// length the marker claims is checked against `buf` before any payload
+/// byte is read, so it is safe on untrusted frames.
+pub fn decode_uint(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = uint_len_from_marker(first)?;
+	check_len(buf, len)?;
+
+	if first & 0xf0 == POS_MULTI_MARKER {
+		let y = unpack_posint_from(&buf[..len]);
+		let x = y.checked_add(POS_2BYTE_MAX + 1).ok_or(DecodeError::Overflow)?;
+		return Ok((x, len));
 	}
-	else if x == POS_2BYTE_MAX + 1 {
-		res[0] = POS_MULTI_MARKER | 0x1;
-		res[1] = 0;
+	Ok((unpack_uint_from(&buf[..len]), len))
+}
+
+/// Signed counterpart of `decode_uint`.
+pub fn decode_int(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	if first >= POS_1BYTE_MARKER {
+		let (x, len) = decode_uint(buf)?;
+		if x > i64::MAX as u64 {
+			return Err(DecodeError::Overflow);
+		}
+		return Ok((x as i64, len));
 	}
-	else {
-		let mut y = x;
-		y -= POS_2BYTE_MAX + 1;
-		res[0] = POS_MULTI_MARKER;
-		pack_posint_into(y, &mut res);
+
+	let len = int_len_from_marker(first)?;
+	check_len(buf, len)?;
+	Ok((unpack_int_from(&buf[..len]), len))
+}
+
+/// Decodes the unsigned value at `*pos` and moves `pos` past it, for
+/// walking values packed back to back. `Truncated` counts from the start
+/// of `buf`, and `pos` is left alone on error.
+pub fn decode_uint_at(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+	let (x, used) = decode_uint(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(x)
+}
+
+/// Signed counterpart of `decode_uint_at`.
+pub fn decode_int_at(buf: &[u8], pos: &mut usize) -> Result<i64, DecodeError> {
+	let (x, used) = decode_int(buf.get(*pos..).unwrap_or(&[])).map_err(|e| at_offset(e, *pos))?;
+	*pos += used;
+	Ok(x)
+}
+
+fn at_offset(e: DecodeError, pos: usize) -> DecodeError {
+	match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+		e => e,
+	}
+}
+
+/// Decodes the unsigned value at the front of `res`.
+pub fn try_unpack_uint(res: &[u8]) -> Result<u64, DecodeError> {
+	decode_uint(res).map(|(x, _)| x)
+}
+
+/// Decodes the signed value at the front of `res`.
+pub fn try_unpack_int(res: &[u8]) -> Result<i64, DecodeError> {
+	decode_int(res).map(|(x, _)| x)
+}
+
+/// Panicking `try_unpack_uint`, for input known to be well-formed.
+pub fn unpack_uint(res: &[u8]) -> u64 {
+	try_unpack_uint(res).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Panicking `try_unpack_int`.
+pub fn unpack_int(res: &[u8]) -> i64 {
+	try_unpack_int(res).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like `try_unpack_uint`, but also rejects a value not in the shortest
+/// encoding `pack_uint` gives it, e.g. a payload with a leading zero byte.
+/// Each accepted value then has exactly one encoding, so the bytes can
+/// stand in for it as a key. Bytes after the value are not checked.
+pub fn unpack_uint_strict(res: &[u8]) -> Result<u64, DecodeError> {
+	let (x, len) = decode_uint(res)?;
+	let mut canonical = [0; MAX_ENCODED_LEN];
+	let canonical_len = pack_uint_into_array(x, &mut canonical);
+	check_canonical(&res[..len], &canonical[..canonical_len])?;
+	Ok(x)
+}
+
+/// Signed counterpart of `unpack_uint_strict`. It also rejects values
+/// that fit a shorter class, like `-1` in a multi-byte negative marker.
+pub fn unpack_int_strict(res: &[u8]) -> Result<i64, DecodeError> {
+	let (x, len) = decode_int(res)?;
+	let mut canonical = [0; MAX_ENCODED_LEN];
+	let canonical_len = pack_int_into_array(x, &mut canonical);
+	check_canonical(&res[..len], &canonical[..canonical_len])?;
+	Ok(x)
+}
+
+fn check_canonical(found: &[u8], canonical: &[u8]) -> Result<(), DecodeError> {
+	if found != canonical {
+		return Err(DecodeError::Malformed("value is not in its shortest encoding"));
+	}
+	Ok(())
+}
+
+/// Number of bytes `pack_uint` produces for `x`.
+pub fn encoded_len_u64(x: u64) -> usize {
+	size_uint(x)
+}
+
+/// Number of bytes `pack_int` produces for `x`.
+pub fn encoded_len_i64(x: i64) -> usize {
+	size_int(x)
+}
+
+/// Exact number of bytes `pack_int` produces for all of `values`, for
+/// reserving buffers before encoding.
+pub fn estimate_encoded_size(values: &[i64]) -> usize {
+	values.iter().map(|&v| size_int(v)).sum()
+}
+
+/// Like `estimate_encoded_size`, but measures only about `samples` evenly
+/// spaced values and scales up. Exact when `values` has no more than
+/// `samples` elements; data whose period divides the spacing skews it.
+pub fn estimate_encoded_size_sampled(values: &[i64], samples: usize) -> usize {
+	if values.len() <= samples {
+		return estimate_encoded_size(values);
+	}
+	let stride = values.len() / samples.max(1);
+	let (n, bytes) = values.iter().step_by(stride).fold((0, 0), |(n, bytes), &v| (n + 1, bytes + size_int(v)));
+	bytes * values.len() / n
+}
+
+/// Every `n`-th unsigned value of a packed stream, starting with the
+/// first. Values in between are skipped by the length in their marker
+/// byte without being decoded. Iteration stops at the first malformed or
+/// truncated value.
+pub fn sample_every(buf: &[u8], n: usize) -> impl Iterator<Item = u64> + '_ {
+	assert!(n > 0, "sample stride must be positive");
+	let mut pos = 0;
+	core::iter::from_fn(move || {
+		let (v, used) = decode_uint(buf.get(pos..)?).ok()?;
+		pos += used;
+		for _ in 1..n {
+			match buf.get(pos).map(|&first| uint_len_from_marker(first)) {
+				Some(Ok(len)) => pos += len,
+				_ => {
+					pos = buf.len();
+					break;
+				}
+			}
+		}
+		Some(v)
+	})
+}
+
+/// Adds one to the single signed value encoded in `buf`. The bytes are
+/// bumped in place unless the carry leaves the value's payload, e.g. when
+/// it moves to a longer length class; then the value is re-encoded.
+#[cfg(feature = "alloc")]
+pub fn increment_encoded(buf: &mut Vec<u8>) -> Result<(), DecodeError> {
+	step_encoded(buf, 1)
+}
+
+/// Subtracts one from the single signed value encoded in `buf`, see
+/// `increment_encoded`.
+#[cfg(feature = "alloc")]
+pub fn decrement_encoded(buf: &mut Vec<u8>) -> Result<(), DecodeError> {
+	step_encoded(buf, -1)
+}
+
+#[cfg(feature = "alloc")]
+fn step_encoded(buf: &mut Vec<u8>, step: i64) -> Result<(), DecodeError> {
+	let (x, len) = decode_int(buf)?;
+	if len != buf.len() {
+		return Err(DecodeError::Malformed("trailing bytes after the encoded value"));
+	}
+	let y = x.checked_add(step).ok_or(DecodeError::Overflow)?;
+
+	// Within a length class consecutive values have consecutive payloads,
+	// but the marker byte of a multi-byte value must not take a carry.
+	let (wraps, reset) = if step > 0 { (0xff, 0x00) } else { (0x00, 0xff) };
+	let first = if len == 1 { 0 } else { 1 };
+	match (first..len).rev().find(|&i| buf[i] != wraps) {
+		Some(i) if size_int(y) == len => {
+			buf[i] = if step > 0 { buf[i] + 1 } else { buf[i] - 1 };
+			for b in &mut buf[i + 1..] {
+				*b = reset;
+			}
+		}
+		_ => {
+			buf.clear();
+			buf.extend_from_slice(&pack_int(y));
+		}
 	}
+	Ok(())
+}
 
-	return res;
+#[cfg(feature = "alloc")]
+pub fn pack_uint(x: u64) -> Vec<u8> {
+	let mut res = [0; INTPACK64_MAXSIZE];
+	let len = pack_uint_into_array(x, &mut res);
+	res[..len].to_vec()
 }
 
+#[cfg(feature = "alloc")]
 pub fn pack_int(x: i64) -> Vec<u8> {
+	let mut res = [0; INTPACK64_MAXSIZE];
+	let len = pack_int_into_array(x, &mut res);
+	res[..len].to_vec()
+}
 
-	// Short circuit positive integer first
-	if x >= 0 {
-		return pack_uint(x as u64);
+/// Encodes `x` onto the end of `out`, for keys built from several values.
+#[cfg(feature = "alloc")]
+pub fn pack_uint_append(x: u64, out: &mut Vec<u8>) {
+	let mut res = [0; INTPACK64_MAXSIZE];
+	let len = pack_uint_into_array(x, &mut res);
+	out.extend_from_slice(&res[..len]);
+}
+
+/// Signed counterpart of `pack_uint_append`.
+#[cfg(feature = "alloc")]
+pub fn pack_int_append(x: i64, out: &mut Vec<u8>) {
+	let mut res = [0; INTPACK64_MAXSIZE];
+	let len = pack_int_into_array(x, &mut res);
+	out.extend_from_slice(&res[..len]);
+}
+
+/// `pack_uint` with every byte complemented, so the encodings sort in
+/// the reverse order of the values, for descending index keys. Lengths
+/// are those of `pack_uint`.
+#[cfg(feature = "alloc")]
+pub fn pack_uint_desc(x: u64) -> Vec<u8> {
+	let mut res = pack_uint(x);
+	complement(&mut res);
+	res
+}
+
+/// Signed counterpart of `pack_uint_desc`.
+#[cfg(feature = "alloc")]
+pub fn pack_int_desc(x: i64) -> Vec<u8> {
+	let mut res = pack_int(x);
+	complement(&mut res);
+	res
+}
+
+/// Decodes one value written by `pack_uint_desc`, returning it with the
+/// number of bytes it occupied.
+pub fn decode_uint_desc(buf: &[u8]) -> Result<(u64, usize), DecodeError> {
+	decode_uint(&uncomplement(buf, uint_len_from_marker)?)
+}
+
+/// Signed counterpart of `decode_uint_desc`.
+pub fn decode_int_desc(buf: &[u8]) -> Result<(i64, usize), DecodeError> {
+	decode_int(&uncomplement(buf, int_len_from_marker)?)
+}
+
+/// Where `pack_opt_u64` and `pack_opt_i64` sort `None`, like SQL's
+/// `NULLS FIRST` and `NULLS LAST`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullPlacement {
+	/// Encoded as `0x01`, before every value.
+	First,
+	/// Encoded as `0xfe`, after every value.
+	Last,
+}
+
+// Free markers, inside the sentinels so that those still bound every key.
+const NULL_FIRST: u8 = 0x01;
+const NULL_LAST: u8 = 0xfe;
+
+/// Encodes a nullable value: `Some(x)` as `pack_uint` does, `None` as the
+/// one byte `nulls` picks.
+#[cfg(feature = "alloc")]
+pub fn pack_opt_u64(x: Option<u64>, nulls: NullPlacement) -> Vec<u8> {
+	x.map_or_else(|| alloc::vec![null_byte(nulls)], pack_uint)
+}
+
+/// Signed counterpart of `pack_opt_u64`.
+#[cfg(feature = "alloc")]
+pub fn pack_opt_i64(x: Option<i64>, nulls: NullPlacement) -> Vec<u8> {
+	x.map_or_else(|| alloc::vec![null_byte(nulls)], pack_int)
+}
+
+#[cfg(feature = "alloc")]
+fn null_byte(nulls: NullPlacement) -> u8 {
+	match nulls {
+		NullPlacement::First => NULL_FIRST,
+		NullPlacement::Last => NULL_LAST,
 	}
+}
 
-	let len = size_int(x);
-	let mut res: Vec<u8> = vec![0; len];
+/// Decodes one value written by `pack_opt_u64`, with either placement of
+/// `None`, returning it with the number of bytes it occupied.
+pub fn decode_opt_u64(buf: &[u8]) -> Result<(Option<u64>, usize), DecodeError> {
+	match buf.first() {
+		Some(&NULL_FIRST) | Some(&NULL_LAST) => Ok((None, 1)),
+		_ => decode_uint(buf).map(|(x, used)| (Some(x), used)),
+	}
+}
 
-	if x < NEG_2BYTE_MIN {
-		res[0] = NEG_MULTI_MARKER;
-		pack_negint_into(x, &mut res);
+/// Signed counterpart of `decode_opt_u64`.
+pub fn decode_opt_i64(buf: &[u8]) -> Result<(Option<i64>, usize), DecodeError> {
+	match buf.first() {
+		Some(&NULL_FIRST) | Some(&NULL_LAST) => Ok((None, 1)),
+		_ => decode_int(buf).map(|(x, used)| (Some(x), used)),
+	}
+}
+
+fn complement(buf: &mut [u8]) {
+	buf.iter_mut().for_each(|b| *b = !*b);
+}
+
+// Copies out and complements the descending value at the front of `buf`.
+fn uncomplement<L>(buf: &[u8], len_from_marker: L) -> Result<[u8; MAX_ENCODED_LEN], DecodeError>
+where
+	L: Fn(u8) -> Result<usize, DecodeError>,
+{
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = len_from_marker(!first).map_err(|e| match e {
+		DecodeError::InvalidMarker(_) => DecodeError::InvalidMarker(first),
+		e => e,
+	})?;
+	check_len(buf, len)?;
+	let mut res = [0; MAX_ENCODED_LEN];
+	res[..len].copy_from_slice(&buf[..len]);
+	complement(&mut res[..len]);
+	Ok(res)
+}
+
+/// Packs every value of `iter` densely into one buffer.
+#[cfg(feature = "alloc")]
+pub fn pack_all<I: IntoIterator<Item = u64>>(iter: I) -> Vec<u8> {
+	let mut out = Vec::new();
+	pack_all_into(iter, &mut out);
+	out
+}
+
+/// Packs every value of `iter` onto the end of `out`. Room for the values
+/// the iterator promises is reserved up front, at one byte each; exact
+/// for small values, and otherwise a floor that saves the early regrowth.
+#[cfg(feature = "alloc")]
+pub fn pack_all_into<I: IntoIterator<Item = u64>>(iter: I, out: &mut Vec<u8>) {
+	let iter = iter.into_iter();
+	out.reserve(iter.size_hint().0);
+	for x in iter {
+		pack_uint_append(x, out);
+	}
+}
+
+/// Packs a whole block of values into a buffer sized exactly for them.
+#[cfg(feature = "alloc")]
+pub fn pack_slice(values: &[u64]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(values.iter().map(|&x| size_uint(x)).sum());
+	let mut res = [0; MAX_ENCODED_LEN];
+	for &x in values {
+		let len = pack_uint_into_array(x, &mut res);
+		out.extend_from_slice(&res[..len]);
 	}
-	else if x < NEG_1BYTE_MIN {
-		let mut y = x;
-		y -= NEG_2BYTE_MIN;
-		res[0] = NEG_2BYTE_MARKER | get_negint_bits(y, 13, 8);
-		res[1] = get_negint_bits(y, 8, 0);
+	out
+}
+
+/// Decodes values from the front of `buf` into `out` until either runs
+/// out, returning how many were written. `Truncated` counts from the
+/// start of `buf`; use `decode_uint_at` to resume part way.
+///
+/// With the `simd` feature on x86-64, runs of one-byte values are decoded
+/// sixteen bytes at a time.
+pub fn unpack_into(buf: &[u8], out: &mut [u64]) -> Result<usize, DecodeError> {
+	let (mut pos, mut n) = (0, 0);
+	while n < out.len() && pos < buf.len() {
+		#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+		#[allow(unsafe_code)]
+		{
+			// SAFETY: SSE2 is part of the x86-64 baseline.
+			let (used, written) = unsafe { simd::decode_short_runs(&buf[pos..], &mut out[n..]) };
+			pos += used;
+			n += written;
+			if n == out.len() || pos == buf.len() {
+				break;
+			}
+		}
+		out[n] = decode_uint_at(buf, &mut pos)?;
+		n += 1;
+	}
+	Ok(n)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[allow(unsafe_code)]
+mod simd {
+	use core::arch::x86_64::*;
+
+	/// Decodes the run of one-byte values at the front of `buf`, sixteen
+	/// bytes at a time while that many remain, returning the bytes
+	/// consumed and values written.
+	#[target_feature(enable = "sse2")]
+	pub(super) unsafe fn decode_short_runs(buf: &[u8], out: &mut [u64]) -> (usize, usize) {
+		let (mut pos, mut n) = (0, 0);
+		let mut values = [0u64; 16];
+		let zero = _mm_setzero_si128();
+		while pos + 16 <= buf.len() && n < out.len() {
+			let data = _mm_loadu_si128(buf.as_ptr().add(pos) as *const __m128i);
+			// one-byte values are the bytes 10xxxxxx
+			let tag = _mm_and_si128(data, _mm_set1_epi8(0xc0u8 as i8));
+			let short = _mm_movemask_epi8(_mm_cmpeq_epi8(tag, _mm_set1_epi8(0x80u8 as i8))) as u32;
+			let run = ((!short).trailing_zeros() as usize).min(out.len() - n);
+			if run == 0 {
+				break;
+			}
+			let payload = _mm_and_si128(data, _mm_set1_epi8(0x3f));
+			let halves = [_mm_unpacklo_epi8(payload, zero), _mm_unpackhi_epi8(payload, zero)];
+			for (i, half) in halves.iter().enumerate() {
+				let quads = [_mm_unpacklo_epi16(*half, zero), _mm_unpackhi_epi16(*half, zero)];
+				for (j, quad) in quads.iter().enumerate() {
+					let pairs = [_mm_unpacklo_epi32(*quad, zero), _mm_unpackhi_epi32(*quad, zero)];
+					for (k, pair) in pairs.iter().enumerate() {
+						_mm_storeu_si128(values.as_mut_ptr().add(8 * i + 4 * j + 2 * k) as *mut __m128i, *pair);
+					}
+				}
+			}
+			out[n..n + run].copy_from_slice(&values[..run]);
+			pos += run;
+			n += run;
+		}
+		(pos, n)
+	}
+}
+
+/// Encodes `x` into the front of `out`, returning the encoded length, or
+/// `None` if `out` is too short to hold it.
+pub fn pack_uint_into_slice(x: u64, out: &mut [u8]) -> Option<usize> {
+	let mut res = [0; MAX_ENCODED_LEN];
+	let len = pack_uint_into_array(x, &mut res);
+	out.get_mut(..len)?.copy_from_slice(&res[..len]);
+	Some(len)
+}
+
+/// Signed counterpart of `pack_uint_into_slice`.
+pub fn pack_int_into_slice(x: i64, out: &mut [u8]) -> Option<usize> {
+	let mut res = [0; MAX_ENCODED_LEN];
+	let len = pack_int_into_array(x, &mut res);
+	out.get_mut(..len)?.copy_from_slice(&res[..len]);
+	Some(len)
+}
+
+/// Encodes `x` into the front of `res` without allocating, returning the
+/// encoded length. Bytes past it are left zeroed.
+pub fn pack_uint_into_array(x: u64, res: &mut [u8; MAX_ENCODED_LEN]) -> usize {
+	if x <= POS_1BYTE_MAX {
+		*res = [0; MAX_ENCODED_LEN];
+		res[0] = POS_1BYTE_MARKER | x as u8;
+		return 1;
+	}
+	if x <= POS_2BYTE_MAX {
+		let y = x - (POS_1BYTE_MAX + 1);
+		*res = [0; MAX_ENCODED_LEN];
+		res[0] = POS_2BYTE_MARKER | (y >> 8) as u8;
+		res[1] = y as u8;
+		return 2;
+	}
+
+	// The payload is `y` in its significant bytes, at least one, shifted
+	// to the top of the word so one big-endian store writes it and zeroes
+	// the bytes after it.
+	let y = x - (POS_2BYTE_MAX + 1);
+	let len = PAYLOAD_LEN[(y | 1).leading_zeros() as usize] as usize;
+	res[0] = POS_MULTI_MARKER | len as u8;
+	res[1..].copy_from_slice(&(y << (64 - 8 * len)).to_be_bytes());
+	len + 1
+}
+
+/// Signed counterpart of `pack_uint_into_array`.
+pub fn pack_int_into_array(x: i64, res: &mut [u8; MAX_ENCODED_LEN]) -> usize {
+
+	// Short circuit positive integer first
+	if x >= 0 {
+		return pack_uint_into_array(x as u64, res);
 	}
-	else if x < 0 {
-		let mut y = x;
-		y -= NEG_1BYTE_MIN;
-		res[0] = NEG_1BYTE_MARKER | get_negint_bits(y, 6, 0);
+	if x >= NEG_1BYTE_MIN {
+		*res = [0; MAX_ENCODED_LEN];
+		res[0] = NEG_1BYTE_MARKER | (x - NEG_1BYTE_MIN) as u8;
+		return 1;
+	}
+	if x >= NEG_2BYTE_MIN {
+		let y = x - NEG_2BYTE_MIN;
+		*res = [0; MAX_ENCODED_LEN];
+		res[0] = NEG_2BYTE_MARKER | (y >> 8) as u8;
+		res[1] = y as u8;
+		return 2;
 	}
 
-	return res;
+	// The payload is `x` without its leading 0xff bytes, which the marker
+	// counts.
+	let len = PAYLOAD_LEN[(!x as u64).leading_zeros() as usize] as usize;
+	res[0] = NEG_MULTI_MARKER | (8 - len) as u8;
+	res[1..].copy_from_slice(&((x as u64) << (64 - 8 * len)).to_be_bytes());
+	len + 1
 }
 
+// Significant bytes of a nonzero word, indexed by its leading zero bits.
+const PAYLOAD_LEN: [u8; 64] = {
+	let mut table = [0; 64];
+	let mut lz = 0;
+	while lz < 64 {
+		table[lz] = (8 - lz / 8) as u8;
+		lz += 1;
+	}
+	table
+};
+
 fn lz_posint(x: u64) -> usize {
-	return if x == 0 {
+	if x == 0 {
 		mem::size_of::<u64>()
 	} else {
 		(x.leading_zeros() >> 3) as usize
-	};
+	}
 }
 
 fn lz_negint(x: i64) -> usize {
-	return if !x == 0 {
+	if !x == 0 {
 		mem::size_of::<u64>()
 	} else {
-		(!x.leading_zeros() >> 3) as usize
-	};
+		((!x).leading_zeros() >> 3) as usize
+	}
 }
 
 fn size_posint(x: u64) -> usize {
-	return INTPACK64_MAXSIZE - lz_posint(x);
+	INTPACK64_MAXSIZE - lz_posint(x)
 }
 
 
 fn size_negint(x: i64) -> usize {
-	return INTPACK64_MAXSIZE - lz_negint(x);
+	INTPACK64_MAXSIZE - lz_negint(x)
 }
 
 
@@ -266,7 +855,7 @@ fn size_uint(x: u64) -> usize {
 
 	let mut y = x;
 	y -= POS_2BYTE_MAX + 1;
-	return size_posint(y);
+	size_posint(y)
 }
 
 fn size_int(x: i64) -> usize {
@@ -280,11 +869,11 @@ fn size_int(x: i64) -> usize {
 		return 1;
 	}
 
-	return size_uint(x as u64);
+	size_uint(x as u64)
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
 	use super::*;
 
@@ -328,4 +917,346 @@ mod tests {
     	x == y
     }
 
+    // quickcheck only generates small magnitudes, so spread them over the
+    // whole range to reach the multi-byte classes
+    #[quickcheck]
+    fn order_is_correct_wide(x: u64, y: u64, sx: u8, sy: u8) -> bool {
+    	let x = x.wrapping_shl(sx as u32 % 64);
+    	let y = y.wrapping_shl(sy as u32 % 64);
+    	let (xa, ya) = (x as i64, y as i64);
+
+    	(x.cmp(&y) == pack_uint(x).cmp(&pack_uint(y))) &&
+    		(xa.cmp(&ya) == pack_int(xa).cmp(&pack_int(ya))) &&
+    		unpack_uint(&pack_uint(x)) == x &&
+    		unpack_int(&pack_int(xa)) == xa
+    }
+
+    #[quickcheck]
+    fn desc_order_is_reversed(x: u64, y: u64, sx: u8, sy: u8) -> bool {
+    	let x = x.wrapping_shl(sx as u32 % 64);
+    	let y = y.wrapping_shl(sy as u32 % 64);
+    	let (xa, ya) = (x as i64, y as i64);
+    	let (ex, exa) = (pack_uint_desc(x), pack_int_desc(xa));
+
+    	(y.cmp(&x) == ex.cmp(&pack_uint_desc(y))) &&
+    		(ya.cmp(&xa) == exa.cmp(&pack_int_desc(ya))) &&
+    		decode_uint_desc(&ex) == Ok((x, size_uint(x))) &&
+    		decode_int_desc(&exa) == Ok((xa, size_int(xa)))
+    }
+
+    #[quickcheck]
+    fn sentinels_bound_every_value(x: i64, shift: u8, suffix: Vec<u8>) -> bool {
+    	let x = x.wrapping_shl(shift as u32 % 64);
+    	let x128 = (x as i128) << 64;
+    	[pack_int(x), pack_int_desc(x), pack_uint(x as u64), wide::pack_i128(x128), wide::pack_u128(x128 as u128)]
+    		.iter()
+    		.map(|key| [&key[..], &suffix[..]].concat())
+    		.all(|key| SENTINEL_BEFORE_ALL[..] < key[..] && key[..] < SENTINEL_AFTER_ALL[..])
+    }
+
+    #[quickcheck]
+    fn options_sort_with_nulls_at_either_end(x: Option<i64>, y: Option<i64>, shift: u8) -> bool {
+    	let (x, y) = (x.map(|v| v.wrapping_shl(shift as u32)), y.map(|v| v.wrapping_shl(shift as u32 / 2)));
+    	let (first_x, first_y) = (pack_opt_i64(x, NullPlacement::First), pack_opt_i64(y, NullPlacement::First));
+    	let (last_x, last_y) = (pack_opt_i64(x, NullPlacement::Last), pack_opt_i64(y, NullPlacement::Last));
+    	// `Option` orders `None` first; reversing both sides of the nulls puts it last
+    	let nulls_last = |v: Option<i64>| (v.is_none(), v);
+    	first_x.cmp(&first_y) == x.cmp(&y) && last_x.cmp(&last_y) == nulls_last(x).cmp(&nulls_last(y)) &&
+    		decode_opt_i64(&first_x) == Ok((x, first_x.len())) && decode_opt_i64(&last_x) == Ok((x, last_x.len())) &&
+    		SENTINEL_BEFORE_ALL[..] < first_x[..] && last_x[..] < SENTINEL_AFTER_ALL[..]
+    }
+
+    #[test]
+    fn unsigned_options_round_trip() {
+    	assert_eq!(pack_opt_u64(None, NullPlacement::First), [0x01]);
+    	assert_eq!(pack_opt_u64(None, NullPlacement::Last), [0xfe]);
+    	assert_eq!(pack_opt_u64(Some(300), NullPlacement::Last), pack_uint(300));
+    	assert_eq!(decode_opt_u64(&[0xfe, 0x81]), Ok((None, 1)));
+    	assert_eq!(decode_opt_u64(&pack_uint(300)), Ok((Some(300), 2)));
+    	assert_eq!(decode_opt_u64(&[0x02]), Err(DecodeError::InvalidMarker(0x02)));
+    	assert_eq!(decode_opt_i64(&[]), Err(DecodeError::Truncated { needed: 1 }));
+    }
+
+    #[test]
+    fn desc_rejects_bad_input() {
+    	assert_eq!(pack_uint_desc(0), [0x7f]);
+    	assert_eq!(decode_uint_desc(&[0xfa]), Err(DecodeError::InvalidMarker(0xfa)));
+    	assert_eq!(decode_int_desc(&[0x1c, 0xfe]), Err(DecodeError::Truncated { needed: 4 }));
+    	assert_eq!(decode_uint_desc(&[]), Err(DecodeError::Truncated { needed: 1 }));
+    	assert_eq!(decode_int_desc(&[pack_int_desc(-5), pack_int_desc(7)].concat()), Ok((-5, 1)));
+    }
+
+    #[test]
+    fn boundaries_round_trip() {
+    	let pos = [0, POS_1BYTE_MAX, POS_1BYTE_MAX + 1, POS_2BYTE_MAX, POS_2BYTE_MAX + 1,
+    		POS_2BYTE_MAX + 2, POS_2BYTE_MAX + 256, 1 << 32, u64::MAX];
+    	for &x in pos.iter() {
+    		let packed = pack_uint(x);
+    		assert_eq!(packed.len(), size_uint(x));
+    		assert_eq!(decode_uint(&packed), Ok((x, packed.len())));
+    	}
+
+    	let neg = [-1, NEG_1BYTE_MIN, NEG_1BYTE_MIN - 1, NEG_2BYTE_MIN, NEG_2BYTE_MIN - 1,
+    		-(1 << 32), i64::MIN, i64::MAX];
+    	for &x in neg.iter() {
+    		let packed = pack_int(x);
+    		assert_eq!(packed.len(), size_int(x));
+    		assert_eq!(decode_int(&packed), Ok((x, packed.len())));
+    	}
+    }
+
+    #[test]
+    fn try_unpack_rejects_bad_input() {
+    	assert_eq!(try_unpack_uint(&[0xf0]), Err(DecodeError::InvalidMarker(0xf0)));
+    	assert_eq!(try_unpack_uint(&[0x05]), Err(DecodeError::InvalidMarker(0x05)));
+    	assert_eq!(try_unpack_uint(&[0xe2, 0x01]), Err(DecodeError::Truncated { needed: 3 }));
+    	assert_eq!(try_unpack_uint(&[]), Err(DecodeError::Truncated { needed: 1 }));
+    	assert_eq!(try_unpack_int(&pack_uint(u64::MAX)), Err(DecodeError::Overflow));
+    	assert_eq!(try_unpack_int(&pack_int(-300)), Ok(-300));
+    }
+
+    #[quickcheck]
+    fn strict_accepts_packed_values(x: u64, shift: u8) -> bool {
+    	let x = x.wrapping_shl(shift as u32 % 64);
+    	unpack_uint_strict(&pack_uint(x)) == Ok(x) && unpack_int_strict(&pack_int(x as i64)) == Ok(x as i64) &&
+    		unpack_int_strict(&pack_int(!x as i64)) == Ok(!x as i64)
+    }
+
+    #[test]
+    fn strict_rejects_longer_encodings() {
+    	let long = DecodeError::Malformed("value is not in its shortest encoding");
+    	// 8261 in two payload bytes instead of one
+    	assert_eq!(try_unpack_uint(&[0xe2, 0x00, 0x05]), Ok(8261));
+    	assert_eq!(unpack_uint_strict(&[0xe2, 0x00, 0x05]), Err(long));
+    	assert_eq!(unpack_uint_strict(&[0xe1, 0x05]), Ok(8261));
+    	// -1 and -100 belong in the one and two byte classes
+    	assert_eq!(unpack_int_strict(&[0x17, 0xff]), Err(long));
+    	assert_eq!(unpack_int_strict(&[0x17, 0x9c]), Err(long));
+    	assert_eq!(unpack_int_strict(&[0x16, 0xff, 0x00]), Err(long));
+    	assert_eq!(unpack_int_strict(&pack_int(-100)), Ok(-100));
+    	assert_eq!(unpack_int_strict(&[0xe2, 0x01]), Err(DecodeError::Truncated { needed: 3 }));
+    }
+
+    #[quickcheck]
+    fn walks_packed_sequences(values: Vec<i64>) -> bool {
+    	let buf: Vec<u8> = values.iter().flat_map(|&v| pack_int(v)).collect();
+    	let mut pos = 0;
+    	let mut out = Vec::new();
+    	while pos < buf.len() {
+    		out.push(decode_int_at(&buf, &mut pos).unwrap());
+    	}
+    	out == values && decode_int_at(&buf, &mut pos) == Err(DecodeError::Truncated { needed: buf.len() + 1 })
+    }
+
+    // quickcheck's integers are small, so spread them over every length
+    #[quickcheck]
+    fn every_length_class_round_trips(v: u64, shift: u8, flip: bool) -> bool {
+    	let x = if flip { !(v << (shift % 64)) } else { v << (shift % 64) };
+    	let (a, b) = (pack_uint(x), pack_int(x as i64));
+    	decode_uint(&a) == Ok((x, a.len())) && a.len() == size_uint(x) &&
+    		decode_int(&b) == Ok((x as i64, b.len())) && b.len() == size_int(x as i64)
+    }
+
+    #[quickcheck]
+    fn packs_into_arrays(x: i64) -> bool {
+    	let mut res = [0xff; 9];
+    	let len = pack_int_into_array(x, &mut res);
+    	let ok = res[..len] == pack_int(x)[..] && res[len..].iter().all(|&b| b == 0);
+    	let len = pack_uint_into_array(x as u64, &mut res);
+    	ok && res[..len] == pack_uint(x as u64)[..]
+    }
+
+    #[quickcheck]
+    fn appends_after_existing_bytes(x: u64, y: i64) -> bool {
+    	let mut out = vec![0xaa];
+    	pack_uint_append(x, &mut out);
+    	pack_int_append(y, &mut out);
+    	let mut want = vec![0xaa];
+    	want.extend_from_slice(&pack_uint(x));
+    	want.extend_from_slice(&pack_int(y));
+    	out == want
+    }
+
+    #[quickcheck]
+    fn encoded_len_is_exact(x: u64, y: i64) -> bool {
+    	encoded_len_u64(x) == pack_uint(x).len() && encoded_len_i64(y) == pack_int(y).len() &&
+    		encoded_len_u64(x) <= MAX_ENCODED_LEN && encoded_len_i64(y) <= MAX_ENCODED_LEN
+    }
+
+    #[quickcheck]
+    fn marker_gives_the_length(x: i64) -> bool {
+    	let packed = pack_int(x);
+    	encoded_len_from_marker(packed[0]) == Ok(packed.len())
+    }
+
+    #[test]
+    fn reserved_markers_have_no_length() {
+    	assert_eq!(encoded_len_from_marker(0x0f), Err(DecodeError::InvalidMarker(0x0f)));
+    	assert_eq!(encoded_len_from_marker(0xf0), Err(DecodeError::InvalidMarker(0xf0)));
+    	assert_eq!(encoded_len_from_marker(0xe9), Err(DecodeError::Overflow));
+    	assert_eq!(encoded_len_from_marker(0xe8), Ok(9));
+    	assert_eq!(encoded_len_from_marker(0x10), Ok(9));
+    }
+
+    #[quickcheck]
+    fn packs_into_slices(x: i64, room: u8) -> bool {
+    	let mut out = [0; 12];
+    	let room = room as usize % out.len();
+    	let want = pack_int(x);
+    	match pack_int_into_slice(x, &mut out[..room]) {
+    		Some(len) => len == want.len() && out[..len] == want[..],
+    		None => room < want.len() && pack_uint_into_slice(x as u64, &mut out[..room]).is_none() == (room < pack_uint(x as u64).len()),
+    	}
+    }
+
+    #[quickcheck]
+    fn packs_iterators_densely(values: Vec<u64>) -> bool {
+    	let want: Vec<u8> = values.iter().flat_map(|&v| pack_uint(v)).collect();
+    	let mut out = vec![0xaa];
+    	pack_all_into(values.iter().copied(), &mut out);
+    	pack_all(values) == want && out[1..] == want[..]
+    }
+
+    #[quickcheck]
+    fn batches_round_trip(values: Vec<u64>, room: usize) -> bool {
+    	let buf = pack_slice(&values);
+    	let mut out = vec![0; room % (values.len() + 2)];
+    	let n = unpack_into(&buf, &mut out).unwrap();
+    	buf == pack_all(values.iter().copied()) && buf.capacity() == buf.len() &&
+    		n == out.len().min(values.len()) && out[..n] == values[..n]
+    }
+
+    // mostly one-byte values, the case the simd path takes
+    #[quickcheck]
+    fn batch_decode_matches_scalar(values: Vec<(u8, bool)>, room: u8) -> bool {
+    	let values: Vec<u64> = values.iter().map(|&(v, wide)| if wide { (v as u64) << 20 } else { v as u64 % 64 }).collect();
+    	let buf = pack_slice(&values);
+    	let mut out = vec![0; room as usize];
+    	let n = unpack_into(&buf, &mut out).unwrap();
+    	let mut pos = 0;
+    	let scalar: Vec<u64> = (0..n).map(|_| decode_uint_at(&buf, &mut pos).unwrap()).collect();
+    	n == out.len().min(values.len()) && out[..n] == scalar[..] && scalar[..] == values[..n]
+    }
+
+    #[test]
+    fn unpack_into_reports_the_bad_value() {
+    	let mut out = [0; 4];
+    	assert_eq!(unpack_into(&[0x81, 0x82, 0xc1], &mut out), Err(DecodeError::Truncated { needed: 4 }));
+    	assert_eq!(out[..2], [1, 2]);
+    	assert_eq!(unpack_into(&[0x81, 0x00], &mut out), Err(DecodeError::InvalidMarker(0x00)));
+    	let mut buf = vec![0xbf; 20];
+    	buf[17] = 0xf0;
+    	let mut out = [0; 20];
+    	assert_eq!(unpack_into(&buf, &mut out), Err(DecodeError::InvalidMarker(0xf0)));
+    	assert!(out[..17].iter().all(|&v| v == 63));
+    }
+
+    #[test]
+    fn decode_at_reports_absolute_offsets() {
+    	let buf = [0x81, 0xe2, 0x01];
+    	let mut pos = 0;
+    	assert_eq!(decode_uint_at(&buf, &mut pos), Ok(1));
+    	assert_eq!(decode_uint_at(&buf, &mut pos), Err(DecodeError::Truncated { needed: 4 }));
+    	assert_eq!(pos, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid marker byte 0xf0")]
+    fn unpack_panics_with_the_error() {
+    	unpack_uint(&[0xf0]);
+    }
+
+    #[quickcheck]
+    fn step_encoded_matches_pack(x: i64, s: u8) -> bool {
+    	let x = x.wrapping_shl(s as u32 % 64);
+    	let (mut up, mut down) = (pack_int(x), pack_int(x));
+    	let up_ok = match x.checked_add(1) {
+    		Some(y) => increment_encoded(&mut up).is_ok() && up == pack_int(y),
+    		None => increment_encoded(&mut up) == Err(DecodeError::Overflow) && up == pack_int(x),
+    	};
+    	let down_ok = match x.checked_sub(1) {
+    		Some(y) => decrement_encoded(&mut down).is_ok() && down == pack_int(y),
+    		None => decrement_encoded(&mut down) == Err(DecodeError::Overflow),
+    	};
+    	up_ok && down_ok
+    }
+
+    #[quickcheck]
+    fn estimates_encoded_size(values: Vec<i64>, s: u8) -> bool {
+    	let values: Vec<i64> = values.into_iter().map(|v| v.wrapping_shl(s as u32 % 64)).collect();
+    	let exact: usize = values.iter().map(|&v| pack_int(v).len()).sum();
+    	estimate_encoded_size(&values) == exact && estimate_encoded_size_sampled(&values, values.len()) == exact
+    }
+
+    #[test]
+    fn sampled_estimate_is_close() {
+    	let values: Vec<i64> = (0..100_000).map(|i| if i % 7 == 0 { i << 20 } else { i % 50 }).collect();
+    	let (exact, sampled) = (estimate_encoded_size(&values), estimate_encoded_size_sampled(&values, 1000));
+    	assert!(sampled.abs_diff(exact) * 50 < exact, "{} vs {}", sampled, exact);
+    	assert_eq!(estimate_encoded_size_sampled(&values, 0), estimate_encoded_size_sampled(&values, 1));
+    }
+
+    #[quickcheck]
+    fn sample_every_matches_step_by(values: Vec<u64>, n: u8) -> bool {
+    	let n = n as usize % 8 + 1;
+    	let buf: Vec<u8> = values.iter().flat_map(|&v| pack_uint(v)).collect();
+    	sample_every(&buf, n).eq(values.iter().copied().step_by(n))
+    }
+
+    #[test]
+    fn sample_every_stops_at_bad_input() {
+    	let mut buf: Vec<u8> = [1, 2, 3, 4].iter().flat_map(|&v| pack_uint(v)).collect();
+    	buf.push(0x05);
+    	buf.extend_from_slice(&pack_uint(6));
+    	assert_eq!(sample_every(&buf, 2).collect::<Vec<_>>(), vec![1, 3]);
+    	assert_eq!(sample_every(&buf, 1).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn step_encoded_crosses_classes() {
+    	let edges = [-1, NEG_1BYTE_MIN, NEG_2BYTE_MIN, -(1 << 16), POS_1BYTE_MAX as i64,
+    		POS_2BYTE_MAX as i64, POS_2BYTE_MAX as i64 + 1, POS_2BYTE_MAX as i64 + 256];
+    	for &x in edges.iter() {
+    		let mut buf = pack_int(x);
+    		increment_encoded(&mut buf).unwrap();
+    		assert_eq!(buf, pack_int(x + 1));
+    		decrement_encoded(&mut buf).unwrap();
+    		decrement_encoded(&mut buf).unwrap();
+    		assert_eq!(buf, pack_int(x - 1));
+    	}
+
+    	let mut buf = pack_int(5);
+    	buf.push(0x80);
+    	assert_eq!(increment_encoded(&mut buf),
+    		Err(DecodeError::Malformed("trailing bytes after the encoded value")));
+    }
+
+    #[test]
+    fn decode_rejects_bad_input() {
+    	assert_eq!(decode_uint(&[]), Err(DecodeError::Truncated { needed: 1 }));
+    	assert_eq!(decode_uint(&[0x05]), Err(DecodeError::InvalidMarker(0x05)));
+    	assert_eq!(decode_uint(&[0xe3, 0x01]), Err(DecodeError::Truncated { needed: 4 }));
+    	assert_eq!(decode_uint(&[0xe8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+    		Err(DecodeError::Overflow));
+    	assert_eq!(decode_int(&pack_uint(u64::MAX)), Err(DecodeError::Overflow));
+    	assert_eq!(decode_int(&[0x18]), Err(DecodeError::InvalidMarker(0x18)));
+    	assert_eq!(decode_int(&SENTINEL_BEFORE_ALL), Err(DecodeError::InvalidMarker(0x00)));
+    	assert_eq!(decode_uint(&SENTINEL_AFTER_ALL), Err(DecodeError::InvalidMarker(0xff)));
+    }
+
+    #[quickcheck]
+    fn decode_never_panics(buf: Vec<u8>) -> bool {
+    	let _ = (decode_uint(&buf), decode_int(&buf), unpack_uint_strict(&buf), unpack_int_strict(&buf));
+    	true
+    }
+
+    #[quickcheck]
+    fn truncated_values_report_their_length(x: i64, shift: u8) -> bool {
+    	let packed = pack_int(x.wrapping_shl(shift as u32 % 64));
+    	(0..packed.len()).all(|cut| {
+    		let needed = if cut == 0 { 1 } else { packed.len() };
+    		decode_int(&packed[..cut]) == Err(DecodeError::Truncated { needed })
+    	})
+    }
+
 }
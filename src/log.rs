@@ -0,0 +1,214 @@
+//! Append-only log of `u64` batches, one checksummed record per batch.
+//!
+//! Each record's payload is its values packed back to back. Next to the
+//! log, `<path>.checkpoint` holds the length of the log at its last
+//! fsync, as 8 bytes big-endian. On open, `recover` trusts everything
+//! before the checkpoint, checks every record after it, and truncates
+//! the log at the first torn or corrupt one.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::record::{decode_record, encode_record};
+use crate::{decode_uint, pack_uint};
+
+/// When appends are made durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyncPolicy {
+	/// fsync after every append.
+	Always,
+	/// fsync after every `n` appends.
+	Every(usize),
+	/// Only when `sync` is called.
+	Manual,
+}
+
+/// An open log file, see the module documentation.
+#[derive(Debug)]
+pub struct Log {
+	file: File,
+	checkpoint_path: PathBuf,
+	policy: SyncPolicy,
+	len: u64,
+	unsynced: usize,
+}
+
+impl Log {
+	/// Opens or creates the log at `path` and recovers it.
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()), err))]
+	pub fn open<P: AsRef<Path>>(path: P, policy: SyncPolicy) -> io::Result<Log> {
+		let path = path.as_ref();
+		let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+		let mut checkpoint_path = path.as_os_str().to_owned();
+		checkpoint_path.push(".checkpoint");
+		let mut log = Log { file, checkpoint_path: checkpoint_path.into(), policy, len: 0, unsynced: 0 };
+		log.recover()?;
+		Ok(log)
+	}
+
+	/// Length of the log in bytes.
+	pub fn len(&self) -> u64 {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	fn read_checkpoint(&self) -> u64 {
+		match fs::read(&self.checkpoint_path) {
+			Ok(bytes) if bytes.len() == 8 => {
+				let mut b = [0; 8];
+				b.copy_from_slice(&bytes);
+				u64::from_be_bytes(b)
+			}
+			_ => 0,
+		}
+	}
+
+	/// Scans the records after the last checkpoint and truncates the log
+	/// after the last intact one. Returns the number of bytes dropped.
+	///
+	/// A checkpoint past the end of the file, e.g. after the file was
+	/// replaced, is ignored and the whole log is scanned.
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, err))]
+	pub fn recover(&mut self) -> io::Result<u64> {
+		let file_len = self.file.metadata()?.len();
+		let start = Some(self.read_checkpoint()).filter(|&c| c <= file_len).unwrap_or(0);
+
+		let mut tail = Vec::new();
+		self.file.seek(SeekFrom::Start(start))?;
+		self.file.read_to_end(&mut tail)?;
+		let mut good = 0;
+		while let Ok((_, used)) = decode_record(&tail[good..]) {
+			good += used;
+		}
+
+		self.len = start + good as u64;
+		if self.len < file_len {
+			self.file.set_len(self.len)?;
+			self.file.sync_all()?;
+		}
+		self.file.seek(SeekFrom::Start(self.len))?;
+		trace!(checkpoint = start, len = self.len, dropped = file_len - self.len, "recovered log");
+		Ok(file_len - self.len)
+	}
+
+	/// Appends `values` as one record, returning its offset.
+	pub fn append(&mut self, values: &[u64]) -> io::Result<u64> {
+		let mut payload = Vec::with_capacity(values.len());
+		for &v in values {
+			payload.extend_from_slice(&pack_uint(v));
+		}
+		let mut record = Vec::with_capacity(payload.len() + 8);
+		encode_record(&mut record, &payload);
+		self.file.write_all(&record)?;
+
+		let offset = self.len;
+		self.len += record.len() as u64;
+		self.unsynced += 1;
+		match self.policy {
+			SyncPolicy::Always => self.sync()?,
+			SyncPolicy::Every(n) if self.unsynced >= n => self.sync()?,
+			_ => {}
+		}
+		Ok(offset)
+	}
+
+	/// Makes every append so far durable and moves the checkpoint to the
+	/// end of the log.
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = self.len, unsynced = self.unsynced), err))]
+	pub fn sync(&mut self) -> io::Result<()> {
+		self.file.sync_data()?;
+		let mut tmp = self.checkpoint_path.clone().into_os_string();
+		tmp.push(".tmp");
+		fs::write(&tmp, self.len.to_be_bytes())?;
+		fs::rename(&tmp, &self.checkpoint_path)?;
+		self.unsynced = 0;
+		Ok(())
+	}
+
+	/// Reads every batch back with its offset.
+	pub fn entries(&mut self) -> io::Result<Vec<(u64, Vec<u64>)>> {
+		let mut buf = Vec::new();
+		self.file.seek(SeekFrom::Start(0))?;
+		self.file.read_to_end(&mut buf)?;
+		self.file.seek(SeekFrom::Start(self.len))?;
+
+		let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+		let mut out = Vec::new();
+		let mut pos = 0;
+		while pos < self.len as usize {
+			let (payload, used) = decode_record(&buf[pos..]).map_err(invalid)?;
+			let (mut values, mut at) = (Vec::new(), 0);
+			while at < payload.len() {
+				let (v, n) = decode_uint(&payload[at..]).map_err(invalid)?;
+				values.push(v);
+				at += n;
+			}
+			out.push((pos as u64, values));
+			pos += used;
+		}
+		Ok(out)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	fn temp_path() -> PathBuf {
+		static NEXT: AtomicUsize = AtomicUsize::new(0);
+		let n = NEXT.fetch_add(1, Ordering::Relaxed);
+		std::env::temp_dir().join(format!("rust-varint-log-{}-{}", std::process::id(), n))
+	}
+
+	fn cleanup(path: &Path) {
+		let mut checkpoint = path.as_os_str().to_owned();
+		checkpoint.push(".checkpoint");
+		fs::remove_file(path).unwrap();
+		let _ = fs::remove_file(checkpoint);
+	}
+
+	#[test]
+	fn appends_and_reopens() {
+		let path = temp_path();
+		let mut log = Log::open(&path, SyncPolicy::Every(2)).unwrap();
+		let a = log.append(&[1, 2, 3]).unwrap();
+		let b = log.append(&[]).unwrap();
+		let c = log.append(&[u64::MAX]).unwrap();
+		assert_eq!(a, 0);
+		drop(log);
+
+		let mut log = Log::open(&path, SyncPolicy::Manual).unwrap();
+		assert_eq!(log.entries().unwrap(), vec![(a, vec![1, 2, 3]), (b, vec![]), (c, vec![u64::MAX])]);
+		cleanup(&path);
+	}
+
+	#[test]
+	fn recovery_truncates_torn_tails() {
+		let path = temp_path();
+		let mut log = Log::open(&path, SyncPolicy::Manual).unwrap();
+		log.append(&[10, 20]).unwrap();
+		log.sync().unwrap();
+		let torn = log.append(&[30, 40]).unwrap();
+		let full = log.len();
+		drop(log);
+
+		// lose the last byte of the second record, then add garbage
+		let file = OpenOptions::new().write(true).open(&path).unwrap();
+		file.set_len(full - 1).unwrap();
+		drop(file);
+		OpenOptions::new().append(true).open(&path).unwrap().write_all(&[0xff; 5]).unwrap();
+
+		let mut log = Log::open(&path, SyncPolicy::Manual).unwrap();
+		assert_eq!(log.len(), torn);
+		assert_eq!(log.entries().unwrap(), vec![(0, vec![10, 20])]);
+		log.append(&[50]).unwrap();
+		assert_eq!(log.recover().unwrap(), 0);
+		assert_eq!(log.entries().unwrap().len(), 2);
+		cleanup(&path);
+	}
+}
@@ -0,0 +1,106 @@
+//! Hooks for counting what the codecs do, without a metrics dependency.
+//!
+//! `column::ColumnWriter`, `column::ColumnReader` and
+//! `formats::Transcoder` take a `CodecMetrics` through their
+//! `with_metrics` methods and report into it. Implement the trait on top
+//! of prometheus, metrics-rs or anything else; `Counters` is a ready-made
+//! implementation on atomics.
+//!
+//! ```
+//! use std::sync::atomic::Ordering;
+//! use rust_varint::column::ColumnWriter;
+//! use rust_varint::metrics::Counters;
+//!
+//! let counters = Counters::default();
+//! let mut w = ColumnWriter::new().with_metrics(&counters);
+//! w.push(1);
+//! w.push(-300);
+//! let column = w.finish();
+//! assert_eq!(counters.values_encoded.load(Ordering::Relaxed), 2);
+//! assert_eq!(counters.bytes_written.load(Ordering::Relaxed), column.len() as u64);
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::DecodeError;
+
+/// Receives codec events. Every method defaults to doing nothing, and
+/// takes `&self` so one sink can be shared between threads.
+pub trait CodecMetrics {
+	fn values_encoded(&self, _n: u64) {}
+
+	fn bytes_written(&self, _n: u64) {}
+
+	/// Called once for each error a decoder returns.
+	fn decode_errors(&self, _error: &DecodeError) {}
+
+	/// Called for each value refused for not being in canonical form.
+	fn canonicality_rejections(&self, _n: u64) {}
+}
+
+/// Reports nowhere; the default for every metered type.
+impl CodecMetrics for () {}
+
+impl<M: CodecMetrics + ?Sized> CodecMetrics for &M {
+	fn values_encoded(&self, n: u64) {
+		(**self).values_encoded(n);
+	}
+
+	fn bytes_written(&self, n: u64) {
+		(**self).bytes_written(n);
+	}
+
+	fn decode_errors(&self, error: &DecodeError) {
+		(**self).decode_errors(error);
+	}
+
+	fn canonicality_rejections(&self, n: u64) {
+		(**self).canonicality_rejections(n);
+	}
+}
+
+impl<M: CodecMetrics + ?Sized> CodecMetrics for Arc<M> {
+	fn values_encoded(&self, n: u64) {
+		(**self).values_encoded(n);
+	}
+
+	fn bytes_written(&self, n: u64) {
+		(**self).bytes_written(n);
+	}
+
+	fn decode_errors(&self, error: &DecodeError) {
+		(**self).decode_errors(error);
+	}
+
+	fn canonicality_rejections(&self, n: u64) {
+		(**self).canonicality_rejections(n);
+	}
+}
+
+/// Totals of every event.
+#[derive(Debug, Default)]
+pub struct Counters {
+	pub values_encoded: AtomicU64,
+	pub bytes_written: AtomicU64,
+	pub decode_errors: AtomicU64,
+	pub canonicality_rejections: AtomicU64,
+}
+
+impl CodecMetrics for Counters {
+	fn values_encoded(&self, n: u64) {
+		self.values_encoded.fetch_add(n, Ordering::Relaxed);
+	}
+
+	fn bytes_written(&self, n: u64) {
+		self.bytes_written.fetch_add(n, Ordering::Relaxed);
+	}
+
+	fn decode_errors(&self, _error: &DecodeError) {
+		self.decode_errors.fetch_add(1, Ordering::Relaxed);
+	}
+
+	fn canonicality_rejections(&self, n: u64) {
+		self.canonicality_rejections.fetch_add(n, Ordering::Relaxed);
+	}
+}
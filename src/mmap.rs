@@ -0,0 +1,180 @@
+//! Columns in memory-mapped files.
+//!
+//! `MappedColumn` decodes blocks straight out of the page cache, and
+//! `MmapAppender` encodes blocks into a pre-sized writable map, so large
+//! column files skip the copy through a `BufWriter` or read buffer.
+//!
+//! Mapped files must not be truncated or modified by another process
+//! while mapped; that is undefined behaviour, as for any `memmap2` map.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use memmap2::{Mmap, MmapMut};
+
+use crate::block;
+use crate::column::{blocks, ColumnReader};
+
+/// A read-only column file.
+#[derive(Debug)]
+pub struct MappedColumn {
+	// `None` for an empty file, which cannot be mapped on every platform
+	map: Option<Mmap>,
+	complete_len: usize,
+}
+
+impl MappedColumn {
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()), err))]
+	pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedColumn> {
+		let file = File::open(path)?;
+		#[allow(unsafe_code)]
+		let map = if file.metadata()?.len() == 0 {
+			None
+		} else {
+			// SAFETY: the module documentation requires the file not to
+			// change while mapped.
+			Some(unsafe { Mmap::map(&file)? })
+		};
+		let bytes = map.as_deref().unwrap_or(&[]);
+		let complete_len = blocks(bytes).map_while(Result::ok).map(|b| b.len()).sum();
+		trace!(len = bytes.len(), complete_len, "mapped column");
+		Ok(MappedColumn { map, complete_len })
+	}
+
+	/// The whole mapped file.
+	pub fn bytes(&self) -> &[u8] {
+		self.map.as_deref().unwrap_or(&[])
+	}
+
+	/// Length of the leading run of whole, well-formed blocks. Anything
+	/// after it, such as a block torn by a crash or the unused tail of
+	/// an unfinished `MmapAppender`, is ignored by `reader`.
+	pub fn complete_len(&self) -> usize {
+		self.complete_len
+	}
+
+	/// Iterates the values of the complete blocks.
+	pub fn reader(&self) -> ColumnReader<'_> {
+		ColumnReader::new(&self.bytes()[..self.complete_len])
+	}
+}
+
+/// Appends blocks to a column file through a writable map of fixed
+/// capacity.
+#[derive(Debug)]
+pub struct MmapAppender {
+	file: File,
+	map: MmapMut,
+	len: usize,
+}
+
+impl MmapAppender {
+	/// Creates (or truncates) `path` and sizes it to `capacity` bytes,
+	/// which must be positive.
+	pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<MmapAppender> {
+		if capacity == 0 {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "mapped column capacity must be positive"));
+		}
+		let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+		file.set_len(capacity as u64)?;
+		// SAFETY: the file was just created by us, and the module
+		// documentation requires nobody else to change it while mapped.
+		#[allow(unsafe_code)]
+		let map = unsafe { MmapMut::map_mut(&file)? };
+		Ok(MmapAppender { file, map, len: 0 })
+	}
+
+	/// Bytes written so far.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Bytes left before the map is full.
+	pub fn remaining(&self) -> usize {
+		self.map.len() - self.len
+	}
+
+	/// Seals `values` into a block at the end of the column. Fails with
+	/// `WriteZero`, writing nothing, if the block does not fit.
+	pub fn append(&mut self, values: &[i64]) -> io::Result<()> {
+		let raw = block::encode_block(values);
+		if raw.len() > self.remaining() {
+			return Err(io::Error::new(io::ErrorKind::WriteZero, "mapped column is full"));
+		}
+		self.map[self.len..self.len + raw.len()].copy_from_slice(&raw);
+		self.len += raw.len();
+		Ok(())
+	}
+
+	/// Flushes the map and trims the file to the bytes written.
+	#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = self.len), err))]
+	pub fn finish(self) -> io::Result<()> {
+		self.map.flush()?;
+		drop(self.map);
+		self.file.set_len(self.len as u64)?;
+		self.file.sync_all()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	fn temp_path() -> PathBuf {
+		static NEXT: AtomicUsize = AtomicUsize::new(0);
+		let n = NEXT.fetch_add(1, Ordering::Relaxed);
+		std::env::temp_dir().join(format!("rust-varint-mmap-{}-{}", std::process::id(), n))
+	}
+
+	// Miri cannot map files, only anonymous memory
+	#[test]
+	#[cfg_attr(miri, ignore)]
+	fn appends_and_reads_back() {
+		let path = temp_path();
+		let mut w = MmapAppender::create(&path, 1 << 16).unwrap();
+		let values: Vec<i64> = (-500..500).map(|i| i * 997).collect();
+		for chunk in values.chunks(100) {
+			w.append(chunk).unwrap();
+		}
+		assert!(w.append(&vec![i64::MAX; 1 << 14]).is_err());
+		let written = w.len();
+		w.finish().unwrap();
+
+		let column = MappedColumn::open(&path).unwrap();
+		assert_eq!((column.bytes().len(), column.complete_len()), (written, written));
+		assert_eq!(column.reader().collect::<Result<Vec<i64>, _>>().unwrap(), values);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	#[cfg_attr(miri, ignore)]
+	fn ignores_torn_tails() {
+		let path = temp_path();
+		let mut w = MmapAppender::create(&path, 4096).unwrap();
+		w.append(&[1, 2, 3]).unwrap();
+		let first = w.len();
+		w.append(&[4, 5, 6]).unwrap();
+		// dropped without `finish`: the rest of the capacity stays zeroed
+		drop(w);
+
+		let column = MappedColumn::open(&path).unwrap();
+		assert_eq!(column.bytes().len(), 4096);
+		assert_eq!(column.reader().collect::<Result<Vec<i64>, _>>().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+
+		let file = OpenOptions::new().write(true).open(&path).unwrap();
+		file.set_len(first as u64 + 2).unwrap();
+		let column = MappedColumn::open(&path).unwrap();
+		assert_eq!(column.complete_len(), first);
+
+		File::create(&path).unwrap();
+		assert_eq!(MappedColumn::open(&path).unwrap().reader().count(), 0);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,167 @@
+//! Decoding into integer types narrower than 64 bits.
+//!
+//! Values are stored at full width, so a reader that wants a `u16` has
+//! to decide what happens to one that does not fit. `Narrowing` names the
+//! choice; telemetry pipelines that would rather keep a lossy sample than
+//! drop it can use one of the saturating shorthands such as
+//! `unpack_u32_saturating`. Schemas that enforce their column types use
+//! the exact pairs such as `pack_u32` and `unpack_u32`, whose decode fails
+//! on a value out of range.
+//!
+//! ```
+//! use rust_varint::narrow::{decode_int_as, unpack_i16_clamped, Narrowing};
+//! use rust_varint::{pack_int, DecodeError};
+//!
+//! let buf = pack_int(-40_000);
+//! assert_eq!(unpack_i16_clamped(&buf), Ok((i16::MIN, buf.len())));
+//! assert_eq!(decode_int_as::<i16>(&buf, Narrowing::Exact), Err(DecodeError::Overflow));
+//! ```
+
+use std::convert::TryFrom;
+
+use crate::{decode_int, decode_uint, pack_int, pack_uint, DecodeError};
+
+/// What to do with a decoded value that does not fit the target type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Narrowing {
+	/// Fail with `DecodeError::Overflow`.
+	Exact,
+	/// Clamp to the nearest value the type can hold.
+	Saturate,
+	/// Keep the low bits, like an `as` cast.
+	Wrap,
+}
+
+/// Integer types a decoded value can be narrowed to.
+pub trait Narrow: Copy {
+	#[doc(hidden)]
+	fn narrow_u64(v: u64, policy: Narrowing) -> Option<Self>;
+	#[doc(hidden)]
+	fn narrow_i64(v: i64, policy: Narrowing) -> Option<Self>;
+}
+
+macro_rules! narrow {
+	($($t:ty),*) => {$(
+		impl Narrow for $t {
+			fn narrow_u64(v: u64, policy: Narrowing) -> Option<$t> {
+				match policy {
+					Narrowing::Exact => <$t>::try_from(v).ok(),
+					Narrowing::Saturate => Some(<$t>::try_from(v).unwrap_or(<$t>::MAX)),
+					Narrowing::Wrap => Some(v as $t),
+				}
+			}
+
+			fn narrow_i64(v: i64, policy: Narrowing) -> Option<$t> {
+				match policy {
+					Narrowing::Exact => <$t>::try_from(v).ok(),
+					Narrowing::Saturate => {
+						Some(<$t>::try_from(v).unwrap_or(if v < 0 { <$t>::MIN } else { <$t>::MAX }))
+					}
+					Narrowing::Wrap => Some(v as $t),
+				}
+			}
+		}
+	)*};
+}
+
+narrow!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Decodes one unsigned value from the front of `buf` into `T`, returning
+/// it with the number of bytes it occupied.
+pub fn decode_uint_as<T: Narrow>(buf: &[u8], policy: Narrowing) -> Result<(T, usize), DecodeError> {
+	let (v, used) = decode_uint(buf)?;
+	Ok((T::narrow_u64(v, policy).ok_or(DecodeError::Overflow)?, used))
+}
+
+/// Signed counterpart of `decode_uint_as`.
+pub fn decode_int_as<T: Narrow>(buf: &[u8], policy: Narrowing) -> Result<(T, usize), DecodeError> {
+	let (v, used) = decode_int(buf)?;
+	Ok((T::narrow_i64(v, policy).ok_or(DecodeError::Overflow)?, used))
+}
+
+macro_rules! exact {
+	($($t:ty, $pack:ident, $unpack:ident, $wide:ty, $pack_wide:ident, $decode_as:ident;)*) => {$(
+		pub fn $pack(x: $t) -> Vec<u8> {
+			$pack_wide(x as $wide)
+		}
+
+		/// Decodes a value written by the matching pack function, failing
+		/// with `Overflow` if it does not fit.
+		pub fn $unpack(buf: &[u8]) -> Result<($t, usize), DecodeError> {
+			$decode_as(buf, Narrowing::Exact)
+		}
+	)*};
+}
+
+exact! {
+	u8, pack_u8, unpack_u8, u64, pack_uint, decode_uint_as;
+	u16, pack_u16, unpack_u16, u64, pack_uint, decode_uint_as;
+	u32, pack_u32, unpack_u32, u64, pack_uint, decode_uint_as;
+	i8, pack_i8, unpack_i8, i64, pack_int, decode_int_as;
+	i16, pack_i16, unpack_i16, i64, pack_int, decode_int_as;
+	i32, pack_i32, unpack_i32, i64, pack_int, decode_int_as;
+}
+
+pub fn unpack_u8_saturating(buf: &[u8]) -> Result<(u8, usize), DecodeError> {
+	decode_uint_as(buf, Narrowing::Saturate)
+}
+
+pub fn unpack_u16_saturating(buf: &[u8]) -> Result<(u16, usize), DecodeError> {
+	decode_uint_as(buf, Narrowing::Saturate)
+}
+
+pub fn unpack_u32_saturating(buf: &[u8]) -> Result<(u32, usize), DecodeError> {
+	decode_uint_as(buf, Narrowing::Saturate)
+}
+
+pub fn unpack_i8_clamped(buf: &[u8]) -> Result<(i8, usize), DecodeError> {
+	decode_int_as(buf, Narrowing::Saturate)
+}
+
+pub fn unpack_i16_clamped(buf: &[u8]) -> Result<(i16, usize), DecodeError> {
+	decode_int_as(buf, Narrowing::Saturate)
+}
+
+pub fn unpack_i32_clamped(buf: &[u8]) -> Result<(i32, usize), DecodeError> {
+	decode_int_as(buf, Narrowing::Saturate)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{pack_int, pack_uint};
+
+	#[quickcheck]
+	fn saturating_clamps(x: u64, y: i64) -> bool {
+		let (a, b) = (pack_uint(x), pack_int(y));
+		unpack_u16_saturating(&a) == Ok((x.min(u16::MAX as u64) as u16, a.len()))
+			&& unpack_i32_clamped(&b) == Ok((y.clamp(i32::MIN as i64, i32::MAX as i64) as i32, b.len()))
+	}
+
+	#[quickcheck]
+	fn exact_and_wrap(y: i64) -> bool {
+		let b = pack_int(y);
+		decode_int_as::<i8>(&b, Narrowing::Exact) == i8::try_from(y).map(|v| (v, b.len())).map_err(|_| DecodeError::Overflow)
+			&& decode_int_as::<u32>(&b, Narrowing::Wrap) == Ok((y as u32, b.len()))
+	}
+
+	#[quickcheck]
+	fn exact_pairs_enforce_the_range(x: u32, y: i16, wide: u64) -> bool {
+		unpack_u32(&pack_u32(x)) == Ok((x, pack_u32(x).len())) && unpack_i16(&pack_i16(y)) == Ok((y, pack_i16(y).len())) &&
+			unpack_u16(&pack_uint(wide)).is_ok() == (wide <= u16::MAX as u64)
+	}
+
+	#[test]
+	fn boundaries() {
+		assert_eq!(unpack_u8_saturating(&pack_uint(255)), Ok((255, 2)));
+		assert_eq!(unpack_u8_saturating(&pack_uint(256)), Ok((255, 2)));
+		assert_eq!(unpack_i8_clamped(&pack_int(-129)), Ok((i8::MIN, 2)));
+		assert_eq!(decode_int_as::<u16>(&pack_int(-1), Narrowing::Saturate), Ok((0, 1)));
+		assert_eq!(decode_uint_as::<i16>(&pack_uint(1 << 15), Narrowing::Exact), Err(DecodeError::Overflow));
+		assert_eq!(unpack_u32_saturating(&[0xe4, 1]), Err(DecodeError::Truncated { needed: 5 }));
+		assert_eq!(unpack_u8(&pack_u16(256)), Err(DecodeError::Overflow));
+		assert_eq!(unpack_i8(&pack_i32(-128)), Ok((-128, 2)));
+		assert_eq!(unpack_i32(&pack_int(i32::MIN as i64 - 1)), Err(DecodeError::Overflow));
+		assert_eq!(unpack_u32(&pack_int(-1)), Err(DecodeError::InvalidMarker(0x7f)));
+	}
+}
@@ -0,0 +1,60 @@
+//! Encoding large inputs in parallel on the rayon thread pool.
+//!
+//! ```
+//! use rust_varint::parallel::pack_blocks_parallel;
+//! use rust_varint::unpack_into;
+//!
+//! let values: Vec<u64> = (0..10_000).collect();
+//! let (buf, blocks) = pack_blocks_parallel(&values, 4096);
+//! assert_eq!(blocks.len(), 3);
+//! let mut out = vec![0; 4096];
+//! assert_eq!(unpack_into(&buf[blocks[1].clone()], &mut out), Ok(4096));
+//! assert_eq!(out[0], 4096);
+//! ```
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::pack_slice;
+
+/// Splits `values` into blocks of `block_len`, the last one shorter, and
+/// packs each with `pack_slice` on the thread pool. Returns the blocks
+/// back to back in input order with the byte range of each, so they can
+/// be decoded independently.
+///
+/// Panics if `block_len` is zero.
+pub fn pack_blocks_parallel(values: &[u64], block_len: usize) -> (Vec<u8>, Vec<Range<usize>>) {
+	assert!(block_len > 0, "block length must be positive");
+	let blocks: Vec<Vec<u8>> = values.par_chunks(block_len).map(pack_slice).collect();
+	let mut buf = Vec::with_capacity(blocks.iter().map(Vec::len).sum());
+	let mut ranges = Vec::with_capacity(blocks.len());
+	for block in blocks {
+		let start = buf.len();
+		buf.extend_from_slice(&block);
+		ranges.push(start..buf.len());
+	}
+	(buf, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::iter::decode_iter;
+
+	#[quickcheck]
+	fn blocks_decode_independently(values: Vec<u64>, block_len: u8) -> bool {
+		let block_len = block_len as usize % 7 + 1;
+		let (buf, ranges) = pack_blocks_parallel(&values, block_len);
+		buf == pack_slice(&values) && ranges.len() == values.len().div_ceil(block_len) &&
+			ranges.iter().zip(values.chunks(block_len)).all(|(r, chunk)| {
+				decode_iter(&buf[r.clone()]).collect::<Result<Vec<_>, _>>().as_deref() == Ok(chunk)
+			})
+	}
+
+	#[test]
+	#[should_panic(expected = "block length must be positive")]
+	fn rejects_empty_blocks() {
+		pack_blocks_parallel(&[1], 0);
+	}
+}
@@ -0,0 +1,192 @@
+//! Edit scripts between two versions of a block.
+//!
+//! A patch is a sequence of operations, each a header followed by the
+//! values it inserts, if any:
+//!
+//! ```text
+//! [count << 2 | op: uint][count packed ints, for insert and change only]
+//! ```
+//!
+//! `op` is 0 to keep, 1 to delete, 2 to insert and 3 to change (replace)
+//! `count` values. The diff walks both blocks like a merge, so it is
+//! minimal when both are sorted; for unsorted blocks it is still correct,
+//! just larger.
+
+use crate::block::{encode_block, BlockIter};
+use crate::{decode_int, decode_uint, pack_int, pack_uint, DecodeError};
+
+const KEEP: u64 = 0;
+const DELETE: u64 = 1;
+const INSERT: u64 = 2;
+const CHANGE: u64 = 3;
+
+/// An encoded edit script, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch(Vec<u8>);
+
+impl Patch {
+	/// Wraps bytes produced by `as_bytes`; they are checked by `apply`.
+	pub fn from_bytes(bytes: Vec<u8>) -> Patch {
+		Patch(bytes)
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+#[derive(Debug)]
+enum Op {
+	Keep(u64),
+	Delete(u64),
+	Insert(Vec<i64>),
+}
+
+fn push(ops: &mut Vec<Op>, op: Op) {
+	match (ops.last_mut(), op) {
+		(Some(Op::Keep(n)), Op::Keep(m)) | (Some(Op::Delete(n)), Op::Delete(m)) => *n += m,
+		(Some(Op::Insert(vs)), Op::Insert(ws)) => vs.extend(ws),
+		(_, op) => ops.push(op),
+	}
+}
+
+fn write_op(out: &mut Vec<u8>, op: u64, count: u64, values: &[i64]) {
+	out.extend_from_slice(&pack_uint(count << 2 | op));
+	for &v in values {
+		out.extend_from_slice(&pack_int(v));
+	}
+}
+
+/// Computes the patch that turns block `old` into block `new`.
+pub fn diff(old: &[u8], new: &[u8]) -> Result<Patch, DecodeError> {
+	let old = BlockIter::new(old)?.0.collect::<Result<Vec<i64>, DecodeError>>()?;
+	let new = BlockIter::new(new)?.0.collect::<Result<Vec<i64>, DecodeError>>()?;
+
+	let mut ops = Vec::new();
+	let (mut i, mut j) = (0, 0);
+	while i < old.len() && j < new.len() {
+		if old[i] == new[j] {
+			push(&mut ops, Op::Keep(1));
+			i += 1;
+			j += 1;
+		} else if old[i] < new[j] {
+			push(&mut ops, Op::Delete(1));
+			i += 1;
+		} else {
+			push(&mut ops, Op::Insert(vec![new[j]]));
+			j += 1;
+		}
+	}
+	push(&mut ops, Op::Delete((old.len() - i) as u64));
+	push(&mut ops, Op::Insert(new[j..].to_vec()));
+
+	// a deletion followed by an insertion is written as a change of the
+	// overlapping part
+	let mut out = Vec::new();
+	let mut ops = ops.into_iter().peekable();
+	while let Some(op) = ops.next() {
+		match op {
+			Op::Keep(0) | Op::Delete(0) => {}
+			Op::Keep(n) => write_op(&mut out, KEEP, n, &[]),
+			Op::Delete(n) => match ops.peek_mut() {
+				Some(Op::Insert(vs)) => {
+					let changed = (n as usize).min(vs.len());
+					write_op(&mut out, CHANGE, changed as u64, &vs[..changed]);
+					if n as usize > changed {
+						write_op(&mut out, DELETE, n - changed as u64, &[]);
+					}
+					vs.drain(..changed);
+				}
+				_ => write_op(&mut out, DELETE, n, &[]),
+			},
+			Op::Insert(vs) if vs.is_empty() => {}
+			Op::Insert(vs) => write_op(&mut out, INSERT, vs.len() as u64, &vs),
+		}
+	}
+	Ok(Patch(out))
+}
+
+/// Applies `patch` to block `old`, returning the new block. Fails if the
+/// patch does not account for exactly the values of `old`.
+pub fn apply(old: &[u8], patch: &Patch) -> Result<Vec<u8>, DecodeError> {
+	let (mut old, _) = BlockIter::new(old)?;
+	let mut next_old = || {
+		old.next()
+			.unwrap_or(Err(DecodeError::Malformed("patch runs past the end of the old block")))
+	};
+	let offset = |e, pos| match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+		e => e,
+	};
+
+	let (buf, mut pos) = (patch.as_bytes(), 0);
+	let mut values = Vec::new();
+	while pos < buf.len() {
+		let (head, used) = decode_uint(&buf[pos..]).map_err(|e| offset(e, pos))?;
+		pos += used;
+		for _ in 0..head >> 2 {
+			let op = head & 3;
+			if op != INSERT {
+				let v = next_old()?;
+				if op == KEEP {
+					values.push(v);
+				}
+			}
+			if op == INSERT || op == CHANGE {
+				let (v, used) = decode_int(&buf[pos..]).map_err(|e| offset(e, pos))?;
+				values.push(v);
+				pos += used;
+			}
+		}
+	}
+	if next_old().is_ok() {
+		return Err(DecodeError::Malformed("patch does not cover the old block"));
+	}
+	Ok(encode_block(&values))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn apply_reverses_diff(old: Vec<i64>, new: Vec<i64>) -> bool {
+		let (old, new) = (encode_block(&old), encode_block(&new));
+		apply(&old, &diff(&old, &new).unwrap()) == Ok(new)
+	}
+
+	#[quickcheck]
+	fn sorted_sets_patch_small(mut set: Vec<i64>, extra: i64) -> bool {
+		set.sort();
+		set.dedup();
+		let old = encode_block(&set);
+		let mut grown = set.clone();
+		if let Err(at) = grown.binary_search(&extra) {
+			grown.insert(at, extra);
+		}
+		let patch = diff(&old, &encode_block(&grown)).unwrap();
+		// two keep runs around one insert header and its value
+		patch.as_bytes().len() <= 3 * 2 + 9
+	}
+
+	#[test]
+	fn encodes_changes() {
+		let old = encode_block(&[1, 2, 3, 9]);
+		let patch = diff(&old, &encode_block(&[1, 4, 5, 9, 10])).unwrap();
+		let mut want = pack_uint(1 << 2 | KEEP);
+		want.extend(pack_uint(2 << 2 | CHANGE));
+		want.extend(pack_int(4));
+		want.extend(pack_int(5));
+		want.extend(pack_uint(1 << 2 | KEEP));
+		want.extend(pack_uint(1 << 2 | INSERT));
+		want.extend(pack_int(10));
+		assert_eq!(patch.as_bytes(), &want[..]);
+
+		let short = encode_block(&[1, 2, 3]);
+		assert_eq!(apply(&short, &patch), Err(DecodeError::Malformed("patch runs past the end of the old block")));
+		let long = encode_block(&[1, 2, 3, 9, 11]);
+		assert_eq!(apply(&long, &patch), Err(DecodeError::Malformed("patch does not cover the old block")));
+		assert_eq!(apply(&old, &Patch::from_bytes(want[..want.len() - 1].to_vec())),
+			Err(DecodeError::Truncated { needed: want.len() }));
+	}
+}
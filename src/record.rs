@@ -0,0 +1,61 @@
+//! Checksummed records, for files that must detect torn or corrupted
+//! writes.
+//!
+//! ```text
+//! [payload length: uint][crc32 of payload: 4 bytes big-endian][payload ...]
+//! ```
+
+use crate::crc32::crc32;
+use crate::{decode_uint, pack_uint, DecodeError};
+
+/// Appends `payload` as one record.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = payload.len())))]
+pub fn encode_record(out: &mut Vec<u8>, payload: &[u8]) {
+	out.extend_from_slice(&pack_uint(payload.len() as u64));
+	out.extend_from_slice(&crc32(payload).to_be_bytes());
+	out.extend_from_slice(payload);
+}
+
+/// Decodes the record at the front of `buf`, returning its payload and
+/// the number of bytes it occupied.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(len = buf.len()), err))]
+pub fn decode_record(buf: &[u8]) -> Result<(&[u8], usize), DecodeError> {
+	let (len, n) = decode_uint(buf)?;
+	let start = n + 4;
+	let end = start.checked_add(len as usize).ok_or(DecodeError::Overflow)?;
+	if buf.len() < end {
+		return Err(DecodeError::Truncated { needed: end });
+	}
+	let mut crc = [0; 4];
+	crc.copy_from_slice(&buf[n..start]);
+	let payload = &buf[start..end];
+	if crc32(payload) != u32::from_be_bytes(crc) {
+		return Err(DecodeError::Malformed("record checksum mismatch"));
+	}
+	Ok((payload, end))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(payload: Vec<u8>, at: usize) -> bool {
+		let mut buf = Vec::new();
+		encode_record(&mut buf, &payload);
+		let ok = decode_record(&buf) == Ok((&payload[..], buf.len()));
+		let at = at % buf.len();
+		buf[at] ^= 0x01;
+		ok && decode_record(&buf).map(|(p, _)| p != &payload[..]).unwrap_or(true)
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let mut buf = Vec::new();
+		encode_record(&mut buf, b"hello");
+		assert_eq!(decode_record(&buf[..buf.len() - 1]), Err(DecodeError::Truncated { needed: buf.len() }));
+		let last = buf.len() - 1;
+		buf[last] ^= 0x20;
+		assert_eq!(decode_record(&buf), Err(DecodeError::Malformed("record checksum mismatch")));
+	}
+}
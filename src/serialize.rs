@@ -0,0 +1,807 @@
+//! A serde data format that writes every integer as this crate packs it,
+//! for compact rows whose integer fields can still be read one by one.
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Row {
+//!     id: u64,
+//!     delta: i32,
+//!     name: String,
+//! }
+//!
+//! let row = Row { id: 300, delta: -2, name: "a".into() };
+//! let bytes = rust_varint::to_vec(&row).unwrap();
+//! assert_eq!(bytes, [0xc0, 0xec, 0x7e, 0x81, b'a']);
+//! assert_eq!(rust_varint::from_slice::<Row>(&bytes).unwrap(), row);
+//! ```
+//!
+//! The format is not self-describing, like bincode's: a value must be
+//! read back as the type that wrote it.
+//!
+//! * Integers are packed as by `pack_uint` and `pack_int`, the 128-bit
+//!   ones as by `wide`. `bool` is the unsigned 0 or 1, `char` its code
+//!   point.
+//! * Floats are their IEEE bits, big-endian.
+//! * Strings, byte strings, sequences and maps start with their length,
+//!   packed as unsigned. Tuples and structs are their fields in order,
+//!   without names.
+//! * `Option` is the unsigned 0 for `None` or 1 before the value, and an
+//!   enum the unsigned index of its variant before its fields.
+//!
+//! For a field of a payload in some other format, `serde_u64`,
+//! `serde_i64`, `serde_u128` and `serde_i128` write just that field
+//! packed, as a byte string:
+//!
+//! ```
+//! # #[cfg(feature = "json")] {
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Debug, PartialEq)]
+//! struct Counter {
+//!     #[serde(with = "rust_varint::serde_u64")]
+//!     hits: u64,
+//! }
+//!
+//! let json = serde_json::to_string(&Counter { hits: 7 }).unwrap();
+//! assert_eq!(json, r#"{"hits":[135]}"#);
+//! assert_eq!(serde_json::from_str::<Counter>(&json).unwrap().hits, 7);
+//! # }
+//! ```
+//!
+//! The format adds its own length prefix to the bytes, one byte in
+//! postcard but eight in bincode's default configuration.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::{ser, Deserialize, Serialize};
+
+use crate::wide::{decode_i128, decode_u128, pack_i128, pack_u128};
+use crate::{decode_int_at, decode_uint_at, pack_int_append, pack_uint_append, DecodeError};
+
+/// Why a value could not be serialized or deserialized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	Decode(DecodeError),
+	/// Raised by a `Serialize` or `Deserialize` impl, or a use of serde
+	/// the format cannot express.
+	Message(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Decode(ref e) => e.fmt(f),
+			Error::Message(ref msg) => f.write_str(msg),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<DecodeError> for Error {
+	fn from(e: DecodeError) -> Error {
+		Error::Decode(e)
+	}
+}
+
+impl ser::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error::Message(msg.to_string())
+	}
+}
+
+impl de::Error for Error {
+	fn custom<T: fmt::Display>(msg: T) -> Error {
+		Error::Message(msg.to_string())
+	}
+}
+
+/// Serializes `value` into a new buffer.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+	let mut out = Vec::new();
+	value.serialize(&mut Serializer::new(&mut out))?;
+	Ok(out)
+}
+
+/// Deserializes a `T` that takes up all of `buf`.
+pub fn from_slice<'de, T: Deserialize<'de>>(buf: &'de [u8]) -> Result<T, Error> {
+	let mut de = Deserializer::from_slice(buf);
+	let value = T::deserialize(&mut de)?;
+	de.end()?;
+	Ok(value)
+}
+
+/// Writes values onto the end of a buffer.
+#[derive(Debug)]
+pub struct Serializer<'a> {
+	out: &'a mut Vec<u8>,
+}
+
+impl<'a> Serializer<'a> {
+	pub fn new(out: &'a mut Vec<u8>) -> Serializer<'a> {
+		Serializer { out }
+	}
+
+	fn write_len(&mut self, len: Option<usize>) -> Result<(), Error> {
+		let len = len.ok_or_else(|| Error::Message("length must be known before serializing".to_string()))?;
+		pack_uint_append(len as u64, self.out);
+		Ok(())
+	}
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
+	type Ok = ();
+	type Error = Error;
+	type SerializeSeq = Self;
+	type SerializeTuple = Self;
+	type SerializeTupleStruct = Self;
+	type SerializeTupleVariant = Self;
+	type SerializeMap = Self;
+	type SerializeStruct = Self;
+	type SerializeStructVariant = Self;
+
+	fn serialize_bool(self, v: bool) -> Result<(), Error> {
+		self.serialize_u64(v as u64)
+	}
+
+	fn serialize_i8(self, v: i8) -> Result<(), Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i16(self, v: i16) -> Result<(), Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i32(self, v: i32) -> Result<(), Error> {
+		self.serialize_i64(v as i64)
+	}
+
+	fn serialize_i64(self, v: i64) -> Result<(), Error> {
+		pack_int_append(v, self.out);
+		Ok(())
+	}
+
+	fn serialize_i128(self, v: i128) -> Result<(), Error> {
+		self.out.extend_from_slice(&pack_i128(v));
+		Ok(())
+	}
+
+	fn serialize_u8(self, v: u8) -> Result<(), Error> {
+		self.serialize_u64(v as u64)
+	}
+
+	fn serialize_u16(self, v: u16) -> Result<(), Error> {
+		self.serialize_u64(v as u64)
+	}
+
+	fn serialize_u32(self, v: u32) -> Result<(), Error> {
+		self.serialize_u64(v as u64)
+	}
+
+	fn serialize_u64(self, v: u64) -> Result<(), Error> {
+		pack_uint_append(v, self.out);
+		Ok(())
+	}
+
+	fn serialize_u128(self, v: u128) -> Result<(), Error> {
+		self.out.extend_from_slice(&pack_u128(v));
+		Ok(())
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<(), Error> {
+		self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+		Ok(())
+	}
+
+	fn serialize_f64(self, v: f64) -> Result<(), Error> {
+		self.out.extend_from_slice(&v.to_bits().to_be_bytes());
+		Ok(())
+	}
+
+	fn serialize_char(self, v: char) -> Result<(), Error> {
+		self.serialize_u64(v as u64)
+	}
+
+	fn serialize_str(self, v: &str) -> Result<(), Error> {
+		self.serialize_bytes(v.as_bytes())
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+		self.write_len(Some(v.len()))?;
+		self.out.extend_from_slice(v);
+		Ok(())
+	}
+
+	fn serialize_none(self) -> Result<(), Error> {
+		self.serialize_u64(0)
+	}
+
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+		self.serialize_u64(1)?;
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<(), Error> {
+		Ok(())
+	}
+
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+		Ok(())
+	}
+
+	fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<(), Error> {
+		self.serialize_u64(index as u64)
+	}
+
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, index: u32, _variant: &'static str,
+		value: &T) -> Result<(), Error> {
+		self.serialize_u64(index as u64)?;
+		value.serialize(self)
+	}
+
+	fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+		self.write_len(len)?;
+		Ok(self)
+	}
+
+	fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+		Ok(self)
+	}
+
+	fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+		Ok(self)
+	}
+
+	fn serialize_tuple_variant(self, _name: &'static str, index: u32, _variant: &'static str, _len: usize)
+		-> Result<Self, Error> {
+		pack_uint_append(index as u64, self.out);
+		Ok(self)
+	}
+
+	fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+		self.write_len(len)?;
+		Ok(self)
+	}
+
+	fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+		Ok(self)
+	}
+
+	fn serialize_struct_variant(self, _name: &'static str, index: u32, _variant: &'static str, _len: usize)
+		-> Result<Self, Error> {
+		pack_uint_append(index as u64, self.out);
+		Ok(self)
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+// Every compound is its elements back to back, whatever its kind.
+macro_rules! compound {
+	($($trait:ident :: $method:ident),*) => {$(
+		impl<'a, 'b> ser::$trait for &'b mut Serializer<'a> {
+			type Ok = ();
+			type Error = Error;
+
+			fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+				value.serialize(&mut **self)
+			}
+
+			fn end(self) -> Result<(), Error> {
+				Ok(())
+			}
+		}
+	)*};
+}
+
+compound!(SerializeSeq::serialize_element, SerializeTuple::serialize_element,
+	SerializeTupleStruct::serialize_field, SerializeTupleVariant::serialize_field);
+
+impl<'a, 'b> ser::SerializeMap for &'b mut Serializer<'a> {
+	type Ok = ();
+	type Error = Error;
+
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+		key.serialize(&mut **self)
+	}
+
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+		value.serialize(&mut **self)
+	}
+
+	fn end(self) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+impl<'a, 'b> ser::SerializeStruct for &'b mut Serializer<'a> {
+	type Ok = ();
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), Error> {
+		value.serialize(&mut **self)
+	}
+
+	fn end(self) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+impl<'a, 'b> ser::SerializeStructVariant for &'b mut Serializer<'a> {
+	type Ok = ();
+	type Error = Error;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), Error> {
+		value.serialize(&mut **self)
+	}
+
+	fn end(self) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+/// Reads values from the front of a buffer. `Truncated` counts from its
+/// start.
+#[derive(Debug)]
+pub struct Deserializer<'de> {
+	buf: &'de [u8],
+	pos: usize,
+}
+
+impl<'de> Deserializer<'de> {
+	pub fn from_slice(buf: &'de [u8]) -> Deserializer<'de> {
+		Deserializer { buf, pos: 0 }
+	}
+
+	/// Bytes read so far.
+	pub fn position(&self) -> usize {
+		self.pos
+	}
+
+	/// Fails if bytes are left after the values read so far.
+	pub fn end(&self) -> Result<(), Error> {
+		if self.pos != self.buf.len() {
+			return Err(DecodeError::Malformed("trailing bytes after the value").into());
+		}
+		Ok(())
+	}
+
+	fn read_u64(&mut self) -> Result<u64, Error> {
+		Ok(decode_uint_at(self.buf, &mut self.pos)?)
+	}
+
+	fn read_i64(&mut self) -> Result<i64, Error> {
+		Ok(decode_int_at(self.buf, &mut self.pos)?)
+	}
+
+	fn read_narrow<T: TryFrom<u64>>(&mut self) -> Result<T, Error> {
+		T::try_from(self.read_u64()?).map_err(|_| DecodeError::Overflow.into())
+	}
+
+	fn read_narrow_signed<T: TryFrom<i64>>(&mut self) -> Result<T, Error> {
+		T::try_from(self.read_i64()?).map_err(|_| DecodeError::Overflow.into())
+	}
+
+	fn read_len(&mut self) -> Result<usize, Error> {
+		self.read_narrow()
+	}
+
+	fn read_slice(&mut self, len: usize) -> Result<&'de [u8], Error> {
+		let end = self.pos.checked_add(len).ok_or(DecodeError::Overflow)?;
+		let bytes = self.buf.get(self.pos..end).ok_or(DecodeError::Truncated { needed: end })?;
+		self.pos = end;
+		Ok(bytes)
+	}
+
+	fn read_bytes(&mut self) -> Result<&'de [u8], Error> {
+		let len = self.read_len()?;
+		self.read_slice(len)
+	}
+
+	fn read_str(&mut self) -> Result<&'de str, Error> {
+		std::str::from_utf8(self.read_bytes()?).map_err(|_| DecodeError::Malformed("string is not UTF-8").into())
+	}
+
+	fn read_fixed<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+		let mut out = [0; N];
+		out.copy_from_slice(self.read_slice(N)?);
+		Ok(out)
+	}
+
+	fn read_wide<T, F: FnOnce(&[u8]) -> Result<(T, usize), DecodeError>>(&mut self, decode: F) -> Result<T, Error> {
+		let (v, used) = decode(&self.buf[self.pos..]).map_err(|e| match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: self.pos + needed },
+			e => e,
+		})?;
+		self.pos += used;
+		Ok(v)
+	}
+
+	// The 0 or 1 that leads an `Option` and stands for a `bool`.
+	fn read_flag(&mut self, what: &'static str) -> Result<bool, Error> {
+		match self.read_u64()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(DecodeError::Malformed(what).into()),
+		}
+	}
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+	type Error = Error;
+
+	fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+		Err(Error::Message("the varint format is not self-describing".to_string()))
+	}
+
+	fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_bool(self.read_flag("invalid bool")?)
+	}
+
+	fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i8(self.read_narrow_signed()?)
+	}
+
+	fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i16(self.read_narrow_signed()?)
+	}
+
+	fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i32(self.read_narrow_signed()?)
+	}
+
+	fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i64(self.read_i64()?)
+	}
+
+	fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_i128(self.read_wide(decode_i128)?)
+	}
+
+	fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u8(self.read_narrow()?)
+	}
+
+	fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u16(self.read_narrow()?)
+	}
+
+	fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u32(self.read_narrow()?)
+	}
+
+	fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u64(self.read_u64()?)
+	}
+
+	fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u128(self.read_wide(decode_u128)?)
+	}
+
+	fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_f32(f32::from_bits(u32::from_be_bytes(self.read_fixed()?)))
+	}
+
+	fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_f64(f64::from_bits(u64::from_be_bytes(self.read_fixed()?)))
+	}
+
+	fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let c = self.read_narrow::<u32>()?;
+		visitor.visit_char(char::from_u32(c).ok_or(DecodeError::Malformed("invalid char"))?)
+	}
+
+	fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_borrowed_str(self.read_str()?)
+	}
+
+	fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_str(visitor)
+	}
+
+	fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_borrowed_bytes(self.read_bytes()?)
+	}
+
+	fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_bytes(visitor)
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		if self.read_flag("invalid option tag")? {
+			visitor.visit_some(self)
+		} else {
+			visitor.visit_none()
+		}
+	}
+
+	fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_unit()
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let len = self.read_len()?;
+		visitor.visit_seq(Elements { de: self, left: len })
+	}
+
+	fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_seq(Elements { de: self, left: len })
+	}
+
+	fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V)
+		-> Result<V::Value, Error> {
+		self.deserialize_tuple(len, visitor)
+	}
+
+	fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		let len = self.read_len()?;
+		visitor.visit_map(Elements { de: self, left: len })
+	}
+
+	fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V)
+		-> Result<V::Value, Error> {
+		self.deserialize_tuple(fields.len(), visitor)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V)
+		-> Result<V::Value, Error> {
+		visitor.visit_enum(self)
+	}
+
+	fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		visitor.visit_u32(self.read_narrow()?)
+	}
+
+	fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+		self.deserialize_any(visitor)
+	}
+
+	fn is_human_readable(&self) -> bool {
+		false
+	}
+}
+
+// The elements of a sequence, tuple, struct or map, `left` of them still
+// to read.
+struct Elements<'a, 'de> {
+	de: &'a mut Deserializer<'de>,
+	left: usize,
+}
+
+impl<'a, 'de> de::SeqAccess<'de> for Elements<'a, 'de> {
+	type Error = Error;
+
+	fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+		if self.left == 0 {
+			return Ok(None);
+		}
+		self.left -= 1;
+		seed.deserialize(&mut *self.de).map(Some)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.left)
+	}
+}
+
+impl<'a, 'de> de::MapAccess<'de> for Elements<'a, 'de> {
+	type Error = Error;
+
+	fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+		if self.left == 0 {
+			return Ok(None);
+		}
+		self.left -= 1;
+		seed.deserialize(&mut *self.de).map(Some)
+	}
+
+	fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+		seed.deserialize(&mut *self.de)
+	}
+
+	fn size_hint(&self) -> Option<usize> {
+		Some(self.left)
+	}
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
+	type Error = Error;
+	type Variant = Self;
+
+	fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+		let index = self.read_narrow::<u32>()?;
+		let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+		Ok((value, self))
+	}
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
+	type Error = Error;
+
+	fn unit_variant(self) -> Result<(), Error> {
+		Ok(())
+	}
+
+	fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+		seed.deserialize(self)
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_tuple(self, len, visitor)
+	}
+
+	fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+		de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+	}
+}
+
+// A `#[serde(with = "..")]` module for one integer type, together with
+// the visitor that reads its bytes back in formats that write byte strings
+// either as bytes or as sequences.
+macro_rules! field_adapter {
+	($($name:ident: $t:ty => $pack:expr, $decode:expr;)*) => {$(
+		#[doc = concat!("`#[serde(with = \"rust_varint::", stringify!($name), "\")]` for a `", stringify!($t),
+			"` field.")]
+		pub mod $name {
+			use super::*;
+
+			pub fn serialize<S: ser::Serializer>(v: &$t, s: S) -> Result<S::Ok, S::Error> {
+				s.serialize_bytes(&$pack(*v))
+			}
+
+			pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<$t, D::Error> {
+				struct Packed;
+
+				impl<'de> Visitor<'de> for Packed {
+					type Value = $t;
+
+					fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+						f.write_str(concat!("a packed ", stringify!($t)))
+					}
+
+					fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<$t, E> {
+						match $decode(v) {
+							Ok((x, used)) if used == v.len() => Ok(x),
+							Ok(_) => Err(E::custom(DecodeError::Malformed("trailing bytes after the value"))),
+							Err(e) => Err(E::custom(e)),
+						}
+					}
+
+					fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<$t, A::Error> {
+						let mut bytes = Vec::with_capacity(crate::wide::MAX_ENCODED_LEN_128);
+						while let Some(b) = seq.next_element()? {
+							bytes.push(b);
+						}
+						self.visit_bytes(&bytes)
+					}
+				}
+
+				d.deserialize_bytes(Packed)
+			}
+		}
+	)*};
+}
+
+field_adapter! {
+	serde_u64: u64 => crate::pack_uint, crate::decode_uint;
+	serde_i64: i64 => crate::pack_int, crate::decode_int;
+	serde_u128: u128 => pack_u128, decode_u128;
+	serde_i128: i128 => pack_i128, decode_i128;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+
+	#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+	enum Shape {
+		Empty,
+		Circle(u32),
+		Rect { w: i16, h: i16 },
+		Pair(i8, char),
+	}
+
+	#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+	struct Row {
+		id: u64,
+		delta: i64,
+		wide: i128,
+		flag: bool,
+		ratio: f64,
+		name: String,
+		parent: Option<u32>,
+		tags: Vec<(u8, String)>,
+		counts: BTreeMap<String, u64>,
+	}
+
+	#[quickcheck]
+	fn rows_round_trip(id: u64, delta: i64, wide: (i64, u64), ratio: f64, name: String, parent: Option<u32>,
+		rest: (Vec<(u8, String)>, BTreeMap<String, u64>)) -> bool {
+		let wide = (wide.0 as i128) << 64 | wide.1 as i128;
+		let (tags, counts) = rest;
+		let row = Row { id, delta, wide, flag: id.is_multiple_of(2), ratio, name, parent, tags, counts };
+		let bytes = to_vec(&row).unwrap();
+		bytes[..crate::encoded_len_u64(id)] == crate::pack_uint(id)[..] && from_slice(&bytes) == Ok(row)
+	}
+
+	#[test]
+	fn encodes_enums_and_options() {
+		let shapes = vec![Shape::Empty, Shape::Circle(300), Shape::Rect { w: -1, h: 2 }, Shape::Pair(-3, 'é')];
+		let bytes = to_vec(&shapes).unwrap();
+		// length, then each variant index before its fields
+		assert_eq!(bytes, [&[0x84, 0x80, 0x81, 0xc0, 0xec, 0x82, 0x7f, 0x82, 0x83, 0x7d][..], &crate::pack_uint('é' as u64)].concat());
+		assert_eq!(from_slice::<Vec<Shape>>(&bytes), Ok(shapes));
+		assert_eq!(to_vec(&Some(5u8)).unwrap(), [0x81, 0x85]);
+		assert_eq!(to_vec(&None::<u8>).unwrap(), [0x80]);
+		assert_eq!(from_slice::<&str>(&to_vec("hi").unwrap()), Ok("hi"));
+	}
+
+	#[test]
+	fn rejects_bad_input() {
+		assert_eq!(from_slice::<u8>(&[0xc0, 0xec]), Err(Error::Decode(DecodeError::Overflow)));
+		assert_eq!(from_slice::<u8>(&[0x81, 0x81]), Err(Error::Decode(DecodeError::Malformed("trailing bytes after the value"))));
+		assert_eq!(from_slice::<bool>(&[0x82]), Err(Error::Decode(DecodeError::Malformed("invalid bool"))));
+		assert_eq!(from_slice::<String>(&[0x83, b'a']), Err(Error::Decode(DecodeError::Truncated { needed: 4 })));
+		assert_eq!(from_slice::<(u8, f64)>(&[0x81, 0x00]), Err(Error::Decode(DecodeError::Truncated { needed: 9 })));
+		assert_eq!(from_slice::<Shape>(&[0x89]), Err(Error::Message(
+			"invalid value: integer `9`, expected variant index 0 <= i < 4".to_string())));
+		assert!(from_slice::<serde::de::IgnoredAny>(&[0x80]).is_err());
+		// an iterator's length is not known up front
+		struct Lazy;
+		impl Serialize for Lazy {
+			fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+				s.collect_seq((0..2u8).filter(|_| true))
+			}
+		}
+		assert!(matches!(to_vec(&Lazy), Err(Error::Message(_))));
+	}
+
+	#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+	struct Counters {
+		#[serde(with = "serde_u64")]
+		hits: u64,
+		#[serde(with = "serde_i64")]
+		drift: i64,
+		#[serde(with = "serde_u128")]
+		bytes: u128,
+		#[serde(with = "serde_i128")]
+		balance: i128,
+	}
+
+	#[quickcheck]
+	fn adapted_fields_round_trip(hits: u64, drift: i64, bytes: (u64, u64), balance: i64) -> bool {
+		let c = Counters { hits, drift, bytes: (bytes.0 as u128) << 64 | bytes.1 as u128, balance: (balance as i128) << 60 };
+		let packed = to_vec(&c).unwrap();
+		// each field is its length and then its packed bytes
+		let hits = crate::pack_uint(hits);
+		packed[0] as usize == 0x80 + hits.len() && packed[1..=hits.len()] == hits[..] && from_slice(&packed) == Ok(c)
+	}
+
+	#[test]
+	fn adapters_reject_bad_bytes() {
+		#[derive(Deserialize, Debug, PartialEq)]
+		struct Hits(#[serde(with = "serde_u64")] u64);
+		assert_eq!(from_slice::<Hits>(&[0x82, 0x81, 0x81]),
+			Err(Error::Message(DecodeError::Malformed("trailing bytes after the value").to_string())));
+		assert_eq!(from_slice::<Hits>(&[0x81, 0xc0]), Err(Error::Message(DecodeError::Truncated { needed: 2 }.to_string())));
+	}
+}
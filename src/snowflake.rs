@@ -0,0 +1,83 @@
+//! Snowflake-style 128-bit ids as ordered keys.
+//!
+//! An id is a millisecond timestamp, a shard and a per-shard sequence
+//! number. Its encoding is the three packed as uints in that order, so
+//! ids sort by time first, and the timestamp can be read from the front
+//! of a key without decoding the rest, see `timestamp_of`.
+
+use std::convert::TryFrom;
+
+use crate::{decode_uint, decode_uint_at, pack_uint, DecodeError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnowflakeId {
+	pub timestamp_ms: u64,
+	pub shard: u32,
+	pub sequence: u32,
+}
+
+impl SnowflakeId {
+	pub fn new(timestamp_ms: u64, shard: u32, sequence: u32) -> SnowflakeId {
+		SnowflakeId { timestamp_ms, shard, sequence }
+	}
+
+	/// The id as one number: timestamp in the high 64 bits, then shard,
+	/// then sequence.
+	pub fn to_u128(self) -> u128 {
+		(self.timestamp_ms as u128) << 64 | (self.shard as u128) << 32 | self.sequence as u128
+	}
+
+	pub fn from_u128(id: u128) -> SnowflakeId {
+		SnowflakeId::new((id >> 64) as u64, (id >> 32) as u32, id as u32)
+	}
+
+	pub fn encode(self) -> Vec<u8> {
+		let mut out = pack_uint(self.timestamp_ms);
+		out.extend_from_slice(&pack_uint(self.shard as u64));
+		out.extend_from_slice(&pack_uint(self.sequence as u64));
+		out
+	}
+
+	/// Decodes the id at the front of `buf`, returning it and the number of
+	/// bytes it occupied.
+	pub fn decode(buf: &[u8]) -> Result<(SnowflakeId, usize), DecodeError> {
+		let mut pos = 0;
+		let timestamp_ms = decode_uint_at(buf, &mut pos)?;
+		let shard = u32::try_from(decode_uint_at(buf, &mut pos)?).map_err(|_| DecodeError::Overflow)?;
+		let sequence = u32::try_from(decode_uint_at(buf, &mut pos)?).map_err(|_| DecodeError::Overflow)?;
+		Ok((SnowflakeId::new(timestamp_ms, shard, sequence), pos))
+	}
+}
+
+/// Reads the timestamp of the id at the front of `buf`, which need not
+/// hold more than the timestamp itself.
+pub fn timestamp_of(buf: &[u8]) -> Result<u64, DecodeError> {
+	decode_uint(buf).map(|(ts, _)| ts)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip_and_order(a: (u64, u32, u32), b: (u64, u32, u32)) -> bool {
+		let (a, b) = (SnowflakeId::new(a.0, a.1, a.2), SnowflakeId::new(b.0, b.1, b.2));
+		let (ka, kb) = (a.encode(), b.encode());
+		SnowflakeId::decode(&ka) == Ok((a, ka.len())) && ka.cmp(&kb) == a.cmp(&b) &&
+			timestamp_of(&ka) == Ok(a.timestamp_ms) && SnowflakeId::from_u128(a.to_u128()) == a &&
+			a.to_u128().cmp(&b.to_u128()) == a.cmp(&b)
+	}
+
+	#[test]
+	fn timestamp_from_a_prefix() {
+		let key = SnowflakeId::new(1_700_000_000_000, 3, 41).encode();
+		let ts_len = pack_uint(1_700_000_000_000).len();
+		assert_eq!(timestamp_of(&key[..ts_len]), Ok(1_700_000_000_000));
+		assert_eq!(SnowflakeId::decode(&key[..ts_len]), Err(DecodeError::Truncated { needed: ts_len + 1 }));
+
+		let mut wide = pack_uint(0);
+		wide.extend_from_slice(&pack_uint(1 << 32));
+		wide.extend_from_slice(&pack_uint(0));
+		assert_eq!(SnowflakeId::decode(&wide), Err(DecodeError::Overflow));
+	}
+}
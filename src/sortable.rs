@@ -0,0 +1,56 @@
+//! Fixed-width order-preserving transforms.
+//!
+//! Flipping the sign bit maps `i64` onto `u64` in order, and big-endian
+//! bytes compare like the `u64` they hold. Radix sorts, roaring bitmap
+//! keys and fixed-width index layouts need these without the
+//! variable-length encoding.
+
+const SIGN_MARK: u64 = 1 << 63;
+
+/// Maps `i64::MIN..=i64::MAX` onto `0..=u64::MAX` in order.
+pub fn i64_to_sortable_u64(v: i64) -> u64 {
+	v as u64 ^ SIGN_MARK
+}
+
+/// Inverse of `i64_to_sortable_u64`.
+pub fn sortable_u64_to_i64(v: u64) -> i64 {
+	(v ^ SIGN_MARK) as i64
+}
+
+/// Big-endian bytes, which compare like the values.
+pub fn u64_to_sortable_bytes(v: u64) -> [u8; 8] {
+	v.to_be_bytes()
+}
+
+pub fn sortable_bytes_to_u64(bytes: [u8; 8]) -> u64 {
+	u64::from_be_bytes(bytes)
+}
+
+/// `u64_to_sortable_bytes` of `i64_to_sortable_u64`.
+pub fn i64_to_sortable_bytes(v: i64) -> [u8; 8] {
+	u64_to_sortable_bytes(i64_to_sortable_u64(v))
+}
+
+pub fn sortable_bytes_to_i64(bytes: [u8; 8]) -> i64 {
+	sortable_u64_to_i64(sortable_bytes_to_u64(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn preserves_order(x: i64, y: i64) -> bool {
+		x.cmp(&y) == i64_to_sortable_u64(x).cmp(&i64_to_sortable_u64(y)) &&
+			x.cmp(&y) == i64_to_sortable_bytes(x).cmp(&i64_to_sortable_bytes(y)) &&
+			sortable_bytes_to_i64(i64_to_sortable_bytes(x)) == x
+	}
+
+	#[test]
+	fn maps_the_extremes() {
+		assert_eq!(i64_to_sortable_u64(i64::MIN), 0);
+		assert_eq!(i64_to_sortable_u64(-1), (1 << 63) - 1);
+		assert_eq!(i64_to_sortable_u64(0), 1 << 63);
+		assert_eq!(i64_to_sortable_bytes(i64::MAX), [0xff; 8]);
+	}
+}
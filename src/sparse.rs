@@ -0,0 +1,202 @@
+//! Sparse integer vectors.
+//!
+//! Only the non-zero entries are stored, ordered by index. Like a block,
+//! an encoded vector is self-delimiting:
+//!
+//! ```text
+//! [dimension: uint][count: uint][payload length: uint][(index gap: uint, zigzag value: uint) ...]
+//! ```
+//!
+//! The first gap is the first index; every later gap is the distance to
+//! the previous index minus one. Float vectors can be stored by quantizing
+//! their values to multiples of a fixed step, see `quantize`.
+
+use crate::zigzag::{zigzag_decode, zigzag_encode};
+use crate::{decode_uint, pack_uint, DecodeError};
+
+/// Encodes a vector of `dim` components given its `(index, value)`
+/// entries. Zero values are dropped.
+///
+/// Panics if the indexes are not strictly increasing or reach `dim`.
+pub fn encode_sparse(dim: u64, entries: &[(u64, i64)]) -> Vec<u8> {
+	let mut payload = Vec::new();
+	let (mut count, mut next, mut last) = (0u64, 0, 0);
+	for &(index, value) in entries {
+		assert!(index >= last && index < dim, "sparse indexes must increase and stay below the dimension");
+		if value != 0 {
+			payload.extend_from_slice(&pack_uint(index - next));
+			payload.extend_from_slice(&pack_uint(zigzag_encode(value)));
+			count += 1;
+			next = index + 1;
+		}
+		last = index + 1;
+	}
+	let mut out = pack_uint(dim);
+	out.extend_from_slice(&pack_uint(count));
+	out.extend_from_slice(&pack_uint(payload.len() as u64));
+	out.extend_from_slice(&payload);
+	out
+}
+
+/// Lazily decodes the entries of one vector.
+#[derive(Debug, Clone)]
+pub struct SparseIter<'a> {
+	dim: u64,
+	payload: &'a [u8],
+	remaining: u64,
+	next: u64,
+	failed: bool,
+}
+
+impl<'a> SparseIter<'a> {
+	/// Parses the header at the front of `buf`. Returns the iterator and
+	/// the total size of the vector.
+	pub fn new(buf: &'a [u8]) -> Result<(SparseIter<'a>, usize), DecodeError> {
+		let (dim, n1) = decode_uint(buf)?;
+		let (count, n2) = decode_uint(&buf[n1..])?;
+		let (len, n3) = decode_uint(&buf[n1 + n2..])?;
+		let start = n1 + n2 + n3;
+		let end = start.checked_add(len as usize).ok_or(DecodeError::Overflow)?;
+		if buf.len() < end {
+			return Err(DecodeError::Truncated { needed: end });
+		}
+		if count > dim {
+			return Err(DecodeError::Malformed("sparse count exceeds the dimension"));
+		}
+		let iter = SparseIter { dim, payload: &buf[start..end], remaining: count, next: 0, failed: false };
+		Ok((iter, end))
+	}
+
+	pub fn dim(&self) -> u64 {
+		self.dim
+	}
+
+	fn entry(&mut self) -> Result<(u64, i64), DecodeError> {
+		let (gap, n1) = decode_uint(self.payload)?;
+		let (value, n2) = decode_uint(&self.payload[n1..])?;
+		let index = self.next.checked_add(gap).filter(|&i| i < self.dim);
+		let index = index.ok_or(DecodeError::Malformed("sparse index exceeds the dimension"))?;
+		self.payload = &self.payload[n1 + n2..];
+		self.next = index + 1;
+		Ok((index, zigzag_decode(value)))
+	}
+}
+
+impl<'a> Iterator for SparseIter<'a> {
+	type Item = Result<(u64, i64), DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.failed {
+			return None;
+		}
+		if self.remaining == 0 {
+			if !self.payload.is_empty() {
+				self.failed = true;
+				return Some(Err(DecodeError::Malformed("trailing bytes after the last sparse entry")));
+			}
+			return None;
+		}
+		let entry = self.entry();
+		match entry {
+			Ok(_) => self.remaining -= 1,
+			Err(_) => self.failed = true,
+		}
+		Some(entry)
+	}
+}
+
+/// A fully decoded vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseVec {
+	pub dim: u64,
+	/// Non-zero `(index, value)` entries by increasing index.
+	pub entries: Vec<(u64, i64)>,
+}
+
+/// Decodes the vector at the front of `buf`, returning it and the number
+/// of bytes it occupied.
+pub fn decode_sparse(buf: &[u8]) -> Result<(SparseVec, usize), DecodeError> {
+	let (iter, used) = SparseIter::new(buf)?;
+	let dim = iter.dim();
+	let entries = iter.collect::<Result<Vec<_>, DecodeError>>()?;
+	Ok((SparseVec { dim, entries }, used))
+}
+
+/// Dot product of two encoded vectors, merging their entries without
+/// decoding either into memory. Each product fits an `i128`, but their
+/// sum may not, which is an `Overflow`.
+pub fn dot(a: &[u8], b: &[u8]) -> Result<i128, DecodeError> {
+	let ((mut a, _), (mut b, _)) = (SparseIter::new(a)?, SparseIter::new(b)?);
+	if a.dim() != b.dim() {
+		return Err(DecodeError::Malformed("sparse vectors differ in dimension"));
+	}
+	let mut sum = 0i128;
+	let (mut x, mut y) = (a.next().transpose()?, b.next().transpose()?);
+	while let (Some((i, u)), Some((j, v))) = (x, y) {
+		if i == j {
+			sum = sum.checked_add(u as i128 * v as i128).ok_or(DecodeError::Overflow)?;
+		}
+		if i <= j {
+			x = a.next().transpose()?;
+		}
+		if j <= i {
+			y = b.next().transpose()?;
+		}
+	}
+	Ok(sum)
+}
+
+/// Rounds `value` to the nearest multiple of `step`, returning the
+/// multiple. The dot product of two quantized vectors is then scaled by
+/// the product of their steps.
+pub fn quantize(value: f64, step: f64) -> i64 {
+	(value / step).round() as i64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+
+	fn entries(raw: Vec<(u16, i64)>) -> Vec<(u64, i64)> {
+		raw.into_iter().map(|(i, v)| (i as u64, v)).collect::<BTreeMap<_, _>>().into_iter().collect()
+	}
+
+	#[quickcheck]
+	fn round_trip(raw: Vec<(u16, i64)>) -> bool {
+		let entries = entries(raw);
+		let buf = encode_sparse(1 << 16, &entries);
+		let nonzero: Vec<_> = entries.into_iter().filter(|&(_, v)| v != 0).collect();
+		decode_sparse(&buf) == Ok((SparseVec { dim: 1 << 16, entries: nonzero }, buf.len()))
+	}
+
+	#[quickcheck]
+	fn dot_matches_dense(a: Vec<(u16, i32)>, b: Vec<(u16, i32)>) -> bool {
+		let widen = |raw: Vec<(u16, i32)>| entries(raw.into_iter().map(|(i, v)| (i, v as i64)).collect());
+		let (a, b) = (widen(a), widen(b));
+		let dense: BTreeMap<u64, i64> = b.iter().copied().collect();
+		let want: i128 = a.iter().map(|&(i, v)| v as i128 * *dense.get(&i).unwrap_or(&0) as i128).sum();
+		dot(&encode_sparse(1 << 16, &a), &encode_sparse(1 << 16, &b)) == Ok(want)
+	}
+
+	#[test]
+	fn layout_and_errors() {
+		let buf = encode_sparse(10, &[(2, 5), (3, 0), (4, -1)]);
+		assert_eq!(buf, vec![0x8a, 0x82, 0x84, 0x82, 0x8a, 0x81, 0x81]);
+		assert_eq!(dot(&buf, &encode_sparse(11, &[])), Err(DecodeError::Malformed("sparse vectors differ in dimension")));
+
+		let mut bad = buf.clone();
+		bad[5] = 0x89;
+		assert_eq!(decode_sparse(&bad), Err(DecodeError::Malformed("sparse index exceeds the dimension")));
+		assert_eq!(quantize(0.26, 0.1), 3);
+	}
+
+	#[test]
+	fn dot_overflows_past_i128() {
+		let min = encode_sparse(4, &[(0, i64::MIN), (1, i64::MIN)]);
+		assert_eq!(dot(&min, &encode_sparse(4, &[(0, i64::MIN)])), Ok(1 << 126));
+		assert_eq!(dot(&min, &min), Err(DecodeError::Overflow));
+		let max = encode_sparse(4, &[(0, i64::MAX), (1, i64::MAX)]);
+		assert_eq!(dot(&max, &min), Ok(-2 * (i64::MAX as i128) * (1 << 63)));
+	}
+}
@@ -0,0 +1,169 @@
+//! Sorted runs of key/value entries, the tables of a small LSM tree.
+//!
+//! A table is a sequence of checksummed records (see `record`), one per
+//! entry, in strictly increasing key order:
+//!
+//! ```text
+//! [key length: uint][key ...][kind: uint, 0 put or 1 delete][value ...]
+//! ```
+//!
+//! Deletes are kept as tombstones so they can shadow older tables until
+//! compaction drops them.
+
+use crate::record::{decode_record, encode_record};
+use crate::{decode_uint, pack_uint, DecodeError};
+
+const PUT: u64 = 0;
+const DELETE: u64 = 1;
+
+/// One entry of a table; `value` is `None` for a tombstone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+	pub key: &'a [u8],
+	pub value: Option<&'a [u8]>,
+}
+
+/// Builds a table in memory.
+#[derive(Debug, Clone, Default)]
+pub struct SstWriter {
+	out: Vec<u8>,
+	last_key: Option<Vec<u8>>,
+	entries: u64,
+}
+
+impl SstWriter {
+	pub fn new() -> SstWriter {
+		SstWriter::default()
+	}
+
+	fn push(&mut self, key: &[u8], kind: u64, value: &[u8]) {
+		if let Some(last) = &self.last_key {
+			assert!(key > &last[..], "sst keys must be strictly increasing");
+		}
+		let mut payload = pack_uint(key.len() as u64);
+		payload.extend_from_slice(key);
+		payload.extend_from_slice(&pack_uint(kind));
+		payload.extend_from_slice(value);
+		encode_record(&mut self.out, &payload);
+		self.last_key = Some(key.to_vec());
+		self.entries += 1;
+	}
+
+	/// Panics unless `key` sorts after every key written so far, as do
+	/// `delete` and `write`.
+	pub fn put(&mut self, key: &[u8], value: &[u8]) {
+		self.push(key, PUT, value);
+	}
+
+	/// Writes a tombstone for `key`.
+	pub fn delete(&mut self, key: &[u8]) {
+		self.push(key, DELETE, &[]);
+	}
+
+	pub fn write(&mut self, entry: Entry) {
+		match entry.value {
+			Some(value) => self.put(entry.key, value),
+			None => self.delete(entry.key),
+		}
+	}
+
+	/// Bytes written so far.
+	pub fn len(&self) -> usize {
+		self.out.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries == 0
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.out
+	}
+}
+
+/// Iterates the entries of a table, checking checksums and key order.
+#[derive(Debug, Clone)]
+pub struct SstReader<'a> {
+	rest: &'a [u8],
+	last_key: Option<&'a [u8]>,
+}
+
+impl<'a> SstReader<'a> {
+	pub fn new(table: &'a [u8]) -> SstReader<'a> {
+		SstReader { rest: table, last_key: None }
+	}
+
+	fn entry(&mut self) -> Result<Entry<'a>, DecodeError> {
+		let (payload, used) = decode_record(self.rest)?;
+		let (len, n) = decode_uint(payload)?;
+		let key = payload.get(n..).and_then(|p| p.get(..len as usize));
+		let key = key.ok_or(DecodeError::Malformed("sst key runs past its record"))?;
+		let (kind, m) = decode_uint(&payload[n + key.len()..])?;
+		let value = &payload[n + key.len() + m..];
+		let value = match kind {
+			PUT => Some(value),
+			DELETE if value.is_empty() => None,
+			_ => return Err(DecodeError::Malformed("unknown sst entry kind")),
+		};
+		if self.last_key.is_some_and(|last| key <= last) {
+			return Err(DecodeError::Malformed("sst keys are out of order"));
+		}
+		self.rest = &self.rest[used..];
+		self.last_key = Some(key);
+		Ok(Entry { key, value })
+	}
+}
+
+impl<'a> Iterator for SstReader<'a> {
+	type Item = Result<Entry<'a>, DecodeError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.rest.is_empty() {
+			return None;
+		}
+		let entry = self.entry();
+		if entry.is_err() {
+			self.rest = &[];
+		}
+		Some(entry)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::BTreeMap;
+
+	#[quickcheck]
+	fn round_trip(entries: BTreeMap<Vec<u8>, Option<Vec<u8>>>) -> bool {
+		let mut w = SstWriter::new();
+		for (k, v) in &entries {
+			w.write(Entry { key: k, value: v.as_deref() });
+		}
+		let table = w.finish();
+		let read: Result<Vec<_>, _> = SstReader::new(&table).collect();
+		read.unwrap().into_iter().map(|e| (e.key.to_vec(), e.value.map(|v| v.to_vec()))).eq(entries)
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let mut w = SstWriter::new();
+		w.put(b"a", b"1");
+		let one = w.len();
+		w.delete(b"b");
+		let table = w.finish();
+
+		let mut swapped = table[one..].to_vec();
+		swapped.extend_from_slice(&table[..one]);
+		assert_eq!(SstReader::new(&swapped).nth(1), Some(Err(DecodeError::Malformed("sst keys are out of order"))));
+		assert!(SstReader::new(&table[..table.len() - 1]).nth(1).unwrap().is_err());
+	}
+
+	#[test]
+	#[should_panic(expected = "strictly increasing")]
+	fn writer_enforces_order() {
+		let mut w = SstWriter::new();
+		w.put(b"b", b"");
+		w.put(b"a", b"");
+	}
+}
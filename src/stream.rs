@@ -0,0 +1,125 @@
+//! Push-based decoding of packed unsigned values that arrive in chunks,
+//! e.g. from a socket.
+//!
+//! ```
+//! use rust_varint::pack_uint;
+//! use rust_varint::stream::StreamDecoder;
+//!
+//! let bytes = [pack_uint(7), pack_uint(300)].concat();
+//! let mut dec = StreamDecoder::new();
+//! assert_eq!(dec.push(&bytes[..2]).values, vec![7]);
+//! assert_eq!(dec.push(&bytes[2..]).values, vec![300]);
+//! assert!(dec.finish().is_ok());
+//! ```
+
+use crate::{decode_uint, uint_len_from_marker, DecodeError, MAX_ENCODED_LEN};
+
+/// What one `StreamDecoder::push` produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeProgress {
+	/// Values completed by this push, in stream order.
+	pub values: Vec<u64>,
+	/// Set once the stream holds a value that cannot be decoded. Values
+	/// before it are still returned, nothing after it is.
+	pub error: Option<DecodeError>,
+}
+
+/// Decodes a stream fed in arbitrary pieces, holding back the bytes of a
+/// value split between pieces until the rest arrives.
+///
+/// After an error every later push returns the same error.
+#[derive(Debug, Clone, Default)]
+pub struct StreamDecoder {
+	partial: Vec<u8>,
+	error: Option<DecodeError>,
+}
+
+impl StreamDecoder {
+	pub fn new() -> StreamDecoder {
+		StreamDecoder { partial: Vec::with_capacity(MAX_ENCODED_LEN), error: None }
+	}
+
+	/// Feeds `input` and decodes every value it completes.
+	pub fn push(&mut self, mut input: &[u8]) -> DecodeProgress {
+		let mut values = Vec::new();
+		if self.error.is_none() && !self.partial.is_empty() {
+			// the marker was checked when the value was held back
+			let len = uint_len_from_marker(self.partial[0]).unwrap_or(1);
+			let take = (len - self.partial.len()).min(input.len());
+			self.partial.extend_from_slice(&input[..take]);
+			input = &input[take..];
+			if self.partial.len() < len {
+				return DecodeProgress { values, error: None };
+			}
+			match decode_uint(&self.partial) {
+				Ok((v, _)) => values.push(v),
+				Err(e) => self.error = Some(e),
+			}
+			self.partial.clear();
+		}
+		while self.error.is_none() && !input.is_empty() {
+			match decode_uint(input) {
+				Ok((v, used)) => {
+					values.push(v);
+					input = &input[used..];
+				}
+				Err(DecodeError::Truncated { .. }) => {
+					self.partial.extend_from_slice(input);
+					break;
+				}
+				Err(e) => self.error = Some(e),
+			}
+		}
+		DecodeProgress { values, error: self.error }
+	}
+
+	/// Bytes held back of a value not yet complete.
+	pub fn pending(&self) -> usize {
+		self.partial.len()
+	}
+
+	/// Ends the stream, failing if it stopped inside a value or hit an
+	/// error.
+	pub fn finish(self) -> Result<(), DecodeError> {
+		if let Some(e) = self.error {
+			return Err(e);
+		}
+		match self.partial.first() {
+			Some(&first) => Err(DecodeError::Truncated { needed: uint_len_from_marker(first)? }),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_uint;
+
+	#[quickcheck]
+	fn any_split_decodes_the_same(values: Vec<u64>, cuts: Vec<u8>) -> bool {
+		let bytes: Vec<u8> = values.iter().flat_map(|&v| pack_uint(v)).collect();
+		let mut dec = StreamDecoder::new();
+		let mut out = Vec::new();
+		let mut rest = &bytes[..];
+		for cut in cuts.into_iter().chain(std::iter::once(u8::MAX)) {
+			let (chunk, tail) = rest.split_at((cut as usize % 12).min(rest.len()));
+			out.extend(dec.push(chunk).values);
+			rest = tail;
+		}
+		out.extend(dec.push(rest).values);
+		out == values && dec.pending() == 0 && dec.finish().is_ok()
+	}
+
+	#[test]
+	fn reports_errors_and_truncation() {
+		let mut dec = StreamDecoder::new();
+		assert_eq!(dec.push(&[0x81, 0xe2, 0x01]), DecodeProgress { values: vec![1], error: None });
+		assert_eq!(dec.pending(), 2);
+		assert_eq!(dec.clone().finish(), Err(DecodeError::Truncated { needed: 3 }));
+		let bad = DecodeProgress { values: vec![64 + 8192 + 256], error: Some(DecodeError::InvalidMarker(0x00)) };
+		assert_eq!(dec.push(&[0x00, 0x00, 0x82]), bad);
+		assert_eq!(dec.push(&[0x82]).error, Some(DecodeError::InvalidMarker(0x00)));
+		assert_eq!(dec.finish(), Err(DecodeError::InvalidMarker(0x00)));
+	}
+}
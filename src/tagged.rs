@@ -0,0 +1,118 @@
+//! Streams that name their own format, for data that must outlive the
+//! format it was written in.
+//!
+//! No packed value starts with a byte below `0x10`, so such a byte can
+//! lead a stream as a tag without being mistaken for data:
+//!
+//! ```text
+//! [tag: 0x01 ..= 0x0f][values ...]
+//! ```
+//!
+//! A stream whose first byte is `0x10` or above has no tag and is read as
+//! the crate's own encoding, so streams written before tagging existed
+//! still decode. Tag `0x00` is never used, to tell a tag apart from
+//! zero-filled space.
+
+use crate::formats::{Format, VarintFormat};
+use crate::DecodeError;
+
+/// Tag of a stream in the crate's own encoding, first revision.
+pub const ORDERED_V1: u8 = 0x01;
+pub const CASSANDRA: u8 = 0x02;
+pub const PREFIX_VARINT: u8 = 0x03;
+pub const LEB128: u8 = 0x04;
+pub const FAST_LE: u8 = 0x05;
+pub const SQLITE: u8 = 0x06;
+pub const GIT_OFFSET: u8 = 0x07;
+pub const COMPACT_SIZE: u8 = 0x08;
+
+/// Bytes below this are tags; `0x09` up to it are reserved for formats to
+/// come.
+const UNTAGGED_MIN: u8 = 0x10;
+
+/// The tag `format` is written with.
+pub fn tag_of(format: Format) -> u8 {
+	match format {
+		Format::Ordered => ORDERED_V1,
+		Format::Cassandra => CASSANDRA,
+		Format::PrefixVarint => PREFIX_VARINT,
+		Format::Leb128 => LEB128,
+		Format::FastLE => FAST_LE,
+		Format::Sqlite => SQLITE,
+		Format::GitOffset => GIT_OFFSET,
+		Format::CompactSize => COMPACT_SIZE,
+	}
+}
+
+/// The format named by `tag`, `None` for tags this build does not know.
+pub fn format_of(tag: u8) -> Option<Format> {
+	match tag {
+		ORDERED_V1 => Some(Format::Ordered),
+		CASSANDRA => Some(Format::Cassandra),
+		PREFIX_VARINT => Some(Format::PrefixVarint),
+		LEB128 => Some(Format::Leb128),
+		FAST_LE => Some(Format::FastLE),
+		SQLITE => Some(Format::Sqlite),
+		GIT_OFFSET => Some(Format::GitOffset),
+		COMPACT_SIZE => Some(Format::CompactSize),
+		_ => None,
+	}
+}
+
+/// Writes `values` in `format` behind its tag.
+pub fn encode_tagged(format: Format, values: &[u64]) -> Vec<u8> {
+	let mut out = vec![tag_of(format)];
+	for &v in values {
+		format.encode_into(&mut out, v);
+	}
+	out
+}
+
+/// Decodes a whole stream, tagged or not, returning the format it was in
+/// and its values.
+///
+/// Fails with `Malformed` for a tag from a newer build.
+pub fn decode_auto(buf: &[u8]) -> Result<(Format, Vec<u64>), DecodeError> {
+	let (format, mut pos) = match buf.first() {
+		Some(&tag) if tag < UNTAGGED_MIN => {
+			(format_of(tag).ok_or(DecodeError::Malformed("unknown stream format tag"))?, 1)
+		}
+		_ => (Format::Ordered, 0),
+	};
+	let mut values = Vec::new();
+	while pos < buf.len() {
+		let (v, used) = format.decode_from(&buf[pos..]).map_err(|e| match e {
+			DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+			e => e,
+		})?;
+		values.push(v);
+		pos += used;
+	}
+	Ok((format, values))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::pack_uint;
+
+	const ALL: [Format; 8] = [Format::Ordered, Format::Cassandra, Format::PrefixVarint, Format::Leb128, Format::FastLE,
+		Format::Sqlite, Format::GitOffset, Format::CompactSize];
+
+	#[quickcheck]
+	fn detects_every_format(values: Vec<u64>) -> bool {
+		let untagged: Vec<u8> = values.iter().flat_map(|&v| pack_uint(v)).collect();
+		decode_auto(&untagged) == Ok((Format::Ordered, values.clone())) &&
+			ALL.iter().all(|&f| decode_auto(&encode_tagged(f, &values)) == Ok((f, values.clone())))
+	}
+
+	#[test]
+	fn tags_and_errors() {
+		assert!(ALL.iter().all(|&f| format_of(tag_of(f)) == Some(f) && tag_of(f) < UNTAGGED_MIN));
+		assert_eq!(encode_tagged(Format::Ordered, &[1]), vec![0x01, 0x81]);
+		assert_eq!(decode_auto(&[]), Ok((Format::Ordered, vec![])));
+		assert_eq!(decode_auto(&[0x09, 0x81]), Err(DecodeError::Malformed("unknown stream format tag")));
+		assert_eq!(decode_auto(&[0x00]), Err(DecodeError::Malformed("unknown stream format tag")));
+		assert_eq!(decode_auto(&[LEB128, 0x01, 0x80]), Err(DecodeError::Truncated { needed: 4 }));
+	}
+}
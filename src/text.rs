@@ -0,0 +1,221 @@
+//! Text forms of encoded keys.
+//!
+//! `to_sortable_string` uses unpadded base32hex (RFC 4648), whose alphabet
+//! is in ASCII order, so the strings compare in the same order as the
+//! bytes they encode. That keeps ordered keys usable inside URLs, object
+//! store names or string sort keys.
+//!
+//! `encode_hex` and `decode_hex` are for fixtures and logs. `decode_hex`
+//! also checks that the bytes are a sequence of packed values, and
+//! explains where and why they are not.
+
+use std::error;
+use std::fmt;
+use std::fmt::Write;
+
+use crate::{decode_int, decode_uint, DecodeError};
+
+const BASE32HEX: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+fn base32hex_value(c: u8) -> Option<u8> {
+	match c {
+		b'0'..=b'9' => Some(c - b'0'),
+		b'A'..=b'V' => Some(c - b'A' + 10),
+		b'a'..=b'v' => Some(c - b'a' + 10),
+		_ => None,
+	}
+}
+
+/// Encodes `bytes` as unpadded, upper-case base32hex.
+pub fn to_sortable_string(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+	let (mut acc, mut bits) = (0u16, 0);
+	for &b in bytes {
+		acc = (acc << 8) | b as u16;
+		bits += 8;
+		while bits >= 5 {
+			bits -= 5;
+			out.push(BASE32HEX[(acc >> bits) as usize & 0x1f] as char);
+		}
+	}
+	if bits > 0 {
+		out.push(BASE32HEX[(acc << (5 - bits)) as usize & 0x1f] as char);
+	}
+	out
+}
+
+/// Decodes the output of `to_sortable_string`. Lower-case letters are
+/// accepted; padding, impossible lengths and non-zero trailing bits are
+/// not, so every byte string has exactly one accepted form.
+pub fn from_sortable_string(s: &str) -> Result<Vec<u8>, DecodeError> {
+	if matches!(s.len() % 8, 1 | 3 | 6) {
+		return Err(DecodeError::Malformed("base32hex length is impossible"));
+	}
+	let mut out = Vec::with_capacity(s.len() * 5 / 8);
+	let (mut acc, mut bits) = (0u16, 0);
+	for c in s.bytes() {
+		let v = base32hex_value(c).ok_or(DecodeError::Malformed("invalid base32hex character"))?;
+		acc = (acc << 5) | v as u16;
+		bits += 5;
+		if bits >= 8 {
+			bits -= 8;
+			out.push((acc >> bits) as u8);
+		}
+	}
+	if acc & ((1 << bits) - 1) != 0 {
+		return Err(DecodeError::Malformed("base32hex has non-zero trailing bits"));
+	}
+	Ok(out)
+}
+
+/// Lower-case hex, two digits per byte.
+pub fn encode_hex(bytes: &[u8]) -> String {
+	let mut out = String::with_capacity(bytes.len() * 2);
+	for b in bytes {
+		write!(out, "{:02x}", b).unwrap();
+	}
+	out
+}
+
+/// Why `decode_hex` rejected its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexError {
+	/// Character of the input for bad hex, otherwise byte of the decoded
+	/// buffer where the failing value starts.
+	pub offset: usize,
+	pub error: DecodeError,
+	/// One line per value decoded before the failure, then the failing one.
+	pub explanation: String,
+}
+
+impl fmt::Display for HexError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} at offset {}\n{}", self.error, self.offset, self.explanation)
+	}
+}
+
+impl error::Error for HexError {}
+
+fn hex_value(c: u8) -> Option<u8> {
+	(c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn spaced_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+// Signed values first, so unsigned ones above `i64::MAX` still decode.
+fn decode_any(buf: &[u8]) -> Result<(String, usize), DecodeError> {
+	match decode_int(buf) {
+		Ok((v, used)) => Ok((v.to_string(), used)),
+		Err(DecodeError::Overflow) => decode_uint(buf).map(|(v, used)| (v.to_string(), used)),
+		Err(e) => Err(e),
+	}
+}
+
+/// Decodes hex, ignoring ASCII whitespace between bytes, and checks that
+/// the result is a sequence of zero or more packed values.
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, HexError> {
+	let mut bytes = Vec::with_capacity(s.len() / 2);
+	let mut high = None;
+	for (i, c) in s.bytes().enumerate() {
+		if c.is_ascii_whitespace() && high.is_none() {
+			continue;
+		}
+		let Some(v) = hex_value(c) else {
+			return Err(HexError {
+				offset: i,
+				error: DecodeError::Malformed("invalid hex digit"),
+				explanation: format!("{}\n{:>width$}", s, "^", width = i + 1),
+			});
+		};
+		match high.take() {
+			None => high = Some(v),
+			Some(h) => bytes.push(h << 4 | v),
+		}
+	}
+	if high.is_some() {
+		return Err(HexError {
+			offset: s.len(),
+			error: DecodeError::Malformed("odd number of hex digits"),
+			explanation: format!("{}\n{:>width$}", s, "^", width = s.len() + 1),
+		});
+	}
+
+	let mut explanation = String::new();
+	let mut pos = 0;
+	while pos < bytes.len() {
+		match decode_any(&bytes[pos..]) {
+			Ok((value, used)) => {
+				writeln!(explanation, "{:04x}: {:<26}  {}", pos, spaced_hex(&bytes[pos..pos + used]), value).unwrap();
+				pos += used;
+			}
+			Err(error) => {
+				let shown = match error {
+					DecodeError::InvalidMarker(_) => 1,
+					_ => (bytes.len() - pos).min(9),
+				};
+				write!(explanation, "{:04x}: {:<26}  error: {}", pos, spaced_hex(&bytes[pos..pos + shown]), error)
+					.unwrap();
+				return Err(HexError { offset: pos, error, explanation });
+			}
+		}
+	}
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn preserves_order(x: Vec<u8>, y: Vec<u8>) -> bool {
+		let (a, b) = (to_sortable_string(&x), to_sortable_string(&y));
+		x.cmp(&y) == a.cmp(&b) && from_sortable_string(&a) == Ok(x) &&
+			from_sortable_string(&b.to_lowercase()) == Ok(y)
+	}
+
+	#[test]
+	fn matches_rfc_4648_vectors() {
+		let vectors = [("", ""), ("f", "CO"), ("fo", "CPNG"), ("foo", "CPNMU"), ("foob", "CPNMUOG"),
+			("fooba", "CPNMUOJ1"), ("foobar", "CPNMUOJ1E8")];
+		for &(raw, text) in &vectors {
+			assert_eq!(to_sortable_string(raw.as_bytes()), text);
+			assert_eq!(from_sortable_string(text), Ok(raw.as_bytes().to_vec()));
+		}
+	}
+
+	#[quickcheck]
+	fn hex_round_trip(values: Vec<i64>) -> bool {
+		let bytes: Vec<u8> = values.iter().flat_map(|&v| crate::pack_int(v)).collect();
+		decode_hex(&encode_hex(&bytes)) == Ok(bytes)
+	}
+
+	#[test]
+	fn explains_bad_hex() {
+		let max = crate::pack_uint(u64::MAX);
+		assert_eq!(decode_hex(&format!("85 E1 00\n{}", encode_hex(&max).to_uppercase())),
+			Ok([&[0x85, 0xe1, 0x00][..], &max].concat()));
+
+		let err = decode_hex("85e100c0").unwrap_err();
+		assert_eq!((err.offset, err.error), (3, DecodeError::Truncated { needed: 2 }));
+		assert_eq!(err.explanation, "0000: 85                          5\n\
+			0001: e1 00                       8256\n\
+			0003: c0                          error: truncated input, value needs 2 bytes");
+
+		let err = decode_hex("850a").unwrap_err();
+		assert_eq!((err.offset, err.error), (1, DecodeError::InvalidMarker(0x0a)));
+		let err = decode_hex("85x").unwrap_err();
+		assert_eq!((err.offset, err.explanation.as_str()), (2, "85x\n  ^"));
+		assert_eq!(decode_hex("851").unwrap_err().error, DecodeError::Malformed("odd number of hex digits"));
+	}
+
+	#[test]
+	fn rejects_bad_text() {
+		assert!(from_sortable_string("CPNMU===").is_err());
+		assert!(from_sortable_string("C").is_err());
+		assert!(from_sortable_string("CW").is_err());
+		assert_eq!(from_sortable_string("CP"),
+			Err(DecodeError::Malformed("base32hex has non-zero trailing bits")));
+	}
+}
@@ -0,0 +1,134 @@
+//! Points in time as keys that sort chronologically.
+//!
+//! A timestamp is the whole seconds since the Unix epoch, packed as by
+//! `pack_int`, followed by the nanoseconds into that second, packed as by
+//! `pack_uint`. Seconds round towards negative infinity, so times before
+//! the epoch keep their order: one nanosecond before it is `-1` seconds
+//! and `999_999_999` nanoseconds.
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use rust_varint::timestamp::{pack_system_time, unpack_system_time};
+//!
+//! let before = UNIX_EPOCH - Duration::from_millis(1500);
+//! let after = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! assert!(pack_system_time(before) < pack_system_time(after));
+//! assert_eq!(unpack_system_time(&pack_system_time(before)).unwrap().0, before);
+//! ```
+//!
+//! With the `time` feature, `time::OffsetDateTime` is encoded the same
+//! way, by its instant; the UTC offset is not kept.
+
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{decode_int, decode_uint, pack_int_append, pack_uint_append, DecodeError};
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+fn pack_parts(secs: i64, nanos: u32) -> Vec<u8> {
+	let mut out = Vec::with_capacity(8);
+	pack_int_append(secs, &mut out);
+	pack_uint_append(nanos as u64, &mut out);
+	out
+}
+
+fn unpack_parts(buf: &[u8]) -> Result<(i64, u32, usize), DecodeError> {
+	let (secs, used) = decode_int(buf)?;
+	let (nanos, more) = decode_uint(&buf[used..]).map_err(|e| match e {
+		DecodeError::Truncated { needed } => DecodeError::Truncated { needed: used + needed },
+		e => e,
+	})?;
+	if nanos >= NANOS_PER_SEC as u64 {
+		return Err(DecodeError::Malformed("timestamp nanoseconds exceed one second"));
+	}
+	Ok((secs, nanos as u32, used + more))
+}
+
+/// Encodes `t`. Panics for a time more than `i64::MAX` seconds from the
+/// epoch, which `SystemTime` can hold on some platforms.
+pub fn pack_system_time(t: SystemTime) -> Vec<u8> {
+	let (secs, nanos) = match t.duration_since(UNIX_EPOCH) {
+		Ok(d) => (d.as_secs() as i128, d.subsec_nanos()),
+		Err(e) => {
+			let d = e.duration();
+			match d.subsec_nanos() {
+				0 => (-(d.as_secs() as i128), 0),
+				n => (-(d.as_secs() as i128) - 1, NANOS_PER_SEC - n),
+			}
+		}
+	};
+	let secs = i64::try_from(secs).unwrap_or_else(|_| panic!("system time is out of the encoding's range"));
+	pack_parts(secs, nanos)
+}
+
+/// Decodes a time written by `pack_system_time`, returning it with the
+/// number of bytes it occupied. Fails with `Overflow` for a time the
+/// platform's `SystemTime` cannot hold.
+pub fn unpack_system_time(buf: &[u8]) -> Result<(SystemTime, usize), DecodeError> {
+	let (secs, nanos, used) = unpack_parts(buf)?;
+	let t = if secs >= 0 {
+		UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+	} else {
+		UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+			.and_then(|t| t.checked_add(Duration::from_nanos(nanos as u64)))
+	};
+	t.map(|t| (t, used)).ok_or(DecodeError::Overflow)
+}
+
+/// Encodes the instant of `t`, like `pack_system_time`.
+#[cfg(feature = "time")]
+pub fn pack_offset_datetime(t: time::OffsetDateTime) -> Vec<u8> {
+	pack_parts(t.unix_timestamp(), t.nanosecond())
+}
+
+/// Decodes a time written by `pack_offset_datetime`, in UTC.
+#[cfg(feature = "time")]
+pub fn unpack_offset_datetime(buf: &[u8]) -> Result<(time::OffsetDateTime, usize), DecodeError> {
+	let (secs, nanos, used) = unpack_parts(buf)?;
+	let t = time::OffsetDateTime::from_unix_timestamp(secs).map_err(|_| DecodeError::Overflow)?;
+	Ok((t + time::Duration::nanoseconds(nanos as i64), used))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn at(secs: i64, nanos: u32) -> SystemTime {
+		if secs >= 0 {
+			UNIX_EPOCH + Duration::new(secs as u64, nanos)
+		} else {
+			UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + Duration::from_nanos(nanos as u64)
+		}
+	}
+
+	#[quickcheck]
+	fn times_sort_and_round_trip(a: (i32, u32), b: (i32, u32)) -> bool {
+		let (x, y) = (at(a.0 as i64 * 1000, a.1 % NANOS_PER_SEC), at(b.0 as i64 * 1000, b.1 % NANOS_PER_SEC));
+		let (px, py) = (pack_system_time(x), pack_system_time(y));
+		px.cmp(&py) == x.cmp(&y) && unpack_system_time(&px) == Ok((x, px.len()))
+	}
+
+	#[test]
+	fn straddles_the_epoch() {
+		let times = [at(-2, 0), at(-1, 0), at(-1, 1), UNIX_EPOCH - Duration::from_nanos(1), UNIX_EPOCH, at(0, 1)];
+		for pair in times.windows(2) {
+			assert!(pack_system_time(pair[0]) < pack_system_time(pair[1]));
+		}
+		assert_eq!(pack_system_time(UNIX_EPOCH - Duration::from_nanos(1)),
+			[crate::pack_int(-1), crate::pack_uint(999_999_999)].concat());
+		assert_eq!(unpack_system_time(&[0x81, 0xe4, 0x3b, 0x9a, 0xca, 0x00]),
+			Err(DecodeError::Malformed("timestamp nanoseconds exceed one second")));
+		assert_eq!(unpack_system_time(&[0x81, 0xe4, 0x3b]), Err(DecodeError::Truncated { needed: 6 }));
+	}
+
+	#[cfg(feature = "time")]
+	#[quickcheck]
+	fn offset_datetimes_match_system_times(secs: i32, nanos: u32, offset: i8) -> bool {
+		let t = time::OffsetDateTime::from_unix_timestamp(secs as i64 * 1000).unwrap() +
+			time::Duration::nanoseconds((nanos % NANOS_PER_SEC) as i64);
+		let t = t.to_offset(time::UtcOffset::from_hms(offset % 24, 0, 0).unwrap());
+		let packed = pack_offset_datetime(t);
+		packed == pack_system_time(t.into()) && unpack_offset_datetime(&packed) == Ok((t, packed.len()))
+	}
+}
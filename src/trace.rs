@@ -0,0 +1,78 @@
+// Instrumentation behind the `tracing` feature.
+//
+// Block, framing and file-level entry points carry `tracing::instrument`
+// spans through `cfg_attr`; `trace!` emits a debug event from inside them
+// and compiles to nothing without the feature. The per-value codecs are
+// not instrumented.
+
+#[cfg(feature = "tracing")]
+macro_rules! trace {
+	($($arg:tt)*) => {
+		tracing::debug!($($arg)*)
+	};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace {
+	($($arg:tt)*) => {};
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::sync::Arc;
+
+	use tracing::span::{Attributes, Id, Record};
+	use tracing::{Event, Metadata, Subscriber};
+
+	use crate::block::{decode_block, encode_block};
+	use crate::DecodeError;
+
+	// counts spans and events, and events that carry an error
+	#[derive(Default)]
+	struct Counts {
+		spans: AtomicUsize,
+		events: AtomicUsize,
+		errors: AtomicUsize,
+	}
+
+	struct Counter(Arc<Counts>);
+
+	impl Subscriber for Counter {
+		fn enabled(&self, _: &Metadata) -> bool {
+			true
+		}
+
+		fn new_span(&self, _: &Attributes) -> Id {
+			Id::from_u64(self.0.spans.fetch_add(1, Ordering::Relaxed) as u64 + 1)
+		}
+
+		fn record(&self, _: &Id, _: &Record) {}
+
+		fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+		fn event(&self, event: &Event) {
+			self.0.events.fetch_add(1, Ordering::Relaxed);
+			if *event.metadata().level() == tracing::Level::ERROR {
+				self.0.errors.fetch_add(1, Ordering::Relaxed);
+			}
+		}
+
+		fn enter(&self, _: &Id) {}
+
+		fn exit(&self, _: &Id) {}
+	}
+
+	#[test]
+	fn instruments_block_codecs() {
+		let counts = Arc::new(Counts::default());
+		tracing::subscriber::with_default(Counter(counts.clone()), || {
+			let block = encode_block(&[1, 2, 3]);
+			decode_block(&block).unwrap();
+			assert_eq!(decode_block(&block[..2]), Err(DecodeError::Truncated { needed: block.len() }));
+		});
+		assert_eq!(counts.spans.load(Ordering::Relaxed), 3);
+		assert_eq!(counts.events.load(Ordering::Relaxed), 3);
+		assert_eq!(counts.errors.load(Ordering::Relaxed), 1);
+	}
+}
@@ -0,0 +1,169 @@
+//! One trait over every integer type the encoding handles, for code
+//! generic over the width it stores.
+//!
+//! Unsigned types use `pack_uint`'s encoding and signed types `pack_int`'s,
+//! widened to 64 bits first, so encodings of one type sort like its
+//! values.
+//!
+//! ```
+//! use rust_varint::varint::VarInt;
+//! use rust_varint::MAX_ENCODED_LEN;
+//!
+//! fn total_len<T: VarInt>(values: &[T]) -> usize {
+//!     values.iter().map(|v| v.encoded_len()).sum()
+//! }
+//!
+//! let mut out = [0; MAX_ENCODED_LEN];
+//! let len = 300u16.encode(&mut out);
+//! assert_eq!(u16::decode(&out), Ok((300, len)));
+//! assert_eq!(total_len(&[-5i8, 100]), 3);
+//! ```
+//!
+//! With `alloc`, `EncodeVarint` gives every such type `encode_varint`
+//! and `encode_varint_into`, for building keys field by field, and
+//! `DecodeVarint` reads them back off a slice:
+//!
+//! ```
+//! use rust_varint::varint::DecodeVarint;
+//!
+//! let buf = [0xe2, 0x00, 0x2c, 0x7f];
+//! let (id, used) = buf.decode_varint::<u32>().unwrap();
+//! assert_eq!((id, used), (8300, 3));
+//! assert_eq!(buf[used..].decode_varint::<i8>(), Ok((-1, 1)));
+//! ```
+
+use core::convert::TryFrom;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{
+	decode_int, decode_uint, encoded_len_i64, encoded_len_u64, pack_int_into_array, pack_uint_into_array, DecodeError,
+	MAX_ENCODED_LEN,
+};
+
+mod sealed {
+	pub trait Sealed {}
+}
+
+/// Integer types with a packed encoding. Sealed: implemented for `u8`
+/// to `u64` and `i8` to `i64` only.
+pub trait VarInt: sealed::Sealed + Copy + Sized {
+	/// Encodes the value into the front of `out`, returning its length.
+	fn encode(self, out: &mut [u8; MAX_ENCODED_LEN]) -> usize;
+
+	/// Decodes a value from the front of `buf`, returning it with the
+	/// number of bytes it occupied. Fails with `Overflow` for a value that
+	/// does not fit `Self`.
+	fn decode(buf: &[u8]) -> Result<(Self, usize), DecodeError>;
+
+	fn encoded_len(self) -> usize;
+
+	/// Encodes the value onto the end of `out`.
+	#[cfg(feature = "alloc")]
+	fn append(self, out: &mut Vec<u8>) {
+		let mut res = [0; MAX_ENCODED_LEN];
+		let len = self.encode(&mut res);
+		out.extend_from_slice(&res[..len]);
+	}
+}
+
+macro_rules! varint {
+	($wide:ty, $pack:ident, $decode:ident, $len:ident; $($t:ty),*) => {$(
+		impl sealed::Sealed for $t {}
+
+		impl VarInt for $t {
+			fn encode(self, out: &mut [u8; MAX_ENCODED_LEN]) -> usize {
+				$pack(self as $wide, out)
+			}
+
+			fn decode(buf: &[u8]) -> Result<($t, usize), DecodeError> {
+				let (v, used) = $decode(buf)?;
+				Ok((<$t>::try_from(v).map_err(|_| DecodeError::Overflow)?, used))
+			}
+
+			fn encoded_len(self) -> usize {
+				$len(self as $wide)
+			}
+		}
+	)*};
+}
+
+varint!(u64, pack_uint_into_array, decode_uint, encoded_len_u64; u8, u16, u32, u64);
+varint!(i64, pack_int_into_array, decode_int, encoded_len_i64; i8, i16, i32, i64);
+
+/// Method-call encoding for every `VarInt` type:
+/// `42u64.encode_varint()`, `(-7i64).encode_varint_into(&mut key)`.
+#[cfg(feature = "alloc")]
+pub trait EncodeVarint: VarInt {
+	fn encode_varint(self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(self.encoded_len());
+		self.append(&mut out);
+		out
+	}
+
+	/// Encodes the value onto the end of `out`.
+	fn encode_varint_into(self, out: &mut Vec<u8>) {
+		self.append(out);
+	}
+}
+
+#[cfg(feature = "alloc")]
+impl<T: VarInt> EncodeVarint for T {}
+
+/// Method-call decoding on byte slices, `buf.decode_varint::<u64>()`.
+pub trait DecodeVarint {
+	/// Decodes a `T` from the front of the slice, returning it with the
+	/// number of bytes it occupied.
+	fn decode_varint<T: VarInt>(&self) -> Result<(T, usize), DecodeError>;
+}
+
+impl DecodeVarint for [u8] {
+	fn decode_varint<T: VarInt>(&self) -> Result<(T, usize), DecodeError> {
+		T::decode(self)
+	}
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+	use crate::{pack_int, pack_uint};
+
+	fn agrees<T: VarInt + Ord>(a: T, b: T) -> bool {
+		let (mut ea, mut eb) = (Vec::new(), Vec::new());
+		a.append(&mut ea);
+		b.append(&mut eb);
+		T::decode(&ea) == Ok((a, ea.len())) && ea.len() == a.encoded_len() && ea.cmp(&eb) == a.cmp(&b)
+	}
+
+	#[quickcheck]
+	fn every_width_round_trips_in_order(a: (u8, u16, u32, u64), b: (i8, i16, i32, i64)) -> bool {
+		agrees(a.0, b.0 as u8) && agrees(a.1, b.1 as u16) && agrees(a.2, b.2 as u32) && agrees(a.3, b.3 as u64) &&
+			agrees(b.0, a.0 as i8) && agrees(b.1, a.1 as i16) && agrees(b.2, a.2 as i32) && agrees(b.3, a.3 as i64)
+	}
+
+	#[test]
+	fn builds_keys_by_method_call() {
+		let mut key = 42u64.encode_varint();
+		(-7i64).encode_varint_into(&mut key);
+		3u8.encode_varint_into(&mut key);
+		assert_eq!(key, [pack_uint(42), pack_int(-7), pack_uint(3)].concat());
+
+		let (a, used) = key.decode_varint::<u64>().unwrap();
+		let (b, more) = key[used..].decode_varint::<i16>().unwrap();
+		assert_eq!((a, b), (42, -7));
+		assert_eq!(key[used + more..].decode_varint::<u8>(), Ok((3, 1)));
+		assert_eq!([0xc0u8][..].decode_varint::<u8>(), Err(DecodeError::Truncated { needed: 2 }));
+	}
+
+	#[test]
+	fn matches_the_64_bit_encoding() {
+		let mut out = [0; MAX_ENCODED_LEN];
+		let len = 200u8.encode(&mut out);
+		assert_eq!(out[..len], pack_uint(200)[..]);
+		let len = (-200i16).encode(&mut out);
+		assert_eq!(out[..len], pack_int(-200)[..]);
+		assert_eq!(u8::decode(&pack_uint(256)), Err(DecodeError::Overflow));
+		assert_eq!(i8::decode(&pack_int(-129)), Err(DecodeError::Overflow));
+	}
+}
@@ -0,0 +1,177 @@
+//! Order-preserving keys for dotted version numbers.
+//!
+//! `pack_version` encodes `1.2.10` so that it sorts after `1.2.9`, and
+//! follows semver precedence for pre-release tags: `1.0.0-alpha` <
+//! `1.0.0-alpha.1` < `1.0.0-beta` < `1.0.0`. Versions with fewer numbers
+//! sort first, so `1.2` < `1.2.0`.
+//!
+//! ```text
+//! [number: uint ...][0x01 pre-release identifiers ... 0x00 | 0x02]
+//! ```
+//!
+//! Numeric identifiers are packed uints; alphanumeric ones are `0xf0`,
+//! their bytes and `0x00`, which puts them after every numeric one. Build
+//! metadata (`+...`) takes no part in precedence and is not stored.
+
+use crate::{decode_uint, pack_uint, DecodeError};
+
+const LIST_END: u8 = 0x00;
+const PRERELEASE: u8 = 0x01;
+const RELEASE: u8 = 0x02;
+const ALPHANUMERIC: u8 = 0xf0;
+
+fn parse_number(s: &str) -> Option<u64> {
+	if s.is_empty() || s.len() > 1 && s.starts_with('0') || !s.bytes().all(|b| b.is_ascii_digit()) {
+		return None;
+	}
+	s.parse().ok()
+}
+
+/// Encodes a version such as `1.2.10` or `2.0.0-rc.1+build.5`.
+pub fn pack_version(version: &str) -> Result<Vec<u8>, DecodeError> {
+	let version = version.split('+').next().unwrap();
+	let (core, pre) = match version.find('-') {
+		Some(i) => (&version[..i], Some(&version[i + 1..])),
+		None => (version, None),
+	};
+
+	let mut out = Vec::new();
+	for segment in core.split('.') {
+		let n = parse_number(segment).ok_or(DecodeError::Malformed("invalid version number"))?;
+		out.extend_from_slice(&pack_uint(n));
+	}
+	match pre {
+		None => out.push(RELEASE),
+		Some(pre) => {
+			out.push(PRERELEASE);
+			for ident in pre.split('.') {
+				if ident.is_empty() || !ident.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+					return Err(DecodeError::Malformed("invalid pre-release identifier"));
+				}
+				if ident.bytes().all(|b| b.is_ascii_digit()) {
+					let n = parse_number(ident).ok_or(DecodeError::Malformed("invalid pre-release identifier"))?;
+					out.extend_from_slice(&pack_uint(n));
+				} else {
+					out.push(ALPHANUMERIC);
+					out.extend_from_slice(ident.as_bytes());
+					out.push(0);
+				}
+			}
+			out.push(LIST_END);
+		}
+	}
+	Ok(out)
+}
+
+/// Decodes a version packed by `pack_version`, without its build
+/// metadata, returning it and the number of bytes it occupied.
+pub fn unpack_version(buf: &[u8]) -> Result<(String, usize), DecodeError> {
+	let mut out = String::new();
+	let mut pos = 0;
+	let truncated = |pos: usize| DecodeError::Truncated { needed: pos + 1 };
+
+	loop {
+		match *buf.get(pos).ok_or_else(|| truncated(pos))? {
+			RELEASE | PRERELEASE if pos == 0 => return Err(DecodeError::Malformed("version has no numbers")),
+			RELEASE => return Ok((out, pos + 1)),
+			PRERELEASE => break,
+			_ => {
+				let (n, used) = decode_uint(&buf[pos..]).map_err(|e| match e {
+					DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+					e => e,
+				})?;
+				if pos > 0 {
+					out.push('.');
+				}
+				out.push_str(&n.to_string());
+				pos += used;
+			}
+		}
+	}
+
+	pos += 1;
+	let mut sep = '-';
+	loop {
+		match *buf.get(pos).ok_or_else(|| truncated(pos))? {
+			LIST_END if sep == '-' => return Err(DecodeError::Malformed("empty pre-release tag")),
+			LIST_END => return Ok((out, pos + 1)),
+			ALPHANUMERIC => {
+				let len = buf[pos + 1..].iter().position(|&b| b == 0).ok_or_else(|| truncated(buf.len()))?;
+				let ident = &buf[pos + 1..pos + 1 + len];
+				if ident.is_empty() || !ident.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'-') {
+					return Err(DecodeError::Malformed("invalid pre-release identifier"));
+				}
+				out.push(sep);
+				out.extend(ident.iter().map(|&b| b as char));
+				pos += len + 2;
+			}
+			_ => {
+				let (n, used) = decode_uint(&buf[pos..]).map_err(|e| match e {
+					DecodeError::Truncated { needed } => DecodeError::Truncated { needed: pos + needed },
+					e => e,
+				})?;
+				out.push(sep);
+				out.push_str(&n.to_string());
+				pos += used;
+			}
+		}
+		sep = '.';
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(core: &[u16], pre: &[(bool, u16)]) -> String {
+		let mut s = core.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(".");
+		if !pre.is_empty() {
+			let idents: Vec<String> = pre
+				.iter()
+				.map(|&(alpha, n)| if alpha { format!("x{}", n % 3) } else { (n % 3).to_string() })
+				.collect();
+			s = format!("{}-{}", s, idents.join("."));
+		}
+		s
+	}
+
+	// semver precedence for versions built by `version`
+	fn precedence(a: (&[u16], &[(bool, u16)]), b: (&[u16], &[(bool, u16)])) -> std::cmp::Ordering {
+		let ident = |&(alpha, n): &(bool, u16)| (alpha, if alpha { format!("x{}", n % 3) } else { String::new() }, n % 3);
+		a.0.cmp(b.0).then_with(|| match (a.1.is_empty(), b.1.is_empty()) {
+			(true, true) => std::cmp::Ordering::Equal,
+			(true, false) => std::cmp::Ordering::Greater,
+			(false, true) => std::cmp::Ordering::Less,
+			(false, false) => a.1.iter().map(ident).cmp(b.1.iter().map(ident)),
+		})
+	}
+
+	#[quickcheck]
+	fn preserves_precedence(a: Vec<u16>, pa: Vec<(bool, u16)>, b: Vec<u16>, pb: Vec<(bool, u16)>) -> bool {
+		if a.is_empty() || b.is_empty() {
+			return true;
+		}
+		let (va, vb) = (version(&a, &pa), version(&b, &pb));
+		let (ka, kb) = (pack_version(&va).unwrap(), pack_version(&vb).unwrap());
+		ka.cmp(&kb) == precedence((&a, &pa), (&b, &pb)) && unpack_version(&ka) == Ok((va, ka.len()))
+	}
+
+	#[test]
+	fn sorts_like_semver() {
+		let sorted = ["0.9", "1.0.0-0.3.7", "1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta",
+			"1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.2.9", "1.2.10", "1.10"];
+		let keys: Vec<Vec<u8>> = sorted.iter().map(|v| pack_version(v).unwrap()).collect();
+		assert!(keys.windows(2).all(|w| w[0] < w[1]));
+		assert_eq!(pack_version("1.0.0+build.5"), pack_version("1.0.0"));
+	}
+
+	#[test]
+	fn rejects_bad_versions() {
+		for bad in ["", "1..2", "1.02", "1.x", "1.0-", "1.0-a..b", "1.0-01", "1.0-a_b"].iter() {
+			assert!(pack_version(bad).is_err(), "{}", bad);
+		}
+		assert_eq!(unpack_version(&[RELEASE]), Err(DecodeError::Malformed("version has no numbers")));
+		let key = pack_version("1.0-rc").unwrap();
+		assert_eq!(unpack_version(&key[..key.len() - 2]), Err(DecodeError::Truncated { needed: key.len() - 1 }));
+	}
+}
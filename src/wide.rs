@@ -0,0 +1,186 @@
+//! 128-bit values, for ids such as ULIDs that outgrow 64 bits.
+//!
+//! The encoding extends the 64-bit one, and agrees with it on every value
+//! both can hold. Positive values with a payload of 9 to 15 bytes take
+//! the length markers `0xe9` to `0xef`, which 64-bit decoding rejects,
+//! and a 16-byte payload takes `0xf0`. Negative values with 9 to 16 bytes
+//! take `0x0f` down to `0x08`, below every 64-bit marker, so unlike 64-bit
+//! values they can start with a byte below `0x10`; do not mix them into
+//! the streams of `tagged`.
+//!
+//! ```
+//! use rust_varint::wide::{decode_u128, pack_u128_into_array, MAX_ENCODED_LEN_128};
+//!
+//! let id = 0x0188_bd2c_5e6f_7a12_8945_0ac1_22ee_f00d_u128;
+//! let mut key = [0; MAX_ENCODED_LEN_128];
+//! assert_eq!(pack_u128_into_array(id, &mut key), 17);
+//! assert_eq!(decode_u128(&key), Ok((id, 17)));
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{
+	decode_int, decode_uint, pack_int_into_array, pack_uint_into_array, DecodeError, MAX_ENCODED_LEN, POS_2BYTE_MAX,
+	POS_MULTI_MARKER,
+};
+
+/// Longest encoding of a 128-bit value, in bytes.
+pub const MAX_ENCODED_LEN_128: usize = 17;
+
+// Marker of a positive value with a 16-byte payload.
+const POS_16BYTE_MARKER: u8 = 0xf0;
+
+// Significant bytes of `x`, at least one.
+fn payload_len(x: u128) -> usize {
+	16 - (x.leading_zeros() as usize / 8).min(15)
+}
+
+/// Encodes `x` into the front of `res` without allocating, returning the
+/// encoded length.
+pub fn pack_u128_into_array(x: u128, res: &mut [u8; MAX_ENCODED_LEN_128]) -> usize {
+	*res = [0; MAX_ENCODED_LEN_128];
+	if x <= u64::MAX as u128 {
+		let mut short = [0; MAX_ENCODED_LEN];
+		let len = pack_uint_into_array(x as u64, &mut short);
+		res[..len].copy_from_slice(&short[..len]);
+		return len;
+	}
+	let y = x - (POS_2BYTE_MAX as u128 + 1);
+	let len = payload_len(y);
+	res[0] = if len == 16 { POS_16BYTE_MARKER } else { POS_MULTI_MARKER | len as u8 };
+	res[1..=len].copy_from_slice(&y.to_be_bytes()[16 - len..]);
+	len + 1
+}
+
+/// Signed counterpart of `pack_u128_into_array`.
+pub fn pack_i128_into_array(x: i128, res: &mut [u8; MAX_ENCODED_LEN_128]) -> usize {
+	if x >= 0 {
+		return pack_u128_into_array(x as u128, res);
+	}
+	*res = [0; MAX_ENCODED_LEN_128];
+	if x >= i64::MIN as i128 {
+		let mut short = [0; MAX_ENCODED_LEN];
+		let len = pack_int_into_array(x as i64, &mut short);
+		res[..len].copy_from_slice(&short[..len]);
+		return len;
+	}
+	// the payload is `x` with its leading 0xff bytes dropped
+	let len = payload_len(!x as u128);
+	res[0] = if len <= 8 { 0x10 | (8 - len) as u8 } else { (0x18 - len) as u8 };
+	res[1..=len].copy_from_slice(&x.to_be_bytes()[16 - len..]);
+	len + 1
+}
+
+#[cfg(feature = "alloc")]
+pub fn pack_u128(x: u128) -> Vec<u8> {
+	let mut res = [0; MAX_ENCODED_LEN_128];
+	let len = pack_u128_into_array(x, &mut res);
+	res[..len].to_vec()
+}
+
+#[cfg(feature = "alloc")]
+pub fn pack_i128(x: i128) -> Vec<u8> {
+	let mut res = [0; MAX_ENCODED_LEN_128];
+	let len = pack_i128_into_array(x, &mut res);
+	res[..len].to_vec()
+}
+
+fn payload(buf: &[u8], len: usize) -> Result<&[u8], DecodeError> {
+	buf.get(1..=len).ok_or(DecodeError::Truncated { needed: len + 1 })
+}
+
+/// Decodes one unsigned value from the front of `buf`, returning it with
+/// the number of bytes it occupied.
+pub fn decode_u128(buf: &[u8]) -> Result<(u128, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = match first {
+		POS_16BYTE_MARKER => 16,
+		0xe1..=0xef => (first & 0xf) as usize,
+		_ => return decode_uint(buf).map(|(x, used)| (x as u128, used)),
+	};
+	let y = payload(buf, len)?.iter().fold(0u128, |y, &b| y << 8 | b as u128);
+	let x = y.checked_add(POS_2BYTE_MAX as u128 + 1).ok_or(DecodeError::Overflow)?;
+	Ok((x, len + 1))
+}
+
+/// Signed counterpart of `decode_u128`.
+pub fn decode_i128(buf: &[u8]) -> Result<(i128, usize), DecodeError> {
+	let first = *buf.first().ok_or(DecodeError::Truncated { needed: 1 })?;
+	let len = match first {
+		0x80..=0xff => {
+			let (x, used) = decode_u128(buf)?;
+			if x > i128::MAX as u128 {
+				return Err(DecodeError::Overflow);
+			}
+			return Ok((x as i128, used));
+		}
+		0x08..=0x0f => 0x18 - first as usize,
+		0x10..=0x17 => 8 - (first & 0xf) as usize,
+		_ => return decode_int(buf).map(|(x, used)| (x as i128, used)),
+	};
+	let x = payload(buf, len)?.iter().fold(-1i128, |x, &b| x << 8 | b as i128);
+	Ok((x, len + 1))
+}
+
+/// Decodes the unsigned value at the front of `res`, panicking on
+/// malformed input like `unpack_uint`.
+pub fn unpack_u128(res: &[u8]) -> u128 {
+	decode_u128(res).map(|(x, _)| x).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Signed counterpart of `unpack_u128`.
+pub fn unpack_i128(res: &[u8]) -> i128 {
+	decode_i128(res).map(|(x, _)| x).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+	use crate::{pack_int, pack_uint};
+
+	// quickcheck's integers are small, so spread them over the full width
+	fn wide(a: u128, shift: u8) -> u128 {
+		a << (shift % 121)
+	}
+
+	#[quickcheck]
+	fn unsigned_order_and_round_trip(a: (u128, u8), b: (u128, u8), flip: bool) -> bool {
+		let (x, y) = (wide(a.0, a.1), wide(b.0, b.1));
+		let x = if flip { !x } else { x };
+		let (ex, ey) = (pack_u128(x), pack_u128(y));
+		decode_u128(&ex) == Ok((x, ex.len())) && ex.cmp(&ey) == x.cmp(&y) &&
+			(x > u64::MAX as u128 || ex == pack_uint(x as u64))
+	}
+
+	#[quickcheck]
+	fn signed_order_and_round_trip(a: (u128, u8, bool), b: (u128, u8, bool)) -> bool {
+		let signed = |(v, s, neg): (u128, u8, bool)| if neg { !(wide(v, s) as i128) } else { wide(v, s) as i128 };
+		let (x, y) = (signed(a), signed(b));
+		let (ex, ey) = (pack_i128(x), pack_i128(y));
+		decode_i128(&ex) == Ok((x, ex.len())) && ex.cmp(&ey) == x.cmp(&y) &&
+			(x < i64::MIN as i128 || x > i64::MAX as i128 || ex == pack_int(x as i64))
+	}
+
+	#[test]
+	fn length_classes_at_the_edges() {
+		let edges = [i128::MIN, i128::MIN + 1, -(1 << 64) - 1, -(1 << 64), i64::MIN as i128 - 1, i64::MIN as i128, -1, 0,
+			u64::MAX as i128, u64::MAX as i128 + 1, u64::MAX as i128 + 8256, i128::MAX];
+		for pair in edges.windows(2) {
+			let (a, b) = (pack_i128(pair[0]), pack_i128(pair[1]));
+			assert!(a < b, "{} {}", pair[0], pair[1]);
+			assert_eq!(decode_i128(&a), Ok((pair[0], a.len())));
+		}
+		assert_eq!(pack_i128(i128::MIN)[0], 0x08);
+		assert_eq!(pack_u128(u128::MAX).len(), MAX_ENCODED_LEN_128);
+		assert_eq!(pack_u128(u128::MAX)[0], 0xf0);
+		assert_eq!(decode_u128(&pack_u128(u128::MAX)), Ok((u128::MAX, 17)));
+		assert_eq!(decode_i128(&pack_u128(u128::MAX)), Err(DecodeError::Overflow));
+		let mut max = [0xff; 17];
+		max[0] = 0xf0;
+		assert_eq!(decode_u128(&max), Err(DecodeError::Overflow));
+		assert_eq!(decode_u128(&[0xe9, 1]), Err(DecodeError::Truncated { needed: 10 }));
+		assert_eq!(decode_i128(&[0x07]), Err(DecodeError::InvalidMarker(0x07)));
+		assert_eq!(decode_uint(&pack_u128(u64::MAX as u128 + 1)), Err(DecodeError::Overflow));
+	}
+}
@@ -0,0 +1,51 @@
+//! ZigZag mapping of signed integers onto unsigned ones, interleaving
+//! negative and positive values so small magnitudes stay small:
+//! 0 -> 0, -1 -> 1, 1 -> 2, -2 -> 3, ...
+//!
+//! This is what formats without a sign of their own, such as LEB128, use
+//! for signed values, and the default signed methods of `VarintFormat`.
+//! The mapped values no longer sort like the originals: -1 maps above 0.
+//!
+//! `pack_int` needs no mapping. Its markers already give small negative
+//! values short encodings while keeping the order, and it is never longer
+//! than `pack_uint` of the mapped value, so prefer it for deltas that
+//! swing around zero too:
+//!
+//! ```
+//! use rust_varint::{encoded_len_i64, encoded_len_u64, zigzag_decode, zigzag_encode};
+//!
+//! assert_eq!(zigzag_encode(-3), 5);
+//! assert_eq!(zigzag_decode(5), -3);
+//! // one byte covers -64..64 for pack_int, but only -32..32 once mapped
+//! assert_eq!((encoded_len_i64(-50), encoded_len_u64(zigzag_encode(-50))), (1, 2));
+//! ```
+
+pub fn zigzag_encode(x: i64) -> u64 {
+	((x << 1) ^ (x >> 63)) as u64
+}
+
+pub fn zigzag_decode(x: u64) -> i64 {
+	((x >> 1) as i64) ^ -((x & 1) as i64)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+	use super::*;
+
+	#[quickcheck]
+	fn round_trip(x: i64) -> bool {
+		zigzag_decode(zigzag_encode(x)) == x
+	}
+
+	#[quickcheck]
+	fn never_beats_pack_int(x: i64, shift: u8) -> bool {
+		let x = x >> (shift % 64);
+		crate::encoded_len_u64(zigzag_encode(x)) >= crate::encoded_len_i64(x)
+	}
+
+	#[test]
+	fn interleaves_signs() {
+		let mapped: Vec<u64> = [0, -1, 1, -2, 2, i64::MAX, i64::MIN].iter().map(|&x| zigzag_encode(x)).collect();
+		assert_eq!(mapped, vec![0, 1, 2, 3, 4, u64::MAX - 1, u64::MAX]);
+	}
+}